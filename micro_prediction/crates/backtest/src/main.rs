@@ -0,0 +1,315 @@
+//! Backtester for `micro_prediction` market parameters: replays a price
+//! history as back-to-back rounds, fills them from a recorded or synthetic
+//! participant model and settles them with the plaintext settlement model.
+
+mod model;
+mod report;
+mod simulate;
+mod ticks;
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, ValueEnum};
+use micro_prediction_settlement::{TieBreak, MAX_PAYOUT_TIERS};
+use model::{ParticipantModel, Recorded, Synthetic, SyntheticParams};
+use report::Report;
+use simulate::{simulate, MarketParams, RoundOutcome, Rule};
+use std::{fs::File, path::PathBuf};
+use ticks::PriceHistory;
+
+#[derive(Parser)]
+#[command(name = "micro-prediction-backtest", version, about)]
+struct Cli {
+    /// CSV of `timestamp,price` ticks in program price units.
+    #[arg(long)]
+    ticks: PathBuf,
+    /// CSV of recorded entries (`timestamp,stake,predicted_price` and
+    /// optionally `lower,upper`); synthetic players are used without it.
+    #[arg(long)]
+    entries: Option<PathBuf>,
+    #[command(flatten)]
+    market: MarketArgs,
+    #[command(flatten)]
+    synthetic: SyntheticArgs,
+    /// Seeds the synthetic players and the per-round settlement randomness.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Also write every round's outcome to this CSV.
+    #[arg(long)]
+    rounds_csv: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    output: Format,
+}
+
+#[derive(Args)]
+struct MarketArgs {
+    #[arg(long, default_value_t = 300)]
+    round_duration_secs: i64,
+    #[arg(long, default_value_t = 0)]
+    fee_bps: u16,
+    #[arg(long, default_value_t = 0)]
+    house_edge_bps: u16,
+    /// Comma-separated bps of the pool per closest distinct distance; empty
+    /// means winner-take-all.
+    #[arg(long, value_delimiter = ',')]
+    payout_tiers: Vec<u16>,
+    #[arg(long, value_enum, default_value_t = TieBreakArg::Split)]
+    tie_break: TieBreakArg,
+    #[arg(long, default_value_t = 0)]
+    lucky_draw_bps: u16,
+    #[arg(long, default_value_t = 0)]
+    early_bonus_bps: u16,
+    #[arg(long, value_enum, default_value_t = RuleArg::Closest)]
+    rule: RuleArg,
+    #[arg(long, default_value_t = 10_000)]
+    interval_alpha_bps: u16,
+    #[arg(long, default_value_t = 1_000_000)]
+    max_interval_penalty: u64,
+    /// Resolve with a TWAP over this window instead of the spot price.
+    #[arg(long)]
+    twap_window_secs: Option<i64>,
+}
+
+#[derive(Args)]
+struct SyntheticArgs {
+    #[arg(long, default_value_t = 2)]
+    min_players: u32,
+    #[arg(long, default_value_t = 20)]
+    max_players: u32,
+    #[arg(long, default_value_t = 1_000_000)]
+    min_stake: u64,
+    #[arg(long, default_value_t = 10_000_000)]
+    max_stake: u64,
+    /// Guesses land uniformly within this many bps of the price at entry.
+    #[arg(long, default_value_t = 50)]
+    noise_bps: u16,
+    /// Width of interval guesses, in bps of the price at entry.
+    #[arg(long, default_value_t = 100)]
+    interval_width_bps: u16,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TieBreakArg {
+    Split,
+    Random,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum RuleArg {
+    Closest,
+    Interval,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+}
+
+impl MarketArgs {
+    fn params(&self) -> Result<MarketParams> {
+        if self.round_duration_secs <= 0 {
+            bail!("--round-duration-secs must be positive");
+        }
+        if self.payout_tiers.len() > MAX_PAYOUT_TIERS {
+            bail!("at most {MAX_PAYOUT_TIERS} payout tiers");
+        }
+        if self
+            .payout_tiers
+            .iter()
+            .map(|tier| *tier as u32)
+            .sum::<u32>()
+            > 10_000
+        {
+            bail!("payout tiers add up to more than 10000 bps");
+        }
+        let mut payout_tiers = [0; MAX_PAYOUT_TIERS];
+        payout_tiers[..self.payout_tiers.len()].copy_from_slice(&self.payout_tiers);
+        Ok(MarketParams {
+            round_duration_secs: self.round_duration_secs,
+            fee_bps: self.fee_bps,
+            house_edge_bps: self.house_edge_bps,
+            payout_tiers,
+            tie_break: match self.tie_break {
+                TieBreakArg::Split => TieBreak::Split,
+                TieBreakArg::Random => TieBreak::Random,
+            },
+            lucky_draw_bps: self.lucky_draw_bps,
+            early_bonus_bps: self.early_bonus_bps,
+            rule: match self.rule {
+                RuleArg::Closest => Rule::Closest,
+                RuleArg::Interval => Rule::Interval {
+                    alpha_bps: self.interval_alpha_bps,
+                    max_penalty: self.max_interval_penalty,
+                },
+            },
+            twap_window_secs: self.twap_window_secs,
+        })
+    }
+}
+
+impl SyntheticArgs {
+    fn params(&self) -> Result<SyntheticParams> {
+        if self.min_players > self.max_players || self.min_stake > self.max_stake {
+            bail!("synthetic minimums must not exceed their maximums");
+        }
+        Ok(SyntheticParams {
+            min_players: self.min_players,
+            max_players: self.max_players,
+            min_stake: self.min_stake,
+            max_stake: self.max_stake,
+            noise_bps: self.noise_bps,
+            interval_width_bps: self.interval_width_bps,
+        })
+    }
+}
+
+fn open(path: &PathBuf) -> Result<File> {
+    File::open(path).with_context(|| format!("failed to open {}", path.display()))
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let params = cli.market.params()?;
+    let history = PriceHistory::from_csv(open(&cli.ticks)?)?;
+    let mut model: Box<dyn ParticipantModel> = match &cli.entries {
+        Some(path) => Box::new(Recorded::from_csv(open(path)?)?),
+        None => Box::new(Synthetic::new(cli.synthetic.params()?, cli.seed)),
+    };
+
+    let outcomes = simulate(&history, model.as_mut(), &params, cli.seed);
+    if outcomes.is_empty() {
+        bail!("the price history is shorter than one round");
+    }
+    if let Some(path) = &cli.rounds_csv {
+        write_rounds(path, &outcomes)?;
+    }
+
+    let report = report::summarize(&outcomes);
+    match cli.output {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        Format::Table => print_report(&report),
+    }
+    Ok(())
+}
+
+fn write_rounds(path: &PathBuf, outcomes: &[RoundOutcome]) -> Result<()> {
+    let mut writer = csv::Writer::from_path(path)
+        .with_context(|| format!("failed to create {}", path.display()))?;
+    writer.write_record([
+        "round_id",
+        "start_ts",
+        "end_ts",
+        "final_price",
+        "entries",
+        "winners",
+        "total_stake",
+        "carried_in",
+        "carried_out",
+        "total_payout",
+        "fee_total",
+        "house_edge",
+        "house_revenue",
+    ])?;
+    for round in outcomes {
+        writer.write_record([
+            round.round_id.to_string(),
+            round.start_ts.to_string(),
+            round.end_ts.to_string(),
+            round
+                .final_price
+                .map_or_else(String::new, |price| price.to_string()),
+            round.entries.len().to_string(),
+            round.winners().to_string(),
+            round.total_stake.to_string(),
+            round.carried_in.to_string(),
+            round.carried_out.to_string(),
+            round.total_payout.to_string(),
+            round.fee_total.to_string(),
+            round.house_edge.to_string(),
+            round.house_revenue().to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn print_report(report: &Report) {
+    let percent = |value: f64| format!("{:.2}%", value * 100.0);
+    let multiple = |value: f64| format!("{value:.2}x");
+    let mut fields = vec![
+        ("rounds", report.rounds.to_string()),
+        ("empty rounds", report.empty_rounds.to_string()),
+        ("refunded rounds", report.refunded_rounds.to_string()),
+        (
+            "rounds without winners",
+            report.rounds_without_winners.to_string(),
+        ),
+        ("entries", report.entries.to_string()),
+        ("winning entries", report.winning_entries.to_string()),
+        ("win rate", percent(report.win_rate)),
+        ("total staked", report.total_staked.to_string()),
+        ("total paid", report.total_paid.to_string()),
+        ("fees", report.fees.to_string()),
+        ("house edge", report.house_edge.to_string()),
+        ("house revenue", report.house_revenue.to_string()),
+        ("carried over", report.carried_over.to_string()),
+        ("house take", percent(report.house_take)),
+        ("winner multiple p10", multiple(report.winner_multiple.p10)),
+        ("winner multiple p50", multiple(report.winner_multiple.p50)),
+        ("winner multiple p90", multiple(report.winner_multiple.p90)),
+        ("winner multiple max", multiple(report.winner_multiple.max)),
+    ];
+    let buckets: Vec<(String, String)> = report
+        .payout_buckets
+        .iter()
+        .map(|bucket| {
+            (
+                format!("payout {}", bucket.label),
+                bucket.entries.to_string(),
+            )
+        })
+        .collect();
+    fields.extend(
+        buckets
+            .iter()
+            .map(|(label, count)| (label.as_str(), count.clone())),
+    );
+
+    let width = fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, value) in fields {
+        println!("{name:<width$}  {value}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_market_params_validation() {
+        let cli = Cli::parse_from([
+            "micro-prediction-backtest",
+            "--ticks",
+            "ticks.csv",
+            "--payout-tiers",
+            "6000,3000,1000",
+        ]);
+        let params = cli.market.params().unwrap();
+        assert_eq!(params.payout_tiers, [6000, 3000, 1000, 0, 0]);
+
+        let cli = Cli::parse_from([
+            "micro-prediction-backtest",
+            "--ticks",
+            "ticks.csv",
+            "--payout-tiers",
+            "6000,5000",
+        ]);
+        assert!(cli.market.params().is_err());
+    }
+}
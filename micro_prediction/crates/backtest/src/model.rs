@@ -0,0 +1,169 @@
+//! Participant models: who enters a round, when, with what stake and what
+//! guess.
+use crate::ticks::PriceHistory;
+use anyhow::{Context, Result};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Deserialize;
+use std::io::Read;
+
+/// One entry as the program would see it. `lower` and `upper` are only read
+/// by the interval rule, `predicted_price` only by the closest-price rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct Entry {
+    #[serde(rename = "timestamp")]
+    pub submitted_at: i64,
+    pub stake: u64,
+    pub predicted_price: u128,
+    #[serde(default)]
+    pub lower: u128,
+    #[serde(default)]
+    pub upper: u128,
+}
+
+pub trait ParticipantModel {
+    /// Entries submitted while the round `[start_ts, end_ts)` is open.
+    fn entries(&mut self, history: &PriceHistory, start_ts: i64, end_ts: i64) -> Vec<Entry>;
+}
+
+/// Entries replayed from a CSV with `timestamp,stake,predicted_price` and
+/// optional `lower,upper` columns.
+pub struct Recorded {
+    entries: Vec<Entry>,
+}
+
+impl Recorded {
+    pub fn from_csv(reader: impl Read) -> Result<Self> {
+        let mut entries = csv::Reader::from_reader(reader)
+            .deserialize()
+            .enumerate()
+            .map(|(row, entry)| entry.with_context(|| format!("invalid entry on row {}", row + 1)))
+            .collect::<Result<Vec<Entry>>>()?;
+        entries.sort_by_key(|entry| entry.submitted_at);
+        Ok(Self { entries })
+    }
+}
+
+impl ParticipantModel for Recorded {
+    fn entries(&mut self, _history: &PriceHistory, start_ts: i64, end_ts: i64) -> Vec<Entry> {
+        let start = self
+            .entries
+            .partition_point(|entry| entry.submitted_at < start_ts);
+        let end = self
+            .entries
+            .partition_point(|entry| entry.submitted_at < end_ts);
+        self.entries[start..end].to_vec()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SyntheticParams {
+    pub min_players: u32,
+    pub max_players: u32,
+    pub min_stake: u64,
+    pub max_stake: u64,
+    /// Guesses land uniformly within this many bps of the price at entry.
+    pub noise_bps: u16,
+    /// Width of interval guesses, in bps of the price at entry.
+    pub interval_width_bps: u16,
+}
+
+/// Players who guess the last visible price plus uniform noise, entering at a
+/// uniformly random time.
+pub struct Synthetic {
+    params: SyntheticParams,
+    rng: StdRng,
+}
+
+impl Synthetic {
+    pub fn new(params: SyntheticParams, seed: u64) -> Self {
+        Self {
+            params,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    fn offset(&mut self, price: u128, bps: u16) -> i128 {
+        let span = (price * bps as u128 / 10_000) as i128;
+        self.rng.gen_range(-span..=span)
+    }
+}
+
+impl ParticipantModel for Synthetic {
+    fn entries(&mut self, history: &PriceHistory, start_ts: i64, end_ts: i64) -> Vec<Entry> {
+        let players = self
+            .rng
+            .gen_range(self.params.min_players..=self.params.max_players);
+        let mut entries = Vec::with_capacity(players as usize);
+        for _ in 0..players {
+            let submitted_at = self.rng.gen_range(start_ts..end_ts);
+            let Some(price) = history.price_at(submitted_at) else {
+                continue;
+            };
+            let price = price as u128;
+            let guess = (price as i128 + self.offset(price, self.params.noise_bps)).max(0) as u128;
+            let half_width = price * self.params.interval_width_bps as u128 / 20_000;
+            entries.push(Entry {
+                submitted_at,
+                stake: self
+                    .rng
+                    .gen_range(self.params.min_stake..=self.params.max_stake),
+                predicted_price: guess,
+                lower: guess.saturating_sub(half_width),
+                upper: guess + half_width,
+            });
+        }
+        entries.sort_by_key(|entry| entry.submitted_at);
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ticks::Tick;
+
+    #[test]
+    fn test_synthetic_entries_stay_in_bounds() {
+        let history = PriceHistory::new(vec![Tick {
+            timestamp: 0,
+            price: 10_000,
+        }])
+        .unwrap();
+        let params = SyntheticParams {
+            min_players: 3,
+            max_players: 6,
+            min_stake: 10,
+            max_stake: 20,
+            noise_bps: 100,
+            interval_width_bps: 200,
+        };
+        let mut model = Synthetic::new(params, 7);
+        for round in 0..20 {
+            let entries = model.entries(&history, round * 60, round * 60 + 60);
+            assert!((3..=6).contains(&entries.len()));
+            for entry in entries {
+                assert!((round * 60..round * 60 + 60).contains(&entry.submitted_at));
+                assert!((10..=20).contains(&entry.stake));
+                assert!((9_900..=10_100).contains(&entry.predicted_price));
+                assert_eq!(entry.upper - entry.lower, 200);
+            }
+        }
+
+        let replay = Synthetic::new(params, 7).entries(&history, 0, 60);
+        assert_eq!(replay, Synthetic::new(params, 7).entries(&history, 0, 60));
+    }
+
+    #[test]
+    fn test_recorded_entries_are_split_by_window() {
+        let csv = "timestamp,stake,predicted_price\n5,1,100\n65,2,200\n60,3,300\n";
+        let mut model = Recorded::from_csv(csv.as_bytes()).unwrap();
+        let history = PriceHistory::new(vec![Tick {
+            timestamp: 0,
+            price: 1,
+        }])
+        .unwrap();
+        let stakes = |entries: Vec<Entry>| entries.iter().map(|e| e.stake).collect::<Vec<_>>();
+        assert_eq!(stakes(model.entries(&history, 0, 60)), [1]);
+        assert_eq!(stakes(model.entries(&history, 60, 120)), [3, 2]);
+    }
+}
@@ -0,0 +1,212 @@
+//! Aggregates of a backtest run: house revenue, win rates and how payouts
+//! are distributed relative to stakes.
+use crate::simulate::RoundOutcome;
+use serde::Serialize;
+
+/// Upper bounds, in multiples of the stake, of the payout histogram buckets
+/// for winning entries; the last bucket is open-ended.
+const MULTIPLE_BOUNDS: [f64; 4] = [1.0, 2.0, 5.0, 10.0];
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Report {
+    pub rounds: usize,
+    pub empty_rounds: usize,
+    /// Rounds that could not resolve; their entries are left out of the
+    /// figures below.
+    pub refunded_rounds: usize,
+    /// Rounds with entries where nobody was paid anything.
+    pub rounds_without_winners: usize,
+    pub entries: usize,
+    pub winning_entries: usize,
+    pub win_rate: f64,
+    pub total_staked: u64,
+    pub total_paid: u64,
+    pub fees: u64,
+    pub house_edge: u64,
+    /// Fees plus house edge; stakes nobody won roll over instead.
+    pub house_revenue: u64,
+    /// Still in the rollover vault after the last round.
+    pub carried_over: u64,
+    /// `house_revenue` over `total_staked`.
+    pub house_take: f64,
+    /// Payout over stake of winning entries.
+    pub winner_multiple: Percentiles,
+    pub payout_buckets: Vec<Bucket>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub p10: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Bucket {
+    pub label: String,
+    pub entries: usize,
+}
+
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator == 0.0 {
+        0.0
+    } else {
+        numerator / denominator
+    }
+}
+
+/// Nearest-rank percentile of an ascending slice.
+fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (fraction * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+pub fn summarize(outcomes: &[RoundOutcome]) -> Report {
+    let rounds = outcomes.len();
+    let outcomes: Vec<&RoundOutcome> = outcomes.iter().filter(|round| !round.refunded()).collect();
+    let entries = outcomes.iter().flat_map(|round| &round.entries);
+    let mut multiples: Vec<f64> = entries
+        .clone()
+        .filter(|entry| entry.payout > 0 && entry.stake > 0)
+        .map(|entry| entry.payout as f64 / entry.stake as f64)
+        .collect();
+    multiples.sort_by(f64::total_cmp);
+
+    let mut payout_buckets = vec![Bucket {
+        label: "lost".into(),
+        entries: entries.clone().filter(|entry| entry.payout == 0).count(),
+    }];
+    let mut lower = 0.0;
+    for upper in MULTIPLE_BOUNDS {
+        payout_buckets.push(Bucket {
+            label: format!("{lower}x-{upper}x"),
+            entries: multiples
+                .iter()
+                .filter(|multiple| **multiple > lower && **multiple <= upper)
+                .count(),
+        });
+        lower = upper;
+    }
+    payout_buckets.push(Bucket {
+        label: format!(">{lower}x"),
+        entries: multiples
+            .iter()
+            .filter(|multiple| **multiple > lower)
+            .count(),
+    });
+
+    let total_staked = outcomes.iter().map(|round| round.total_stake).sum::<u64>();
+    let house_revenue = outcomes
+        .iter()
+        .map(|round| round.house_revenue())
+        .sum::<u64>();
+    let entry_count = entries.count();
+    Report {
+        rounds,
+        refunded_rounds: rounds - outcomes.len(),
+        empty_rounds: outcomes
+            .iter()
+            .filter(|round| round.entries.is_empty())
+            .count(),
+        rounds_without_winners: outcomes
+            .iter()
+            .filter(|round| !round.entries.is_empty() && round.winners() == 0)
+            .count(),
+        entries: entry_count,
+        winning_entries: multiples.len(),
+        win_rate: ratio(multiples.len() as f64, entry_count as f64),
+        total_staked,
+        total_paid: outcomes.iter().map(|round| round.total_payout).sum(),
+        fees: outcomes.iter().map(|round| round.fee_total).sum(),
+        house_edge: outcomes.iter().map(|round| round.house_edge).sum(),
+        house_revenue,
+        carried_over: outcomes.last().map_or(0, |round| round.carried_out),
+        house_take: ratio(house_revenue as f64, total_staked as f64),
+        winner_multiple: Percentiles {
+            p10: percentile(&multiples, 0.1),
+            p50: percentile(&multiples, 0.5),
+            p90: percentile(&multiples, 0.9),
+            max: multiples.last().copied().unwrap_or_default(),
+        },
+        payout_buckets,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulate::EntryOutcome;
+
+    fn round(entries: &[(u64, u64)], carried_in: u64) -> RoundOutcome {
+        let entries: Vec<EntryOutcome> = entries
+            .iter()
+            .map(|(stake, payout)| EntryOutcome {
+                stake: *stake,
+                payout: *payout,
+            })
+            .collect();
+        let total_stake: u64 = entries.iter().map(|entry| entry.stake).sum();
+        let total_payout: u64 = entries.iter().map(|entry| entry.payout).sum();
+        let fee_total = u64::from(!entries.is_empty());
+        RoundOutcome {
+            round_id: 0,
+            start_ts: 0,
+            end_ts: 60,
+            final_price: Some(1),
+            total_stake,
+            carried_in,
+            carried_out: total_stake + carried_in - total_payout - fee_total,
+            house_edge: 0,
+            fee_total,
+            total_payout,
+            entries,
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let mut refunded = round(&[(40, 40)], 99);
+        refunded.final_price = None;
+        refunded.fee_total = 0;
+        refunded.carried_out = 99;
+        let outcomes = [
+            round(&[(100, 0), (100, 150), (50, 0)], 0),
+            round(&[], 99),
+            refunded,
+            round(&[(10, 0)], 99),
+            round(&[(20, 100), (10, 5)], 108),
+        ];
+        let report = summarize(&outcomes);
+
+        assert_eq!((report.rounds, report.empty_rounds), (5, 1));
+        assert_eq!(report.refunded_rounds, 1);
+        assert_eq!(report.rounds_without_winners, 1);
+        assert_eq!((report.entries, report.winning_entries), (6, 3));
+        assert_eq!(report.win_rate, 0.5);
+        assert_eq!((report.total_staked, report.total_paid), (290, 255));
+        assert_eq!(report.house_revenue, 3);
+        assert_eq!(report.fees, 3);
+        // The last round paid out more than its stakes from the rollover.
+        assert_eq!(report.carried_over, 32);
+        assert_eq!(
+            report.winner_multiple,
+            Percentiles {
+                p10: 0.5,
+                p50: 1.5,
+                p90: 5.0,
+                max: 5.0,
+            }
+        );
+        let counts: Vec<usize> = report
+            .payout_buckets
+            .iter()
+            .map(|bucket| bucket.entries)
+            .collect();
+        assert_eq!(counts, [3, 1, 1, 1, 0, 0]);
+        assert_eq!(report.payout_buckets[5].label, ">10x");
+    }
+}
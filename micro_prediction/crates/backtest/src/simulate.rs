@@ -0,0 +1,354 @@
+//! Replays back-to-back rounds over the price history through the plaintext
+//! settlement model, exactly as the program and circuits would settle them.
+use crate::{
+    model::{Entry, ParticipantModel},
+    ticks::PriceHistory,
+};
+use micro_prediction_settlement::{
+    determine_winners, entry_weight_bps, house_edge, payout_pool, score_interval_predictions,
+    Prediction, RangePrediction, Settlement, TieBreak, WinnerParams, MAX_PAYOUT_TIERS,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// `determine_winners`: closest guess, or payout tiers when configured.
+    Closest,
+    /// `score_interval_predictions`.
+    Interval { alpha_bps: u16, max_penalty: u64 },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MarketParams {
+    pub round_duration_secs: i64,
+    pub fee_bps: u16,
+    pub house_edge_bps: u16,
+    pub payout_tiers: [u16; MAX_PAYOUT_TIERS],
+    pub tie_break: TieBreak,
+    pub lucky_draw_bps: u16,
+    pub early_bonus_bps: u16,
+    pub rule: Rule,
+    /// Resolve with a TWAP over this window instead of the spot price.
+    pub twap_window_secs: Option<i64>,
+}
+
+/// Stake and payout of one entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EntryOutcome {
+    pub stake: u64,
+    pub payout: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoundOutcome {
+    pub round_id: u64,
+    pub start_ts: i64,
+    pub end_ts: i64,
+    /// `None` when the round could not resolve and was refunded.
+    pub final_price: Option<i64>,
+    pub total_stake: u64,
+    /// Rollover seeded into the pool from earlier rounds.
+    pub carried_in: u64,
+    /// Pool left unpaid, which the sweep returns to the rollover vault for
+    /// the next round.
+    pub carried_out: u64,
+    pub house_edge: u64,
+    pub fee_total: u64,
+    pub total_payout: u64,
+    pub entries: Vec<EntryOutcome>,
+}
+
+impl RoundOutcome {
+    pub fn winners(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.payout > 0).count()
+    }
+
+    pub fn refunded(&self) -> bool {
+        self.final_price.is_none()
+    }
+
+    /// House edge and fees, the part of the sweep `collect_fees` may take out.
+    pub fn house_revenue(&self) -> u64 {
+        self.house_edge + self.fee_total
+    }
+}
+
+fn account(index: usize) -> [u8; 32] {
+    let mut account = [0u8; 32];
+    account[..8].copy_from_slice(&(index as u64).to_le_bytes());
+    account
+}
+
+/// Runs every round that ends within the history. `seed` stands in for the
+/// randomness the settlement circuit generates per round. Whatever a round
+/// leaves unpaid is seeded into the next one, as an operator calling
+/// `seed_round_from_rollover` after every sweep would.
+pub fn simulate(
+    history: &PriceHistory,
+    model: &mut dyn ParticipantModel,
+    params: &MarketParams,
+    seed: u64,
+) -> Vec<RoundOutcome> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut outcomes = Vec::new();
+    let mut start_ts = history.first_timestamp();
+    let mut round_id = 0;
+    let mut carried_in = 0;
+    while start_ts + params.round_duration_secs <= history.last_timestamp() {
+        let end_ts = start_ts + params.round_duration_secs;
+        let entries = model.entries(history, start_ts, end_ts);
+        let final_price = match params.twap_window_secs {
+            Some(window) => history.twap(end_ts, window),
+            None => Some(
+                history
+                    .price_at(end_ts)
+                    .expect("rounds start at the first tick"),
+            ),
+        };
+        let round_seed: [u8; 32] = rng.gen();
+        let outcome = match final_price {
+            Some(final_price) => settle_round(
+                round_id,
+                (start_ts, end_ts),
+                final_price,
+                carried_in,
+                &entries,
+                params,
+                &round_seed,
+            ),
+            None => refund_round(round_id, (start_ts, end_ts), carried_in, &entries),
+        };
+        carried_in = outcome.carried_out;
+        outcomes.push(outcome);
+        start_ts = end_ts;
+        round_id += 1;
+    }
+    outcomes
+}
+
+fn total_stake(entries: &[Entry]) -> u64 {
+    entries
+        .iter()
+        .fold(0u64, |total, entry| total.saturating_add(entry.stake))
+}
+
+/// Every stake goes back and the carried-in funds stay in the rollover.
+fn refund_round(
+    round_id: u64,
+    (start_ts, end_ts): (i64, i64),
+    carried_in: u64,
+    entries: &[Entry],
+) -> RoundOutcome {
+    let total_stake = total_stake(entries);
+    RoundOutcome {
+        round_id,
+        start_ts,
+        end_ts,
+        final_price: None,
+        total_stake,
+        carried_in,
+        carried_out: carried_in,
+        house_edge: 0,
+        fee_total: 0,
+        total_payout: total_stake,
+        entries: entries
+            .iter()
+            .map(|entry| EntryOutcome {
+                stake: entry.stake,
+                payout: entry.stake,
+            })
+            .collect(),
+    }
+}
+
+fn settle_round(
+    round_id: u64,
+    (start_ts, end_ts): (i64, i64),
+    final_price: i64,
+    carried_in: u64,
+    entries: &[Entry],
+    params: &MarketParams,
+    seed: &[u8; 32],
+) -> RoundOutcome {
+    let total_stake = total_stake(entries);
+    let pool = payout_pool(total_stake, carried_in, params.house_edge_bps).unwrap_or_default();
+    let weight =
+        |submitted_at| entry_weight_bps(start_ts, end_ts, submitted_at, params.early_bonus_bps);
+
+    let settlement: Settlement = match params.rule {
+        Rule::Closest => {
+            let predictions: Vec<Prediction> = entries
+                .iter()
+                .enumerate()
+                .map(|(index, entry)| Prediction {
+                    account: account(index),
+                    predicted_price: entry.predicted_price,
+                    stake: entry.stake as u128,
+                    weight_bps: weight(entry.submitted_at),
+                })
+                .collect();
+            let winner_params = WinnerParams {
+                fee_bps: params.fee_bps,
+                payout_tiers: params.payout_tiers,
+                payout_pool: pool,
+                tie_break: params.tie_break,
+                lucky_draw_bps: params.lucky_draw_bps,
+            };
+            determine_winners(&predictions, final_price as u128, &winner_params, seed)
+        }
+        Rule::Interval {
+            alpha_bps,
+            max_penalty,
+        } => {
+            let predictions: Vec<RangePrediction> = entries
+                .iter()
+                .enumerate()
+                .map(|(index, entry)| RangePrediction {
+                    account: account(index),
+                    lower: entry.lower,
+                    upper: entry.upper,
+                    stake: entry.stake as u128,
+                    weight_bps: weight(entry.submitted_at),
+                })
+                .collect();
+            score_interval_predictions(
+                &predictions,
+                final_price as u128,
+                params.fee_bps,
+                alpha_bps,
+                max_penalty,
+                pool,
+            )
+        }
+    };
+
+    RoundOutcome {
+        round_id,
+        start_ts,
+        end_ts,
+        final_price: Some(final_price),
+        total_stake,
+        carried_in,
+        carried_out: pool
+            .saturating_sub(settlement.total_payout)
+            .saturating_sub(settlement.fee_total),
+        house_edge: house_edge(total_stake, params.house_edge_bps).unwrap_or_default(),
+        fee_total: settlement.fee_total,
+        total_payout: settlement.total_payout,
+        entries: entries
+            .iter()
+            .zip(&settlement.payouts)
+            .map(|(entry, payout)| EntryOutcome {
+                stake: entry.stake,
+                payout: payout.payout,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{model::Recorded, ticks::Tick};
+
+    fn params(rule: Rule) -> MarketParams {
+        MarketParams {
+            round_duration_secs: 60,
+            fee_bps: 100,
+            house_edge_bps: 0,
+            payout_tiers: [0; MAX_PAYOUT_TIERS],
+            tie_break: TieBreak::Split,
+            lucky_draw_bps: 0,
+            early_bonus_bps: 0,
+            rule,
+            twap_window_secs: None,
+        }
+    }
+
+    fn history() -> PriceHistory {
+        PriceHistory::new(vec![
+            Tick {
+                timestamp: 0,
+                price: 1_000,
+            },
+            Tick {
+                timestamp: 60,
+                price: 1_050,
+            },
+            Tick {
+                timestamp: 150,
+                price: 900,
+            },
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rounds_follow_the_history() {
+        let csv = "timestamp,stake,predicted_price\n1,100,1040\n2,300,1100\n";
+        let mut model = Recorded::from_csv(csv.as_bytes()).unwrap();
+        let outcomes = simulate(&history(), &mut model, &params(Rule::Closest), 1);
+
+        // Only [0, 60) and [60, 120) end within the history.
+        assert_eq!(outcomes.len(), 2);
+        let first = &outcomes[0];
+        assert_eq!(first.final_price, Some(1_050));
+        assert_eq!(first.total_stake, 400);
+        // The closest guess gets its stake back less the 1% fee.
+        assert_eq!(
+            first.entries[0],
+            EntryOutcome {
+                stake: 100,
+                payout: 99
+            }
+        );
+        assert_eq!(first.entries[1].payout, 0);
+        assert_eq!(first.fee_total, 1);
+        assert_eq!(first.house_revenue(), 1);
+        assert_eq!(first.winners(), 1);
+        // The losing stake is not revenue: it rolls over into the next round.
+        assert_eq!(first.carried_out, 300);
+
+        assert_eq!(outcomes[1].entries, vec![]);
+        assert_eq!(outcomes[1].winners(), 0);
+        assert_eq!(outcomes[1].carried_in, 300);
+        assert_eq!(outcomes[1].carried_out, 300);
+    }
+
+    #[test]
+    fn test_interval_rule_and_twap() {
+        let ticks = [(0, 1_000), (20, 1_020), (40, 1_040), (60, 1_060)]
+            .map(|(timestamp, price)| Tick { timestamp, price });
+        let history = PriceHistory::new(ticks.to_vec()).unwrap();
+        let csv = "timestamp,stake,predicted_price,lower,upper\n10,100,0,1000,1100\n";
+        let mut market = params(Rule::Interval {
+            alpha_bps: 10_000,
+            max_penalty: 1_000,
+        });
+        market.twap_window_secs = Some(60);
+        let mut model = Recorded::from_csv(csv.as_bytes()).unwrap();
+        let outcomes = simulate(&history, &mut model, &market, 1);
+
+        // The tick at the end of the window holds for no time.
+        assert_eq!(outcomes[0].final_price, Some(1_020));
+        // A lone entry scores the whole pool less the fee.
+        assert_eq!(outcomes[0].total_payout, 99);
+
+        // Only two ticks fall in a 30s window, too few to resolve.
+        market.twap_window_secs = Some(30);
+        let mut model = Recorded::from_csv(csv.as_bytes()).unwrap();
+        let outcomes = simulate(&history, &mut model, &market, 1);
+        assert!(outcomes[0].refunded());
+        assert_eq!(outcomes[0].entries[0].payout, 100);
+        assert_eq!(outcomes[0].house_revenue(), 0);
+    }
+
+    #[test]
+    fn test_entries_are_not_reused_across_rounds() {
+        let csv = "timestamp,stake,predicted_price\n70,5,1050\n";
+        let mut model = Recorded::from_csv(csv.as_bytes()).unwrap();
+        let outcomes = simulate(&history(), &mut model, &params(Rule::Closest), 1);
+        assert_eq!(outcomes[0].entries.len(), 0);
+        assert_eq!(outcomes[1].entries.len(), 1);
+    }
+}
@@ -0,0 +1,124 @@
+//! Price history the backtest replays: a CSV of `timestamp,price` ticks in
+//! the program's integer price units.
+use anyhow::{bail, Context, Result};
+use micro_prediction::oracle::{
+    time_weighted_average, PriceObservation, MAX_PRICE_OBSERVATIONS, MIN_PRICE_OBSERVATIONS,
+};
+use serde::Deserialize;
+use std::io::Read;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct Tick {
+    pub timestamp: i64,
+    pub price: i64,
+}
+
+pub struct PriceHistory {
+    ticks: Vec<Tick>,
+}
+
+impl PriceHistory {
+    pub fn new(mut ticks: Vec<Tick>) -> Result<Self> {
+        if ticks.is_empty() {
+            bail!("price history is empty");
+        }
+        if let Some(tick) = ticks.iter().find(|tick| tick.price < 0) {
+            bail!("negative price {} at {}", tick.price, tick.timestamp);
+        }
+        ticks.sort_by_key(|tick| tick.timestamp);
+        Ok(Self { ticks })
+    }
+
+    pub fn from_csv(reader: impl Read) -> Result<Self> {
+        let ticks = csv::Reader::from_reader(reader)
+            .deserialize()
+            .enumerate()
+            .map(|(row, tick)| tick.with_context(|| format!("invalid tick on row {}", row + 1)))
+            .collect::<Result<Vec<Tick>>>()?;
+        Self::new(ticks)
+    }
+
+    pub fn first_timestamp(&self) -> i64 {
+        self.ticks[0].timestamp
+    }
+
+    pub fn last_timestamp(&self) -> i64 {
+        self.ticks[self.ticks.len() - 1].timestamp
+    }
+
+    /// Latest price published at or before `timestamp`.
+    pub fn price_at(&self, timestamp: i64) -> Option<i64> {
+        let after = self
+            .ticks
+            .partition_point(|tick| tick.timestamp <= timestamp);
+        after.checked_sub(1).map(|index| self.ticks[index].price)
+    }
+
+    /// TWAP the program resolves a round ending at `timestamp` to, with a
+    /// crank recording every tick in `[timestamp - window_secs, timestamp]`
+    /// that `record_price_observation` accepts. `None` when too few would be
+    /// recorded and the round could only be refunded.
+    pub fn twap(&self, timestamp: i64, window_secs: i64) -> Option<i64> {
+        let spacing = (window_secs / MAX_PRICE_OBSERVATIONS as i64).max(1);
+        let start = self
+            .ticks
+            .partition_point(|tick| tick.timestamp < timestamp - window_secs);
+        let mut observations: Vec<PriceObservation> = Vec::new();
+        for tick in &self.ticks[start..] {
+            if tick.timestamp > timestamp {
+                break;
+            }
+            if observations
+                .last()
+                .is_some_and(|last| tick.timestamp - last.timestamp < spacing)
+            {
+                continue;
+            }
+            observations.push(PriceObservation {
+                price: tick.price,
+                timestamp: tick.timestamp,
+            });
+        }
+        // The ring buffer only keeps the latest observations.
+        let kept = &observations[observations.len().saturating_sub(MAX_PRICE_OBSERVATIONS)..];
+        if kept.len() < MIN_PRICE_OBSERVATIONS {
+            return None;
+        }
+        time_weighted_average(kept, timestamp).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_lookup() {
+        let csv = "timestamp,price\n20,300\n10,100\n15,200\n";
+        let history = PriceHistory::from_csv(csv.as_bytes()).unwrap();
+        assert_eq!(
+            (history.first_timestamp(), history.last_timestamp()),
+            (10, 20)
+        );
+        assert_eq!(history.price_at(9), None);
+        assert_eq!(history.price_at(10), Some(100));
+        assert_eq!(history.price_at(19), Some(200));
+        // 100 and 200 held for 5s each; the tick at the end holds for none.
+        assert_eq!(history.twap(20, 10), Some(150));
+        assert_eq!(history.twap(19, 10), None);
+        assert_eq!(history.twap(30, 5), None);
+
+        assert!(PriceHistory::from_csv("timestamp,price\n".as_bytes()).is_err());
+        assert!(PriceHistory::from_csv("timestamp,price\n1,-5\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_twap_observation_spacing() {
+        // With a 64s window observations must be 2s apart, so the tick at 1
+        // is never recorded.
+        let ticks = [(0, 100), (1, 1_000_000), (2, 200), (4, 300), (6, 400)]
+            .map(|(timestamp, price)| Tick { timestamp, price });
+        let history = PriceHistory::new(ticks.to_vec()).unwrap();
+        assert_eq!(history.twap(8, 64), Some(250));
+    }
+}
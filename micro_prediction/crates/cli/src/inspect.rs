@@ -0,0 +1,303 @@
+//! `inspect`: on-chain accounts rendered as tables or JSON.
+use crate::{Format, InspectTarget};
+use anyhow::{Context, Result};
+use micro_prediction_client::{
+    pda::{config_acc, round_acc},
+    state::{decode_config, decode_prediction, decode_round, PredictionExt, RoundExt},
+    Config, Prediction, Round, MICRO_PREDICTION_PROGRAM_ID,
+};
+use serde::Serialize;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::RpcProgramAccountsConfig,
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::pubkey::Pubkey;
+
+/// Offsets of `Prediction.round` and `Prediction.owner`, after the discriminator.
+const PREDICTION_ROUND_OFFSET: usize = 8;
+const PREDICTION_OWNER_OFFSET: usize = 40;
+
+#[derive(Serialize)]
+struct ConfigView {
+    address: String,
+    authority: String,
+    settlement_authority: String,
+    token_mint: String,
+    fee_treasury: String,
+    fee_bps: u16,
+    amendment_fee: u64,
+    house_edge_bps: u16,
+    rollover_vault: String,
+    accrued_fees: u64,
+}
+
+impl ConfigView {
+    fn new(config: &Config) -> Self {
+        Self {
+            address: config_acc().to_string(),
+            authority: config.authority.to_string(),
+            settlement_authority: config.settlement_authority.to_string(),
+            token_mint: config.token_mint.to_string(),
+            fee_treasury: config.fee_treasury.to_string(),
+            fee_bps: config.fee_bps,
+            amendment_fee: config.amendment_fee,
+            house_edge_bps: config.house_edge_bps,
+            rollover_vault: config.rollover_vault.to_string(),
+            accrued_fees: config.accrued_fees,
+        }
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("address", self.address.clone()),
+            ("authority", self.authority.clone()),
+            ("settlement authority", self.settlement_authority.clone()),
+            ("token mint", self.token_mint.clone()),
+            ("fee treasury", self.fee_treasury.clone()),
+            ("fee bps", self.fee_bps.to_string()),
+            ("amendment fee", self.amendment_fee.to_string()),
+            ("house edge bps", self.house_edge_bps.to_string()),
+            ("rollover vault", self.rollover_vault.clone()),
+            ("accrued fees", self.accrued_fees.to_string()),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+struct RoundView {
+    round_id: u64,
+    address: String,
+    status: String,
+    round_type: String,
+    start_ts: i64,
+    end_ts: i64,
+    total_stake: u64,
+    total_paid: u64,
+    committed_payout: u64,
+    carried_in: u64,
+    rolled_over: u64,
+    house_edge_bps: u16,
+    final_price: Option<i64>,
+    settlement_timestamp: Option<i64>,
+    result_commitment: Option<String>,
+    arcium_comp_id: Option<String>,
+    pending_folds: u32,
+    chunks_settled: u16,
+    chunk_count: u16,
+}
+
+impl RoundView {
+    fn new(round: &Round) -> Self {
+        Self {
+            round_id: round.round_id,
+            address: round_acc(round.round_id).to_string(),
+            status: describe(round.round_status(), round.status),
+            round_type: describe(round.round_type(), round.round_type),
+            start_ts: round.start_ts,
+            end_ts: round.end_ts,
+            total_stake: round.total_stake,
+            total_paid: round.total_paid,
+            committed_payout: round.committed_payout,
+            carried_in: round.carried_in,
+            rolled_over: round.rolled_over,
+            house_edge_bps: round.house_edge_bps,
+            final_price: round.final_price,
+            settlement_timestamp: round.settlement_timestamp,
+            result_commitment: round.result_commitment.as_ref().map(hex),
+            arcium_comp_id: round.arcium_comp_id.map(|id| id.to_string()),
+            pending_folds: round.pending_folds,
+            chunks_settled: round.chunks_settled,
+            chunk_count: round.chunk_count,
+        }
+    }
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("round", self.round_id.to_string()),
+            ("address", self.address.clone()),
+            ("status", self.status.clone()),
+            ("type", self.round_type.clone()),
+            ("window", format!("{} .. {}", self.start_ts, self.end_ts)),
+            ("total stake", self.total_stake.to_string()),
+            ("total paid", self.total_paid.to_string()),
+            ("committed payout", self.committed_payout.to_string()),
+            ("carried in", self.carried_in.to_string()),
+            ("rolled over", self.rolled_over.to_string()),
+            ("house edge bps", self.house_edge_bps.to_string()),
+            ("final price", optional(&self.final_price)),
+            ("settled at", optional(&self.settlement_timestamp)),
+            ("result commitment", optional(&self.result_commitment)),
+            ("arcium computation", optional(&self.arcium_comp_id)),
+            ("pending folds", self.pending_folds.to_string()),
+            (
+                "chunks settled",
+                format!("{}/{}", self.chunks_settled, self.chunk_count),
+            ),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+struct PredictionView {
+    address: String,
+    round: String,
+    owner: String,
+    prediction_index: u16,
+    status: String,
+    stake: u64,
+    window_index: u8,
+    weight_bps: u16,
+    accumulated: bool,
+    commitment: String,
+}
+
+impl PredictionView {
+    fn new(address: &Pubkey, prediction: &Prediction) -> Self {
+        Self {
+            address: address.to_string(),
+            round: prediction.round.to_string(),
+            owner: prediction.owner.to_string(),
+            prediction_index: prediction.prediction_index,
+            status: describe(prediction.prediction_status(), prediction.status),
+            stake: prediction.stake,
+            window_index: prediction.window_index,
+            weight_bps: prediction.weight_bps,
+            accumulated: prediction.accumulated,
+            commitment: hex(&prediction.commitment),
+        }
+    }
+
+    const HEADERS: [&'static str; 6] = ["address", "owner", "index", "status", "stake", "weight"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.address.clone(),
+            self.owner.clone(),
+            self.prediction_index.to_string(),
+            self.status.clone(),
+            self.stake.to_string(),
+            self.weight_bps.to_string(),
+        ]
+    }
+}
+
+pub fn run(rpc: &RpcClient, target: InspectTarget, format: Format) -> Result<()> {
+    match target {
+        InspectTarget::Config => {
+            let data = rpc
+                .get_account_data(&config_acc())
+                .context("config not found")?;
+            let view = ConfigView::new(&decode_config(&data)?);
+            emit(format, &view, || print_fields(&view.fields()))
+        }
+        InspectTarget::Round { round_id } => {
+            let data = rpc
+                .get_account_data(&round_acc(round_id))
+                .with_context(|| format!("round {round_id} not found"))?;
+            let view = RoundView::new(&decode_round(&data)?);
+            emit(format, &view, || print_fields(&view.fields()))
+        }
+        InspectTarget::Prediction { address } => {
+            let data = rpc
+                .get_account_data(&address)
+                .with_context(|| format!("prediction {address} not found"))?;
+            let view = PredictionView::new(&address, &decode_prediction(&data)?);
+            emit(format, &view, || {
+                print_table(&PredictionView::HEADERS, &[view.row()])
+            })
+        }
+        InspectTarget::Predictions { round_id, owner } => {
+            let mut filters = vec![
+                RpcFilterType::DataSize(Prediction::SPACE as u64),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    PREDICTION_ROUND_OFFSET,
+                    round_acc(round_id).as_ref(),
+                )),
+            ];
+            if let Some(owner) = owner {
+                filters.push(RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                    PREDICTION_OWNER_OFFSET,
+                    owner.as_ref(),
+                )));
+            }
+            let config = RpcProgramAccountsConfig {
+                filters: Some(filters),
+                ..Default::default()
+            };
+            let accounts = rpc
+                .get_program_accounts_with_config(&MICRO_PREDICTION_PROGRAM_ID, config)
+                .context("failed to list predictions")?;
+
+            let mut views = accounts
+                .iter()
+                .map(|(address, account)| {
+                    Ok(PredictionView::new(
+                        address,
+                        &decode_prediction(&account.data)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            views.sort_by(|a, b| {
+                (&a.owner, a.prediction_index).cmp(&(&b.owner, b.prediction_index))
+            });
+            emit(format, &views, || {
+                let rows: Vec<_> = views.iter().map(PredictionView::row).collect();
+                print_table(&PredictionView::HEADERS, &rows)
+            })
+        }
+    }
+}
+
+fn emit<T: Serialize>(format: Format, value: &T, table: impl FnOnce()) -> Result<()> {
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        Format::Table => table(),
+    }
+    Ok(())
+}
+
+fn print_fields(fields: &[(&str, String)]) {
+    let width = fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, value) in fields {
+        println!("{name:<width$}  {value}");
+    }
+}
+
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(headers.to_vec());
+    for row in rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
+
+fn describe<T: std::fmt::Debug, E>(value: Result<T, E>, raw: u8) -> String {
+    match value {
+        Ok(value) => format!("{value:?}"),
+        Err(_) => format!("unknown({raw})"),
+    }
+}
+
+fn optional<T: ToString>(value: &Option<T>) -> String {
+    value
+        .as_ref()
+        .map_or_else(|| "-".to_string(), ToString::to_string)
+}
+
+fn hex(bytes: &[u8; 32]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -0,0 +1,327 @@
+//! Operator CLI for `micro_prediction`: config and round administration,
+//! resolution steps and inspection of on-chain state.
+
+mod inspect;
+mod runner;
+
+use anyhow::{ensure, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use micro_prediction_client::{instruction::*, pda::config_acc, state::decode_config};
+use runner::Runner;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Signer},
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Parser)]
+#[command(name = "micro-prediction-cli", version, about)]
+struct Cli {
+    /// Keypair that signs and pays: the config authority for admin commands,
+    /// the settlement authority for `finalize`.
+    #[arg(long, short = 'k', global = true, env = "MICRO_PREDICTION_KEYPAIR")]
+    keypair: Option<String>,
+    /// RPC URL or one of `localhost`, `devnet`, `mainnet-beta`.
+    #[arg(long, short = 'u', global = true, default_value = "localhost")]
+    url: String,
+    /// Simulate instead of sending, printing every instruction and the logs.
+    #[arg(long, global = true)]
+    dry_run: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the global config, optionally with its rollover vault.
+    InitConfig(InitConfigArgs),
+    /// Open a single round.
+    OpenRound {
+        round_id: u64,
+        #[command(flatten)]
+        schedule: ScheduleArgs,
+    },
+    /// Open `count` consecutive rounds, back to back unless `--gap-secs` is set.
+    OpenSeries {
+        first_round_id: u64,
+        count: u64,
+        #[command(flatten)]
+        schedule: ScheduleArgs,
+        #[arg(long, default_value_t = 0)]
+        gap_secs: i64,
+    },
+    /// Print on-chain state as a table or JSON.
+    Inspect {
+        #[command(subcommand)]
+        target: InspectTarget,
+        #[arg(long, value_enum, default_value_t = Format::Table, global = true)]
+        output: Format,
+    },
+    /// Close a round to new entries once it has ended.
+    BeginResolution {
+        round_id: u64,
+        /// Hex-encoded Merkle root of the payouts, if already known.
+        #[arg(long, value_parser = parse_hash)]
+        result_commitment: Option<[u8; 32]>,
+        /// Arcium computation that resolves the round.
+        #[arg(long)]
+        comp_id: Option<Pubkey>,
+    },
+    /// Record the final price of a resolving round; signed by the settlement authority.
+    Finalize {
+        round_id: u64,
+        #[arg(long, allow_hyphen_values = true)]
+        final_price: i64,
+        /// Settlement time; defaults to now.
+        #[arg(long)]
+        timestamp: Option<i64>,
+    },
+    /// Abandon rounds so every entry can be refunded.
+    MarkRefunded {
+        #[arg(required = true)]
+        round_ids: Vec<u64>,
+    },
+    /// Move what finalized or refunded rounds left behind into the rollover vault.
+    Sweep {
+        #[arg(required = true)]
+        round_ids: Vec<u64>,
+    },
+    /// Pay house revenue from the rollover vault out to the fee treasury.
+    CollectFees {
+        /// Defaults to everything accrued so far.
+        #[arg(long)]
+        amount: Option<u64>,
+    },
+}
+
+#[derive(Args)]
+struct InitConfigArgs {
+    #[arg(long)]
+    mint: Pubkey,
+    #[arg(long)]
+    fee_treasury: Pubkey,
+    #[arg(long)]
+    settlement_authority: Pubkey,
+    #[arg(long)]
+    fee_bps: u16,
+    #[arg(long)]
+    house_edge_bps: Option<u16>,
+    #[arg(long)]
+    amendment_fee: Option<u64>,
+    /// Also create the rollover vault.
+    #[arg(long)]
+    rollover: bool,
+}
+
+#[derive(Args)]
+struct ScheduleArgs {
+    /// Start of the (first) round; defaults to now.
+    #[arg(long)]
+    start_ts: Option<i64>,
+    #[arg(long)]
+    duration_secs: i64,
+    /// Pyth price account the round resolves against.
+    #[arg(long)]
+    price_account: Pubkey,
+}
+
+#[derive(Subcommand)]
+enum InspectTarget {
+    Config,
+    Round {
+        round_id: u64,
+    },
+    /// Every prediction of a round, optionally of one owner.
+    Predictions {
+        round_id: u64,
+        #[arg(long)]
+        owner: Option<Pubkey>,
+    },
+    Prediction {
+        address: Pubkey,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+}
+
+fn parse_hash(value: &str) -> Result<[u8; 32], String> {
+    let value = value.strip_prefix("0x").unwrap_or(value);
+    if value.len() != 64 {
+        return Err("expected 32 hex-encoded bytes".to_string());
+    }
+    let mut hash = [0u8; 32];
+    for (byte, chunk) in hash.iter_mut().zip(value.as_bytes().chunks(2)) {
+        let chunk = std::str::from_utf8(chunk).map_err(|err| err.to_string())?;
+        *byte = u8::from_str_radix(chunk, 16).map_err(|err| err.to_string())?;
+    }
+    Ok(hash)
+}
+
+fn resolve_url(url: &str) -> &str {
+    match url {
+        "localhost" | "l" => "http://127.0.0.1:8899",
+        "devnet" | "d" => "https://api.devnet.solana.com",
+        "mainnet-beta" | "m" => "https://api.mainnet-beta.solana.com",
+        url => url,
+    }
+}
+
+fn default_keypair_path() -> Result<String> {
+    let home = std::env::var("HOME").context("HOME is not set; pass --keypair")?;
+    Ok(format!("{home}/.config/solana/id.json"))
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let keypair_path = match cli.keypair {
+        Some(path) => path,
+        None => default_keypair_path()?,
+    };
+    let signer = read_keypair_file(&keypair_path)
+        .map_err(|err| anyhow::anyhow!("failed to read keypair {keypair_path}: {err}"))?;
+    let runner = Runner::new(resolve_url(&cli.url), signer, cli.dry_run);
+    let operator = runner.signer().pubkey();
+
+    match cli.command {
+        Command::InitConfig(args) => {
+            let mut ixs = vec![initialize_ix(
+                &operator,
+                &args.mint,
+                &args.fee_treasury,
+                &args.settlement_authority,
+                args.fee_bps,
+            )];
+            if let Some(house_edge_bps) = args.house_edge_bps {
+                ixs.push(set_house_edge_ix(&operator, house_edge_bps));
+            }
+            if let Some(amendment_fee) = args.amendment_fee {
+                ixs.push(set_amendment_fee_ix(&operator, amendment_fee));
+            }
+            if args.rollover {
+                ixs.push(init_rollover_vault_ix(&operator, &args.mint));
+            }
+            runner.submit("init-config", ixs)?;
+        }
+        Command::OpenRound { round_id, schedule } => {
+            let mint = config_mint(&runner)?;
+            let start_ts = schedule.start_ts.unwrap_or_else(now);
+            let ix = initialize_round_ix(
+                &operator,
+                &mint,
+                round_id,
+                start_ts,
+                start_ts + schedule.duration_secs,
+                &schedule.price_account,
+            );
+            runner.submit(&format!("open-round {round_id}"), vec![ix])?;
+        }
+        Command::OpenSeries {
+            first_round_id,
+            count,
+            schedule,
+            gap_secs,
+        } => {
+            let mint = config_mint(&runner)?;
+            let mut start_ts = schedule.start_ts.unwrap_or_else(now);
+            for round_id in first_round_id..first_round_id + count {
+                let end_ts = start_ts + schedule.duration_secs;
+                let ix = initialize_round_ix(
+                    &operator,
+                    &mint,
+                    round_id,
+                    start_ts,
+                    end_ts,
+                    &schedule.price_account,
+                );
+                runner.submit(&format!("open-round {round_id}"), vec![ix])?;
+                start_ts = end_ts + gap_secs;
+            }
+        }
+        Command::Inspect { target, output } => inspect::run(runner.rpc(), target, output)?,
+        Command::BeginResolution {
+            round_id,
+            result_commitment,
+            comp_id,
+        } => {
+            let ix = begin_resolution_ix(&operator, round_id, result_commitment, comp_id);
+            runner.submit(&format!("begin-resolution {round_id}"), vec![ix])?;
+        }
+        Command::Finalize {
+            round_id,
+            final_price,
+            timestamp,
+        } => {
+            let ix = finalize_round_ix(
+                &operator,
+                round_id,
+                final_price,
+                timestamp.unwrap_or_else(now),
+            );
+            runner.submit(&format!("finalize {round_id}"), vec![ix])?;
+        }
+        Command::MarkRefunded { round_ids } => {
+            for round_id in round_ids {
+                let ix = mark_round_refunded_ix(&operator, round_id);
+                runner.submit(&format!("mark-refunded {round_id}"), vec![ix])?;
+            }
+        }
+        Command::Sweep { round_ids } => {
+            for round_id in round_ids {
+                let ix = sweep_round_to_rollover_ix(round_id);
+                runner.submit(&format!("sweep {round_id}"), vec![ix])?;
+            }
+        }
+        Command::CollectFees { amount } => {
+            let config = fetch_config(&runner)?;
+            let amount = amount.unwrap_or(config.accrued_fees);
+            ensure!(amount > 0, "no fees have accrued");
+            let ix = collect_fees_ix(&operator, &config.fee_treasury, amount);
+            runner.submit("collect-fees", vec![ix])?;
+        }
+    }
+
+    Ok(())
+}
+
+fn fetch_config(runner: &Runner) -> Result<micro_prediction_client::Config> {
+    let data = runner
+        .rpc()
+        .get_account_data(&config_acc())
+        .context("failed to fetch the config; run init-config first")?;
+    Ok(decode_config(&data)?)
+}
+
+fn config_mint(runner: &Runner) -> Result<Pubkey> {
+    Ok(fetch_config(runner)?.token_mint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_hash() {
+        let hex = "ab".repeat(32);
+        assert_eq!(parse_hash(&hex).unwrap(), [0xab; 32]);
+        assert_eq!(parse_hash(&format!("0x{hex}")).unwrap(), [0xab; 32]);
+        assert!(parse_hash("abcd").is_err());
+        assert!(parse_hash(&"zz".repeat(32)).is_err());
+    }
+}
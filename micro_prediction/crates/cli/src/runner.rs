@@ -0,0 +1,91 @@
+//! Sends, or with `--dry-run` simulates, the instructions of one command.
+use anyhow::{bail, Context, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+pub struct Runner {
+    rpc: RpcClient,
+    signer: Keypair,
+    dry_run: bool,
+}
+
+impl Runner {
+    pub fn new(url: &str, signer: Keypair, dry_run: bool) -> Self {
+        Self {
+            rpc: RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed()),
+            signer,
+            dry_run,
+        }
+    }
+
+    pub fn rpc(&self) -> &RpcClient {
+        &self.rpc
+    }
+
+    pub fn signer(&self) -> &Keypair {
+        &self.signer
+    }
+
+    /// Sends `ixs` as one transaction signed and paid for by the operator.
+    pub fn submit(&self, label: &str, ixs: Vec<Instruction>) -> Result<()> {
+        let blockhash = self
+            .rpc
+            .get_latest_blockhash()
+            .context("failed to fetch a recent blockhash")?;
+        let tx = Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&self.signer.pubkey()),
+            &[&self.signer],
+            blockhash,
+        );
+
+        if !self.dry_run {
+            let signature = self
+                .rpc
+                .send_and_confirm_transaction(&tx)
+                .with_context(|| format!("{label} failed"))?;
+            println!("{label}: {signature}");
+            return Ok(());
+        }
+
+        println!("{label} (dry run)");
+        for (index, ix) in ixs.iter().enumerate() {
+            print_instruction(index, ix);
+        }
+        let simulation = self
+            .rpc
+            .simulate_transaction(&tx)
+            .with_context(|| format!("failed to simulate {label}"))?
+            .value;
+        for line in simulation.logs.unwrap_or_default() {
+            println!("  | {line}");
+        }
+        if let Some(units) = simulation.units_consumed {
+            println!("  compute units: {units}");
+        }
+        if let Some(err) = simulation.err {
+            bail!("{label} would fail: {err}");
+        }
+        Ok(())
+    }
+}
+
+fn print_instruction(index: usize, ix: &Instruction) {
+    println!("  #{index} program {}", ix.program_id);
+    for meta in &ix.accounts {
+        let flags = match (meta.is_signer, meta.is_writable) {
+            (true, true) => "signer, writable",
+            (true, false) => "signer",
+            (false, true) => "writable",
+            (false, false) => "readonly",
+        };
+        println!("     {} ({flags})", meta.pubkey);
+    }
+    let data: String = ix.data.iter().map(|byte| format!("{byte:02x}")).collect();
+    println!("     data {data}");
+}
@@ -0,0 +1,748 @@
+//! Instruction builders for the `micro_prediction` program.
+use crate::pda::{
+    accumulator_acc, config_acc, escrow_acc, observations_acc, prediction_acc,
+    resolution_chunks_acc, rollover_vault_acc, round_acc, round_stats_acc,
+};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::Instruction, system_program::ID as SYSTEM_PROGRAM_ID,
+        sysvar::instructions::ID as INSTRUCTIONS_SYSVAR_ID,
+    },
+    InstructionData,
+};
+use micro_prediction::{
+    accounts, instruction as args, EncryptedPayload, PriceAttestation, PriceSource,
+    RoundStatsInput, ACCUMULATOR_STATE_LEN, ID as PROGRAM_ID, SCAN_STATE_LEN,
+};
+
+/// SPL Token program, which owns every vault of the program.
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+fn build(accounts: Vec<AccountMeta>, data: Vec<u8>) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts,
+        data,
+    }
+}
+
+fn readonly(keys: &[Pubkey]) -> impl Iterator<Item = AccountMeta> + '_ {
+    keys.iter()
+        .map(|key| AccountMeta::new_readonly(*key, false))
+}
+
+pub fn initialize_ix(
+    authority: &Pubkey,
+    token_mint: &Pubkey,
+    fee_treasury: &Pubkey,
+    settlement_authority: &Pubkey,
+    fee_bps: u16,
+) -> Instruction {
+    let accounts = accounts::Initialize {
+        authority: *authority,
+        token_mint: *token_mint,
+        fee_treasury: *fee_treasury,
+        config: config_acc(),
+        system_program: SYSTEM_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+    let data = args::Initialize {
+        settlement_authority: *settlement_authority,
+        fee_bps,
+    }
+    .data();
+
+    build(accounts, data)
+}
+
+fn update_config_accounts(authority: &Pubkey) -> Vec<AccountMeta> {
+    accounts::UpdateConfig {
+        authority: *authority,
+        config: config_acc(),
+    }
+    .to_account_metas(None)
+}
+
+pub fn migrate_config_ix(authority: &Pubkey) -> Instruction {
+    let accounts = accounts::MigrateConfig {
+        authority: *authority,
+        config: config_acc(),
+        system_program: SYSTEM_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::MigrateConfig {}.data())
+}
+
+pub fn migrate_round_ix(authority: &Pubkey, round_id: u64) -> Instruction {
+    let accounts = accounts::MigrateRound {
+        authority: *authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+        system_program: SYSTEM_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::MigrateRound { _round_id: round_id }.data())
+}
+
+pub fn migrate_prediction_ix(authority: &Pubkey, prediction: &Pubkey) -> Instruction {
+    let accounts = accounts::MigratePrediction {
+        authority: *authority,
+        config: config_acc(),
+        prediction: *prediction,
+        system_program: SYSTEM_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::MigratePrediction {}.data())
+}
+
+pub fn set_amendment_fee_ix(authority: &Pubkey, amendment_fee: u64) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        args::SetAmendmentFee { amendment_fee }.data(),
+    )
+}
+
+pub fn set_house_edge_ix(authority: &Pubkey, house_edge_bps: u16) -> Instruction {
+    build(
+        update_config_accounts(authority),
+        args::SetHouseEdge { house_edge_bps }.data(),
+    )
+}
+
+pub fn init_rollover_vault_ix(authority: &Pubkey, token_mint: &Pubkey) -> Instruction {
+    let accounts = accounts::InitRolloverVault {
+        authority: *authority,
+        config: config_acc(),
+        token_mint: *token_mint,
+        rollover_vault: rollover_vault_acc(),
+        token_program: TOKEN_PROGRAM_ID,
+        system_program: SYSTEM_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::InitRolloverVault {}.data())
+}
+
+pub fn top_up_rollover_ix(
+    funder: &Pubkey,
+    funder_token_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let accounts = accounts::TopUpRollover {
+        funder: *funder,
+        config: config_acc(),
+        funder_token_account: *funder_token_account,
+        rollover_vault: rollover_vault_acc(),
+        token_program: TOKEN_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::TopUpRollover { amount }.data())
+}
+
+pub fn seed_round_from_rollover_ix(authority: &Pubkey, round_id: u64, amount: u64) -> Instruction {
+    let accounts = accounts::SeedRoundFromRollover {
+        authority: *authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+        rollover_vault: rollover_vault_acc(),
+        escrow_vault: escrow_acc(round_id),
+        token_program: TOKEN_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::SeedRoundFromRollover { amount }.data())
+}
+
+/// Permissionless.
+pub fn sweep_round_to_rollover_ix(round_id: u64) -> Instruction {
+    let accounts = accounts::SweepRoundToRollover {
+        config: config_acc(),
+        round: round_acc(round_id),
+        escrow_vault: escrow_acc(round_id),
+        rollover_vault: rollover_vault_acc(),
+        token_program: TOKEN_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::SweepRoundToRollover {}.data())
+}
+
+pub fn collect_fees_ix(authority: &Pubkey, fee_treasury: &Pubkey, amount: u64) -> Instruction {
+    let accounts = accounts::CollectFees {
+        authority: *authority,
+        config: config_acc(),
+        rollover_vault: rollover_vault_acc(),
+        fee_treasury: *fee_treasury,
+        token_program: TOKEN_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::CollectFees { amount }.data())
+}
+
+pub fn initialize_round_ix(
+    authority: &Pubkey,
+    token_mint: &Pubkey,
+    round_id: u64,
+    start_ts: i64,
+    end_ts: i64,
+    pyth_price_account: &Pubkey,
+) -> Instruction {
+    let accounts = accounts::InitializeRound {
+        authority: *authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+        token_mint: *token_mint,
+        escrow_vault: escrow_acc(round_id),
+        token_program: TOKEN_PROGRAM_ID,
+        system_program: SYSTEM_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+    let data = args::InitializeRound {
+        round_id,
+        start_ts,
+        end_ts,
+        pyth_price_account: *pyth_price_account,
+    }
+    .data();
+
+    build(accounts, data)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn configure_price_sources_ix(
+    authority: &Pubkey,
+    round_id: u64,
+    sources: Vec<PriceSource>,
+    min_price_sources: u8,
+    max_price_age_secs: u32,
+    max_price_deviation_bps: u16,
+    max_price_age_slots: u64,
+    min_pull_signatures: u8,
+) -> Instruction {
+    let accounts = accounts::ConfigurePriceSources {
+        authority: *authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+    }
+    .to_account_metas(None);
+    let data = args::ConfigurePriceSources {
+        sources,
+        min_price_sources,
+        max_price_age_secs,
+        max_price_deviation_bps,
+        max_price_age_slots,
+        min_pull_signatures,
+    }
+    .data();
+
+    build(accounts, data)
+}
+
+pub fn configure_twap_ix(authority: &Pubkey, round_id: u64, window_secs: u32) -> Instruction {
+    let accounts = accounts::ConfigureTwap {
+        authority: *authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+        observations: observations_acc(round_id),
+        system_program: SYSTEM_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::ConfigureTwap { window_secs }.data())
+}
+
+/// Permissionless crank. `price_accounts` are the round's push and pull
+/// sources, in any order; signed attestations need their Ed25519 verify
+/// instructions earlier in the same transaction.
+pub fn record_price_observation_ix(
+    round_id: u64,
+    price_accounts: &[Pubkey],
+    attestations: Vec<PriceAttestation>,
+) -> Instruction {
+    let mut accounts = accounts::RecordPriceObservation {
+        round: round_acc(round_id),
+        observations: observations_acc(round_id),
+        instructions_sysvar: INSTRUCTIONS_SYSVAR_ID,
+    }
+    .to_account_metas(None);
+    accounts.extend(readonly(price_accounts));
+
+    build(
+        accounts,
+        args::RecordPriceObservation { attestations }.data(),
+    )
+}
+
+fn configure_round_accounts(authority: &Pubkey, round_id: u64) -> Vec<AccountMeta> {
+    accounts::ConfigureRound {
+        authority: *authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+    }
+    .to_account_metas(None)
+}
+
+pub fn set_payout_tiers_ix(
+    authority: &Pubkey,
+    round_id: u64,
+    payout_tiers: Vec<u16>,
+) -> Instruction {
+    build(
+        configure_round_accounts(authority, round_id),
+        args::SetPayoutTiers { payout_tiers }.data(),
+    )
+}
+
+pub fn set_round_type_ix(
+    authority: &Pubkey,
+    round_id: u64,
+    round_type: u8,
+    interval_alpha_bps: u16,
+    max_interval_penalty: u64,
+) -> Instruction {
+    build(
+        configure_round_accounts(authority, round_id),
+        args::SetRoundType {
+            round_type,
+            interval_alpha_bps,
+            max_interval_penalty,
+        }
+        .data(),
+    )
+}
+
+pub fn set_early_bonus_ix(authority: &Pubkey, round_id: u64, early_bonus_bps: u16) -> Instruction {
+    build(
+        configure_round_accounts(authority, round_id),
+        args::SetEarlyBonus { early_bonus_bps }.data(),
+    )
+}
+
+pub fn set_draw_rules_ix(
+    authority: &Pubkey,
+    round_id: u64,
+    tie_break: u8,
+    lucky_draw_bps: u16,
+) -> Instruction {
+    build(
+        configure_round_accounts(authority, round_id),
+        args::SetDrawRules {
+            tie_break,
+            lucky_draw_bps,
+        }
+        .data(),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn submit_prediction_ix(
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    round_id: u64,
+    prediction_index: u16,
+    commitment: [u8; 32],
+    window_index: u8,
+    stake: u64,
+    payload: EncryptedPayload,
+) -> Instruction {
+    let accounts = accounts::SubmitPrediction {
+        user: *user,
+        config: config_acc(),
+        round: round_acc(round_id),
+        prediction: prediction_acc(round_id, user, prediction_index),
+        user_token_account: *user_token_account,
+        escrow_vault: escrow_acc(round_id),
+        token_program: TOKEN_PROGRAM_ID,
+        system_program: SYSTEM_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+    let data = args::SubmitPrediction {
+        commitment,
+        window_index,
+        stake,
+        prediction_index,
+        payload,
+    }
+    .data();
+
+    build(accounts, data)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn amend_prediction_ix(
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    fee_treasury: &Pubkey,
+    round_id: u64,
+    prediction_index: u16,
+    commitment: [u8; 32],
+    payload: EncryptedPayload,
+    new_stake: u64,
+) -> Instruction {
+    let accounts = accounts::AmendPrediction {
+        user: *user,
+        config: config_acc(),
+        round: round_acc(round_id),
+        prediction: prediction_acc(round_id, user, prediction_index),
+        user_token_account: *user_token_account,
+        escrow_vault: escrow_acc(round_id),
+        fee_treasury: *fee_treasury,
+        token_program: TOKEN_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+    let data = args::AmendPrediction {
+        commitment,
+        payload,
+        new_stake,
+    }
+    .data();
+
+    build(accounts, data)
+}
+
+pub fn cancel_prediction_ix(
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    round_id: u64,
+    prediction_index: u16,
+) -> Instruction {
+    let accounts = accounts::CancelPrediction {
+        user: *user,
+        config: config_acc(),
+        round: round_acc(round_id),
+        prediction: prediction_acc(round_id, user, prediction_index),
+        user_token_account: *user_token_account,
+        escrow_vault: escrow_acc(round_id),
+        token_program: TOKEN_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::CancelPrediction {}.data())
+}
+
+pub fn init_round_accumulator_ix(
+    authority: &Pubkey,
+    round_id: u64,
+    bucket_origin: u64,
+    bucket_width: u64,
+    initial_state: [[u8; 32]; ACCUMULATOR_STATE_LEN],
+    nonce: u128,
+) -> Instruction {
+    let accounts = accounts::InitRoundAccumulator {
+        authority: *authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+        accumulator: accumulator_acc(round_id),
+        system_program: SYSTEM_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+    let data = args::InitRoundAccumulator {
+        bucket_origin,
+        bucket_width,
+        initial_state,
+        nonce,
+    }
+    .data();
+
+    build(accounts, data)
+}
+
+pub fn fold_prediction_ix(
+    settlement_authority: &Pubkey,
+    round_id: u64,
+    prediction: &Pubkey,
+    new_state: [[u8; 32]; ACCUMULATOR_STATE_LEN],
+    nonce: u128,
+    arcium_comp_id: Option<Pubkey>,
+) -> Instruction {
+    let accounts = accounts::FoldPrediction {
+        settlement_authority: *settlement_authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+        accumulator: accumulator_acc(round_id),
+        prediction: *prediction,
+    }
+    .to_account_metas(None);
+    let data = args::FoldPrediction {
+        new_state,
+        nonce,
+        arcium_comp_id,
+    }
+    .data();
+
+    build(accounts, data)
+}
+
+pub fn begin_resolution_ix(
+    authority: &Pubkey,
+    round_id: u64,
+    result_commitment: Option<[u8; 32]>,
+    arcium_comp_id: Option<Pubkey>,
+) -> Instruction {
+    let accounts = accounts::BeginResolution {
+        authority: *authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+    }
+    .to_account_metas(None);
+    let data = args::BeginResolution {
+        result_commitment,
+        arcium_comp_id,
+    }
+    .data();
+
+    build(accounts, data)
+}
+
+pub fn commit_settlement_ix(
+    settlement_authority: &Pubkey,
+    round_id: u64,
+    result_commitment: [u8; 32],
+    total_payout: u64,
+    fee_total: u64,
+    randomness_seed: Option<[u8; 32]>,
+    seed_proof: Vec<[u8; 32]>,
+) -> Instruction {
+    let accounts = accounts::CommitSettlement {
+        settlement_authority: *settlement_authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+    }
+    .to_account_metas(None);
+    let data = args::CommitSettlement {
+        result_commitment,
+        total_payout,
+        fee_total,
+        randomness_seed,
+        seed_proof,
+    }
+    .data();
+
+    build(accounts, data)
+}
+
+pub fn begin_chunked_resolution_ix(
+    settlement_authority: &Pubkey,
+    round_id: u64,
+    chunk_count: u16,
+) -> Instruction {
+    let accounts = accounts::BeginChunkedResolution {
+        settlement_authority: *settlement_authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+        resolution_chunks: resolution_chunks_acc(round_id),
+        system_program: SYSTEM_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+
+    build(
+        accounts,
+        args::BeginChunkedResolution { chunk_count }.data(),
+    )
+}
+
+fn record_chunk_accounts(settlement_authority: &Pubkey, round_id: u64) -> Vec<AccountMeta> {
+    accounts::RecordChunkResolution {
+        settlement_authority: *settlement_authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+        resolution_chunks: resolution_chunks_acc(round_id),
+    }
+    .to_account_metas(None)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn record_chunk_scan_ix(
+    settlement_authority: &Pubkey,
+    round_id: u64,
+    chunk_index: u16,
+    scan_state: [[u8; 32]; SCAN_STATE_LEN],
+    nonce: u128,
+    chunk_entries: u32,
+    chunk_stake: u64,
+    arcium_comp_id: Option<Pubkey>,
+) -> Instruction {
+    let data = args::RecordChunkScan {
+        chunk_index,
+        scan_state,
+        nonce,
+        chunk_entries,
+        chunk_stake,
+        arcium_comp_id,
+    }
+    .data();
+
+    build(record_chunk_accounts(settlement_authority, round_id), data)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn record_chunk_settlement_ix(
+    settlement_authority: &Pubkey,
+    round_id: u64,
+    chunk_index: u16,
+    chunk_root: [u8; 32],
+    chunk_payout: u64,
+    chunk_fees: u64,
+    chunk_entries: u32,
+    scanned_entries: u32,
+    scanned_stake: u64,
+) -> Instruction {
+    let data = args::RecordChunkSettlement {
+        chunk_index,
+        chunk_root,
+        chunk_payout,
+        chunk_fees,
+        chunk_entries,
+        scanned_entries,
+        scanned_stake,
+    }
+    .data();
+
+    build(record_chunk_accounts(settlement_authority, round_id), data)
+}
+
+pub fn record_entry_settlement_ix(
+    settlement_authority: &Pubkey,
+    round_id: u64,
+    prediction: &Pubkey,
+    leaf: [u8; 32],
+) -> Instruction {
+    let accounts = accounts::RecordEntrySettlement {
+        settlement_authority: *settlement_authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+        prediction: *prediction,
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::RecordEntrySettlement { leaf }.data())
+}
+
+/// Permissionless; the payout always goes to a token account of the
+/// prediction's owner.
+pub fn settle_prediction_ix(
+    round_id: u64,
+    prediction: &Pubkey,
+    recipient_token_account: &Pubkey,
+    payout: u64,
+    commitment: [u8; 32],
+    blinding: [u8; 32],
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    let accounts = accounts::SettlePrediction {
+        round: round_acc(round_id),
+        prediction: *prediction,
+        escrow_vault: escrow_acc(round_id),
+        recipient_token_account: *recipient_token_account,
+        token_program: TOKEN_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+    let data = args::SettlePrediction {
+        payout,
+        commitment,
+        blinding,
+        proof,
+    }
+    .data();
+
+    build(accounts, data)
+}
+
+/// Permissionless; the refund always goes to a token account of the
+/// prediction's owner.
+pub fn refund_prediction_ix(
+    round_id: u64,
+    prediction: &Pubkey,
+    user_token_account: &Pubkey,
+) -> Instruction {
+    let accounts = accounts::RefundPrediction {
+        round: round_acc(round_id),
+        prediction: *prediction,
+        escrow_vault: escrow_acc(round_id),
+        user_token_account: *user_token_account,
+        token_program: TOKEN_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::RefundPrediction {}.data())
+}
+
+/// Permissionless. Pass `twap` for rounds resolved from the observation
+/// buffer; `price_accounts` as for `record_price_observation_ix`.
+pub fn resolve_round_price_ix(
+    round_id: u64,
+    twap: bool,
+    price_accounts: &[Pubkey],
+    attestations: Vec<PriceAttestation>,
+) -> Instruction {
+    let mut accounts = accounts::ResolveRoundPrice {
+        round: round_acc(round_id),
+        observations: twap.then(|| observations_acc(round_id)),
+        instructions_sysvar: INSTRUCTIONS_SYSVAR_ID,
+    }
+    .to_account_metas(None);
+    accounts.extend(readonly(price_accounts));
+
+    build(accounts, args::ResolveRoundPrice { attestations }.data())
+}
+
+pub fn record_round_stats_ix(
+    settlement_authority: &Pubkey,
+    round_id: u64,
+    stats: RoundStatsInput,
+    arcium_comp_id: Option<Pubkey>,
+) -> Instruction {
+    let accounts = accounts::RecordRoundStats {
+        settlement_authority: *settlement_authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+        round_stats: round_stats_acc(round_id),
+        system_program: SYSTEM_PROGRAM_ID,
+    }
+    .to_account_metas(None);
+    let data = args::RecordRoundStats {
+        stats,
+        arcium_comp_id,
+    }
+    .data();
+
+    build(accounts, data)
+}
+
+pub fn finalize_round_ix(
+    settlement_authority: &Pubkey,
+    round_id: u64,
+    final_price: i64,
+    timestamp: i64,
+) -> Instruction {
+    let accounts = accounts::FinalizeRound {
+        settlement_authority: *settlement_authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+    }
+    .to_account_metas(None);
+    let data = args::FinalizeRound {
+        final_price,
+        timestamp,
+    }
+    .data();
+
+    build(accounts, data)
+}
+
+pub fn mark_round_refunded_ix(authority: &Pubkey, round_id: u64) -> Instruction {
+    let accounts = accounts::MarkRoundRefunded {
+        authority: *authority,
+        config: config_acc(),
+        round: round_acc(round_id),
+    }
+    .to_account_metas(None);
+
+    build(accounts, args::MarkRoundRefunded {}.data())
+}
@@ -0,0 +1,17 @@
+//! Typed client for the `micro_prediction` program: PDA helpers, instruction
+//! builders for every entrypoint and account decoders, for native services that
+//! should not re-derive seeds by hand.
+
+use anchor_lang::prelude::Pubkey;
+
+pub mod instruction;
+pub mod pda;
+pub mod state;
+
+pub use micro_prediction::{
+    Config, EncryptedPayload, Prediction, PriceAttestation, PriceObservations, PriceSource,
+    ResolutionChunks, Round, RoundAccumulator, RoundStats, RoundStatsInput, ACCUMULATOR_STATE_LEN,
+    SCAN_STATE_LEN,
+};
+
+pub const MICRO_PREDICTION_PROGRAM_ID: Pubkey = micro_prediction::ID;
@@ -0,0 +1,118 @@
+//! Program Derived Addresses of the `micro_prediction` program.
+
+use anchor_lang::prelude::Pubkey;
+use micro_prediction::{
+    ACCUMULATOR_SEED, CONFIG_SEED, ESCROW_SEED, ID as PROGRAM_ID, OBSERVATIONS_SEED,
+    PREDICTION_SEED, RESOLUTION_CHUNKS_SEED, ROLLOVER_SEED, ROUND_SEED, ROUND_STATS_SEED,
+};
+
+pub fn config_acc() -> Pubkey {
+    config_acc_w_bump().0
+}
+
+pub fn config_acc_w_bump() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], &PROGRAM_ID)
+}
+
+pub fn rollover_vault_acc() -> Pubkey {
+    rollover_vault_acc_w_bump().0
+}
+
+pub fn rollover_vault_acc_w_bump() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ROLLOVER_SEED], &PROGRAM_ID)
+}
+
+pub fn round_acc(round_id: u64) -> Pubkey {
+    round_acc_w_bump(round_id).0
+}
+
+pub fn round_acc_w_bump(round_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ROUND_SEED, round_id.to_le_bytes().as_ref()], &PROGRAM_ID)
+}
+
+pub fn escrow_acc(round_id: u64) -> Pubkey {
+    escrow_acc_w_bump(round_id).0
+}
+
+pub fn escrow_acc_w_bump(round_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ESCROW_SEED, round_id.to_le_bytes().as_ref()], &PROGRAM_ID)
+}
+
+pub fn prediction_acc(round_id: u64, owner: &Pubkey, prediction_index: u16) -> Pubkey {
+    prediction_acc_w_bump(round_id, owner, prediction_index).0
+}
+
+pub fn prediction_acc_w_bump(round_id: u64, owner: &Pubkey, prediction_index: u16) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            PREDICTION_SEED,
+            round_id.to_le_bytes().as_ref(),
+            owner.as_ref(),
+            prediction_index.to_le_bytes().as_ref(),
+        ],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn observations_acc(round_id: u64) -> Pubkey {
+    observations_acc_w_bump(round_id).0
+}
+
+pub fn observations_acc_w_bump(round_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[OBSERVATIONS_SEED, round_id.to_le_bytes().as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn round_stats_acc(round_id: u64) -> Pubkey {
+    round_stats_acc_w_bump(round_id).0
+}
+
+pub fn round_stats_acc_w_bump(round_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ROUND_STATS_SEED, round_id.to_le_bytes().as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn accumulator_acc(round_id: u64) -> Pubkey {
+    accumulator_acc_w_bump(round_id).0
+}
+
+pub fn accumulator_acc_w_bump(round_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ACCUMULATOR_SEED, round_id.to_le_bytes().as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn resolution_chunks_acc(round_id: u64) -> Pubkey {
+    resolution_chunks_acc_w_bump(round_id).0
+}
+
+pub fn resolution_chunks_acc_w_bump(round_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[RESOLUTION_CHUNKS_SEED, round_id.to_le_bytes().as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_seeds_match_relayer() {
+        // The relayer derives rounds as ["round", u64 little endian].
+        let (expected, _) =
+            Pubkey::find_program_address(&[b"round", &7u64.to_le_bytes()], &PROGRAM_ID);
+        assert_eq!(round_acc(7), expected);
+    }
+
+    #[test]
+    fn test_predictions_differ_per_index() {
+        let owner = Pubkey::new_unique();
+        assert_ne!(prediction_acc(1, &owner, 0), prediction_acc(1, &owner, 1));
+    }
+}
@@ -0,0 +1,189 @@
+//! Account decoders and typed views of the program's `u8` status fields.
+use anchor_lang::AccountDeserialize;
+use micro_prediction::{
+    Config, Prediction, PriceObservations, ResolutionChunks, Round, RoundAccumulator, RoundStats,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum StateError {
+    #[error("failed to decode {account} account: {source}")]
+    Decode {
+        account: &'static str,
+        source: anchor_lang::error::Error,
+    },
+    #[error("unknown {field} value {value}")]
+    UnknownVariant { field: &'static str, value: u8 },
+}
+
+fn decode<T: AccountDeserialize>(account: &'static str, data: &[u8]) -> Result<T, StateError> {
+    let mut data = data;
+    T::try_deserialize(&mut data).map_err(|source| StateError::Decode { account, source })
+}
+
+pub fn decode_config(data: &[u8]) -> Result<Config, StateError> {
+    decode("Config", data)
+}
+
+pub fn decode_round(data: &[u8]) -> Result<Round, StateError> {
+    decode("Round", data)
+}
+
+pub fn decode_prediction(data: &[u8]) -> Result<Prediction, StateError> {
+    decode("Prediction", data)
+}
+
+pub fn decode_price_observations(data: &[u8]) -> Result<PriceObservations, StateError> {
+    decode("PriceObservations", data)
+}
+
+pub fn decode_round_accumulator(data: &[u8]) -> Result<RoundAccumulator, StateError> {
+    decode("RoundAccumulator", data)
+}
+
+pub fn decode_resolution_chunks(data: &[u8]) -> Result<ResolutionChunks, StateError> {
+    decode("ResolutionChunks", data)
+}
+
+pub fn decode_round_stats(data: &[u8]) -> Result<RoundStats, StateError> {
+    decode("RoundStats", data)
+}
+
+/// Declares a client-side mirror of one of the program's `#[repr(u8)]` enums.
+macro_rules! status_enum {
+    ($name:ident, $field:literal { $($variant:ident = $value:literal),+ $(,)? }) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant),+
+        }
+
+        impl TryFrom<u8> for $name {
+            type Error = StateError;
+
+            fn try_from(value: u8) -> Result<Self, Self::Error> {
+                match value {
+                    $($value => Ok(Self::$variant),)+
+                    _ => Err(StateError::UnknownVariant { field: $field, value }),
+                }
+            }
+        }
+
+        impl From<$name> for u8 {
+            fn from(value: $name) -> u8 {
+                match value {
+                    $($name::$variant => $value,)+
+                }
+            }
+        }
+    };
+}
+
+status_enum!(RoundStatus, "round status" {
+    Open = 0,
+    Resolving = 1,
+    Finalized = 2,
+    Refunded = 3,
+});
+
+status_enum!(RoundType, "round type" {
+    ClosestPrice = 0,
+    IntervalScore = 1,
+});
+
+status_enum!(TieBreak, "tie break" {
+    Split = 0,
+    Random = 1,
+});
+
+status_enum!(ResolutionMode, "resolution mode" {
+    Spot = 0,
+    Twap = 1,
+});
+
+status_enum!(PredictionStatus, "prediction status" {
+    Submitted = 0,
+    Cancelled = 1,
+    Settled = 2,
+    Refunded = 3,
+});
+
+/// Typed accessors for the `u8` fields of a decoded `Round`.
+pub trait RoundExt {
+    fn round_status(&self) -> Result<RoundStatus, StateError>;
+    fn round_type(&self) -> Result<RoundType, StateError>;
+    fn tie_break(&self) -> Result<TieBreak, StateError>;
+    fn resolution_mode(&self) -> Result<ResolutionMode, StateError>;
+}
+
+impl RoundExt for Round {
+    fn round_status(&self) -> Result<RoundStatus, StateError> {
+        self.status.try_into()
+    }
+
+    fn round_type(&self) -> Result<RoundType, StateError> {
+        self.round_type.try_into()
+    }
+
+    fn tie_break(&self) -> Result<TieBreak, StateError> {
+        self.tie_break.try_into()
+    }
+
+    fn resolution_mode(&self) -> Result<ResolutionMode, StateError> {
+        self.resolution_mode.try_into()
+    }
+}
+
+pub trait PredictionExt {
+    fn prediction_status(&self) -> Result<PredictionStatus, StateError>;
+}
+
+impl PredictionExt for Prediction {
+    fn prediction_status(&self) -> Result<PredictionStatus, StateError> {
+        self.status.try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{prelude::Pubkey, AccountSerialize};
+
+    #[test]
+    fn test_status_round_trip() {
+        for value in 0..4u8 {
+            assert_eq!(u8::from(RoundStatus::try_from(value).unwrap()), value);
+            assert_eq!(u8::from(PredictionStatus::try_from(value).unwrap()), value);
+        }
+        assert!(RoundStatus::try_from(4).is_err());
+        assert!(TieBreak::try_from(2).is_err());
+    }
+
+    #[test]
+    fn test_decode_config() {
+        let config = Config {
+            authority: Pubkey::new_unique(),
+            settlement_authority: Pubkey::new_unique(),
+            token_mint: Pubkey::new_unique(),
+            fee_treasury: Pubkey::new_unique(),
+            fee_bps: 250,
+            amendment_fee: 10,
+            house_edge_bps: 100,
+            rollover_vault: Pubkey::new_unique(),
+            bump: 255,
+            rollover_bump: 254,
+            accrued_fees: 50,
+        };
+        let mut data = Vec::new();
+        config.try_serialize(&mut data).unwrap();
+
+        let decoded = decode_config(&data).unwrap();
+        assert_eq!(decoded.authority, config.authority);
+        assert_eq!(decoded.fee_bps, 250);
+        assert!(matches!(
+            decode_round(&data),
+            Err(StateError::Decode {
+                account: "Round",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,152 @@
+//! Read-only JSON API over the indexed tables.
+use crate::db::{Db, HistoryEntry, PredictionRow, RoundSummary, UserTotals, Window};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::{str::FromStr, sync::Arc};
+
+const DEFAULT_LIMIT: u32 = 100;
+const MAX_LIMIT: u32 = 1_000;
+
+pub fn router(db: Arc<Db>) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/config", get(config))
+        .route("/rounds", get(rounds))
+        .route("/rounds/{round_id}", get(round))
+        .route("/users/{owner}/predictions", get(user_predictions))
+        .route("/users/{owner}/history", get(user_history))
+        .with_state(db)
+}
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<rusqlite::Error> for ApiError {
+    fn from(err: rusqlite::Error) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+    }
+}
+
+fn not_found(what: &str) -> ApiError {
+    ApiError(StatusCode::NOT_FOUND, format!("{what} not indexed"))
+}
+
+fn parse_owner(owner: &str) -> Result<String, ApiError> {
+    Pubkey::from_str(owner)
+        .map(|owner| owner.to_string())
+        .map_err(|err| ApiError(StatusCode::BAD_REQUEST, format!("owner: {err}")))
+}
+
+fn clamp_limit(limit: Option<u32>) -> u32 {
+    limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)
+}
+
+#[derive(Serialize)]
+struct Health {
+    snapshot_slot: Option<u64>,
+    cursor: Option<String>,
+}
+
+async fn health(State(db): State<Arc<Db>>) -> Result<Json<Health>, ApiError> {
+    Ok(Json(Health {
+        snapshot_slot: db.snapshot_slot()?,
+        cursor: db.cursor()?,
+    }))
+}
+
+async fn config(State(db): State<Arc<Db>>) -> Result<Response, ApiError> {
+    let config = db.config()?.ok_or_else(|| not_found("config"))?;
+    Ok(Json(config).into_response())
+}
+
+#[derive(Deserialize)]
+struct RoundsQuery {
+    status: Option<String>,
+    limit: Option<u32>,
+}
+
+async fn rounds(
+    State(db): State<Arc<Db>>,
+    Query(query): Query<RoundsQuery>,
+) -> Result<Json<Vec<RoundSummary>>, ApiError> {
+    let status = query.status.map(|status| status.to_lowercase());
+    Ok(Json(db.round_summaries(
+        status.as_deref(),
+        clamp_limit(query.limit),
+    )?))
+}
+
+async fn round(
+    State(db): State<Arc<Db>>,
+    Path(round_id): Path<u64>,
+) -> Result<Json<RoundSummary>, ApiError> {
+    let summary = db
+        .round_summary(round_id)?
+        .ok_or_else(|| not_found("round"))?;
+    Ok(Json(summary))
+}
+
+#[derive(Serialize)]
+struct UserPrediction {
+    round_id: Option<u64>,
+    #[serde(flatten)]
+    prediction: PredictionRow,
+}
+
+async fn user_predictions(
+    State(db): State<Arc<Db>>,
+    Path(owner): Path<String>,
+) -> Result<Json<Vec<UserPrediction>>, ApiError> {
+    let predictions = db
+        .user_predictions(&parse_owner(&owner)?)?
+        .into_iter()
+        .map(|(round_id, prediction)| UserPrediction {
+            round_id,
+            prediction,
+        })
+        .collect();
+    Ok(Json(predictions))
+}
+
+/// Unix-second bounds: `since` is inclusive, `until` exclusive.
+#[derive(Deserialize)]
+struct HistoryQuery {
+    since: Option<i64>,
+    until: Option<i64>,
+    limit: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct History {
+    totals: UserTotals,
+    events: Vec<HistoryEntry>,
+}
+
+async fn user_history(
+    State(db): State<Arc<Db>>,
+    Path(owner): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<History>, ApiError> {
+    let owner = parse_owner(&owner)?;
+    let window = Window {
+        since: query.since,
+        until: query.until,
+        limit: clamp_limit(query.limit),
+    };
+    Ok(Json(History {
+        totals: db.user_totals(&owner, window)?,
+        events: db.user_history(&owner, window)?,
+    }))
+}
@@ -0,0 +1,738 @@
+//! SQLite tables the indexer maintains and the queries the API serves.
+//!
+//! Account tables mirror the latest `getProgramAccounts` snapshot and are
+//! replaced wholesale, so an account that vanished in a rolled-back block
+//! disappears with the next snapshot. Transactions and their events are
+//! appended in slot order and removed again if their block is rolled back
+//! before it is finalized.
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::{path::Path, sync::Mutex};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS config (
+    address TEXT PRIMARY KEY,
+    authority TEXT NOT NULL,
+    settlement_authority TEXT NOT NULL,
+    token_mint TEXT NOT NULL,
+    fee_treasury TEXT NOT NULL,
+    fee_bps INTEGER NOT NULL,
+    amendment_fee INTEGER NOT NULL,
+    house_edge_bps INTEGER NOT NULL,
+    rollover_vault TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS rounds (
+    address TEXT PRIMARY KEY,
+    round_id INTEGER NOT NULL UNIQUE,
+    status TEXT NOT NULL,
+    start_ts INTEGER NOT NULL,
+    end_ts INTEGER NOT NULL,
+    total_stake INTEGER NOT NULL,
+    total_paid INTEGER NOT NULL,
+    committed_payout INTEGER NOT NULL,
+    carried_in INTEGER NOT NULL,
+    rolled_over INTEGER NOT NULL,
+    final_price INTEGER,
+    settlement_timestamp INTEGER
+);
+CREATE TABLE IF NOT EXISTS predictions (
+    address TEXT PRIMARY KEY,
+    round TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    prediction_index INTEGER NOT NULL,
+    window_index INTEGER NOT NULL,
+    stake INTEGER NOT NULL,
+    status TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS predictions_by_owner ON predictions (owner);
+CREATE INDEX IF NOT EXISTS predictions_by_round ON predictions (round);
+CREATE TABLE IF NOT EXISTS transactions (
+    signature TEXT PRIMARY KEY,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    failed INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS transactions_by_slot ON transactions (slot);
+CREATE TABLE IF NOT EXISTS events (
+    signature TEXT NOT NULL,
+    ix_index INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    round TEXT NOT NULL,
+    round_id INTEGER,
+    prediction TEXT,
+    user TEXT,
+    amount INTEGER,
+    PRIMARY KEY (signature, ix_index)
+);
+CREATE INDEX IF NOT EXISTS events_by_user ON events (user);
+CREATE INDEX IF NOT EXISTS events_by_prediction ON events (prediction);
+CREATE INDEX IF NOT EXISTS events_by_round ON events (round);
+";
+
+/// Newest transaction already indexed; `getSignaturesForAddress` pages stop there.
+const CURSOR: &str = "cursor";
+const SNAPSHOT_SLOT: &str = "snapshot_slot";
+
+pub type Result<T> = rusqlite::Result<T>;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ConfigRow {
+    pub address: String,
+    pub authority: String,
+    pub settlement_authority: String,
+    pub token_mint: String,
+    pub fee_treasury: String,
+    pub fee_bps: u16,
+    pub amendment_fee: u64,
+    pub house_edge_bps: u16,
+    pub rollover_vault: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RoundRow {
+    pub address: String,
+    pub round_id: u64,
+    pub status: String,
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub total_stake: u64,
+    pub total_paid: u64,
+    pub committed_payout: u64,
+    pub carried_in: u64,
+    pub rolled_over: u64,
+    pub final_price: Option<i64>,
+    pub settlement_timestamp: Option<i64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PredictionRow {
+    pub address: String,
+    pub round: String,
+    pub owner: String,
+    pub prediction_index: u16,
+    pub window_index: u8,
+    pub stake: u64,
+    pub status: String,
+}
+
+/// Every program account at one slot.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshot {
+    pub slot: u64,
+    pub config: Option<ConfigRow>,
+    pub rounds: Vec<RoundRow>,
+    pub predictions: Vec<PredictionRow>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventRow {
+    pub ix_index: u32,
+    pub kind: &'static str,
+    pub round: String,
+    pub round_id: Option<u64>,
+    pub prediction: Option<String>,
+    pub user: Option<String>,
+    pub amount: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionRecord {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub failed: bool,
+    /// Empty for failed transactions.
+    pub events: Vec<EventRow>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct HistoryEntry {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub kind: String,
+    pub round_id: Option<u64>,
+    pub prediction: Option<String>,
+    pub amount: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct UserTotals {
+    /// Stake of the predictions submitted in the window, as submitted.
+    pub submitted_stake: u64,
+    pub submissions: u64,
+    pub paid_out: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RoundSummary {
+    #[serde(flatten)]
+    pub round: RoundRow,
+    pub entries: u64,
+    pub players: u64,
+    pub cancelled: u64,
+    pub settled: u64,
+    pub refunded: u64,
+}
+
+/// Time and paging bounds of a history query.
+#[derive(Clone, Copy, Debug)]
+pub struct Window {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: u32,
+}
+
+pub struct Db {
+    conn: Mutex<Connection>,
+}
+
+impl Db {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        // WAL lets API reads run while a snapshot is being written.
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::with_connection(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        // A panic while holding the lock cannot leave SQLite half-written.
+        self.conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn meta(&self, key: &str) -> Result<Option<String>> {
+        self.conn()
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+    }
+
+    pub fn cursor(&self) -> Result<Option<String>> {
+        self.meta(CURSOR)
+    }
+
+    pub fn snapshot_slot(&self) -> Result<Option<u64>> {
+        Ok(self.meta(SNAPSHOT_SLOT)?.and_then(|slot| slot.parse().ok()))
+    }
+
+    /// Replaces every account table with `snapshot` in one transaction.
+    pub fn apply_snapshot(&self, snapshot: &Snapshot) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute_batch("DELETE FROM config; DELETE FROM rounds; DELETE FROM predictions;")?;
+        if let Some(config) = &snapshot.config {
+            tx.execute(
+                "INSERT INTO config VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    config.address,
+                    config.authority,
+                    config.settlement_authority,
+                    config.token_mint,
+                    config.fee_treasury,
+                    config.fee_bps,
+                    config.amendment_fee as i64,
+                    config.house_edge_bps,
+                    config.rollover_vault,
+                ],
+            )?;
+        }
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO rounds VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            )?;
+            for round in &snapshot.rounds {
+                insert.execute(params![
+                    round.address,
+                    round.round_id as i64,
+                    round.status,
+                    round.start_ts,
+                    round.end_ts,
+                    round.total_stake as i64,
+                    round.total_paid as i64,
+                    round.committed_payout as i64,
+                    round.carried_in as i64,
+                    round.rolled_over as i64,
+                    round.final_price,
+                    round.settlement_timestamp,
+                ])?;
+            }
+            let mut insert =
+                tx.prepare("INSERT INTO predictions VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+            for prediction in &snapshot.predictions {
+                insert.execute(params![
+                    prediction.address,
+                    prediction.round,
+                    prediction.owner,
+                    prediction.prediction_index,
+                    prediction.window_index,
+                    prediction.stake as i64,
+                    prediction.status,
+                ])?;
+            }
+        }
+        set_meta(&tx, SNAPSHOT_SLOT, &snapshot.slot.to_string())?;
+        tx.commit()
+    }
+
+    /// Records a transaction and its events and moves the cursor to it.
+    /// Recording the same transaction twice is a no-op.
+    pub fn record_transaction(&self, record: &TransactionRecord) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO transactions (signature, slot, block_time, failed)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                record.signature,
+                record.slot as i64,
+                record.block_time,
+                record.failed
+            ],
+        )?;
+        if inserted > 0 {
+            let mut insert = tx.prepare(
+                "INSERT INTO events
+                 (signature, ix_index, kind, round, round_id, prediction, user, amount)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            for event in &record.events {
+                insert.execute(params![
+                    record.signature,
+                    event.ix_index,
+                    event.kind,
+                    event.round,
+                    event.round_id.map(|id| id as i64),
+                    event.prediction,
+                    event.user,
+                    event.amount.map(|amount| amount as i64),
+                ])?;
+            }
+        }
+        set_meta(&tx, CURSOR, &record.signature)?;
+        tx.commit()
+    }
+
+    /// Transactions in slots the cluster has not finalized yet.
+    pub fn unfinalized_signatures(&self, finalized_slot: u64) -> Result<Vec<String>> {
+        let conn = self.conn();
+        let mut statement =
+            conn.prepare("SELECT signature FROM transactions WHERE slot > ?1 ORDER BY slot")?;
+        let rows = statement.query_map([finalized_slot as i64], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Drops transactions whose block was rolled back, with their events, and
+    /// moves the cursor back to the newest transaction still indexed.
+    pub fn remove_transactions(&self, signatures: &[String]) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        for signature in signatures {
+            tx.execute("DELETE FROM events WHERE signature = ?1", [signature])?;
+            tx.execute("DELETE FROM transactions WHERE signature = ?1", [signature])?;
+        }
+        let newest: Option<String> = tx
+            .query_row(
+                "SELECT signature FROM transactions ORDER BY slot DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match newest {
+            Some(signature) => set_meta(&tx, CURSOR, &signature)?,
+            None => {
+                tx.execute("DELETE FROM meta WHERE key = ?1", [CURSOR])?;
+            }
+        }
+        tx.commit()
+    }
+
+    pub fn config(&self) -> Result<Option<ConfigRow>> {
+        self.conn()
+            .query_row("SELECT * FROM config", [], |row| {
+                Ok(ConfigRow {
+                    address: row.get(0)?,
+                    authority: row.get(1)?,
+                    settlement_authority: row.get(2)?,
+                    token_mint: row.get(3)?,
+                    fee_treasury: row.get(4)?,
+                    fee_bps: row.get(5)?,
+                    amendment_fee: row.get::<_, i64>(6)? as u64,
+                    house_edge_bps: row.get(7)?,
+                    rollover_vault: row.get(8)?,
+                })
+            })
+            .optional()
+    }
+
+    pub fn user_predictions(&self, owner: &str) -> Result<Vec<(Option<u64>, PredictionRow)>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT r.round_id, p.* FROM predictions p
+             LEFT JOIN rounds r ON r.address = p.round
+             WHERE p.owner = ?1
+             ORDER BY r.round_id DESC, p.prediction_index",
+        )?;
+        let rows = statement.query_map([owner], |row| {
+            let round_id: Option<i64> = row.get(0)?;
+            Ok((
+                round_id.map(|id| id as u64),
+                PredictionRow {
+                    address: row.get(1)?,
+                    round: row.get(2)?,
+                    owner: row.get(3)?,
+                    prediction_index: row.get(4)?,
+                    window_index: row.get(5)?,
+                    stake: row.get::<_, i64>(6)? as u64,
+                    status: row.get(7)?,
+                },
+            ))
+        })?;
+        rows.collect()
+    }
+
+    /// Events of `owner`'s predictions, newest first. Settlements and refunds
+    /// are sent by anyone, so they are matched through the prediction.
+    pub fn user_history(&self, owner: &str, window: Window) -> Result<Vec<HistoryEntry>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT t.signature, t.slot, t.block_time, e.kind, r.round_id, e.prediction, e.amount
+             {USER_EVENTS}
+             ORDER BY t.slot DESC, e.ix_index DESC
+             LIMIT ?4"
+        ))?;
+        let rows = statement.query_map(
+            params![owner, window.since, window.until, window.limit],
+            |row| {
+                Ok(HistoryEntry {
+                    signature: row.get(0)?,
+                    slot: row.get::<_, i64>(1)? as u64,
+                    block_time: row.get(2)?,
+                    kind: row.get(3)?,
+                    round_id: row.get::<_, Option<i64>>(4)?.map(|id| id as u64),
+                    prediction: row.get(5)?,
+                    amount: row.get::<_, Option<i64>>(6)?.map(|amount| amount as u64),
+                })
+            },
+        )?;
+        rows.collect()
+    }
+
+    pub fn user_totals(&self, owner: &str, window: Window) -> Result<UserTotals> {
+        self.conn().query_row(
+            &format!(
+                "SELECT
+                    COALESCE(SUM(CASE WHEN e.kind = 'prediction_submitted' THEN e.amount END), 0),
+                    COUNT(CASE WHEN e.kind = 'prediction_submitted' THEN 1 END),
+                    COALESCE(SUM(CASE WHEN e.kind = 'prediction_settled' THEN e.amount END), 0)
+                 {USER_EVENTS}"
+            ),
+            params![owner, window.since, window.until],
+            |row| {
+                Ok(UserTotals {
+                    submitted_stake: row.get::<_, i64>(0)? as u64,
+                    submissions: row.get::<_, i64>(1)? as u64,
+                    paid_out: row.get::<_, i64>(2)? as u64,
+                })
+            },
+        )
+    }
+
+    pub fn round_summary(&self, round_id: u64) -> Result<Option<RoundSummary>> {
+        self.conn()
+            .query_row(
+                &format!("{ROUND_SUMMARY} WHERE r.round_id = ?1 GROUP BY r.address"),
+                [round_id as i64],
+                round_summary,
+            )
+            .optional()
+    }
+
+    /// Newest rounds first, optionally only those in `status`.
+    pub fn round_summaries(&self, status: Option<&str>, limit: u32) -> Result<Vec<RoundSummary>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "{ROUND_SUMMARY}
+             WHERE ?1 IS NULL OR r.status = ?1
+             GROUP BY r.address
+             ORDER BY r.round_id DESC
+             LIMIT ?2"
+        ))?;
+        let rows = statement.query_map(params![status, limit], round_summary)?;
+        rows.collect()
+    }
+}
+
+/// Shared `FROM` clause of the per-user event queries; binds the owner to
+/// `?1` and the optional window bounds to `?2` and `?3`.
+const USER_EVENTS: &str = "
+    FROM events e
+    JOIN transactions t ON t.signature = e.signature
+    LEFT JOIN rounds r ON r.address = e.round
+    WHERE (e.user = ?1 OR e.prediction IN (SELECT address FROM predictions WHERE owner = ?1))
+      AND (?2 IS NULL OR t.block_time >= ?2)
+      AND (?3 IS NULL OR t.block_time < ?3)";
+
+const ROUND_SUMMARY: &str = "
+    SELECT r.*,
+        COUNT(p.address),
+        COUNT(DISTINCT p.owner),
+        COUNT(CASE WHEN p.status = 'cancelled' THEN 1 END),
+        COUNT(CASE WHEN p.status = 'settled' THEN 1 END),
+        COUNT(CASE WHEN p.status = 'refunded' THEN 1 END)
+    FROM rounds r
+    LEFT JOIN predictions p ON p.round = r.address";
+
+fn round_summary(row: &Row<'_>) -> Result<RoundSummary> {
+    let amount = |index: usize| row.get::<_, i64>(index).map(|value| value as u64);
+    Ok(RoundSummary {
+        round: RoundRow {
+            address: row.get(0)?,
+            round_id: amount(1)?,
+            status: row.get(2)?,
+            start_ts: row.get(3)?,
+            end_ts: row.get(4)?,
+            total_stake: amount(5)?,
+            total_paid: amount(6)?,
+            committed_payout: amount(7)?,
+            carried_in: amount(8)?,
+            rolled_over: amount(9)?,
+            final_price: row.get(10)?,
+            settlement_timestamp: row.get(11)?,
+        },
+        entries: amount(12)?,
+        players: amount(13)?,
+        cancelled: amount(14)?,
+        settled: amount(15)?,
+        refunded: amount(16)?,
+    })
+}
+
+fn set_meta(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round(round_id: u64, status: &str) -> RoundRow {
+        RoundRow {
+            address: format!("round{round_id}"),
+            round_id,
+            status: status.into(),
+            start_ts: 100,
+            end_ts: 200,
+            total_stake: 0,
+            total_paid: 0,
+            committed_payout: 0,
+            carried_in: 0,
+            rolled_over: 0,
+            final_price: None,
+            settlement_timestamp: None,
+        }
+    }
+
+    fn prediction(address: &str, round_id: u64, owner: &str, status: &str) -> PredictionRow {
+        PredictionRow {
+            address: address.into(),
+            round: format!("round{round_id}"),
+            owner: owner.into(),
+            prediction_index: 0,
+            window_index: 0,
+            stake: 10,
+            status: status.into(),
+        }
+    }
+
+    fn event(ix_index: u32, kind: &'static str, prediction: &str, amount: u64) -> EventRow {
+        EventRow {
+            ix_index,
+            kind,
+            round: "round1".into(),
+            round_id: None,
+            prediction: Some(prediction.into()),
+            user: None,
+            amount: Some(amount),
+        }
+    }
+
+    fn transaction(signature: &str, slot: u64, events: Vec<EventRow>) -> TransactionRecord {
+        TransactionRecord {
+            signature: signature.into(),
+            slot,
+            block_time: Some(slot as i64 * 10),
+            failed: false,
+            events,
+        }
+    }
+
+    const ALL: Window = Window {
+        since: None,
+        until: None,
+        limit: 100,
+    };
+
+    #[test]
+    fn test_snapshot_replaces_accounts() {
+        let db = Db::open_in_memory().unwrap();
+        db.apply_snapshot(&Snapshot {
+            slot: 5,
+            config: None,
+            rounds: vec![round(1, "open"), round(2, "open")],
+            predictions: vec![
+                prediction("p1", 1, "alice", "submitted"),
+                prediction("p2", 1, "bob", "cancelled"),
+                prediction("p3", 1, "bob", "submitted"),
+            ],
+        })
+        .unwrap();
+
+        let summary = db.round_summary(1).unwrap().unwrap();
+        assert_eq!(
+            (summary.entries, summary.players, summary.cancelled),
+            (3, 2, 1)
+        );
+        assert_eq!(db.round_summary(2).unwrap().unwrap().entries, 0);
+        assert_eq!(db.round_summaries(Some("open"), 10).unwrap().len(), 2);
+        assert_eq!(db.round_summaries(None, 1).unwrap()[0].round.round_id, 2);
+
+        // Round 2 was rolled back and no longer shows up in the next snapshot.
+        db.apply_snapshot(&Snapshot {
+            slot: 6,
+            config: None,
+            rounds: vec![round(1, "resolving")],
+            predictions: vec![prediction("p1", 1, "alice", "settled")],
+        })
+        .unwrap();
+        assert_eq!(db.round_summary(2).unwrap(), None);
+        let summary = db.round_summary(1).unwrap().unwrap();
+        assert_eq!(
+            (summary.round.status.as_str(), summary.settled),
+            ("resolving", 1)
+        );
+        assert_eq!(db.user_predictions("bob").unwrap(), vec![]);
+        assert_eq!(db.snapshot_slot().unwrap(), Some(6));
+    }
+
+    #[test]
+    fn test_user_history_and_totals() {
+        let db = Db::open_in_memory().unwrap();
+        db.apply_snapshot(&Snapshot {
+            slot: 1,
+            config: None,
+            rounds: vec![round(1, "resolving")],
+            predictions: vec![
+                prediction("p1", 1, "alice", "settled"),
+                prediction("p2", 1, "bob", "submitted"),
+            ],
+        })
+        .unwrap();
+        let mut submit = event(0, "prediction_submitted", "p1", 40);
+        submit.user = Some("alice".into());
+        db.record_transaction(&transaction("s1", 10, vec![submit]))
+            .unwrap();
+        db.record_transaction(&transaction(
+            "s2",
+            11,
+            vec![event(0, "prediction_submitted", "p2", 5)],
+        ))
+        .unwrap();
+        // The settlement is sent by a keeper and matched through the prediction.
+        db.record_transaction(&transaction(
+            "s3",
+            20,
+            vec![event(1, "prediction_settled", "p1", 70)],
+        ))
+        .unwrap();
+        // Recording a transaction again does not duplicate its events.
+        db.record_transaction(&transaction(
+            "s3",
+            20,
+            vec![event(1, "prediction_settled", "p1", 70)],
+        ))
+        .unwrap();
+
+        let history = db.user_history("alice", ALL).unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|entry| entry.signature.as_str())
+                .collect::<Vec<_>>(),
+            ["s3", "s1"]
+        );
+        assert_eq!(history[0].round_id, Some(1));
+        assert_eq!(
+            db.user_totals("alice", ALL).unwrap(),
+            UserTotals {
+                submitted_stake: 40,
+                submissions: 1,
+                paid_out: 70,
+            }
+        );
+
+        let before_settlement = Window {
+            since: Some(100),
+            until: Some(200),
+            limit: 100,
+        };
+        assert_eq!(
+            db.user_history("alice", before_settlement).unwrap().len(),
+            1
+        );
+        assert_eq!(
+            db.user_totals("alice", before_settlement).unwrap().paid_out,
+            0
+        );
+    }
+
+    #[test]
+    fn test_rolled_back_transactions_are_removed() {
+        let db = Db::open_in_memory().unwrap();
+        db.record_transaction(&transaction(
+            "s1",
+            10,
+            vec![event(0, "prediction_submitted", "p1", 1)],
+        ))
+        .unwrap();
+        db.record_transaction(&transaction(
+            "s2",
+            12,
+            vec![event(0, "prediction_submitted", "p1", 2)],
+        ))
+        .unwrap();
+        assert_eq!(db.cursor().unwrap().as_deref(), Some("s2"));
+        assert_eq!(db.unfinalized_signatures(10).unwrap(), ["s2"]);
+
+        db.remove_transactions(&["s2".into()]).unwrap();
+        assert_eq!(db.cursor().unwrap().as_deref(), Some("s1"));
+        assert_eq!(db.unfinalized_signatures(0).unwrap(), ["s1"]);
+
+        db.remove_transactions(&["s1".into()]).unwrap();
+        assert_eq!(db.cursor().unwrap(), None);
+    }
+}
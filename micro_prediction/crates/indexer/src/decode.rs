@@ -0,0 +1,257 @@
+//! Program instructions decoded into the events the indexer records. The
+//! program does not `emit!` anything, so the instructions of its confirmed
+//! transactions are the event stream.
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use micro_prediction::instruction as ix;
+use solana_sdk::pubkey::Pubkey;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    RoundOpened,
+    PredictionSubmitted,
+    PredictionAmended,
+    PredictionCancelled,
+    ResolutionBegun,
+    SettlementCommitted,
+    PredictionSettled,
+    PredictionRefunded,
+    RoundFinalized,
+    RoundRefunded,
+    RoundSwept,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::RoundOpened => "round_opened",
+            Self::PredictionSubmitted => "prediction_submitted",
+            Self::PredictionAmended => "prediction_amended",
+            Self::PredictionCancelled => "prediction_cancelled",
+            Self::ResolutionBegun => "resolution_begun",
+            Self::SettlementCommitted => "settlement_committed",
+            Self::PredictionSettled => "prediction_settled",
+            Self::PredictionRefunded => "prediction_refunded",
+            Self::RoundFinalized => "round_finalized",
+            Self::RoundRefunded => "round_refunded",
+            Self::RoundSwept => "round_swept",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub kind: EventKind,
+    pub round: Pubkey,
+    /// Only `initialize_round` names the id; the others are joined through
+    /// the round address.
+    pub round_id: Option<u64>,
+    pub prediction: Option<Pubkey>,
+    /// Signer acting on the prediction, when the instruction has one.
+    pub user: Option<Pubkey>,
+    /// Stake for submissions and amendments, payout for settlements and the
+    /// total payout for commitments.
+    pub amount: Option<u64>,
+}
+
+/// Positions of the accounts an event refers to, in the order of the
+/// instruction's `Accounts` struct.
+struct Layout {
+    round: usize,
+    prediction: Option<usize>,
+    user: Option<usize>,
+}
+
+const ROUND_AUTHORITY: Layout = Layout {
+    round: 2,
+    prediction: None,
+    user: None,
+};
+
+const USER_PREDICTION: Layout = Layout {
+    round: 2,
+    prediction: Some(3),
+    user: Some(0),
+};
+
+const PERMISSIONLESS_PREDICTION: Layout = Layout {
+    round: 0,
+    prediction: Some(1),
+    user: None,
+};
+
+fn parse<T: Discriminator + AnchorDeserialize>(data: &[u8]) -> Option<T> {
+    let mut rest = data.strip_prefix(T::DISCRIMINATOR)?;
+    T::deserialize(&mut rest).ok()
+}
+
+fn is<T: Discriminator>(data: &[u8]) -> bool {
+    data.starts_with(T::DISCRIMINATOR)
+}
+
+/// Decodes one `micro_prediction` instruction given its data and resolved
+/// account keys. Instructions that do not change a round's or a
+/// prediction's funds or lifecycle are not events and yield `None`.
+pub fn decode_instruction(data: &[u8], accounts: &[Pubkey]) -> Option<Event> {
+    let (kind, layout, round_id, amount) = if let Some(args) = parse::<ix::InitializeRound>(data) {
+        (
+            EventKind::RoundOpened,
+            ROUND_AUTHORITY,
+            Some(args.round_id),
+            None,
+        )
+    } else if let Some(args) = parse::<ix::SubmitPrediction>(data) {
+        (
+            EventKind::PredictionSubmitted,
+            USER_PREDICTION,
+            None,
+            Some(args.stake),
+        )
+    } else if let Some(args) = parse::<ix::AmendPrediction>(data) {
+        (
+            EventKind::PredictionAmended,
+            USER_PREDICTION,
+            None,
+            Some(args.new_stake),
+        )
+    } else if is::<ix::CancelPrediction>(data) {
+        (EventKind::PredictionCancelled, USER_PREDICTION, None, None)
+    } else if is::<ix::BeginResolution>(data) {
+        (EventKind::ResolutionBegun, ROUND_AUTHORITY, None, None)
+    } else if let Some(args) = parse::<ix::CommitSettlement>(data) {
+        (
+            EventKind::SettlementCommitted,
+            ROUND_AUTHORITY,
+            None,
+            Some(args.total_payout),
+        )
+    } else if let Some(args) = parse::<ix::SettlePrediction>(data) {
+        (
+            EventKind::PredictionSettled,
+            PERMISSIONLESS_PREDICTION,
+            None,
+            Some(args.payout),
+        )
+    } else if is::<ix::RefundPrediction>(data) {
+        (
+            EventKind::PredictionRefunded,
+            PERMISSIONLESS_PREDICTION,
+            None,
+            None,
+        )
+    } else if is::<ix::FinalizeRound>(data) {
+        (EventKind::RoundFinalized, ROUND_AUTHORITY, None, None)
+    } else if is::<ix::MarkRoundRefunded>(data) {
+        (EventKind::RoundRefunded, ROUND_AUTHORITY, None, None)
+    } else if is::<ix::SweepRoundToRollover>(data) {
+        let layout = Layout {
+            round: 1,
+            prediction: None,
+            user: None,
+        };
+        (EventKind::RoundSwept, layout, None, None)
+    } else {
+        return None;
+    };
+
+    let account = |index: Option<usize>| match index {
+        Some(index) => accounts.get(index).copied().map(Some),
+        None => Some(None),
+    };
+    Some(Event {
+        kind,
+        round: *accounts.get(layout.round)?,
+        round_id,
+        prediction: account(layout.prediction)?,
+        user: account(layout.user)?,
+        amount,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use micro_prediction_client::{
+        instruction::{
+            initialize_round_ix, settle_prediction_ix, submit_prediction_ix,
+            sweep_round_to_rollover_ix,
+        },
+        pda::{prediction_acc, round_acc},
+        EncryptedPayload,
+    };
+    use solana_sdk::instruction::Instruction;
+
+    fn decode(ix: &Instruction) -> Option<Event> {
+        let accounts: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+        decode_instruction(&ix.data, &accounts)
+    }
+
+    #[test]
+    fn test_decode_matches_client_builders() {
+        let user = Pubkey::new_unique();
+        let round = round_acc(7);
+        let prediction = prediction_acc(7, &user, 2);
+
+        let opened = decode(&initialize_round_ix(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            7,
+            100,
+            200,
+            &Pubkey::new_unique(),
+        ))
+        .unwrap();
+        assert_eq!(opened.kind, EventKind::RoundOpened);
+        assert_eq!((opened.round, opened.round_id), (round, Some(7)));
+
+        let payload = EncryptedPayload {
+            encryption_pubkey: [0; 32],
+            nonce: 0,
+            ciphertexts: [[0; 32]; 2],
+        };
+        let submitted = decode(&submit_prediction_ix(
+            &user,
+            &Pubkey::new_unique(),
+            7,
+            2,
+            [1; 32],
+            0,
+            5_000,
+            payload,
+        ))
+        .unwrap();
+        assert_eq!(
+            submitted,
+            Event {
+                kind: EventKind::PredictionSubmitted,
+                round,
+                round_id: None,
+                prediction: Some(prediction),
+                user: Some(user),
+                amount: Some(5_000),
+            }
+        );
+
+        let settled = decode(&settle_prediction_ix(
+            7,
+            &prediction,
+            &Pubkey::new_unique(),
+            9_000,
+            [2; 32],
+            [3; 32],
+            vec![],
+        ))
+        .unwrap();
+        assert_eq!(settled.kind, EventKind::PredictionSettled);
+        assert_eq!(settled.prediction, Some(prediction));
+        assert_eq!(settled.amount, Some(9_000));
+
+        let swept = decode(&sweep_round_to_rollover_ix(7)).unwrap();
+        assert_eq!((swept.kind, swept.round), (EventKind::RoundSwept, round));
+    }
+
+    #[test]
+    fn test_unrelated_instructions_are_ignored() {
+        assert_eq!(decode_instruction(&[0; 8], &[Pubkey::new_unique()]), None);
+        assert_eq!(decode_instruction(&[], &[]), None);
+    }
+}
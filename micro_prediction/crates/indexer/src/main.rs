@@ -0,0 +1,70 @@
+//! Indexer for `micro_prediction`: mirrors the program's `Config`, `Round`
+//! and `Prediction` accounts and its instruction history into SQLite at
+//! confirmed commitment, and serves per-user history and round summaries
+//! over HTTP.
+
+mod api;
+mod db;
+mod decode;
+mod sync;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use db::Db;
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use sync::Indexer;
+use tracing::info;
+
+#[derive(Parser)]
+#[command(name = "micro-prediction-indexer", version, about)]
+struct Args {
+    #[arg(
+        long,
+        short = 'u',
+        env = "INDEXER_RPC_URL",
+        default_value = "http://127.0.0.1:8899"
+    )]
+    url: String,
+    #[arg(long, env = "INDEXER_DB", default_value = "indexer.sqlite")]
+    db: PathBuf,
+    #[arg(long, env = "INDEXER_LISTEN", default_value = "127.0.0.1:9465")]
+    listen: SocketAddr,
+    #[arg(long, default_value_t = 5)]
+    poll_interval_secs: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
+        )
+        .init();
+    let args = Args::parse();
+
+    let db = Arc::new(
+        Db::open(&args.db).with_context(|| format!("failed to open {}", args.db.display()))?,
+    );
+    let indexer = Indexer::new(args.url, db.clone());
+
+    let listener = tokio::net::TcpListener::bind(args.listen).await?;
+    info!(listen = %args.listen, "serving the query API");
+    let server = tokio::spawn(async move {
+        axum::serve(listener, api::router(db))
+            .with_graceful_shutdown(shutdown_signal())
+            .await
+    });
+
+    indexer
+        .run(
+            Duration::from_secs(args.poll_interval_secs),
+            shutdown_signal(),
+        )
+        .await;
+    server.await??;
+    Ok(())
+}
+
+async fn shutdown_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
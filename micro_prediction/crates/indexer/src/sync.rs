@@ -0,0 +1,315 @@
+//! Polls the cluster at confirmed commitment: drops transactions whose block
+//! was rolled back, appends new ones oldest first, then replaces the account
+//! tables with a fresh `getProgramAccounts` snapshot.
+use crate::{
+    db::{ConfigRow, Db, EventRow, PredictionRow, RoundRow, Snapshot, TransactionRecord},
+    decode::decode_instruction,
+};
+use anchor_lang::Discriminator;
+use anyhow::{Context, Result};
+use micro_prediction_client::{
+    state::{decode_config, decode_prediction, decode_round, PredictionExt, RoundExt, StateError},
+    Config, Prediction, Round, MICRO_PREDICTION_PROGRAM_ID,
+};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::RpcTransactionConfig, rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta,
+    UiTransactionEncoding,
+};
+use std::{fmt::Debug, future::Future, str::FromStr, sync::Arc, time::Duration};
+use tracing::{info, warn};
+
+const SIGNATURE_PAGE: usize = 1_000;
+/// Most signatures `getSignatureStatuses` accepts per call.
+const STATUS_BATCH: usize = 256;
+
+pub struct Indexer {
+    rpc: RpcClient,
+    db: Arc<Db>,
+}
+
+impl Indexer {
+    pub fn new(url: String, db: Arc<Db>) -> Self {
+        Self {
+            rpc: RpcClient::new_with_commitment(url, CommitmentConfig::confirmed()),
+            db,
+        }
+    }
+
+    pub async fn run(self, poll_interval: Duration, shutdown: impl Future<Output = ()>) {
+        let mut interval = tokio::time::interval(poll_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut shutdown => break,
+            }
+            if let Err(err) = self.poll().await {
+                warn!("indexer poll failed: {err:#}");
+            }
+        }
+        info!("indexer stopped");
+    }
+
+    async fn poll(&self) -> Result<()> {
+        // Rollbacks first, so the cursor never points at a vanished transaction.
+        self.drop_rolled_back().await?;
+        self.index_transactions().await?;
+        self.snapshot_accounts().await
+    }
+
+    /// Confirmed blocks can still be skipped by the cluster until they are
+    /// finalized; their transactions then stop resolving to a status.
+    async fn drop_rolled_back(&self) -> Result<()> {
+        let finalized = self
+            .rpc
+            .get_slot_with_commitment(CommitmentConfig::finalized())
+            .await
+            .context("failed to fetch the finalized slot")?;
+        let pending = self.db.unfinalized_signatures(finalized)?;
+        let mut rolled_back = Vec::new();
+        for batch in pending.chunks(STATUS_BATCH) {
+            let signatures = batch
+                .iter()
+                .map(|signature| Signature::from_str(signature))
+                .collect::<Result<Vec<_>, _>>()
+                .context("stored signature is not valid base58")?;
+            let statuses = self
+                .rpc
+                .get_signature_statuses(&signatures)
+                .await
+                .context("failed to fetch signature statuses")?
+                .value;
+            rolled_back.extend(
+                batch
+                    .iter()
+                    .zip(statuses)
+                    .filter(|(_, status)| status.is_none())
+                    .map(|(signature, _)| signature.clone()),
+            );
+        }
+        if !rolled_back.is_empty() {
+            warn!(
+                count = rolled_back.len(),
+                "dropping rolled back transactions"
+            );
+            self.db.remove_transactions(&rolled_back)?;
+        }
+        Ok(())
+    }
+
+    /// Pages back from the newest program transaction to the cursor, then
+    /// records what it found oldest first so the cursor only moves forward.
+    async fn index_transactions(&self) -> Result<()> {
+        let until = self
+            .db
+            .cursor()?
+            .map(|signature| Signature::from_str(&signature))
+            .transpose()
+            .context("stored cursor is not a valid signature")?;
+        let mut found: Vec<RpcConfirmedTransactionStatusWithSignature> = Vec::new();
+        loop {
+            let before = found
+                .last()
+                .map(|status| Signature::from_str(&status.signature))
+                .transpose()?;
+            let page = self
+                .rpc
+                .get_signatures_for_address_with_config(
+                    &MICRO_PREDICTION_PROGRAM_ID,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit: Some(SIGNATURE_PAGE),
+                        commitment: Some(CommitmentConfig::confirmed()),
+                    },
+                )
+                .await
+                .context("failed to fetch program signatures")?;
+            let done = page.len() < SIGNATURE_PAGE;
+            found.extend(page);
+            if done {
+                break;
+            }
+        }
+
+        for status in found.iter().rev() {
+            let record = if status.err.is_some() {
+                TransactionRecord {
+                    signature: status.signature.clone(),
+                    slot: status.slot,
+                    block_time: status.block_time,
+                    failed: true,
+                    events: Vec::new(),
+                }
+            } else {
+                self.fetch_transaction(&status.signature).await?
+            };
+            self.db.record_transaction(&record)?;
+        }
+        if !found.is_empty() {
+            info!(count = found.len(), "indexed transactions");
+        }
+        Ok(())
+    }
+
+    async fn fetch_transaction(&self, signature: &str) -> Result<TransactionRecord> {
+        let transaction = self
+            .rpc
+            .get_transaction_with_config(
+                &Signature::from_str(signature)?,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .await
+            .with_context(|| format!("failed to fetch transaction {signature}"))?;
+        transaction_record(signature, &transaction)
+    }
+
+    async fn snapshot_accounts(&self) -> Result<()> {
+        let slot = self.rpc.get_slot().await.context("failed to fetch slot")?;
+        let accounts = self
+            .rpc
+            .get_program_accounts(&MICRO_PREDICTION_PROGRAM_ID)
+            .await
+            .context("failed to list program accounts")?;
+
+        let mut snapshot = Snapshot {
+            slot,
+            ..Snapshot::default()
+        };
+        for (address, account) in &accounts {
+            let data = account.data.as_slice();
+            if data.starts_with(Round::DISCRIMINATOR) {
+                snapshot
+                    .rounds
+                    .push(round_row(address, &decode_round(data)?));
+            } else if data.starts_with(Prediction::DISCRIMINATOR) {
+                snapshot
+                    .predictions
+                    .push(prediction_row(address, &decode_prediction(data)?));
+            } else if data.starts_with(Config::DISCRIMINATOR) {
+                snapshot.config = Some(config_row(address, &decode_config(data)?));
+            }
+        }
+        self.db.apply_snapshot(&snapshot)?;
+        Ok(())
+    }
+}
+
+/// Decodes the program's top-level instructions of a confirmed transaction.
+/// Instructions reached through CPI are not indexed.
+fn transaction_record(
+    signature: &str,
+    confirmed: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<TransactionRecord> {
+    let transaction = confirmed
+        .transaction
+        .transaction
+        .decode()
+        .with_context(|| format!("failed to decode transaction {signature}"))?;
+    let message = &transaction.message;
+
+    // Lookup-table addresses follow the static keys, writable ones first.
+    let mut keys = message.static_account_keys().to_vec();
+    let meta = confirmed.transaction.meta.as_ref();
+    if let Some(OptionSerializer::Some(loaded)) = meta.map(|meta| &meta.loaded_addresses) {
+        for key in loaded.writable.iter().chain(&loaded.readonly) {
+            keys.push(Pubkey::from_str(key).context("invalid loaded address")?);
+        }
+    }
+    let failed = meta.is_some_and(|meta| meta.err.is_some());
+
+    let mut events = Vec::new();
+    for (ix_index, ix) in message.instructions().iter().enumerate() {
+        if failed || keys.get(ix.program_id_index as usize) != Some(&MICRO_PREDICTION_PROGRAM_ID) {
+            continue;
+        }
+        let Some(accounts) = ix
+            .accounts
+            .iter()
+            .map(|index| keys.get(*index as usize).copied())
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        if let Some(event) = decode_instruction(&ix.data, &accounts) {
+            events.push(EventRow {
+                ix_index: ix_index as u32,
+                kind: event.kind.as_str(),
+                round: event.round.to_string(),
+                round_id: event.round_id,
+                prediction: event.prediction.map(|key| key.to_string()),
+                user: event.user.map(|key| key.to_string()),
+                amount: event.amount,
+            });
+        }
+    }
+
+    Ok(TransactionRecord {
+        signature: signature.to_string(),
+        slot: confirmed.slot,
+        block_time: confirmed.block_time,
+        failed,
+        events,
+    })
+}
+
+/// Lower-case variant name, matching the values the API filters on.
+fn status_name<T: Debug>(value: Result<T, StateError>, raw: u8) -> String {
+    match value {
+        Ok(value) => format!("{value:?}").to_lowercase(),
+        Err(_) => format!("unknown({raw})"),
+    }
+}
+
+fn config_row(address: &Pubkey, config: &Config) -> ConfigRow {
+    ConfigRow {
+        address: address.to_string(),
+        authority: config.authority.to_string(),
+        settlement_authority: config.settlement_authority.to_string(),
+        token_mint: config.token_mint.to_string(),
+        fee_treasury: config.fee_treasury.to_string(),
+        fee_bps: config.fee_bps,
+        amendment_fee: config.amendment_fee,
+        house_edge_bps: config.house_edge_bps,
+        rollover_vault: config.rollover_vault.to_string(),
+    }
+}
+
+fn round_row(address: &Pubkey, round: &Round) -> RoundRow {
+    RoundRow {
+        address: address.to_string(),
+        round_id: round.round_id,
+        status: status_name(round.round_status(), round.status),
+        start_ts: round.start_ts,
+        end_ts: round.end_ts,
+        total_stake: round.total_stake,
+        total_paid: round.total_paid,
+        committed_payout: round.committed_payout,
+        carried_in: round.carried_in,
+        rolled_over: round.rolled_over,
+        final_price: round.final_price,
+        settlement_timestamp: round.settlement_timestamp,
+    }
+}
+
+fn prediction_row(address: &Pubkey, prediction: &Prediction) -> PredictionRow {
+    PredictionRow {
+        address: address.to_string(),
+        round: prediction.round.to_string(),
+        owner: prediction.owner.to_string(),
+        prediction_index: prediction.prediction_index,
+        window_index: prediction.window_index,
+        stake: prediction.stake,
+        status: status_name(prediction.prediction_status(), prediction.status),
+    }
+}
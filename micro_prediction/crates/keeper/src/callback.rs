@@ -0,0 +1,287 @@
+//! Reading a settlement straight from the callback transaction that finalized
+//! its Arcium computation, so a posted result can be checked against what the
+//! cluster actually produced.
+use anchor_client::{
+    anchor_lang::{AnchorDeserialize, Discriminator},
+    solana_sdk::{instruction::CompiledInstruction, message::VersionedMessage, pubkey::Pubkey},
+};
+use arcium_client::{idl::arcium::client::args::CallbackComputation, ARCIUM_PROGRAM_ID};
+
+/// Length of the Anchor discriminator in front of callback instruction data.
+const DISCRIMINATOR_LEN: usize = 8;
+/// Positions of the node's signer and the computation account among the
+/// accounts of Arcium's `callback_computation`.
+const CALLBACK_SIGNER: usize = 0;
+const CALLBACK_COMPUTATION: usize = 4;
+
+/// Public part of a `determine_winners` output; mirrors `SettlementSummary`
+/// in the circuits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettlementSummary {
+    pub round_id: u64,
+    pub final_price: i64,
+    pub fee_total: u64,
+    pub total_payout: u64,
+    pub leaves: Vec<[u8; 32]>,
+    pub payout_root: [u8; 32],
+    /// All zero when the round has no draws.
+    pub randomness_seed: [u8; 32],
+}
+
+impl SettlementSummary {
+    /// Decodes the summary at the start of a `SettlementOutput`; the encrypted
+    /// user results after it are ignored.
+    pub fn decode(output: &[u8]) -> Option<Self> {
+        let mut reader = Reader(output);
+        let round_id = u64::from_le_bytes(reader.take()?);
+        let final_price = i64::from_le_bytes(reader.take()?);
+        let fee_total = u64::from_le_bytes(reader.take()?);
+        let total_payout = u64::from_le_bytes(reader.take()?);
+        let count = u32::from_le_bytes(reader.take()?) as usize;
+        let leaves = (0..count)
+            .map(|_| reader.take())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            round_id,
+            final_price,
+            fee_total,
+            total_payout,
+            leaves,
+            payout_root: reader.take()?,
+            randomness_seed: reader.take()?,
+        })
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (head, rest) = self.0.split_at_checked(N)?;
+        self.0 = rest;
+        head.try_into().ok()
+    }
+}
+
+/// The output a callback transaction handed to `mxe_program`, if `message`
+/// is the successful callback of the computation account `computation`: it
+/// must carry Arcium's `callback_computation` for that computation of
+/// `mxe_program`, signed by the node it names, which only a node of the
+/// computation's cluster can send, next to the MXE callback.
+pub fn callback_output(
+    message: &VersionedMessage,
+    mxe_program: &Pubkey,
+    computation: &Pubkey,
+) -> Option<Vec<u8>> {
+    let keys = message.static_account_keys();
+    let key = |index: u8| keys.get(index as usize);
+    let instructions = message.instructions();
+
+    let confirmed = instructions.iter().any(|ix| {
+        key(ix.program_id_index) == Some(&ARCIUM_PROGRAM_ID)
+            && confirms_computation(message, ix, mxe_program, computation)
+    });
+    if !confirmed {
+        return None;
+    }
+    instructions
+        .iter()
+        .filter(|ix| key(ix.program_id_index) == Some(mxe_program))
+        .find_map(|ix| match ix.data.get(DISCRIMINATOR_LEN..)? {
+            // `ComputationOutputs::Success`.
+            [0, output @ ..] => Some(output.to_vec()),
+            _ => None,
+        })
+}
+
+/// Whether the Arcium instruction `ix` is `callback_computation` for
+/// `computation` of `mxe_program`, with its node signer signing `message`.
+fn confirms_computation(
+    message: &VersionedMessage,
+    ix: &CompiledInstruction,
+    mxe_program: &Pubkey,
+    computation: &Pubkey,
+) -> bool {
+    let Some(mut data) = ix.data.strip_prefix(CallbackComputation::DISCRIMINATOR) else {
+        return false;
+    };
+    let Ok(args) = CallbackComputation::deserialize(&mut data) else {
+        return false;
+    };
+    let account = |position: usize| ix.accounts.get(position).map(|index| *index as usize);
+    args.mxe_program == *mxe_program
+        && account(CALLBACK_COMPUTATION).and_then(|index| message.static_account_keys().get(index))
+            == Some(computation)
+        && account(CALLBACK_SIGNER).is_some_and(|index| message.is_signer(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::solana_sdk::{
+        hash::Hash,
+        instruction::Instruction,
+        message::{legacy::Message, VersionedMessage},
+    };
+    use arcium_client::idl::arcium::{
+        accounts::ComputationAccount,
+        types::{
+            AcccountAccessInfo, CallbackAccount, CallbackInstruction, ComputationReference,
+            ComputationStatus, ExecutionFailure, ExecutionFee,
+        },
+    };
+    use arcium_client::pda::computation_acc;
+    use micro_prediction_mock_arcium::cluster::{callback_instructions, ClusterConfig};
+
+    fn summary() -> SettlementSummary {
+        SettlementSummary {
+            round_id: 4,
+            final_price: -42,
+            fee_total: 3,
+            total_payout: 97,
+            leaves: vec![[1; 32], [2; 32]],
+            payout_root: [3; 32],
+            randomness_seed: [0; 32],
+        }
+    }
+
+    fn encode(summary: &SettlementSummary) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend_from_slice(&summary.round_id.to_le_bytes());
+        output.extend_from_slice(&summary.final_price.to_le_bytes());
+        output.extend_from_slice(&summary.fee_total.to_le_bytes());
+        output.extend_from_slice(&summary.total_payout.to_le_bytes());
+        output.extend_from_slice(&(summary.leaves.len() as u32).to_le_bytes());
+        for leaf in &summary.leaves {
+            output.extend_from_slice(leaf);
+        }
+        output.extend_from_slice(&summary.payout_root);
+        output.extend_from_slice(&summary.randomness_seed);
+        // One encrypted user result, which the keeper never reads.
+        output.extend_from_slice(&1u32.to_le_bytes());
+        output.extend_from_slice(&[9; 80]);
+        output
+    }
+
+    fn callback_ixs(
+        config: &ClusterConfig,
+        offset: u64,
+        result: &Result<Vec<u8>, ExecutionFailure>,
+    ) -> Vec<Instruction> {
+        let computation = ComputationAccount {
+            payer: Pubkey::new_unique(),
+            cluster_index: None,
+            computation_definition_offset: 7,
+            execution_fee: ExecutionFee {
+                base_fee: 0,
+                priority_fee: 0,
+                input_delivery_fee: 0,
+                output_delivery_fee: 0,
+            },
+            slot: 0,
+            slot_counter: 0,
+            status: ComputationStatus::Queued,
+            arguments: vec![],
+            callback_url: None,
+            custom_callback_instructions: vec![CallbackInstruction {
+                program_id: config.mxe_program,
+                discriminator: vec![1, 2, 3, 4, 5, 6, 7, 8],
+                accounts: vec![CallbackAccount {
+                    pubkey: Pubkey::new_unique(),
+                    is_writable: true,
+                }],
+            }],
+            bump: 0,
+        };
+        let reference = ComputationReference {
+            computation_offset: offset,
+            priority_fee: 0,
+            computation_definition_offset: 7,
+            accs: [AcccountAccessInfo { inner: 0 }; 10],
+        };
+        callback_instructions(
+            &Pubkey::new_unique(),
+            config,
+            &reference,
+            &computation,
+            result,
+        )
+    }
+
+    /// `ixs` in a transaction paid for by the node that signs the callback.
+    fn signed_message(ixs: &[Instruction]) -> VersionedMessage {
+        let node = ixs[0].accounts[CALLBACK_SIGNER].pubkey;
+        VersionedMessage::Legacy(Message::new_with_blockhash(
+            ixs,
+            Some(&node),
+            &Hash::default(),
+        ))
+    }
+
+    fn callback_message(
+        config: &ClusterConfig,
+        offset: u64,
+        result: &Result<Vec<u8>, ExecutionFailure>,
+    ) -> VersionedMessage {
+        signed_message(&callback_ixs(config, offset, result))
+    }
+
+    #[test]
+    fn test_summary_round_trip() {
+        assert_eq!(
+            SettlementSummary::decode(&encode(&summary())),
+            Some(summary())
+        );
+        let encoded = encode(&summary());
+        assert_eq!(SettlementSummary::decode(&encoded[..60]), None);
+    }
+
+    #[test]
+    fn test_callback_output() {
+        let config = ClusterConfig {
+            mxe_program: Pubkey::new_unique(),
+            node_offset: 1,
+            cluster_offset: 2,
+        };
+        let computation = computation_acc(&config.mxe_program, 11);
+        let output = encode(&summary());
+
+        let message = callback_message(&config, 11, &Ok(output.clone()));
+        assert_eq!(
+            callback_output(&message, &config.mxe_program, &computation),
+            Some(output.clone())
+        );
+        // The callback of another computation does not vouch for this one.
+        let other = callback_message(&config, 12, &Ok(output.clone()));
+        assert_eq!(
+            callback_output(&other, &config.mxe_program, &computation),
+            None
+        );
+        let failed = callback_message(&config, 11, &Err(ExecutionFailure::Inputs));
+        assert_eq!(
+            callback_output(&failed, &config.mxe_program, &computation),
+            None
+        );
+
+        // Any other Arcium instruction on the computation vouches for nothing.
+        let mut ixs = callback_ixs(&config, 11, &Ok(output.clone()));
+        ixs[0].data[0] ^= 1;
+        assert_eq!(
+            callback_output(&signed_message(&ixs), &config.mxe_program, &computation),
+            None
+        );
+        // Nor does a callback its node did not sign.
+        let mut ixs = callback_ixs(&config, 11, &Ok(output));
+        ixs[0].accounts[CALLBACK_SIGNER].is_signer = false;
+        let payer = Pubkey::new_unique();
+        let unsigned = VersionedMessage::Legacy(Message::new_with_blockhash(
+            &ixs,
+            Some(&payer),
+            &Hash::default(),
+        ));
+        assert_eq!(
+            callback_output(&unsigned, &config.mxe_program, &computation),
+            None
+        );
+    }
+}
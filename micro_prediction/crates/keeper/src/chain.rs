@@ -0,0 +1,151 @@
+//! The keeper's view of the cluster: account reads and transaction sends.
+use crate::callback::callback_output;
+use anchor_client::{
+    anchor_lang::AccountDeserialize,
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        instruction::Instruction,
+        pubkey::Pubkey,
+        signature::{Keypair, Signature},
+        signer::Signer,
+    },
+};
+use anyhow::{Context, Result};
+use arcium_client::{
+    idl::arcium::{accounts::ComputationAccount, types::ComputationStatus},
+    pda::computation_acc,
+    transactions::{send_instructions, SendConfig},
+};
+use micro_prediction_client::{
+    pda::{config_acc, round_acc},
+    state::{decode_config, decode_prediction, decode_round},
+    Config, Prediction, Round,
+};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
+use solana_transaction_status::UiTransactionEncoding;
+use std::{str::FromStr, sync::Arc};
+
+pub struct Chain {
+    rpc: RpcClient,
+    signer: Arc<Keypair>,
+}
+
+impl Chain {
+    pub fn new(url: String, signer: Arc<Keypair>) -> Self {
+        Self {
+            rpc: RpcClient::new_with_commitment(url, CommitmentConfig::confirmed()),
+            signer,
+        }
+    }
+
+    pub fn signer(&self) -> Pubkey {
+        self.signer.pubkey()
+    }
+
+    /// Cluster time of the latest confirmed slot, which is what the program's
+    /// round windows are checked against.
+    pub async fn unix_timestamp(&self) -> Result<i64> {
+        let slot = self.rpc.get_slot().await.context("failed to fetch slot")?;
+        self.rpc
+            .get_block_time(slot)
+            .await
+            .context("failed to fetch block time")
+    }
+
+    async fn account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>> {
+        let account = self
+            .rpc
+            .get_account_with_commitment(address, self.rpc.commitment())
+            .await
+            .with_context(|| format!("failed to fetch account {address}"))?
+            .value;
+        Ok(account.map(|account| account.data))
+    }
+
+    pub async fn config(&self) -> Result<Config> {
+        let data = self
+            .account_data(&config_acc())
+            .await?
+            .context("the program config is not initialized")?;
+        Ok(decode_config(&data)?)
+    }
+
+    pub async fn round(&self, round_id: u64) -> Result<Option<Round>> {
+        self.account_data(&round_acc(round_id))
+            .await?
+            .map(|data| decode_round(&data))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    pub async fn prediction(&self, address: &Pubkey) -> Result<Option<Prediction>> {
+        self.account_data(address)
+            .await?
+            .map(|data| decode_prediction(&data))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// Status of an Arcium computation of `mxe_program`, `None` if it was never queued.
+    pub async fn computation_status(
+        &self,
+        mxe_program: &Pubkey,
+        computation_offset: u64,
+    ) -> Result<Option<ComputationStatus>> {
+        let Some(data) = self
+            .account_data(&computation_acc(mxe_program, computation_offset))
+            .await?
+        else {
+            return Ok(None);
+        };
+        let computation = ComputationAccount::try_deserialize(&mut data.as_slice())
+            .context("failed to decode computation account")?;
+        Ok(Some(computation.status))
+    }
+
+    /// Output the callback of a computation of `mxe_program` delivered to it,
+    /// found among the transactions that touched the computation account.
+    /// `None` until a successful callback has landed.
+    pub async fn computation_output(
+        &self,
+        mxe_program: &Pubkey,
+        computation_offset: u64,
+    ) -> Result<Option<Vec<u8>>> {
+        let computation = computation_acc(mxe_program, computation_offset);
+        let statuses = self
+            .rpc
+            .get_signatures_for_address(&computation)
+            .await
+            .with_context(|| format!("failed to fetch signatures of {computation}"))?;
+        for status in statuses.iter().filter(|status| status.err.is_none()) {
+            let signature = Signature::from_str(&status.signature)?;
+            let transaction = self
+                .rpc
+                .get_transaction_with_config(
+                    &signature,
+                    RpcTransactionConfig {
+                        encoding: Some(UiTransactionEncoding::Base64),
+                        commitment: Some(self.rpc.commitment()),
+                        max_supported_transaction_version: Some(0),
+                    },
+                )
+                .await
+                .with_context(|| format!("failed to fetch transaction {signature}"))?;
+            let Some(transaction) = transaction.transaction.transaction.decode() else {
+                continue;
+            };
+            if let Some(output) = callback_output(&transaction.message, mxe_program, &computation) {
+                return Ok(Some(output));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Sends `ixs` in one transaction paid for and signed by the keeper, and
+    /// waits until it lands. A transaction whose send request failed is
+    /// confirmed until its blockhash expires before it is signed again, so a
+    /// retry never lands the same step twice.
+    pub async fn send(&self, ixs: &[Instruction]) -> Result<Signature> {
+        Ok(send_instructions(&self.rpc, ixs, &self.signer, &SendConfig::default()).await?)
+    }
+}
@@ -0,0 +1,431 @@
+//! The round lifecycle loop. Every step re-reads the round from the chain and
+//! only sends what the chain is still missing, so a tick can be interrupted at
+//! any point and simply run again.
+use crate::{
+    callback::SettlementSummary,
+    chain::Chain,
+    metrics::Metrics,
+    store::{Claim, ClaimStatus, Stage, Store, StoredSettlement},
+};
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anchor_spl::associated_token::{
+    get_associated_token_address,
+    spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+};
+use anyhow::{bail, Context, Result};
+use arcium_client::idl::arcium::types::ComputationStatus;
+use micro_prediction_client::{
+    instruction::{
+        begin_resolution_ix, commit_settlement_ix, finalize_round_ix, initialize_round_ix,
+        mark_round_refunded_ix, settle_prediction_ix, sweep_round_to_rollover_ix, TOKEN_PROGRAM_ID,
+    },
+    state::{PredictionExt, PredictionStatus, RoundExt, RoundStatus},
+    Round,
+};
+use micro_prediction_settlement::merkle;
+use std::{
+    future::Future,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// Rounds are opened slightly in the future so that cluster clock drift
+/// cannot push `start_ts` into the past.
+const OPEN_LEAD_SECS: i64 = 5;
+
+/// Opens a new round whenever the previous one has ended.
+pub struct Schedule {
+    pub first_round_id: u64,
+    pub duration_secs: i64,
+    pub price_account: Pubkey,
+}
+
+/// Exponential backoff for claims that fail to settle.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay_secs: i64,
+    pub max_delay_secs: i64,
+}
+
+impl RetryPolicy {
+    /// When to retry after the failure of attempt number `attempts + 1`, or
+    /// `None` once the budget is spent.
+    pub fn retry_at(&self, attempts: u32, now: i64) -> Option<i64> {
+        if attempts + 1 >= self.max_attempts {
+            return None;
+        }
+        let delay = self
+            .base_delay_secs
+            .saturating_mul(1i64 << attempts.min(32))
+            .min(self.max_delay_secs);
+        Some(now.saturating_add(delay))
+    }
+}
+
+pub struct KeeperConfig {
+    pub poll_interval: Duration,
+    pub schedule: Option<Schedule>,
+    /// How long after a result is committed users get to claim for themselves
+    /// before the round is finalized.
+    pub claim_window_secs: i64,
+    /// Rounds still without a settlement this long after `end_ts` are refunded.
+    pub refund_after_secs: i64,
+    pub retry: RetryPolicy,
+    /// Sweep finalized rounds into the rollover vault.
+    pub sweep: bool,
+    /// Results must name a finalized computation of this MXE program.
+    pub mxe_program: Pubkey,
+}
+
+pub struct Keeper {
+    chain: Chain,
+    store: Arc<Store>,
+    metrics: Arc<Metrics>,
+    config: KeeperConfig,
+}
+
+impl Keeper {
+    pub fn new(
+        chain: Chain,
+        store: Arc<Store>,
+        metrics: Arc<Metrics>,
+        config: KeeperConfig,
+    ) -> Self {
+        Self {
+            chain,
+            store,
+            metrics,
+            config,
+        }
+    }
+
+    pub async fn run(self, shutdown: impl Future<Output = ()>) {
+        let mut interval = tokio::time::interval(self.config.poll_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut shutdown => break,
+            }
+            Metrics::inc(&self.metrics.ticks);
+            match self.tick().await {
+                Ok(now) => self.metrics.last_success.store(now, Ordering::Relaxed),
+                Err(err) => {
+                    Metrics::inc(&self.metrics.tick_errors);
+                    warn!("keeper tick failed: {err:#}");
+                }
+            }
+        }
+        info!("keeper stopped");
+    }
+
+    /// One pass over the schedule and every active round. A failing round does
+    /// not hold up the others; the first error is reported after all ran.
+    async fn tick(&self) -> Result<i64> {
+        let now = self.chain.unix_timestamp().await?;
+        if let Some(schedule) = &self.config.schedule {
+            self.ensure_open_round(schedule, now).await?;
+        }
+
+        let rounds = self.store.active_rounds()?;
+        let mut first_error = None;
+        for (round_id, _) in &rounds {
+            if let Err(err) = self.advance(*round_id, now).await {
+                warn!(round_id, "round step failed: {err:#}");
+                first_error.get_or_insert(err);
+            }
+        }
+
+        let counts = self.store.claim_counts(None)?;
+        self.metrics
+            .active_rounds
+            .store(self.store.active_rounds()?.len() as u64, Ordering::Relaxed);
+        self.metrics
+            .pending_claims
+            .store(counts.pending, Ordering::Relaxed);
+        self.metrics
+            .failed_claims
+            .store(counts.failed, Ordering::Relaxed);
+        match first_error {
+            Some(err) => Err(err),
+            None => Ok(now),
+        }
+    }
+
+    async fn ensure_open_round(&self, schedule: &Schedule, now: i64) -> Result<()> {
+        let next = self
+            .store
+            .next_round_id()?
+            .unwrap_or(schedule.first_round_id);
+        if next > schedule.first_round_id {
+            if let Some(current) = self.chain.round(next - 1).await? {
+                if now < current.end_ts {
+                    return Ok(());
+                }
+            }
+        }
+
+        // The round may exist already if we crashed right after opening it.
+        if self.chain.round(next).await?.is_none() {
+            let config = self.chain.config().await?;
+            let start_ts = now + OPEN_LEAD_SECS;
+            let ix = initialize_round_ix(
+                &self.chain.signer(),
+                &config.token_mint,
+                next,
+                start_ts,
+                start_ts + schedule.duration_secs,
+                &schedule.price_account,
+            );
+            let signature = self.chain.send(&[ix]).await?;
+            Metrics::inc(&self.metrics.rounds_opened);
+            info!(round_id = next, %signature, "opened round");
+        }
+        self.store.track_round(next, Stage::Open, now)?;
+        self.store.set_next_round_id(next + 1)?;
+        Ok(())
+    }
+
+    async fn advance(&self, round_id: u64, now: i64) -> Result<()> {
+        let Some(round) = self.chain.round(round_id).await? else {
+            warn!(round_id, "tracked round does not exist on chain");
+            return Ok(());
+        };
+
+        match round.round_status()? {
+            RoundStatus::Open => {
+                if now >= round.end_ts {
+                    let ix = begin_resolution_ix(&self.chain.signer(), round_id, None, None);
+                    let signature = self.chain.send(&[ix]).await?;
+                    Metrics::inc(&self.metrics.resolutions_begun);
+                    info!(round_id, %signature, "began resolution");
+                    self.store.set_stage(round_id, Stage::Resolving, now)?;
+                }
+            }
+            RoundStatus::Resolving => self.resolve(round_id, &round, now).await?,
+            RoundStatus::Finalized => {
+                // Unclaimed payouts stay in escrow; only the rest can be swept.
+                let unreserved = round.unreserved_balance().unwrap_or_default();
+                if self.config.sweep && unreserved > 0 {
+                    let signature = self
+                        .chain
+                        .send(&[sweep_round_to_rollover_ix(round_id)])
+                        .await?;
+                    Metrics::inc(&self.metrics.rounds_swept);
+                    info!(round_id, unreserved, %signature, "swept round");
+                }
+                self.store.set_stage(round_id, Stage::Closed, now)?;
+            }
+            RoundStatus::Refunded => self.store.set_stage(round_id, Stage::Closed, now)?,
+        }
+        Ok(())
+    }
+
+    async fn resolve(&self, round_id: u64, round: &Round, now: i64) -> Result<()> {
+        let Some(settlement) = self.store.settlement(round_id)? else {
+            if now >= round.end_ts.saturating_add(self.config.refund_after_secs) {
+                let ix = mark_round_refunded_ix(&self.chain.signer(), round_id);
+                let signature = self.chain.send(&[ix]).await?;
+                Metrics::inc(&self.metrics.rounds_refunded);
+                warn!(round_id, %signature, "no settlement arrived in time, refunded round");
+                self.store.set_stage(round_id, Stage::Closed, now)?;
+            }
+            return Ok(());
+        };
+
+        // A round without entries has nothing to commit or claim.
+        if !settlement.leaves.is_empty() {
+            let root = merkle::root(&settlement.leaves);
+            let Some(committed_root) = round.result_commitment else {
+                // Chunked rounds get their commitment from the chunk results.
+                if round.chunk_count == 0 && self.verified(round_id, &settlement).await? {
+                    // With draws the seed's leaf closes the list of leaves.
+                    let randomness_seed = settlement.randomness_seed.filter(|_| round.uses_draws());
+                    let seed_proof = match randomness_seed {
+                        Some(_) => merkle::proof(&settlement.leaves, settlement.leaves.len() - 1),
+                        None => Vec::new(),
+                    };
+                    let ix = commit_settlement_ix(
+                        &self.chain.signer(),
+                        round_id,
+                        root,
+                        settlement.total_payout,
+                        settlement.fee_total,
+                        randomness_seed,
+                        seed_proof,
+                    );
+                    let signature = self.chain.send(&[ix]).await?;
+                    Metrics::inc(&self.metrics.settlements_committed);
+                    info!(round_id, %signature, "committed settlement");
+                    self.store.set_stage(round_id, Stage::Committed, now)?;
+                }
+                return Ok(());
+            };
+            if committed_root != root {
+                bail!("stored settlement does not match the committed root");
+            }
+            self.store.set_stage(round_id, Stage::Committed, now)?;
+            self.settle_claims(round, &settlement, now).await?;
+        }
+
+        let counts = self.store.claim_counts(Some(round_id))?;
+        if counts.failed > 0 {
+            // Finalizing would let a sweep treat a stuck winner's payout as
+            // settled; an operator has to look at it first.
+            warn!(
+                round_id,
+                failed = counts.failed,
+                "claims gave up retrying, not finalizing until they are retried"
+            );
+            return Ok(());
+        }
+        let pending = counts.pending;
+        let window_over = settlement.leaves.is_empty()
+            || now
+                >= settlement
+                    .received_at
+                    .saturating_add(self.config.claim_window_secs);
+        if pending == 0 && window_over {
+            // An oracle-resolved price is binding; the program rejects any other.
+            let final_price = round.final_price.unwrap_or(settlement.final_price);
+            let ix = finalize_round_ix(&self.chain.signer(), round_id, final_price, now);
+            let signature = self.chain.send(&[ix]).await?;
+            Metrics::inc(&self.metrics.rounds_finalized);
+            info!(round_id, final_price, %signature, "finalized round");
+            self.store.set_stage(round_id, Stage::Finalized, now)?;
+        }
+        Ok(())
+    }
+
+    /// Whether `settlement` may be committed. The posted result only names its
+    /// computation: it must be exactly what the callback of that finalized
+    /// computation delivered for this round.
+    async fn verified(&self, round_id: u64, settlement: &StoredSettlement) -> Result<bool> {
+        let mxe_program = &self.config.mxe_program;
+        let offset = settlement
+            .computation_offset
+            .context("settlement does not name its Arcium computation")?;
+        let status = self.chain.computation_status(mxe_program, offset).await?;
+        if !matches!(status, Some(ComputationStatus::Finalized)) {
+            return Ok(false);
+        }
+        let Some(output) = self.chain.computation_output(mxe_program, offset).await? else {
+            return Ok(false);
+        };
+        let summary = SettlementSummary::decode(&output)
+            .with_context(|| format!("computation {offset} did not output a settlement"))?;
+        let matches = summary.round_id == round_id
+            && summary.final_price == settlement.final_price
+            && summary.fee_total == settlement.fee_total
+            && summary.total_payout == settlement.total_payout
+            && summary.leaves == settlement.leaves
+            && summary.randomness_seed == settlement.randomness_seed.unwrap_or_default();
+        if !matches {
+            bail!("stored settlement does not match the output of computation {offset}");
+        }
+        Ok(true)
+    }
+
+    async fn settle_claims(
+        &self,
+        round: &Round,
+        settlement: &StoredSettlement,
+        now: i64,
+    ) -> Result<()> {
+        for claim in self.store.due_claims(round.round_id, now)? {
+            match self.settle_claim(round, settlement, &claim).await {
+                Ok(status) => self.store.set_claim_status(&claim.prediction, status)?,
+                Err(err) => {
+                    Metrics::inc(&self.metrics.claim_failures);
+                    let retry_at = self.config.retry.retry_at(claim.attempts, now);
+                    let prediction = Pubkey::new_from_array(claim.prediction);
+                    match retry_at {
+                        Some(retry_at) => {
+                            warn!(%prediction, retry_at, "settlement failed: {err:#}")
+                        }
+                        None => warn!(%prediction, "settlement failed, giving up: {err:#}"),
+                    }
+                    self.store.record_claim_failure(
+                        &claim.prediction,
+                        &format!("{err:#}"),
+                        retry_at,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Settles one claim unless the prediction already left `Submitted`, which
+    /// makes retries after a lost confirmation harmless.
+    async fn settle_claim(
+        &self,
+        round: &Round,
+        settlement: &StoredSettlement,
+        claim: &Claim,
+    ) -> Result<ClaimStatus> {
+        let address = Pubkey::new_from_array(claim.prediction);
+        let prediction = self
+            .chain
+            .prediction(&address)
+            .await?
+            .context("prediction account not found")?;
+        match prediction.prediction_status()? {
+            PredictionStatus::Submitted => {}
+            PredictionStatus::Settled => return Ok(ClaimStatus::Settled),
+            PredictionStatus::Cancelled | PredictionStatus::Refunded => {
+                return Ok(ClaimStatus::Skipped)
+            }
+        }
+
+        let leaf = merkle::payout_leaf(&claim.prediction, claim.payout, &claim.blinding);
+        let index = settlement
+            .leaves
+            .iter()
+            .position(|candidate| *candidate == leaf)
+            .context("claim does not open any leaf of the settlement")?;
+        let recipient = get_associated_token_address(&prediction.owner, &round.token_mint);
+        let ixs = [
+            create_associated_token_account_idempotent(
+                &self.chain.signer(),
+                &prediction.owner,
+                &round.token_mint,
+                &TOKEN_PROGRAM_ID,
+            ),
+            settle_prediction_ix(
+                round.round_id,
+                &address,
+                &recipient,
+                claim.payout,
+                prediction.commitment,
+                claim.blinding,
+                merkle::proof(&settlement.leaves, index),
+            ),
+        ];
+        let signature = self.chain.send(&ixs).await?;
+        Metrics::inc(&self.metrics.claims_settled);
+        info!(%address, payout = claim.payout, %signature, "settled claim");
+        Ok(ClaimStatus::Settled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy_backs_off_and_gives_up() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay_secs: 10,
+            max_delay_secs: 60,
+        };
+        assert_eq!(policy.retry_at(0, 1_000), Some(1_010));
+        assert_eq!(policy.retry_at(1, 1_000), Some(1_020));
+        assert_eq!(policy.retry_at(3, 1_000), Some(1_060));
+        assert_eq!(policy.retry_at(4, 1_000), None);
+    }
+}
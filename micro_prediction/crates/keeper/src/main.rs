@@ -0,0 +1,143 @@
+//! Keeper daemon for `micro_prediction`: opens rounds on a schedule, begins
+//! resolution when they end, commits settlement results, settles delegated
+//! claims and finalizes, persisting its progress so it can resume after a
+//! restart. Serves `/health` and `/metrics` alongside the result endpoints.
+
+mod callback;
+mod chain;
+mod keeper;
+mod metrics;
+mod server;
+mod store;
+
+use anchor_client::solana_sdk::{pubkey::Pubkey, signature::read_keypair_file};
+use anyhow::{Context, Result};
+use chain::Chain;
+use clap::Parser;
+use keeper::{Keeper, KeeperConfig, RetryPolicy, Schedule};
+use metrics::Metrics;
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use store::{Stage, Store};
+use tracing::info;
+
+#[derive(Parser)]
+#[command(name = "micro-prediction-keeper", version, about)]
+struct Args {
+    /// Keypair of the config authority, which must also be the settlement authority.
+    #[arg(long, short = 'k', env = "KEEPER_KEYPAIR")]
+    keypair: PathBuf,
+    #[arg(
+        long,
+        short = 'u',
+        env = "KEEPER_RPC_URL",
+        default_value = "http://127.0.0.1:8899"
+    )]
+    url: String,
+    #[arg(long, env = "KEEPER_DB", default_value = "keeper.sqlite")]
+    db: PathBuf,
+    #[arg(long, env = "KEEPER_LISTEN", default_value = "127.0.0.1:9464")]
+    listen: SocketAddr,
+    /// Bearer token required to post settlement results and retry failed claims.
+    #[arg(long, env = "KEEPER_API_TOKEN", hide_env_values = true)]
+    api_token: String,
+    #[arg(long, default_value_t = 10)]
+    poll_interval_secs: u64,
+    /// Open a new round of this length whenever the previous one ended.
+    #[arg(long, requires = "price_account")]
+    round_duration_secs: Option<i64>,
+    /// Round id to open first when the store has no schedule yet.
+    #[arg(long, default_value_t = 0)]
+    first_round_id: u64,
+    #[arg(long)]
+    price_account: Option<Pubkey>,
+    /// Rounds opened elsewhere that the keeper should drive as well.
+    #[arg(long = "track-round")]
+    track_rounds: Vec<u64>,
+    #[arg(long, default_value_t = 3_600)]
+    claim_window_secs: i64,
+    #[arg(long, default_value_t = 86_400)]
+    refund_after_secs: i64,
+    #[arg(long, default_value_t = 8)]
+    max_attempts: u32,
+    #[arg(long, default_value_t = 15)]
+    retry_base_secs: i64,
+    #[arg(long, default_value_t = 3_600)]
+    retry_max_secs: i64,
+    /// Sweep finalized rounds into the rollover vault.
+    #[arg(long)]
+    sweep: bool,
+    /// Require settlement results to match the callback output of a finalized
+    /// Arcium computation of this MXE program.
+    #[arg(long)]
+    mxe_program: Pubkey,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
+        )
+        .init();
+    let args = Args::parse();
+
+    let signer = read_keypair_file(&args.keypair)
+        .map_err(|err| anyhow::anyhow!("failed to read {}: {err}", args.keypair.display()))?;
+    let store = Arc::new(
+        Store::open(&args.db).with_context(|| format!("failed to open {}", args.db.display()))?,
+    );
+    for round_id in &args.track_rounds {
+        store.track_round(*round_id, Stage::Open, 0)?;
+    }
+    let metrics = Arc::new(Metrics::default());
+
+    let schedule =
+        args.round_duration_secs
+            .zip(args.price_account)
+            .map(|(duration_secs, price_account)| Schedule {
+                first_round_id: args.first_round_id,
+                duration_secs,
+                price_account,
+            });
+    let config = KeeperConfig {
+        poll_interval: Duration::from_secs(args.poll_interval_secs),
+        schedule,
+        claim_window_secs: args.claim_window_secs,
+        refund_after_secs: args.refund_after_secs,
+        retry: RetryPolicy {
+            max_attempts: args.max_attempts,
+            base_delay_secs: args.retry_base_secs,
+            max_delay_secs: args.retry_max_secs,
+        },
+        sweep: args.sweep,
+        mxe_program: args.mxe_program,
+    };
+    let keeper = Keeper::new(
+        Chain::new(args.url, Arc::new(signer)),
+        store.clone(),
+        metrics.clone(),
+        config,
+    );
+
+    let app = server::router(server::AppState {
+        store,
+        metrics,
+        stale_after_secs: 3 * args.poll_interval_secs as i64 + 30,
+        api_token: args.api_token.into(),
+    });
+    let listener = tokio::net::TcpListener::bind(args.listen).await?;
+    info!(listen = %args.listen, "serving health and metrics");
+    let server = tokio::spawn(async move {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown_signal())
+            .await
+    });
+
+    keeper.run(shutdown_signal()).await;
+    server.await??;
+    Ok(())
+}
+
+async fn shutdown_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
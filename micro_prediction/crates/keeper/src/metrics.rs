@@ -0,0 +1,127 @@
+//! Counters exported on `/metrics` in the Prometheus text format.
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+};
+
+#[derive(Default)]
+pub struct Metrics {
+    pub ticks: AtomicU64,
+    pub tick_errors: AtomicU64,
+    pub rounds_opened: AtomicU64,
+    pub resolutions_begun: AtomicU64,
+    pub settlements_committed: AtomicU64,
+    pub claims_settled: AtomicU64,
+    pub claim_failures: AtomicU64,
+    pub rounds_finalized: AtomicU64,
+    pub rounds_refunded: AtomicU64,
+    pub rounds_swept: AtomicU64,
+    /// Unix time of the last tick that completed without an error.
+    pub last_success: AtomicI64,
+    pub active_rounds: AtomicU64,
+    pub pending_claims: AtomicU64,
+    pub failed_claims: AtomicU64,
+}
+
+impl Metrics {
+    pub fn inc(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let counters = [
+            ("ticks_total", "Keeper loop iterations.", &self.ticks),
+            (
+                "tick_errors_total",
+                "Iterations that hit an error.",
+                &self.tick_errors,
+            ),
+            ("rounds_opened_total", "Rounds opened.", &self.rounds_opened),
+            (
+                "resolutions_begun_total",
+                "begin_resolution calls sent.",
+                &self.resolutions_begun,
+            ),
+            (
+                "settlements_committed_total",
+                "commit_settlement calls sent.",
+                &self.settlements_committed,
+            ),
+            (
+                "claims_settled_total",
+                "Claims settled on behalf of users.",
+                &self.claims_settled,
+            ),
+            (
+                "claim_failures_total",
+                "Failed settle_prediction attempts.",
+                &self.claim_failures,
+            ),
+            (
+                "rounds_finalized_total",
+                "Rounds finalized.",
+                &self.rounds_finalized,
+            ),
+            (
+                "rounds_refunded_total",
+                "Rounds marked refunded after resolution timed out.",
+                &self.rounds_refunded,
+            ),
+            ("rounds_swept_total", "Rounds swept.", &self.rounds_swept),
+        ];
+        let gauges = [
+            (
+                "active_rounds",
+                "Tracked rounds that are not closed.",
+                self.active_rounds.load(Ordering::Relaxed) as i64,
+            ),
+            (
+                "pending_claims",
+                "Claims waiting to be settled.",
+                self.pending_claims.load(Ordering::Relaxed) as i64,
+            ),
+            (
+                "failed_claims",
+                "Claims that gave up retrying and hold up finalization.",
+                self.failed_claims.load(Ordering::Relaxed) as i64,
+            ),
+            (
+                "last_success_timestamp_seconds",
+                "Unix time of the last successful iteration.",
+                self.last_success.load(Ordering::Relaxed),
+            ),
+        ];
+
+        let mut out = String::new();
+        for (name, help, counter) in counters {
+            let value = counter.load(Ordering::Relaxed);
+            let _ = writeln!(out, "# HELP keeper_{name} {help}");
+            let _ = writeln!(out, "# TYPE keeper_{name} counter");
+            let _ = writeln!(out, "keeper_{name} {value}");
+        }
+        for (name, help, value) in gauges {
+            let _ = writeln!(out, "# HELP keeper_{name} {help}");
+            let _ = writeln!(out, "# TYPE keeper_{name} gauge");
+            let _ = writeln!(out, "keeper_{name} {value}");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        Metrics::inc(&metrics.claims_settled);
+        Metrics::inc(&metrics.claims_settled);
+        metrics.last_success.store(1_700_000_000, Ordering::Relaxed);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("# TYPE keeper_claims_settled_total counter\n"));
+        assert!(rendered.contains("\nkeeper_claims_settled_total 2\n"));
+        assert!(rendered.contains("\nkeeper_last_success_timestamp_seconds 1700000000\n"));
+    }
+}
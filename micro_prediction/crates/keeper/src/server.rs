@@ -0,0 +1,272 @@
+//! HTTP side of the keeper: health and metrics for monitoring, the endpoints
+//! through which settlement results and delegated claims reach it, and the
+//! failed claims an operator has to look into.
+//!
+//! Settlement results and claim retries need the API token as a bearer token.
+//! Claims do not: they are only accepted if they open a leaf of the result.
+use crate::{
+    metrics::Metrics,
+    store::{Claim, FailedClaim, Stage, Store, StoreError, StoredSettlement},
+};
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use axum::{
+    extract::{Path, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use micro_prediction_settlement::merkle;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub store: Arc<Store>,
+    pub metrics: Arc<Metrics>,
+    /// Unhealthy once the last successful tick is older than this.
+    pub stale_after_secs: i64,
+    /// Bearer token for the operator endpoints.
+    pub api_token: Arc<str>,
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/metrics", get(metrics))
+        .route("/rounds/{round_id}/settlement", post(post_settlement))
+        .route("/claims", post(post_claim))
+        .route("/claims/failed", get(failed_claims))
+        .route("/claims/{prediction}/retry", post(retry_claim))
+        .with_state(state)
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[derive(Serialize)]
+struct Health {
+    healthy: bool,
+    last_success: i64,
+    /// Claims waiting for manual action; see `/claims/failed`.
+    failed_claims: u64,
+}
+
+async fn health(State(state): State<AppState>) -> (StatusCode, Json<Health>) {
+    let last_success = state.metrics.last_success.load(Ordering::Relaxed);
+    let failed_claims = state.metrics.failed_claims.load(Ordering::Relaxed);
+    let healthy = last_success > 0 && unix_now() - last_success <= state.stale_after_secs;
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(Health {
+            healthy,
+            last_success,
+            failed_claims,
+        }),
+    )
+}
+
+async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [("content-type", "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<StoreError> for ApiError {
+    fn from(err: StoreError) -> Self {
+        let status = match err {
+            StoreError::SettlementConflict(_) => StatusCode::CONFLICT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self(status, err.to_string())
+    }
+}
+
+fn bad_request(message: impl Into<String>) -> ApiError {
+    ApiError(StatusCode::BAD_REQUEST, message.into())
+}
+
+fn authorize(state: &AppState, headers: &HeaderMap) -> Result<(), ApiError> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) if constant_time_eq(token.as_bytes(), state.api_token.as_bytes()) => Ok(()),
+        _ => Err(ApiError(
+            StatusCode::UNAUTHORIZED,
+            "missing or wrong API token".into(),
+        )),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn parse_hash(field: &str, value: &str) -> Result<[u8; 32], ApiError> {
+    let bytes = hex::decode(value.strip_prefix("0x").unwrap_or(value))
+        .map_err(|err| bad_request(format!("{field}: {err}")))?;
+    bytes
+        .try_into()
+        .map_err(|_| bad_request(format!("{field}: expected 32 bytes")))
+}
+
+/// Public part of a settlement computation's output.
+#[derive(Deserialize)]
+struct SettlementBody {
+    final_price: i64,
+    total_payout: u64,
+    fee_total: u64,
+    /// Hex-encoded blinded payout leaves, in the computation's order; empty
+    /// for a round without entries.
+    leaves: Vec<String>,
+    randomness_seed: Option<String>,
+    computation_offset: Option<u64>,
+}
+
+async fn post_settlement(
+    State(state): State<AppState>,
+    Path(round_id): Path<u64>,
+    headers: HeaderMap,
+    Json(body): Json<SettlementBody>,
+) -> Result<StatusCode, ApiError> {
+    authorize(&state, &headers)?;
+    let leaves = body
+        .leaves
+        .iter()
+        .map(|leaf| parse_hash("leaves", leaf))
+        .collect::<Result<Vec<_>, _>>()?;
+    let randomness_seed = body
+        .randomness_seed
+        .as_deref()
+        .map(|seed| parse_hash("randomness_seed", seed))
+        .transpose()?;
+
+    let now = unix_now();
+    state.store.save_settlement(&StoredSettlement {
+        round_id,
+        final_price: body.final_price,
+        total_payout: body.total_payout,
+        fee_total: body.fee_total,
+        randomness_seed,
+        computation_offset: body.computation_offset,
+        leaves,
+        received_at: now,
+    })?;
+    state.store.track_round(round_id, Stage::Resolving, now)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// A participant's decrypted `UserPayout`, handed over so the keeper claims
+/// on their behalf.
+#[derive(Deserialize)]
+struct ClaimBody {
+    round_id: u64,
+    prediction: String,
+    payout: u64,
+    blinding: String,
+}
+
+async fn post_claim(
+    State(state): State<AppState>,
+    Json(body): Json<ClaimBody>,
+) -> Result<StatusCode, ApiError> {
+    let prediction: Pubkey = body
+        .prediction
+        .parse()
+        .map_err(|err| bad_request(format!("prediction: {err}")))?;
+    let blinding = parse_hash("blinding", &body.blinding)?;
+    let settlement = state.store.settlement(body.round_id)?.ok_or_else(|| {
+        ApiError(
+            StatusCode::NOT_FOUND,
+            "no settlement for this round yet".into(),
+        )
+    })?;
+
+    // Only accept claims that open a leaf, so junk never reaches the chain.
+    let leaf = merkle::payout_leaf(&prediction.to_bytes(), body.payout, &blinding);
+    if !settlement.leaves.contains(&leaf) {
+        return Err(bad_request(
+            "claim does not open any leaf of the settlement",
+        ));
+    }
+
+    state.store.add_claim(&Claim {
+        prediction: prediction.to_bytes(),
+        round_id: body.round_id,
+        payout: body.payout,
+        blinding,
+        attempts: 0,
+    })?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[derive(Serialize)]
+struct FailedClaimView {
+    prediction: String,
+    round_id: u64,
+    payout: u64,
+    attempts: u32,
+    last_error: Option<String>,
+}
+
+impl From<FailedClaim> for FailedClaimView {
+    fn from(claim: FailedClaim) -> Self {
+        Self {
+            prediction: Pubkey::new_from_array(claim.prediction).to_string(),
+            round_id: claim.round_id,
+            payout: claim.payout,
+            attempts: claim.attempts,
+            last_error: claim.last_error,
+        }
+    }
+}
+
+/// Claims that ran out of retries. Their rounds are not finalized until each
+/// one is retried, e.g. after topping up the keeper or fixing the RPC.
+async fn failed_claims(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<FailedClaimView>>, ApiError> {
+    let claims = state.store.failed_claims()?;
+    Ok(Json(claims.into_iter().map(Into::into).collect()))
+}
+
+async fn retry_claim(
+    State(state): State<AppState>,
+    Path(prediction): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, ApiError> {
+    authorize(&state, &headers)?;
+    let prediction: Pubkey = prediction
+        .parse()
+        .map_err(|err| bad_request(format!("prediction: {err}")))?;
+    if !state.store.retry_claim(&prediction.to_bytes())? {
+        return Err(ApiError(
+            StatusCode::NOT_FOUND,
+            "no failed claim for this prediction".into(),
+        ));
+    }
+    Ok(StatusCode::ACCEPTED)
+}
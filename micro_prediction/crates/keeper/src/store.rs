@@ -0,0 +1,633 @@
+//! Embedded SQLite store of everything the keeper must not forget across
+//! restarts: the round schedule, settlement results handed to it and the
+//! claims it settles on behalf of users.
+//!
+//! On-chain state stays the source of truth: the keeper re-reads the round and
+//! prediction accounts before every step, so a row that lags behind the chain
+//! after a crash only costs a redundant read.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{path::Path, sync::Mutex};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS rounds (
+    round_id INTEGER PRIMARY KEY,
+    stage TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS settlements (
+    round_id INTEGER PRIMARY KEY,
+    final_price INTEGER NOT NULL,
+    total_payout INTEGER NOT NULL,
+    fee_total INTEGER NOT NULL,
+    randomness_seed BLOB,
+    computation_offset INTEGER,
+    leaves BLOB NOT NULL,
+    received_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS claims (
+    prediction BLOB PRIMARY KEY,
+    round_id INTEGER NOT NULL,
+    payout INTEGER NOT NULL,
+    blinding BLOB NOT NULL,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT
+);
+CREATE INDEX IF NOT EXISTS claims_by_round ON claims (round_id, status);
+";
+
+const NEXT_ROUND_ID: &str = "next_round_id";
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("sqlite: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("round {0} already has a different settlement")]
+    SettlementConflict(u64),
+    #[error("corrupt {0} column")]
+    Corrupt(&'static str),
+}
+
+pub type Result<T> = std::result::Result<T, StoreError>;
+
+/// How far the keeper has taken a round. Only advisory; see the module docs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Open,
+    Resolving,
+    Committed,
+    Finalized,
+    /// Swept or refunded; nothing left for the keeper to do.
+    Closed,
+}
+
+impl Stage {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Resolving => "resolving",
+            Self::Committed => "committed",
+            Self::Finalized => "finalized",
+            Self::Closed => "closed",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "open" => Self::Open,
+            "resolving" => Self::Resolving,
+            "committed" => Self::Committed,
+            "finalized" => Self::Finalized,
+            "closed" => Self::Closed,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClaimStatus {
+    Pending,
+    Settled,
+    /// The prediction was cancelled or refunded instead.
+    Skipped,
+    /// Gave up after the retry budget.
+    Failed,
+}
+
+impl ClaimStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Settled => "settled",
+            Self::Skipped => "skipped",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredSettlement {
+    pub round_id: u64,
+    pub final_price: i64,
+    pub total_payout: u64,
+    /// Fees the computation kept back for the house.
+    pub fee_total: u64,
+    pub randomness_seed: Option<[u8; 32]>,
+    /// Arcium computation that produced the result, if it came from one.
+    pub computation_offset: Option<u64>,
+    pub leaves: Vec<[u8; 32]>,
+    pub received_at: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Claim {
+    pub prediction: [u8; 32],
+    pub round_id: u64,
+    pub payout: u64,
+    pub blinding: [u8; 32],
+    pub attempts: u32,
+}
+
+/// A claim the keeper gave up on, kept for an operator to look into.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FailedClaim {
+    pub prediction: [u8; 32],
+    pub round_id: u64,
+    pub payout: u64,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClaimCounts {
+    pub pending: u64,
+    pub settled: u64,
+    pub skipped: u64,
+    pub failed: u64,
+}
+
+pub struct Store {
+    conn: Mutex<Connection>,
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        // WAL keeps the HTTP handlers' reads from blocking the keeper loop.
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "FULL")?;
+        Self::with_connection(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        // A panic while holding the lock cannot leave SQLite half-written.
+        self.conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn next_round_id(&self) -> Result<Option<u64>> {
+        let value: Option<i64> = self
+            .conn()
+            .query_row(
+                "SELECT value FROM meta WHERE key = ?1",
+                [NEXT_ROUND_ID],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value.map(|value| value as u64))
+    }
+
+    pub fn set_next_round_id(&self, round_id: u64) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![NEXT_ROUND_ID, round_id as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Starts tracking a round; a round that is already tracked keeps its stage.
+    pub fn track_round(&self, round_id: u64, stage: Stage, now: i64) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR IGNORE INTO rounds (round_id, stage, updated_at) VALUES (?1, ?2, ?3)",
+            params![round_id as i64, stage.as_str(), now],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_stage(&self, round_id: u64, stage: Stage, now: i64) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO rounds (round_id, stage, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (round_id) DO UPDATE SET
+                stage = excluded.stage, updated_at = excluded.updated_at",
+            params![round_id as i64, stage.as_str(), now],
+        )?;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub fn stage(&self, round_id: u64) -> Result<Option<Stage>> {
+        let stage: Option<String> = self
+            .conn()
+            .query_row(
+                "SELECT stage FROM rounds WHERE round_id = ?1",
+                [round_id as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+        stage
+            .map(|stage| Stage::parse(&stage).ok_or(StoreError::Corrupt("stage")))
+            .transpose()
+    }
+
+    /// Tracked rounds that are not closed yet, oldest first.
+    pub fn active_rounds(&self) -> Result<Vec<(u64, Stage)>> {
+        let conn = self.conn();
+        let mut statement =
+            conn.prepare("SELECT round_id, stage FROM rounds WHERE stage != ?1 ORDER BY round_id")?;
+        let rows = statement.query_map([Stage::Closed.as_str()], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        rows.map(|row| {
+            let (round_id, stage) = row?;
+            let stage = Stage::parse(&stage).ok_or(StoreError::Corrupt("stage"))?;
+            Ok((round_id as u64, stage))
+        })
+        .collect()
+    }
+
+    /// Saves a settlement result. Posting the same result again is a no-op;
+    /// a different one for the same round is rejected.
+    pub fn save_settlement(&self, settlement: &StoredSettlement) -> Result<()> {
+        if let Some(existing) = self.settlement(settlement.round_id)? {
+            return if existing.leaves == settlement.leaves
+                && existing.total_payout == settlement.total_payout
+                && existing.fee_total == settlement.fee_total
+                && existing.final_price == settlement.final_price
+                && existing.randomness_seed == settlement.randomness_seed
+            {
+                Ok(())
+            } else {
+                Err(StoreError::SettlementConflict(settlement.round_id))
+            };
+        }
+        self.conn().execute(
+            "INSERT INTO settlements (
+                round_id, final_price, total_payout, fee_total, randomness_seed,
+                computation_offset, leaves, received_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                settlement.round_id as i64,
+                settlement.final_price,
+                settlement.total_payout as i64,
+                settlement.fee_total as i64,
+                settlement.randomness_seed.as_ref().map(|seed| &seed[..]),
+                settlement.computation_offset.map(|offset| offset as i64),
+                settlement.leaves.concat(),
+                settlement.received_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn settlement(&self, round_id: u64) -> Result<Option<StoredSettlement>> {
+        let row = self
+            .conn()
+            .query_row(
+                "SELECT final_price, total_payout, fee_total, randomness_seed,
+                        computation_offset, leaves, received_at
+                 FROM settlements WHERE round_id = ?1",
+                [round_id as i64],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, Option<Vec<u8>>>(3)?,
+                        row.get::<_, Option<i64>>(4)?,
+                        row.get::<_, Vec<u8>>(5)?,
+                        row.get::<_, i64>(6)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((final_price, total_payout, fee_total, seed, offset, leaves, received_at)) = row
+        else {
+            return Ok(None);
+        };
+
+        let randomness_seed = seed
+            .map(|seed| to_hash(&seed).ok_or(StoreError::Corrupt("randomness_seed")))
+            .transpose()?;
+        if leaves.len() % 32 != 0 {
+            return Err(StoreError::Corrupt("leaves"));
+        }
+        let leaves = leaves
+            .chunks_exact(32)
+            .map(|leaf| to_hash(leaf).ok_or(StoreError::Corrupt("leaves")))
+            .collect::<Result<_>>()?;
+
+        Ok(Some(StoredSettlement {
+            round_id,
+            final_price,
+            total_payout: total_payout as u64,
+            fee_total: fee_total as u64,
+            randomness_seed,
+            computation_offset: offset.map(|offset| offset as u64),
+            leaves,
+            received_at,
+        }))
+    }
+
+    /// Queues a claim; re-adding a known prediction is a no-op.
+    pub fn add_claim(&self, claim: &Claim) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR IGNORE INTO claims (prediction, round_id, payout, blinding, status)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                &claim.prediction[..],
+                claim.round_id as i64,
+                claim.payout as i64,
+                &claim.blinding[..],
+                ClaimStatus::Pending.as_str(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Pending claims of a round whose backoff has elapsed.
+    pub fn due_claims(&self, round_id: u64, now: i64) -> Result<Vec<Claim>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT prediction, payout, blinding, attempts FROM claims
+             WHERE round_id = ?1 AND status = ?2 AND next_attempt_at <= ?3
+             ORDER BY prediction",
+        )?;
+        let rows = statement.query_map(
+            params![round_id as i64, ClaimStatus::Pending.as_str(), now],
+            |row| {
+                Ok((
+                    row.get::<_, Vec<u8>>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, Vec<u8>>(2)?,
+                    row.get::<_, u32>(3)?,
+                ))
+            },
+        )?;
+        rows.map(|row| {
+            let (prediction, payout, blinding, attempts) = row?;
+            Ok(Claim {
+                prediction: to_hash(&prediction).ok_or(StoreError::Corrupt("prediction"))?,
+                round_id,
+                payout: payout as u64,
+                blinding: to_hash(&blinding).ok_or(StoreError::Corrupt("blinding"))?,
+                attempts,
+            })
+        })
+        .collect()
+    }
+
+    pub fn set_claim_status(&self, prediction: &[u8; 32], status: ClaimStatus) -> Result<()> {
+        self.conn().execute(
+            "UPDATE claims SET status = ?2, last_error = NULL WHERE prediction = ?1",
+            params![&prediction[..], status.as_str()],
+        )?;
+        Ok(())
+    }
+
+    /// Records a failed attempt and schedules the next one, or gives up on
+    /// the claim when `retry_at` is `None`.
+    pub fn record_claim_failure(
+        &self,
+        prediction: &[u8; 32],
+        error: &str,
+        retry_at: Option<i64>,
+    ) -> Result<()> {
+        let status = match retry_at {
+            Some(_) => ClaimStatus::Pending,
+            None => ClaimStatus::Failed,
+        };
+        self.conn().execute(
+            "UPDATE claims SET
+                attempts = attempts + 1,
+                last_error = ?2,
+                status = ?3,
+                next_attempt_at = ?4
+             WHERE prediction = ?1",
+            params![
+                &prediction[..],
+                error,
+                status.as_str(),
+                retry_at.unwrap_or(0)
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Claims that ran out of retries, oldest round first.
+    pub fn failed_claims(&self) -> Result<Vec<FailedClaim>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT prediction, round_id, payout, attempts, last_error FROM claims
+             WHERE status = ?1
+             ORDER BY round_id, prediction",
+        )?;
+        let rows = statement.query_map([ClaimStatus::Failed.as_str()], |row| {
+            Ok((
+                row.get::<_, Vec<u8>>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, u32>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })?;
+        rows.map(|row| {
+            let (prediction, round_id, payout, attempts, last_error) = row?;
+            Ok(FailedClaim {
+                prediction: to_hash(&prediction).ok_or(StoreError::Corrupt("prediction"))?,
+                round_id: round_id as u64,
+                payout: payout as u64,
+                attempts,
+                last_error,
+            })
+        })
+        .collect()
+    }
+
+    /// Puts a failed claim back in the queue with a fresh retry budget.
+    /// Returns whether there was a failed claim for `prediction`.
+    pub fn retry_claim(&self, prediction: &[u8; 32]) -> Result<bool> {
+        let updated = self.conn().execute(
+            "UPDATE claims SET status = ?2, attempts = 0, next_attempt_at = 0
+             WHERE prediction = ?1 AND status = ?3",
+            params![
+                &prediction[..],
+                ClaimStatus::Pending.as_str(),
+                ClaimStatus::Failed.as_str()
+            ],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Claim counts of one round, or of every round when `round_id` is `None`.
+    pub fn claim_counts(&self, round_id: Option<u64>) -> Result<ClaimCounts> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT status, COUNT(*) FROM claims
+             WHERE ?1 IS NULL OR round_id = ?1
+             GROUP BY status",
+        )?;
+        let rows = statement.query_map([round_id.map(|id| id as i64)], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        let mut counts = ClaimCounts::default();
+        for row in rows {
+            let (status, count) = row?;
+            let count = count as u64;
+            match status.as_str() {
+                "pending" => counts.pending = count,
+                "settled" => counts.settled = count,
+                "skipped" => counts.skipped = count,
+                "failed" => counts.failed = count,
+                _ => return Err(StoreError::Corrupt("status")),
+            }
+        }
+        Ok(counts)
+    }
+}
+
+fn to_hash(bytes: &[u8]) -> Option<[u8; 32]> {
+    bytes.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settlement(round_id: u64) -> StoredSettlement {
+        StoredSettlement {
+            round_id,
+            final_price: -5,
+            total_payout: u64::MAX,
+            fee_total: 3,
+            randomness_seed: Some([9; 32]),
+            computation_offset: Some(7),
+            leaves: vec![[1; 32], [2; 32], [3; 32]],
+            received_at: 100,
+        }
+    }
+
+    fn claim(tag: u8) -> Claim {
+        Claim {
+            prediction: [tag; 32],
+            round_id: 1,
+            payout: 50,
+            blinding: [tag + 100; 32],
+            attempts: 0,
+        }
+    }
+
+    #[test]
+    fn test_round_tracking() {
+        let store = Store::open_in_memory().unwrap();
+        assert_eq!(store.next_round_id().unwrap(), None);
+        store.set_next_round_id(3).unwrap();
+        store.set_next_round_id(4).unwrap();
+        assert_eq!(store.next_round_id().unwrap(), Some(4));
+
+        store.track_round(1, Stage::Open, 0).unwrap();
+        store.track_round(2, Stage::Open, 0).unwrap();
+        store.set_stage(1, Stage::Committed, 1).unwrap();
+        // Tracking again must not roll the stage back.
+        store.track_round(1, Stage::Open, 2).unwrap();
+        store.set_stage(2, Stage::Closed, 1).unwrap();
+
+        assert_eq!(store.stage(1).unwrap(), Some(Stage::Committed));
+        assert_eq!(store.active_rounds().unwrap(), vec![(1, Stage::Committed)]);
+    }
+
+    #[test]
+    fn test_settlement_round_trip_and_conflicts() {
+        let store = Store::open_in_memory().unwrap();
+        assert_eq!(store.settlement(1).unwrap(), None);
+
+        store.save_settlement(&settlement(1)).unwrap();
+        assert_eq!(store.settlement(1).unwrap(), Some(settlement(1)));
+        // Re-posting the same result is idempotent.
+        store.save_settlement(&settlement(1)).unwrap();
+
+        let mut different = settlement(1);
+        different.leaves.pop();
+        assert!(matches!(
+            store.save_settlement(&different),
+            Err(StoreError::SettlementConflict(1))
+        ));
+    }
+
+    #[test]
+    fn test_claim_retries() {
+        let store = Store::open_in_memory().unwrap();
+        store.add_claim(&claim(1)).unwrap();
+        store.add_claim(&claim(2)).unwrap();
+        store.add_claim(&claim(1)).unwrap();
+        assert_eq!(store.due_claims(1, 0).unwrap(), vec![claim(1), claim(2)]);
+
+        store
+            .record_claim_failure(&[1; 32], "rpc timeout", Some(30))
+            .unwrap();
+        assert_eq!(store.due_claims(1, 29).unwrap(), vec![claim(2)]);
+        let retried = store.due_claims(1, 30).unwrap();
+        assert_eq!(retried[0].attempts, 1);
+
+        store
+            .set_claim_status(&[2; 32], ClaimStatus::Settled)
+            .unwrap();
+        store
+            .record_claim_failure(&[1; 32], "still failing", None)
+            .unwrap();
+        assert!(store.due_claims(1, i64::MAX).unwrap().is_empty());
+        assert_eq!(
+            store.claim_counts(Some(1)).unwrap(),
+            ClaimCounts {
+                pending: 0,
+                settled: 1,
+                skipped: 0,
+                failed: 1,
+            }
+        );
+        assert_eq!(store.claim_counts(Some(2)).unwrap(), ClaimCounts::default());
+
+        assert_eq!(
+            store.failed_claims().unwrap(),
+            vec![FailedClaim {
+                prediction: [1; 32],
+                round_id: 1,
+                payout: 50,
+                attempts: 2,
+                last_error: Some("still failing".into()),
+            }]
+        );
+        assert!(!store.retry_claim(&[2; 32]).unwrap());
+        assert!(store.retry_claim(&[1; 32]).unwrap());
+        assert!(store.failed_claims().unwrap().is_empty());
+        assert_eq!(store.due_claims(1, 0).unwrap(), vec![claim(1)]);
+    }
+
+    #[test]
+    fn test_state_survives_reopen() {
+        let path = std::env::temp_dir().join(format!("keeper-store-{}.sqlite", std::process::id()));
+        {
+            let store = Store::open(&path).unwrap();
+            store.set_next_round_id(9).unwrap();
+            store.save_settlement(&settlement(8)).unwrap();
+            store.add_claim(&claim(1)).unwrap();
+        }
+        let store = Store::open(&path).unwrap();
+        assert_eq!(store.next_round_id().unwrap(), Some(9));
+        assert_eq!(store.settlement(8).unwrap(), Some(settlement(8)));
+        assert_eq!(store.due_claims(1, 0).unwrap().len(), 1);
+        drop(store);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+        }
+    }
+}
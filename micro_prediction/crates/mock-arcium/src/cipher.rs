@@ -0,0 +1,155 @@
+//! How the mock opens `Enc<Shared, _>` inputs and seals outputs.
+//!
+//! A real cluster derives a shared secret from the client's x25519 key and
+//! the MXE key and runs the Rescue cipher, which [`Rescue`] does with the MXE
+//! secret in the clear. [`Plaintext`] lets tests submit inputs unencrypted.
+use arcium_client::cipher::{FieldElement, Nonce, RescueCipher};
+use curve25519_dalek::montgomery::MontgomeryPoint;
+
+/// Encryption of one `Enc<Shared, _>` value: the scalars of a struct are
+/// encrypted together under the client's `key` and `nonce`, one ciphertext
+/// per scalar.
+pub trait Cipher: Send + Sync {
+    /// `None` if a ciphertext does not decrypt to a valid scalar.
+    fn decrypt(&self, key: &[u8; 32], nonce: u128, ciphertexts: &[[u8; 32]]) -> Option<Vec<u128>>;
+
+    fn encrypt(&self, key: &[u8; 32], nonce: u128, values: &[u128]) -> Vec<[u8; 32]>;
+}
+
+/// Identity cipher: a ciphertext is the scalar in its low 16 bytes,
+/// little-endian, with the high 16 bytes zero.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Plaintext;
+
+impl Cipher for Plaintext {
+    fn decrypt(
+        &self,
+        _key: &[u8; 32],
+        _nonce: u128,
+        ciphertexts: &[[u8; 32]],
+    ) -> Option<Vec<u128>> {
+        ciphertexts
+            .iter()
+            .map(|ciphertext| {
+                let (low, high) = ciphertext.split_at(16);
+                if high.iter().any(|byte| *byte != 0) {
+                    return None;
+                }
+                Some(u128::from_le_bytes(low.try_into().unwrap()))
+            })
+            .collect()
+    }
+
+    fn encrypt(&self, _key: &[u8; 32], _nonce: u128, values: &[u128]) -> Vec<[u8; 32]> {
+        values
+            .iter()
+            .map(|value| {
+                let mut ciphertext = [0u8; 32];
+                ciphertext[..16].copy_from_slice(&value.to_le_bytes());
+                ciphertext
+            })
+            .collect()
+    }
+}
+
+/// The x25519 public key of `secret`.
+pub fn x25519_public_key(secret: &[u8; 32]) -> [u8; 32] {
+    MontgomeryPoint::mul_base_clamped(*secret).to_bytes()
+}
+
+/// The x25519 shared secret of `secret` and a peer's `public_key`, which keys
+/// the Rescue cipher on both sides.
+pub fn x25519(secret: &[u8; 32], public_key: &[u8; 32]) -> [u8; 32] {
+    MontgomeryPoint(*public_key).mul_clamped(*secret).to_bytes()
+}
+
+/// The Rescue cipher clients encrypt for the MXE with, keyed by the MXE's
+/// x25519 secret.
+pub struct Rescue {
+    secret: [u8; 32],
+}
+
+impl Rescue {
+    pub fn new(secret: [u8; 32]) -> Self {
+        Self { secret }
+    }
+
+    /// The x25519 key clients exchange with; the MXE account publishes it.
+    pub fn public_key(&self) -> [u8; 32] {
+        x25519_public_key(&self.secret)
+    }
+
+    fn cipher(&self, key: &[u8; 32]) -> RescueCipher {
+        RescueCipher::new(&x25519(&self.secret, key))
+    }
+}
+
+impl Cipher for Rescue {
+    fn decrypt(&self, key: &[u8; 32], nonce: u128, ciphertexts: &[[u8; 32]]) -> Option<Vec<u128>> {
+        self.cipher(key)
+            .decrypt_scalars(ciphertexts, &Nonce::from_u128(nonce))
+            .ok()?
+            .iter()
+            .map(FieldElement::to_u128)
+            .collect()
+    }
+
+    fn encrypt(&self, key: &[u8; 32], nonce: u128, values: &[u128]) -> Vec<[u8; 32]> {
+        let scalars: Vec<FieldElement> = values
+            .iter()
+            .copied()
+            .map(FieldElement::from_u128)
+            .collect();
+        self.cipher(key)
+            .encrypt_scalars(&scalars, &Nonce::from_u128(nonce))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plaintext_round_trip() {
+        let values = [0, 7, u64::MAX as u128, u128::MAX];
+        let ciphertexts = Plaintext.encrypt(&[1; 32], 5, &values);
+        assert_eq!(
+            Plaintext.decrypt(&[1; 32], 5, &ciphertexts).unwrap(),
+            values
+        );
+
+        let mut invalid = ciphertexts[0];
+        invalid[31] = 1;
+        assert_eq!(Plaintext.decrypt(&[1; 32], 5, &[invalid]), None);
+    }
+
+    #[test]
+    fn test_rescue_matches_the_client() {
+        let mxe = Rescue::new([1; 32]);
+        let client_key = x25519_public_key(&[2; 32]);
+        assert_eq!(
+            x25519(&[2; 32], &mxe.public_key()),
+            x25519(&[1; 32], &client_key)
+        );
+        let client = RescueCipher::new(&x25519(&[2; 32], &mxe.public_key()));
+
+        let values = [1_010, 100, u64::MAX as u128];
+        let scalars: Vec<FieldElement> = values
+            .iter()
+            .copied()
+            .map(FieldElement::from_u128)
+            .collect();
+        let ciphertexts = client.encrypt_scalars(&scalars, &Nonce::from_u128(5));
+        assert_eq!(mxe.decrypt(&client_key, 5, &ciphertexts).unwrap(), values);
+        assert_ne!(
+            mxe.decrypt(&client_key, 6, &ciphertexts),
+            Some(values.to_vec())
+        );
+
+        let sealed = mxe.encrypt(&client_key, 6, &values);
+        let opened = client
+            .decrypt_scalars(&sealed, &Nonce::from_u128(6))
+            .unwrap();
+        assert_eq!(opened, scalars);
+    }
+}
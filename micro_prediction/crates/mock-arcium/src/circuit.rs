@@ -0,0 +1,218 @@
+//! Plaintext circuits and the arguments a queued computation hands them.
+use crate::cipher::Cipher;
+use arcium_client::{
+    idl::arcium::types::{Argument, ExecutionFailure},
+    pda::comp_def_offset,
+};
+use std::{collections::HashMap, slice};
+
+/// A plaintext implementation of an `#[instruction]` of the MXE's circuits.
+pub trait Circuit: Send + Sync {
+    /// Name of the `#[instruction]`; its computation definition offset is
+    /// derived from it as on-chain.
+    fn name(&self) -> &'static str;
+
+    /// Reads the circuit's parameters from `inputs` in declaration order and
+    /// returns its serialized output. `seed` stands in for the randomness the
+    /// cluster would generate with `ArcisRNG`.
+    fn execute(&self, inputs: &mut Inputs, seed: &[u8; 32]) -> Result<Vec<u8>, ExecutionFailure>;
+}
+
+/// Key context of an `Enc<Shared, _>` input: the client's x25519 key and the
+/// nonce it encrypted with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shared {
+    pub key: [u8; 32],
+    pub nonce: u128,
+}
+
+/// Cursor over the arguments of a queued computation. Any argument of the
+/// wrong kind, or a missing or left-over one, fails the computation with
+/// `ExecutionFailure::Inputs` as a real cluster would.
+pub struct Inputs<'a> {
+    args: slice::Iter<'a, Argument>,
+    cipher: &'a dyn Cipher,
+}
+
+macro_rules! plaintext {
+    ($name:ident, $variant:ident, $ty:ty) => {
+        pub fn $name(&mut self) -> Result<$ty, ExecutionFailure> {
+            match self.next()? {
+                Argument::$variant(value) => Ok(*value),
+                _ => Err(ExecutionFailure::Inputs),
+            }
+        }
+    };
+}
+
+impl<'a> Inputs<'a> {
+    pub fn new(args: &'a [Argument], cipher: &'a dyn Cipher) -> Self {
+        Self {
+            args: args.iter(),
+            cipher,
+        }
+    }
+
+    pub fn cipher(&self) -> &'a dyn Cipher {
+        self.cipher
+    }
+
+    fn next(&mut self) -> Result<&'a Argument, ExecutionFailure> {
+        self.args.next().ok_or(ExecutionFailure::Inputs)
+    }
+
+    plaintext!(bool, PlaintextBool, bool);
+    plaintext!(u8, PlaintextU8, u8);
+    plaintext!(u16, PlaintextU16, u16);
+    plaintext!(u32, PlaintextU32, u32);
+    plaintext!(u64, PlaintextU64, u64);
+    plaintext!(u128, PlaintextU128, u128);
+
+    /// A public 32-byte value, passed as an `ArcisPubkey` argument.
+    pub fn bytes32(&mut self) -> Result<[u8; 32], ExecutionFailure> {
+        match self.next()? {
+            Argument::ArcisPubkey(bytes) => Ok(*bytes),
+            _ => Err(ExecutionFailure::Inputs),
+        }
+    }
+
+    /// The key and nonce that precede the ciphertexts of an `Enc<Shared, _>`.
+    pub fn shared(&mut self) -> Result<Shared, ExecutionFailure> {
+        Ok(Shared {
+            key: self.bytes32()?,
+            nonce: self.u128()?,
+        })
+    }
+
+    /// Decrypts the next `count` encrypted scalars, which were encrypted
+    /// together under `shared`.
+    pub fn encrypted(
+        &mut self,
+        shared: &Shared,
+        count: usize,
+    ) -> Result<Vec<u128>, ExecutionFailure> {
+        let mut ciphertexts = Vec::with_capacity(count);
+        for _ in 0..count {
+            match self.next()? {
+                Argument::EncryptedBool(ciphertext)
+                | Argument::EncryptedU8(ciphertext)
+                | Argument::EncryptedU16(ciphertext)
+                | Argument::EncryptedU32(ciphertext)
+                | Argument::EncryptedU64(ciphertext)
+                | Argument::EncryptedU128(ciphertext) => ciphertexts.push(*ciphertext),
+                _ => return Err(ExecutionFailure::Inputs),
+            }
+        }
+        self.cipher
+            .decrypt(&shared.key, shared.nonce, &ciphertexts)
+            .ok_or(ExecutionFailure::Inputs)
+    }
+
+    pub fn finish(mut self) -> Result<(), ExecutionFailure> {
+        match self.args.next() {
+            Some(_) => Err(ExecutionFailure::Inputs),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Circuits the mock cluster can execute, by computation definition offset.
+#[derive(Default)]
+pub struct Registry {
+    circuits: HashMap<u32, Box<dyn Circuit>>,
+}
+
+impl Registry {
+    pub fn register(&mut self, circuit: impl Circuit + 'static) -> &mut Self {
+        self.circuits
+            .insert(comp_def_offset(circuit.name()), Box::new(circuit));
+        self
+    }
+
+    pub fn get(&self, comp_def_offset: u32) -> Option<&dyn Circuit> {
+        self.circuits
+            .get(&comp_def_offset)
+            .map(|circuit| &**circuit)
+    }
+
+    /// Runs the circuit over all of `args`. `None` if no circuit is
+    /// registered under `comp_def_offset`.
+    pub fn execute(
+        &self,
+        comp_def_offset: u32,
+        args: &[Argument],
+        cipher: &dyn Cipher,
+        seed: &[u8; 32],
+    ) -> Option<Result<Vec<u8>, ExecutionFailure>> {
+        let circuit = self.get(comp_def_offset)?;
+        let mut inputs = Inputs::new(args, cipher);
+        Some(
+            circuit
+                .execute(&mut inputs, seed)
+                .and_then(|output| inputs.finish().map(|()| output)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cipher::Plaintext;
+
+    struct Sum;
+
+    impl Circuit for Sum {
+        fn name(&self) -> &'static str {
+            "sum"
+        }
+
+        fn execute(
+            &self,
+            inputs: &mut Inputs,
+            _seed: &[u8; 32],
+        ) -> Result<Vec<u8>, ExecutionFailure> {
+            let shared = inputs.shared()?;
+            let values = inputs.encrypted(&shared, 2)?;
+            let offset = inputs.u64()? as u128;
+            Ok((values[0] + values[1] + offset).to_le_bytes().to_vec())
+        }
+    }
+
+    fn encrypted(value: u128) -> Argument {
+        Argument::EncryptedU64(Plaintext.encrypt(&[0; 32], 0, &[value])[0])
+    }
+
+    #[test]
+    fn test_registry_executes_by_offset() {
+        let mut registry = Registry::default();
+        registry.register(Sum);
+        let offset = comp_def_offset("sum");
+        let mut args = vec![
+            Argument::ArcisPubkey([9; 32]),
+            Argument::PlaintextU128(3),
+            encrypted(4),
+            encrypted(5),
+            Argument::PlaintextU64(1),
+        ];
+
+        let output = registry
+            .execute(offset, &args, &Plaintext, &[0; 32])
+            .unwrap()
+            .unwrap();
+        assert_eq!(output, 10u128.to_le_bytes());
+        assert!(registry
+            .execute(offset + 1, &args, &Plaintext, &[0; 32])
+            .is_none());
+
+        args.push(Argument::PlaintextU8(0));
+        assert!(matches!(
+            registry.execute(offset, &args, &Plaintext, &[0; 32]),
+            Some(Err(ExecutionFailure::Inputs))
+        ));
+        args.truncate(3);
+        assert!(matches!(
+            registry.execute(offset, &args, &Plaintext, &[0; 32]),
+            Some(Err(ExecutionFailure::Inputs))
+        ));
+    }
+}
@@ -0,0 +1,286 @@
+//! Plaintext versions of the circuits in `encrypted-ixs`, built on the
+//! settlement model.
+//!
+//! Arguments follow the parameter order of the `#[instruction]`, with struct
+//! fields flattened in declaration order: a `Vec` is prefixed by its length as
+//! `PlaintextU32`, a `[u8; 32]` is an `ArcisPubkey`, and an `Enc<Shared, _>`
+//! or `Shared` owner is the client key (`ArcisPubkey`) and nonce
+//! (`PlaintextU128`) followed by its encrypted scalars.
+//!
+//! Outputs are Borsh-encoded in field order. An `Enc<Shared, T>` output is the
+//! client key, the nonce (the input nonce plus one) and one ciphertext per
+//! scalar of `T`, with byte arrays encrypted one byte per scalar as Arcis
+//! does.
+use crate::circuit::{Circuit, Inputs, Shared};
+use arcium_client::idl::arcium::types::ExecutionFailure;
+use micro_prediction_settlement::{
+    determine_winners, draw, merkle, Prediction, TieBreak, WinnerParams, MAX_PAYOUT_TIERS,
+};
+use sha2::{Digest, Sha256};
+
+/// `tie_break` values; must match `TIE_BREAK_*` in the circuits.
+const TIE_BREAK_SPLIT: u8 = 0;
+const TIE_BREAK_RANDOM: u8 = 1;
+
+/// Derives the blinding of payout leaf `index` from the computation seed. The
+/// real circuit draws each one independently.
+fn blinding(seed: &[u8; 32], index: usize) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(b"mock_arcium:blinding")
+        .chain_update(seed)
+        .chain_update((index as u64).to_le_bytes())
+        .finalize()
+        .into()
+}
+
+/// `determine_winners(predictions, final_price, fee_bps, round_id,
+/// payout_tiers, payout_pool, tie_break, lucky_draw_bps) -> SettlementOutput`.
+pub struct DetermineWinners;
+
+struct Entry {
+    account: [u8; 32],
+    owner: Shared,
+}
+
+impl Circuit for DetermineWinners {
+    fn name(&self) -> &'static str {
+        "determine_winners"
+    }
+
+    fn execute(&self, inputs: &mut Inputs, seed: &[u8; 32]) -> Result<Vec<u8>, ExecutionFailure> {
+        let count = inputs.u32()? as usize;
+        let mut entries = Vec::with_capacity(count);
+        let mut predictions = Vec::with_capacity(count);
+        for _ in 0..count {
+            let account = inputs.bytes32()?;
+            let _commitment = inputs.bytes32()?;
+            let owner = inputs.shared()?;
+            let values = inputs.encrypted(&owner, 2)?;
+            let weight_bps = inputs.u16()?;
+            predictions.push(Prediction {
+                account,
+                predicted_price: values[0],
+                stake: values[1],
+                weight_bps,
+            });
+            entries.push(Entry { account, owner });
+        }
+        let price_owner = inputs.shared()?;
+        let final_price = inputs.encrypted(&price_owner, 1)?[0];
+        let fee_bps = inputs.u16()?;
+        let round_id = inputs.u64()?;
+        let mut payout_tiers = [0u16; MAX_PAYOUT_TIERS];
+        for tier in &mut payout_tiers {
+            *tier = inputs.u16()?;
+        }
+        let payout_pool = inputs.u64()?;
+        let tie_break = match inputs.u8()? {
+            TIE_BREAK_SPLIT => TieBreak::Split,
+            TIE_BREAK_RANDOM => TieBreak::Random,
+            _ => return Err(ExecutionFailure::Inputs),
+        };
+        let lucky_draw_bps = inputs.u16()?;
+
+        let params = WinnerParams {
+            fee_bps,
+            payout_tiers,
+            payout_pool,
+            tie_break,
+            lucky_draw_bps,
+        };
+        let settlement = determine_winners(&predictions, final_price, &params, seed);
+
+        let blindings: Vec<[u8; 32]> = (0..entries.len())
+            .map(|index| blinding(seed, index))
+            .collect();
+        let mut leaves: Vec<[u8; 32]> = settlement
+            .payouts
+            .iter()
+            .zip(&blindings)
+            .map(|(payout, blinding)| merkle::payout_leaf(&payout.account, payout.payout, blinding))
+            .collect();
+        let draws = tie_break == TieBreak::Random || lucky_draw_bps > 0;
+        if draws && !entries.is_empty() {
+            leaves.push(draw::seed_leaf(seed));
+        }
+
+        let mut output = Vec::new();
+        output.extend_from_slice(&round_id.to_le_bytes());
+        output.extend_from_slice(&(final_price as i64).to_le_bytes());
+        output.extend_from_slice(&settlement.fee_total.to_le_bytes());
+        output.extend_from_slice(&settlement.total_payout.to_le_bytes());
+        output.extend_from_slice(&(leaves.len() as u32).to_le_bytes());
+        for leaf in &leaves {
+            output.extend_from_slice(leaf);
+        }
+        output.extend_from_slice(&merkle::root(&leaves));
+        output.extend_from_slice(if draws { seed } else { &[0; 32] });
+
+        output.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for ((entry, payout), blinding) in entries.iter().zip(&settlement.payouts).zip(&blindings) {
+            let scalars: Vec<u128> = entry
+                .account
+                .iter()
+                .map(|byte| *byte as u128)
+                .chain([payout.payout as u128])
+                .chain(blinding.iter().map(|byte| *byte as u128))
+                .collect();
+            let nonce = entry.owner.nonce.wrapping_add(1);
+            output.extend_from_slice(&entry.owner.key);
+            output.extend_from_slice(&nonce.to_le_bytes());
+            for ciphertext in inputs.cipher().encrypt(&entry.owner.key, nonce, &scalars) {
+                output.extend_from_slice(&ciphertext);
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cipher::{Cipher, Plaintext},
+        circuit::Registry,
+    };
+    use arcium_client::{idl::arcium::types::Argument, pda::comp_def_offset};
+
+    fn encrypted(value: u128) -> Argument {
+        Argument::EncryptedU64(Plaintext.encrypt(&[0; 32], 0, &[value])[0])
+    }
+
+    fn prediction(account: u8, price: u128, stake: u128) -> Vec<Argument> {
+        vec![
+            Argument::ArcisPubkey([account; 32]),
+            Argument::ArcisPubkey([0; 32]),
+            Argument::ArcisPubkey([account + 100; 32]),
+            Argument::PlaintextU128(account as u128),
+            encrypted(price),
+            encrypted(stake),
+            Argument::PlaintextU16(10_000),
+        ]
+    }
+
+    fn args(tie_break: u8) -> Vec<Argument> {
+        let mut args = vec![Argument::PlaintextU32(3)];
+        args.extend(prediction(1, 1_010, 100));
+        args.extend(prediction(2, 990, 300));
+        args.extend(prediction(3, 1_200, 50));
+        args.extend([
+            Argument::ArcisPubkey([7; 32]),
+            Argument::PlaintextU128(0),
+            encrypted(1_000),
+            Argument::PlaintextU16(100),
+            Argument::PlaintextU64(42),
+        ]);
+        args.extend((0..MAX_PAYOUT_TIERS).map(|_| Argument::PlaintextU16(0)));
+        args.extend([
+            Argument::PlaintextU64(450),
+            Argument::PlaintextU8(tie_break),
+            Argument::PlaintextU16(0),
+        ]);
+        args
+    }
+
+    fn u64_at(output: &[u8], at: usize) -> u64 {
+        u64::from_le_bytes(output[at..at + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_determine_winners_output() {
+        let mut registry = Registry::default();
+        registry.register(DetermineWinners);
+        let seed = [5u8; 32];
+        let output = registry
+            .execute(
+                comp_def_offset("determine_winners"),
+                &args(TIE_BREAK_SPLIT),
+                &Plaintext,
+                &seed,
+            )
+            .unwrap()
+            .unwrap();
+
+        // Both closest entries are 10 away and get their stake back less 1%.
+        assert_eq!(u64_at(&output, 0), 42);
+        assert_eq!(u64_at(&output, 8), 1_000);
+        assert_eq!(u64_at(&output, 16), 4);
+        assert_eq!(u64_at(&output, 24), 396);
+        assert_eq!(&output[32..36], &3u32.to_le_bytes());
+        let leaves: Vec<[u8; 32]> = output[36..36 + 96]
+            .chunks(32)
+            .map(|leaf| leaf.try_into().unwrap())
+            .collect();
+        assert_eq!(
+            leaves[0],
+            merkle::payout_leaf(&[1; 32], 99, &blinding(&seed, 0))
+        );
+        assert_eq!(&output[132..164], &merkle::root(&leaves));
+        // Nothing was drawn, so no seed is published.
+        assert_eq!(&output[164..196], &[0; 32]);
+
+        // The first user result opens to its account, payout and blinding.
+        let results = &output[196..];
+        assert_eq!(&results[..4], &3u32.to_le_bytes());
+        assert_eq!(&results[4..36], &[101; 32]);
+        assert_eq!(u128::from_le_bytes(results[36..52].try_into().unwrap()), 2);
+        let ciphertexts: Vec<[u8; 32]> = results[52..52 + 65 * 32]
+            .chunks(32)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect();
+        let scalars = Plaintext.decrypt(&[101; 32], 2, &ciphertexts).unwrap();
+        assert_eq!(scalars[..32], [1u128; 32]);
+        assert_eq!(scalars[32], 99);
+        assert_eq!(
+            scalars[33..]
+                .iter()
+                .map(|byte| *byte as u8)
+                .collect::<Vec<_>>(),
+            blinding(&seed, 0)
+        );
+        assert_eq!(results.len(), 4 + 3 * (32 + 16 + 65 * 32));
+    }
+
+    #[test]
+    fn test_determine_winners_commits_to_its_seed() {
+        let mut registry = Registry::default();
+        registry.register(DetermineWinners);
+        let seed = [5u8; 32];
+        let output = registry
+            .execute(
+                comp_def_offset("determine_winners"),
+                &args(TIE_BREAK_RANDOM),
+                &Plaintext,
+                &seed,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(&output[32..36], &4u32.to_le_bytes());
+        let leaves: Vec<[u8; 32]> = output[36..36 + 128]
+            .chunks(32)
+            .map(|leaf| leaf.try_into().unwrap())
+            .collect();
+        assert_eq!(leaves[3], draw::seed_leaf(&seed));
+        assert_eq!(&output[164..196], &merkle::root(&leaves));
+        assert_eq!(&output[196..228], &seed);
+    }
+
+    #[test]
+    fn test_determine_winners_rejects_bad_inputs() {
+        let mut registry = Registry::default();
+        registry.register(DetermineWinners);
+        let offset = comp_def_offset("determine_winners");
+        assert!(matches!(
+            registry.execute(offset, &args(9), &Plaintext, &[0; 32]),
+            Some(Err(ExecutionFailure::Inputs))
+        ));
+
+        let mut swapped = args(TIE_BREAK_SPLIT);
+        swapped[5] = Argument::PlaintextU64(1_010);
+        assert!(matches!(
+            registry.execute(offset, &swapped, &Plaintext, &[0; 32]),
+            Some(Err(ExecutionFailure::Inputs))
+        ));
+    }
+}
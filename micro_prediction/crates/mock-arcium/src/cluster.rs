@@ -0,0 +1,304 @@
+//! The mock cluster's loop: watch the MXE mempool, execute what it can and
+//! call back.
+use crate::{cipher::Cipher, circuit::Registry};
+use anchor_client::{
+    anchor_lang::AccountDeserialize,
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        instruction::Instruction,
+        pubkey::Pubkey,
+        signature::{Keypair, Signature},
+        signer::Signer,
+        transaction::Transaction,
+    },
+};
+use anyhow::{anyhow, Context, Result};
+use arcium_client::{
+    idl::arcium::{
+        accounts::ComputationAccount,
+        types::{ComputationReference, ComputationStatus, ExecutionFailure, ExecutionStatus},
+    },
+    instruction::callback_computation_ix,
+    pda::{computation_acc, mempool_acc},
+    state::get_mempool_acc_data,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use std::{collections::HashSet, future::Future, sync::Arc, time::Duration};
+use tracing::{debug, info, warn};
+
+/// Which MXE to serve and which node and cluster the callbacks claim to come
+/// from; both must be registered for the MXE on the local validator.
+#[derive(Clone, Copy, Debug)]
+pub struct ClusterConfig {
+    pub mxe_program: Pubkey,
+    pub node_offset: u32,
+    pub cluster_offset: u32,
+}
+
+pub struct MockCluster {
+    rpc: RpcClient,
+    signer: Arc<Keypair>,
+    config: ClusterConfig,
+    registry: Registry,
+    cipher: Box<dyn Cipher>,
+    /// Computations already called back that may still sit in the mempool.
+    handled: HashSet<u64>,
+}
+
+/// Data passed to the MXE program's callback instructions, the Borsh
+/// encoding of `ComputationOutputs`: `0` and the output on success, `1` on
+/// failure.
+pub fn callback_data(result: &Result<Vec<u8>, ExecutionFailure>) -> Vec<u8> {
+    match result {
+        Ok(output) => [&[0u8][..], output].concat(),
+        Err(_) => vec![1],
+    }
+}
+
+/// The transaction a cluster node sends once `reference` has executed: the
+/// Arcium callback followed by every callback instruction the queuer
+/// registered on the computation.
+pub fn callback_instructions(
+    signer: &Pubkey,
+    config: &ClusterConfig,
+    reference: &ComputationReference,
+    computation: &ComputationAccount,
+    result: &Result<Vec<u8>, ExecutionFailure>,
+) -> Vec<Instruction> {
+    let status = match result {
+        Ok(_) => ExecutionStatus::Success,
+        Err(failure) => ExecutionStatus::Failure(*failure, [0u8; 32]),
+    };
+    let data = callback_data(result);
+    let mut ixs = vec![callback_computation_ix(
+        signer,
+        &config.mxe_program,
+        reference.computation_offset,
+        reference.computation_definition_offset,
+        config.node_offset,
+        config.cluster_offset,
+        status,
+    )];
+    ixs.extend(
+        computation
+            .custom_callback_instructions
+            .iter()
+            .map(|callback| callback.to_instruction(&data)),
+    );
+    ixs
+}
+
+impl MockCluster {
+    pub fn new(
+        url: String,
+        signer: Arc<Keypair>,
+        config: ClusterConfig,
+        registry: Registry,
+        cipher: impl Cipher + 'static,
+    ) -> Self {
+        let rpc = RpcClient::new_with_commitment(url, CommitmentConfig::confirmed());
+        Self::with_rpc(rpc, signer, config, registry, cipher)
+    }
+
+    /// Serves the validator behind `rpc`, which may be an in-process sender.
+    pub fn with_rpc(
+        rpc: RpcClient,
+        signer: Arc<Keypair>,
+        config: ClusterConfig,
+        registry: Registry,
+        cipher: impl Cipher + 'static,
+    ) -> Self {
+        Self {
+            rpc,
+            signer,
+            config,
+            registry,
+            cipher: Box::new(cipher),
+            handled: HashSet::new(),
+        }
+    }
+
+    pub async fn run(mut self, poll_interval: Duration, shutdown: impl Future<Output = ()>) {
+        let mut interval = tokio::time::interval(poll_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut shutdown => break,
+            }
+            if let Err(err) = self.poll().await {
+                warn!("mempool poll failed: {err:#}");
+            }
+        }
+        info!("mock cluster stopped");
+    }
+
+    /// One pass over the mempool. Returns the offsets of the computations
+    /// called back; one that fails to execute or send is retried on the next
+    /// pass.
+    pub async fn poll(&mut self) -> Result<Vec<u64>> {
+        let mempool = get_mempool_acc_data(&self.rpc, &mempool_acc(&self.config.mxe_program))
+            .await
+            .map_err(|err| anyhow!("failed to read the mempool: {err:?}"))?;
+        let queued = mempool.computations();
+        self.handled.retain(|offset| {
+            queued
+                .iter()
+                .any(|reference| reference.computation_offset == *offset)
+        });
+
+        let mut called_back = Vec::new();
+        for reference in &queued {
+            if self.handled.contains(&reference.computation_offset) {
+                continue;
+            }
+            if self
+                .registry
+                .get(reference.computation_definition_offset)
+                .is_none()
+            {
+                debug!(%reference, "no circuit registered, skipping");
+                continue;
+            }
+            match self.execute(reference).await {
+                Ok(Some(signature)) => {
+                    info!(offset = reference.computation_offset, %signature, "called back");
+                    self.handled.insert(reference.computation_offset);
+                    called_back.push(reference.computation_offset);
+                }
+                Ok(None) => {
+                    self.handled.insert(reference.computation_offset);
+                }
+                Err(err) => warn!(
+                    offset = reference.computation_offset,
+                    "computation failed: {err:#}"
+                ),
+            }
+        }
+        Ok(called_back)
+    }
+
+    /// `None` if the computation was already called back by someone else.
+    async fn execute(&self, reference: &ComputationReference) -> Result<Option<Signature>> {
+        let address = computation_acc(&self.config.mxe_program, reference.computation_offset);
+        let data = self
+            .rpc
+            .get_account_data(&address)
+            .await
+            .with_context(|| format!("failed to fetch computation {address}"))?;
+        let computation = ComputationAccount::try_deserialize(&mut data.as_slice())
+            .context("failed to decode computation account")?;
+        if !matches!(computation.status, ComputationStatus::Queued) {
+            debug!(%address, "computation is no longer queued");
+            return Ok(None);
+        }
+
+        let seed: [u8; 32] = rand::random();
+        let result = self
+            .registry
+            .execute(
+                reference.computation_definition_offset,
+                &computation.arguments,
+                &*self.cipher,
+                &seed,
+            )
+            .context("no circuit registered")?;
+        if let Err(failure) = &result {
+            warn!(
+                offset = reference.computation_offset,
+                ?failure,
+                "circuit failed"
+            );
+        }
+
+        let ixs = callback_instructions(
+            &self.signer.pubkey(),
+            &self.config,
+            reference,
+            &computation,
+            &result,
+        );
+        let blockhash = self
+            .rpc
+            .get_latest_blockhash()
+            .await
+            .context("failed to fetch a recent blockhash")?;
+        let tx = Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&self.signer.pubkey()),
+            &[&*self.signer],
+            blockhash,
+        );
+        Ok(Some(self.rpc.send_and_confirm_transaction(&tx).await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arcium_client::idl::arcium::types::{
+        AcccountAccessInfo, CallbackAccount, CallbackInstruction, ExecutionFee,
+    };
+
+    #[test]
+    fn test_callback_instructions() {
+        let config = ClusterConfig {
+            mxe_program: Pubkey::new_unique(),
+            node_offset: 1,
+            cluster_offset: 2,
+        };
+        let callback_program = Pubkey::new_unique();
+        let round = Pubkey::new_unique();
+        let computation = ComputationAccount {
+            payer: Pubkey::new_unique(),
+            cluster_index: None,
+            computation_definition_offset: 7,
+            execution_fee: ExecutionFee {
+                base_fee: 0,
+                priority_fee: 0,
+                input_delivery_fee: 0,
+                output_delivery_fee: 0,
+            },
+            slot: 0,
+            slot_counter: 0,
+            status: ComputationStatus::Queued,
+            arguments: vec![],
+            callback_url: None,
+            custom_callback_instructions: vec![CallbackInstruction {
+                program_id: callback_program,
+                discriminator: vec![1, 2, 3, 4, 5, 6, 7, 8],
+                accounts: vec![CallbackAccount {
+                    pubkey: round,
+                    is_writable: true,
+                }],
+            }],
+            bump: 0,
+        };
+        let reference = ComputationReference {
+            computation_offset: 11,
+            priority_fee: 0,
+            computation_definition_offset: 7,
+            accs: [AcccountAccessInfo { inner: 0 }; 10],
+        };
+        let signer = Pubkey::new_unique();
+
+        let ixs = callback_instructions(&signer, &config, &reference, &computation, &Ok(vec![9]));
+        assert_eq!(ixs.len(), 2);
+        assert_eq!(ixs[0].program_id, arcium_client::ARCIUM_PROGRAM_ID);
+        assert_eq!(ixs[1].program_id, callback_program);
+        assert_eq!(ixs[1].data, [1, 2, 3, 4, 5, 6, 7, 8, 0, 9]);
+        assert_eq!(ixs[1].accounts[0].pubkey, round);
+        assert!(ixs[1].accounts[0].is_writable);
+
+        let failed = callback_instructions(
+            &signer,
+            &config,
+            &reference,
+            &computation,
+            &Err(ExecutionFailure::Inputs),
+        );
+        assert_eq!(failed[1].data, [1, 2, 3, 4, 5, 6, 7, 8, 1]);
+        assert_ne!(failed[0].data, ixs[0].data);
+    }
+}
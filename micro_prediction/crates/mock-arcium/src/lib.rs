@@ -0,0 +1,18 @@
+//! Stand-in for an Arcium cluster in local end-to-end tests. It watches the
+//! mempool of an MXE program on a local validator, runs plaintext
+//! implementations of the queued circuits and submits the callback
+//! transaction a real cluster would, so the queue→callback path can be
+//! exercised without MPC nodes.
+//!
+//! Nothing here is private: encrypted inputs are opened with a [`Cipher`]
+//! that the tests control, either [`Rescue`] with a known MXE secret or
+//! [`Plaintext`].
+
+pub mod cipher;
+pub mod circuit;
+pub mod circuits;
+pub mod cluster;
+
+pub use cipher::{Cipher, Plaintext, Rescue};
+pub use circuit::{Circuit, Inputs, Registry};
+pub use cluster::MockCluster;
@@ -0,0 +1,85 @@
+//! Runs the mock Arcium cluster against a local validator until interrupted.
+//! Inputs are Rescue-encrypted for the MXE key `--x25519-secret`, or expected
+//! in the clear without it (see `cipher::Plaintext`).
+
+use anchor_client::solana_sdk::{pubkey::Pubkey, signature::read_keypair_file};
+use anyhow::{Context, Result};
+use clap::Parser;
+use micro_prediction_mock_arcium::{
+    circuits::DetermineWinners,
+    cluster::{ClusterConfig, MockCluster},
+    Plaintext, Registry, Rescue,
+};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use tracing::info;
+
+#[derive(Parser)]
+#[command(name = "micro-prediction-mock-arcium", version, about)]
+struct Args {
+    /// Keypair the callbacks are signed and paid for with.
+    #[arg(long, short = 'k', env = "MOCK_ARCIUM_KEYPAIR")]
+    keypair: PathBuf,
+    #[arg(
+        long,
+        short = 'u',
+        env = "MOCK_ARCIUM_RPC_URL",
+        default_value = "http://127.0.0.1:8899"
+    )]
+    url: String,
+    /// The MXE program whose computations to execute.
+    #[arg(long)]
+    mxe_program: Pubkey,
+    #[arg(long, default_value_t = 0)]
+    node_offset: u32,
+    #[arg(long, default_value_t = 0)]
+    cluster_offset: u32,
+    #[arg(long, default_value_t = 1_000)]
+    poll_interval_ms: u64,
+    /// The MXE's x25519 secret in hex; the MXE account must publish the
+    /// matching public key, which is logged on start.
+    #[arg(long, env = "MOCK_ARCIUM_X25519_SECRET", hide_env_values = true)]
+    x25519_secret: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
+        )
+        .init();
+    let args = Args::parse();
+
+    let signer = read_keypair_file(&args.keypair)
+        .map_err(|err| anyhow::anyhow!("failed to read {}: {err}", args.keypair.display()))?;
+    let mut registry = Registry::default();
+    registry.register(DetermineWinners);
+    let signer = Arc::new(signer);
+    let config = ClusterConfig {
+        mxe_program: args.mxe_program,
+        node_offset: args.node_offset,
+        cluster_offset: args.cluster_offset,
+    };
+    let cluster = match &args.x25519_secret {
+        Some(secret) => {
+            let secret: [u8; 32] = hex::decode(secret)
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .context("--x25519-secret must be 32 bytes of hex")?;
+            let cipher = Rescue::new(secret);
+            info!(
+                x25519_pubkey = hex::encode(cipher.public_key()),
+                "decrypting inputs with Rescue"
+            );
+            MockCluster::new(args.url, signer, config, registry, cipher)
+        }
+        None => MockCluster::new(args.url, signer, config, registry, Plaintext),
+    };
+
+    cluster
+        .run(Duration::from_millis(args.poll_interval_ms), async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await;
+    Ok(())
+}
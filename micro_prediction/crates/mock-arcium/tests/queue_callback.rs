@@ -0,0 +1,453 @@
+//! The queue→callback path end to end: a computation is queued through
+//! Arcium's `queue_computation` on an in-process chain, the mock cluster picks
+//! it out of the mempool, runs `determine_winners` on the Rescue-encrypted
+//! inputs and calls back, and each client opens its own result.
+use anchor_client::{
+    anchor_lang::{AccountDeserialize, AccountSerialize, AnchorDeserialize, Discriminator},
+    solana_sdk::{
+        commitment_config::CommitmentConfig, hash::Hash, pubkey::Pubkey, signature::Keypair,
+        signer::Signer, transaction::Transaction,
+    },
+};
+use arcium_client::{
+    cipher::{FieldElement, Nonce, RescueCipher},
+    idl::arcium::{
+        accounts::{ComputationAccount, MXEAccount, TinyMempool},
+        client::args::{CallbackComputation, QueueComputation},
+        types::{
+            AcccountAccessInfo, Argument, CallbackAccount, CallbackInstruction,
+            ComputationReference, ComputationStatus, ExecutionFee, TinyMempoolInner,
+            TinyMempoolInnerBuffer, TinyMempoolInnerBufferHeap, X25519Pubkey,
+        },
+    },
+    instruction::queue_computation_ix,
+    pda::{comp_def_offset, computation_acc, mempool_acc, signer_acc},
+    ARCIUM_PROGRAM_ID,
+};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use micro_prediction_mock_arcium::{
+    cipher::{x25519, x25519_public_key},
+    circuits::DetermineWinners,
+    cluster::{ClusterConfig, MockCluster},
+    Registry, Rescue,
+};
+use micro_prediction_settlement::{merkle, MAX_PAYOUT_TIERS};
+use serde_json::{json, Value};
+use solana_client::{
+    client_error::Result as ClientResult,
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use std::{
+    collections::HashMap,
+    mem::{offset_of, size_of},
+    sync::{Arc, Mutex},
+};
+
+/// Discriminator of the MXE program's callback instruction.
+const CALLBACK: [u8; 8] = [8, 7, 6, 5, 4, 3, 2, 1];
+const COMPUTATION_OFFSET: u64 = 11;
+
+#[derive(Default)]
+struct State {
+    accounts: HashMap<Pubkey, Vec<u8>>,
+    queued: Vec<ComputationReference>,
+    landed: Vec<String>,
+    /// Instruction data the callbacks delivered to the MXE program.
+    callbacks: Vec<Vec<u8>>,
+}
+
+/// A validator with just enough of the Arcium program to queue computations
+/// and accept their callbacks, served to `RpcClient`s in process.
+#[derive(Clone)]
+struct LocalChain {
+    mxe_program: Pubkey,
+    state: Arc<Mutex<State>>,
+}
+
+/// A tiny mempool with one queued computation per slot, oldest first.
+fn mempool(queued: &[ComputationReference]) -> Vec<u8> {
+    let discriminator = TinyMempool::DISCRIMINATOR.len();
+    let buffer =
+        discriminator + offset_of!(TinyMempool, inner) + offset_of!(TinyMempoolInner, computations);
+    let heaps = buffer + offset_of!(TinyMempoolInnerBuffer, elems);
+    let heap_size = size_of::<TinyMempoolInnerBufferHeap>();
+
+    let mut data = vec![0u8; discriminator + size_of::<TinyMempool>()];
+    data[..discriminator].copy_from_slice(TinyMempool::DISCRIMINATOR);
+    for (slot, reference) in queued.iter().enumerate() {
+        let heap = heaps + slot * heap_size;
+        data[heap..heap + size_of::<ComputationReference>()]
+            .copy_from_slice(bytemuck::bytes_of(reference));
+        data[heap + offset_of!(TinyMempoolInnerBufferHeap, count)] = 1;
+        data[buffer + offset_of!(TinyMempoolInnerBuffer, valid_bits) + slot / 8] |= 1 << (slot % 8);
+    }
+    data[buffer + offset_of!(TinyMempoolInnerBuffer, length)] = queued.len() as u8;
+    data
+}
+
+impl LocalChain {
+    fn new(mxe_program: Pubkey) -> Self {
+        let chain = Self {
+            mxe_program,
+            state: Default::default(),
+        };
+        chain.state.lock().unwrap().set_queued(&mxe_program, vec![]);
+        chain
+    }
+
+    fn rpc(&self) -> RpcClient {
+        RpcClient::new_sender(
+            self.clone(),
+            RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+        )
+    }
+
+    fn computation(&self, offset: u64) -> ComputationAccount {
+        let state = self.state.lock().unwrap();
+        let data = &state.accounts[&computation_acc(&self.mxe_program, offset)];
+        ComputationAccount::try_deserialize(&mut data.as_slice()).unwrap()
+    }
+
+    fn callbacks(&self) -> Vec<Vec<u8>> {
+        self.state.lock().unwrap().callbacks.clone()
+    }
+
+    fn execute(&self, tx: &Transaction) {
+        let mut state = self.state.lock().unwrap();
+        let keys = &tx.message.account_keys;
+        for ix in &tx.message.instructions {
+            let program = keys[ix.program_id_index as usize];
+            if program == self.mxe_program {
+                state.callbacks.push(ix.data.clone());
+                continue;
+            }
+            assert_eq!(program, ARCIUM_PROGRAM_ID);
+            let (discriminator, mut data) = ix.data.split_at(8);
+            match discriminator {
+                QueueComputation::DISCRIMINATOR => {
+                    let args = QueueComputation::deserialize(&mut data).unwrap();
+                    let computation = ComputationAccount {
+                        payer: keys[0],
+                        cluster_index: args.cluster_index,
+                        computation_definition_offset: args.computation_definition_offset,
+                        execution_fee: ExecutionFee {
+                            base_fee: 0,
+                            priority_fee: args.cu_price_micro,
+                            input_delivery_fee: args.input_delivery_fee,
+                            output_delivery_fee: args.output_delivery_fee,
+                        },
+                        slot: 0,
+                        slot_counter: 0,
+                        status: ComputationStatus::Queued,
+                        arguments: args.args,
+                        callback_url: args.callback_url,
+                        custom_callback_instructions: args.custom_callback_instructions,
+                        bump: 0,
+                    };
+                    state.store(&args.mxe_program, args.comp_offset, &computation);
+                    let mut queued = state.queued.clone();
+                    queued.push(ComputationReference {
+                        computation_offset: args.comp_offset,
+                        priority_fee: args.cu_price_micro,
+                        computation_definition_offset: args.computation_definition_offset,
+                        accs: [AcccountAccessInfo { inner: 0 }; 10],
+                    });
+                    state.set_queued(&args.mxe_program, queued);
+                }
+                CallbackComputation::DISCRIMINATOR => {
+                    let args = CallbackComputation::deserialize(&mut data).unwrap();
+                    let address = computation_acc(&args.mxe_program, args.comp_offset);
+                    let mut computation = ComputationAccount::try_deserialize(
+                        &mut state.accounts[&address].as_slice(),
+                    )
+                    .unwrap();
+                    assert!(matches!(computation.status, ComputationStatus::Queued));
+                    computation.status = ComputationStatus::Finalized;
+                    state.store(&args.mxe_program, args.comp_offset, &computation);
+                    let mut queued = state.queued.clone();
+                    queued.retain(|reference| reference.computation_offset != args.comp_offset);
+                    state.set_queued(&args.mxe_program, queued);
+                }
+                _ => panic!("unexpected Arcium instruction {discriminator:?}"),
+            }
+        }
+        state.landed.push(tx.signatures[0].to_string());
+    }
+}
+
+impl State {
+    fn store(&mut self, mxe_program: &Pubkey, offset: u64, computation: &ComputationAccount) {
+        let mut data = Vec::new();
+        computation.try_serialize(&mut data).unwrap();
+        self.accounts
+            .insert(computation_acc(mxe_program, offset), data);
+    }
+
+    fn set_queued(&mut self, mxe_program: &Pubkey, queued: Vec<ComputationReference>) {
+        self.accounts
+            .insert(mempool_acc(mxe_program), mempool(&queued));
+        self.queued = queued;
+    }
+}
+
+#[async_trait]
+impl RpcSender for LocalChain {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let context = json!({ "slot": 1 });
+        Ok(match request {
+            RpcRequest::GetAccountInfo => {
+                let address: Pubkey = params[0].as_str().unwrap().parse().unwrap();
+                let state = self.state.lock().unwrap();
+                let account = state.accounts.get(&address).map(|data| {
+                    json!({
+                        "lamports": 1_000_000_000u64,
+                        "data": [STANDARD.encode(data), "base64"],
+                        "owner": ARCIUM_PROGRAM_ID.to_string(),
+                        "executable": false,
+                        "rentEpoch": 0,
+                        "space": data.len(),
+                    })
+                });
+                json!({ "context": context, "value": account })
+            }
+            RpcRequest::GetLatestBlockhash => json!({
+                "context": context,
+                "value": { "blockhash": Hash::default().to_string(), "lastValidBlockHeight": 100 },
+            }),
+            RpcRequest::SendTransaction => {
+                let wire = STANDARD.decode(params[0].as_str().unwrap()).unwrap();
+                let tx: Transaction = bincode::deserialize(&wire).unwrap();
+                self.execute(&tx);
+                json!(tx.signatures[0].to_string())
+            }
+            RpcRequest::GetSignatureStatuses => {
+                let state = self.state.lock().unwrap();
+                let statuses: Vec<Value> = params[0]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|signature| {
+                        let landed = state.landed.iter().any(|landed| signature == landed);
+                        match landed {
+                            true => json!({
+                                "slot": 1,
+                                "confirmations": null,
+                                "status": { "Ok": null },
+                                "err": null,
+                                "confirmationStatus": "finalized",
+                            }),
+                            false => Value::Null,
+                        }
+                    })
+                    .collect();
+                json!({ "context": context, "value": statuses })
+            }
+            request => panic!("unexpected request {request:?}"),
+        })
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        "local".into()
+    }
+}
+
+/// A client encrypting for the MXE under its own x25519 key.
+struct Client {
+    key: [u8; 32],
+    nonce: u128,
+    cipher: RescueCipher,
+}
+
+impl Client {
+    fn new(seed: u8, mxe_key: &[u8; 32]) -> Self {
+        let secret = [seed; 32];
+        Self {
+            key: x25519_public_key(&secret),
+            nonce: seed as u128 * 1_000,
+            cipher: RescueCipher::new(&x25519(&secret, mxe_key)),
+        }
+    }
+
+    /// The key, nonce and ciphertexts of an `Enc<Shared, _>` argument.
+    fn encrypt(&self, values: &[u128]) -> Vec<Argument> {
+        let scalars: Vec<FieldElement> = values
+            .iter()
+            .copied()
+            .map(FieldElement::from_u128)
+            .collect();
+        let ciphertexts = self
+            .cipher
+            .encrypt_scalars(&scalars, &Nonce::from_u128(self.nonce));
+        [
+            Argument::ArcisPubkey(self.key),
+            Argument::PlaintextU128(self.nonce),
+        ]
+        .into_iter()
+        .chain(ciphertexts.into_iter().map(Argument::EncryptedU64))
+        .collect()
+    }
+}
+
+fn u64_at(output: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(output[at..at + 8].try_into().unwrap())
+}
+
+fn bytes(scalars: &[FieldElement]) -> [u8; 32] {
+    let bytes: Vec<u8> = scalars
+        .iter()
+        .map(|scalar| scalar.to_u128().unwrap() as u8)
+        .collect();
+    bytes.try_into().unwrap()
+}
+
+#[tokio::test]
+async fn test_queued_computation_is_called_back() {
+    let mxe_program = Pubkey::new_unique();
+    let chain = LocalChain::new(mxe_program);
+    let mxe = Rescue::new([200; 32]);
+    let mxe_key = mxe.public_key();
+
+    // (account, predicted price, stake); the price resolves to 1_000.
+    let entries = [(1u8, 1_010u128, 100u128), (2, 980, 300), (3, 1_200, 50)];
+    let clients: Vec<Client> = entries
+        .iter()
+        .map(|(account, ..)| Client::new(*account, &mxe_key))
+        .collect();
+    let mut args = vec![Argument::PlaintextU32(entries.len() as u32)];
+    for ((account, price, stake), client) in entries.iter().zip(&clients) {
+        args.push(Argument::ArcisPubkey([*account; 32]));
+        args.push(Argument::ArcisPubkey([0; 32]));
+        args.extend(client.encrypt(&[*price, *stake]));
+        args.push(Argument::PlaintextU16(10_000));
+    }
+    args.extend(Client::new(100, &mxe_key).encrypt(&[1_000]));
+    args.extend([Argument::PlaintextU16(100), Argument::PlaintextU64(42)]);
+    args.extend((0..MAX_PAYOUT_TIERS).map(|_| Argument::PlaintextU16(0)));
+    args.extend([
+        Argument::PlaintextU64(450),
+        Argument::PlaintextU8(0),
+        Argument::PlaintextU16(0),
+    ]);
+
+    let payer = Keypair::new();
+    let round = Pubkey::new_unique();
+    let mxe_account = MXEAccount {
+        authority: None,
+        cluster: Some(0),
+        x25519_pubkey: X25519Pubkey::Set(mxe_key),
+        fallback_clusters: vec![],
+        rejected_clusters: vec![],
+        computation_definitions: vec![],
+        bump: 0,
+    };
+    let mut queue = queue_computation_ix(
+        &payer.pubkey(),
+        &mxe_program,
+        COMPUTATION_OFFSET,
+        comp_def_offset("determine_winners"),
+        None,
+        args,
+        0,
+        0,
+        0,
+        mxe_account,
+        None,
+        vec![CallbackInstruction {
+            program_id: mxe_program,
+            discriminator: CALLBACK.to_vec(),
+            accounts: vec![CallbackAccount {
+                pubkey: round,
+                is_writable: true,
+            }],
+        }],
+    )
+    .unwrap();
+    // The MXE program queues through a CPI that signs for its sign PDA; here
+    // the payer queues directly.
+    let sign_pda = signer_acc(&mxe_program);
+    for meta in queue
+        .accounts
+        .iter_mut()
+        .filter(|meta| meta.pubkey == sign_pda)
+    {
+        meta.is_signer = false;
+    }
+    let tx = Transaction::new_signed_with_payer(
+        &[queue],
+        Some(&payer.pubkey()),
+        &[&payer],
+        Hash::default(),
+    );
+    chain.rpc().send_and_confirm_transaction(&tx).await.unwrap();
+
+    let mut registry = Registry::default();
+    registry.register(DetermineWinners);
+    let config = ClusterConfig {
+        mxe_program,
+        node_offset: 1,
+        cluster_offset: 0,
+    };
+    let mut cluster =
+        MockCluster::with_rpc(chain.rpc(), Arc::new(Keypair::new()), config, registry, mxe);
+    assert_eq!(cluster.poll().await.unwrap(), [COMPUTATION_OFFSET]);
+    // Called back once and no longer queued.
+    assert_eq!(cluster.poll().await.unwrap(), Vec::<u64>::new());
+    assert!(matches!(
+        chain.computation(COMPUTATION_OFFSET).status,
+        ComputationStatus::Finalized
+    ));
+
+    let callbacks = chain.callbacks();
+    assert_eq!(callbacks.len(), 1);
+    let (discriminator, outputs) = callbacks[0].split_at(8);
+    assert_eq!(discriminator, CALLBACK);
+    let output = outputs
+        .strip_prefix(&[0])
+        .expect("the computation succeeded");
+    assert_eq!(u64_at(output, 0), 42);
+    assert_eq!(u64_at(output, 8), 1_000);
+    // Only the closest guess is paid: its stake back less the 1% fee.
+    assert_eq!((u64_at(output, 16), u64_at(output, 24)), (1, 99));
+    assert_eq!(&output[32..36], &3u32.to_le_bytes());
+    let leaves: Vec<[u8; 32]> = output[36..132]
+        .chunks(32)
+        .map(|leaf| leaf.try_into().unwrap())
+        .collect();
+    assert_eq!(&output[132..164], &merkle::root(&leaves));
+
+    let results = &output[196..];
+    assert_eq!(&results[..4], &3u32.to_le_bytes());
+    let size = 32 + 16 + 65 * 32;
+    for (index, client) in clients.iter().enumerate() {
+        let result = &results[4 + index * size..4 + (index + 1) * size];
+        assert_eq!(&result[..32], &client.key);
+        let nonce = u128::from_le_bytes(result[32..48].try_into().unwrap());
+        assert_eq!(nonce, client.nonce + 1);
+        let ciphertexts: Vec<[u8; 32]> = result[48..]
+            .chunks(32)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect();
+        let scalars = client
+            .cipher
+            .decrypt_scalars(&ciphertexts, &Nonce::from_u128(nonce))
+            .unwrap();
+
+        let account = bytes(&scalars[..32]);
+        let payout = scalars[32].to_u128().unwrap() as u64;
+        let blinding = bytes(&scalars[33..]);
+        assert_eq!(account, [entries[index].0; 32]);
+        assert_eq!(payout, [99, 0, 0][index]);
+        assert_eq!(
+            leaves[index],
+            merkle::payout_leaf(&account, payout, &blinding)
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! `scan_chunk` and `settle_chunk` without encryption.
+
+use alloc::vec::Vec;
+
+use crate::scoring::{distance, weighted_stake};
+use crate::{Payout, Prediction, Settlement};
+
+/// Plaintext of the MXE-owned `ScanState` carried between chunk scans.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScanState {
+    pub entry_count: u32,
+    pub total_stake: u64,
+    pub min_distance: u128,
+    pub winning_weight: u128,
+}
+
+impl Default for ScanState {
+    fn default() -> Self {
+        Self {
+            entry_count: 0,
+            total_stake: 0,
+            min_distance: u128::MAX,
+            winning_weight: 0,
+        }
+    }
+}
+
+/// Folds one chunk into the running scan; start from `ScanState::default()`.
+pub fn scan_chunk(state: ScanState, chunk: &[Prediction], final_price: u128) -> ScanState {
+    let mut scan = state;
+    for prediction in chunk {
+        let diff = distance(prediction.predicted_price, final_price);
+        let weight = weighted_stake(prediction.stake as u64, prediction.weight_bps) as u128;
+        if diff < scan.min_distance {
+            scan.min_distance = diff;
+            scan.winning_weight = weight;
+        } else if diff == scan.min_distance {
+            scan.winning_weight += weight;
+        }
+        scan.entry_count += 1;
+        scan.total_stake += prediction.stake as u64;
+    }
+    scan
+}
+
+/// Pays one chunk against a finished scan with the winner-take-all split rule.
+pub fn settle_chunk(
+    scan: &ScanState,
+    chunk: &[Prediction],
+    final_price: u128,
+    fee_bps: u16,
+    payout_pool: u64,
+) -> Settlement {
+    let pool = payout_pool as u128;
+    let mut payouts = Vec::with_capacity(chunk.len());
+    let mut fee_total: u128 = 0;
+    for prediction in chunk {
+        let mut payout: u128 = 0;
+        if distance(prediction.predicted_price, final_price) == scan.min_distance {
+            let weight = weighted_stake(prediction.stake as u64, prediction.weight_bps) as u128;
+            let gross = if scan.winning_weight > pool {
+                weight * pool / scan.winning_weight
+            } else {
+                weight
+            };
+            let fee = gross * fee_bps as u128 / 10_000u128;
+            payout = gross.saturating_sub(fee);
+            fee_total = fee_total.saturating_add(fee);
+        }
+        payouts.push(Payout {
+            account: prediction.account,
+            payout: payout as u64,
+        });
+    }
+
+    Settlement::new(payouts, fee_total as u64)
+}
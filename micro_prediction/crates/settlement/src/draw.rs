@@ -0,0 +1,27 @@
+//! Draws derived from the seed a settlement publishes.
+
+use solana_sha256_hasher::hashv;
+
+/// Domain prefix of every draw; must match `DRAW_DOMAIN` in the settlement
+/// circuits.
+pub const DRAW_DOMAIN: &[u8] = b"micro_prediction:draw:v1";
+pub const TIE_BREAK_TAG: &[u8] = b"tie_break";
+pub const LUCKY_DRAW_TAG: &[u8] = b"lucky_draw";
+/// Domain prefix of the seed leaf; must match `SEED_DOMAIN` in the circuits.
+pub const SEED_DOMAIN: &[u8] = b"micro_prediction:seed:v1";
+
+/// Uniform index below `n`; matches `draw_index` in the circuits, so anyone
+/// can replay the draws of a settled round from its `randomness_seed`.
+pub fn draw_index(seed: &[u8; 32], tag: &[u8], n: u64) -> u64 {
+    let digest = hashv(&[DRAW_DOMAIN, tag, seed]).to_bytes();
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&digest[..8]);
+    u64::from_le_bytes(word) % n.max(1)
+}
+
+/// Leaf a settlement with draws appends after its payout leaves, which ties
+/// the published seed to the committed payout root; matches `seed_leaf` in
+/// the circuits.
+pub fn seed_leaf(seed: &[u8; 32]) -> [u8; 32] {
+    hashv(&[SEED_DOMAIN, seed]).to_bytes()
+}
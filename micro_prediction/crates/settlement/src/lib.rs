@@ -0,0 +1,91 @@
+//! Plaintext reference of the settlement math in `encrypted-ixs`.
+//!
+//! Every function mirrors its circuit counterpart step for step, including the
+//! integer widths and the order of rounding, so a result computed here must be
+//! bit-for-bit what the MPC cluster reveals. The on-chain program uses the
+//! weight and pool helpers and verifies claims and draws with `merkle` and
+//! `draw`; the relayer and the mock cluster use the rest.
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod chunk;
+pub mod draw;
+pub mod merkle;
+pub mod payout;
+pub mod pool;
+pub mod scoring;
+
+pub use payout::{determine_winners, score_interval_predictions, WinnerParams};
+pub use pool::{entry_weight_bps, house_edge, payout_pool};
+
+pub const MAX_PAYOUT_TIERS: usize = 5;
+/// Weight of an entry without any early-entry bonus.
+pub const BASE_WEIGHT_BPS: u16 = 10_000;
+
+/// Plaintext of one `EncryptedPrediction`; `account` is the `Prediction` PDA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Prediction {
+    pub account: [u8; 32],
+    pub predicted_price: u128,
+    pub stake: u128,
+    pub weight_bps: u16,
+}
+
+/// Plaintext of one `EncryptedRangePrediction`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangePrediction {
+    pub account: [u8; 32],
+    pub lower: u128,
+    pub upper: u128,
+    pub stake: u128,
+    pub weight_bps: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Payout {
+    pub account: [u8; 32],
+    pub payout: u64,
+}
+
+/// Payouts in input order plus the totals the circuit reveals.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Settlement {
+    pub payouts: alloc::vec::Vec<Payout>,
+    pub fee_total: u64,
+    pub total_payout: u64,
+}
+
+impl Settlement {
+    pub(crate) fn new(payouts: alloc::vec::Vec<Payout>, fee_total: u64) -> Self {
+        let total_payout = payouts
+            .iter()
+            .fold(0u64, |total, entry| total.saturating_add(entry.payout));
+        Self {
+            payouts,
+            fee_total,
+            total_payout,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum TieBreak {
+    #[default]
+    Split = 0,
+    Random = 1,
+}
+
+impl TryFrom<u8> for TieBreak {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Split),
+            1 => Ok(Self::Random),
+            other => Err(other),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Payout leaves and the sorted-pair Merkle tree claims are proven against,
+//! as built by `seal_settlement` in the circuits.
+
+use alloc::vec::Vec;
+use solana_sha256_hasher::hashv;
+
+/// Domain prefix of a payout leaf; must match `LEAF_DOMAIN` in the settlement
+/// circuits.
+pub const LEAF_DOMAIN: &[u8] = b"micro_prediction:payout:v1";
+
+/// Leaf committing to one prediction's payout. The blinding is only known to
+/// the prediction's owner, so public leaves do not leak low-entropy payouts.
+pub fn payout_leaf(account: &[u8; 32], payout: u64, blinding: &[u8; 32]) -> [u8; 32] {
+    hashv(&[LEAF_DOMAIN, account, &payout.to_le_bytes(), blinding]).to_bytes()
+}
+
+fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[first, second]).to_bytes()
+}
+
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [a, b] => hash_pair(a, b),
+            [a] => *a,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Sorted-pair Merkle root; an odd node is carried up unchanged.
+pub fn root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    if leaves.is_empty() {
+        return [0u8; 32];
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+/// Siblings from `leaves[index]` up to the root, in the order `verify` expects.
+pub fn proof(leaves: &[[u8; 32]], mut index: usize) -> Vec<[u8; 32]> {
+    let mut siblings = Vec::new();
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        let sibling = index ^ 1;
+        if sibling < level.len() {
+            siblings.push(level[sibling]);
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    siblings
+}
+
+pub fn verify(proof: &[[u8; 32]], root: &[u8; 32], leaf: [u8; 32]) -> bool {
+    let computed = proof
+        .iter()
+        .fold(leaf, |node, sibling| hash_pair(&node, sibling));
+    &computed == root
+}
@@ -0,0 +1,224 @@
+//! `determine_winners` and `score_interval_predictions` without encryption.
+
+use alloc::vec::Vec;
+
+use crate::draw::{draw_index, LUCKY_DRAW_TAG, TIE_BREAK_TAG};
+use crate::scoring::{distance, interval_penalty};
+use crate::{
+    Payout, Prediction, RangePrediction, Settlement, TieBreak, BASE_WEIGHT_BPS, MAX_PAYOUT_TIERS,
+};
+
+/// Public parameters of a `determine_winners` call.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WinnerParams {
+    pub fee_bps: u16,
+    pub payout_tiers: [u16; MAX_PAYOUT_TIERS],
+    pub payout_pool: u64,
+    pub tie_break: TieBreak,
+    pub lucky_draw_bps: u16,
+}
+
+/// `seed` stands in for the randomness the circuit generates; replaying a
+/// settled round with its published `randomness_seed` reproduces its payouts.
+pub fn determine_winners(
+    predictions: &[Prediction],
+    final_price: u128,
+    params: &WinnerParams,
+    seed: &[u8; 32],
+) -> Settlement {
+    let lucky_pool = params.payout_pool as u128 * params.lucky_draw_bps as u128 / 10_000u128;
+
+    let (mut payouts, fee_total) = if params.payout_tiers.iter().any(|tier| *tier > 0) {
+        tiered_payouts(
+            predictions,
+            final_price,
+            params.fee_bps,
+            &params.payout_tiers,
+            params.payout_pool - lucky_pool as u64,
+        )
+    } else {
+        closest_payouts(
+            predictions,
+            final_price,
+            params.fee_bps,
+            params.tie_break,
+            params.payout_pool,
+            seed,
+        )
+    };
+
+    if lucky_pool > 0 && !payouts.is_empty() {
+        // Fees are paid out of the pool too.
+        let paid: u128 = payouts.iter().map(|entry| entry.payout as u128).sum();
+        let available = (params.payout_pool as u128)
+            .saturating_sub(paid)
+            .saturating_sub(fee_total as u128);
+        let lucky = draw_index(seed, LUCKY_DRAW_TAG, payouts.len() as u64) as usize;
+        payouts[lucky].payout += lucky_pool.min(available) as u64;
+    }
+
+    Settlement::new(payouts, fee_total)
+}
+
+/// Closest entries get their weighted stake back minus the fee, scaled down
+/// to `payout_pool` if they would exceed it.
+fn closest_payouts(
+    predictions: &[Prediction],
+    final_price: u128,
+    fee_bps: u16,
+    tie_break: TieBreak,
+    payout_pool: u64,
+    seed: &[u8; 32],
+) -> (Vec<Payout>, u64) {
+    let min_diff = predictions
+        .iter()
+        .map(|prediction| distance(prediction.predicted_price, final_price))
+        .min()
+        .unwrap_or_default();
+    let tied_weight: u128 = predictions
+        .iter()
+        .filter(|prediction| distance(prediction.predicted_price, final_price) == min_diff)
+        .map(weighted_stake)
+        .sum();
+    let pool = payout_pool as u128;
+
+    let mut payouts = Vec::with_capacity(predictions.len());
+    let mut fee_total: u128 = 0;
+    let mut tied_count: u64 = 0;
+    let mut tied_gross: u128 = 0;
+    for prediction in predictions {
+        let mut payout: u128 = 0;
+        if distance(prediction.predicted_price, final_price) == min_diff {
+            let weight = weighted_stake(prediction);
+            let gross = if tied_weight > pool {
+                weight * pool / tied_weight
+            } else {
+                weight
+            };
+            let fee = gross * fee_bps as u128 / 10_000u128;
+            payout = gross.saturating_sub(fee);
+            fee_total = fee_total.saturating_add(fee);
+            tied_count += 1;
+            tied_gross += gross;
+        }
+        payouts.push(Payout {
+            account: prediction.account,
+            payout: payout as u64,
+        });
+    }
+
+    if tie_break == TieBreak::Random && tied_count > 1 {
+        let winner = draw_index(seed, TIE_BREAK_TAG, tied_count);
+        let mut tied_seen: u64 = 0;
+        for (prediction, entry) in predictions.iter().zip(payouts.iter_mut()) {
+            if distance(prediction.predicted_price, final_price) != min_diff {
+                continue;
+            }
+            entry.payout = if tied_seen == winner {
+                (tied_gross - tied_gross * fee_bps as u128 / 10_000u128) as u64
+            } else {
+                0
+            };
+            tied_seen += 1;
+        }
+        fee_total = tied_gross * fee_bps as u128 / 10_000u128;
+    }
+
+    (payouts, fee_total as u64)
+}
+
+/// `stake * weight_bps / BASE_WEIGHT_BPS`, truncated to `u64` like the
+/// circuit's `weighted_stake`.
+fn weighted_stake(prediction: &Prediction) -> u128 {
+    (prediction.stake as u64 as u128 * prediction.weight_bps as u128 / BASE_WEIGHT_BPS as u128)
+        as u64 as u128
+}
+
+/// Splits the pool across tiers of distinct distances, closest first, sharing
+/// each tier by early-entry weight among the entries tied on it.
+fn tiered_payouts(
+    predictions: &[Prediction],
+    final_price: u128,
+    fee_bps: u16,
+    payout_tiers: &[u16; MAX_PAYOUT_TIERS],
+    payout_pool: u64,
+) -> (Vec<Payout>, u64) {
+    let pool = payout_pool as u128;
+    let fee_total = pool * fee_bps as u128 / 10_000u128;
+    let net_pool = pool - fee_total;
+
+    let mut payouts: Vec<u128> = predictions.iter().map(|_| 0u128).collect();
+    let mut previous_diff: Option<u128> = None;
+    for tier_bps in payout_tiers.iter().filter(|tier| **tier > 0) {
+        let tier_diff = predictions
+            .iter()
+            .map(|prediction| distance(prediction.predicted_price, final_price))
+            .filter(|diff| previous_diff.is_none_or(|previous| *diff > previous))
+            .min();
+        let Some(tier_diff) = tier_diff else {
+            break;
+        };
+
+        let tied_weight: u128 = predictions
+            .iter()
+            .filter(|prediction| distance(prediction.predicted_price, final_price) == tier_diff)
+            .map(|prediction| prediction.weight_bps as u128)
+            .sum();
+        let tier_amount = net_pool * *tier_bps as u128 / 10_000u128;
+        for (prediction, payout) in predictions.iter().zip(payouts.iter_mut()) {
+            if distance(prediction.predicted_price, final_price) == tier_diff {
+                *payout += tier_amount * prediction.weight_bps as u128 / tied_weight;
+            }
+        }
+        previous_diff = Some(tier_diff);
+    }
+
+    let payouts = predictions
+        .iter()
+        .zip(payouts)
+        .map(|(prediction, payout)| Payout {
+            account: prediction.account,
+            payout: payout as u64,
+        })
+        .collect();
+    (payouts, fee_total as u64)
+}
+
+/// Pays the net pool in proportion to `(max_penalty - penalty) * stake *
+/// weight`.
+pub fn score_interval_predictions(
+    predictions: &[RangePrediction],
+    final_price: u128,
+    fee_bps: u16,
+    alpha_bps: u16,
+    max_penalty: u64,
+    payout_pool: u64,
+) -> Settlement {
+    let pool = payout_pool as u128;
+    let fee_total = pool * fee_bps as u128 / 10_000u128;
+    let net_pool = pool - fee_total;
+
+    let weights: Vec<u128> = predictions
+        .iter()
+        .map(|prediction| {
+            let penalty =
+                interval_penalty(prediction.lower, prediction.upper, final_price, alpha_bps);
+            let reward = (max_penalty as u128).saturating_sub(penalty);
+            reward * prediction.stake * prediction.weight_bps as u128 / BASE_WEIGHT_BPS as u128
+        })
+        .collect();
+    let total_weight = weights
+        .iter()
+        .fold(0u128, |total, weight| total.saturating_add(*weight));
+
+    let payouts = predictions
+        .iter()
+        .zip(weights)
+        .map(|(prediction, weight)| Payout {
+            account: prediction.account,
+            payout: (net_pool * weight).checked_div(total_weight).unwrap_or(0) as u64,
+        })
+        .collect();
+
+    Settlement::new(payouts, fee_total as u64)
+}
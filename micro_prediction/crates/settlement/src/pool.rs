@@ -0,0 +1,27 @@
+//! Round-level amounts shared by the program and the circuits' inputs.
+
+use crate::BASE_WEIGHT_BPS;
+
+/// Early-entry weight: `BASE_WEIGHT_BPS` plus a bonus decaying linearly from
+/// `early_bonus_bps` at `start_ts` to zero at `end_ts`.
+pub fn entry_weight_bps(start_ts: i64, end_ts: i64, now: i64, early_bonus_bps: u16) -> u16 {
+    let duration = end_ts.saturating_sub(start_ts);
+    if early_bonus_bps == 0 || duration <= 0 {
+        return BASE_WEIGHT_BPS;
+    }
+    let remaining = end_ts.saturating_sub(now).clamp(0, duration);
+    let bonus = early_bonus_bps as i64 * remaining / duration;
+    BASE_WEIGHT_BPS + bonus as u16
+}
+
+pub fn house_edge(total_stake: u64, house_edge_bps: u16) -> Option<u64> {
+    let edge = total_stake as u128 * house_edge_bps as u128 / 10_000;
+    u64::try_from(edge).ok()
+}
+
+/// Stakes plus carried-in funds minus the house edge reserved for rollover.
+pub fn payout_pool(total_stake: u64, carried_in: u64, house_edge_bps: u16) -> Option<u64> {
+    total_stake
+        .checked_add(carried_in)?
+        .checked_sub(house_edge(total_stake, house_edge_bps)?)
+}
@@ -0,0 +1,29 @@
+//! Distance and interval scores, as in the circuits.
+
+use crate::BASE_WEIGHT_BPS;
+
+pub fn distance(a: u128, b: u128) -> u128 {
+    a.abs_diff(b)
+}
+
+/// Interval score in price units. An inverted interval scores as the worst
+/// possible entry.
+pub fn interval_penalty(lower: u128, upper: u128, price: u128, alpha_bps: u16) -> u128 {
+    if lower > upper || alpha_bps == 0 {
+        return u128::MAX;
+    }
+    let width = upper - lower;
+    let miss = lower.saturating_sub(price) + price.saturating_sub(upper);
+    width.saturating_add(miss.saturating_mul(2 * 10_000u128) / alpha_bps as u128)
+}
+
+pub fn weighted_stake(stake: u64, weight_bps: u16) -> u64 {
+    (stake as u128 * weight_bps as u128 / BASE_WEIGHT_BPS as u128) as u64
+}
+
+/// Bucket of `price` in a layout of `buckets` buckets of `width` starting at
+/// `origin`; prices outside the layout land in the first or last bucket.
+pub fn bucket_index(price: u128, origin: u64, width: u64, buckets: usize) -> usize {
+    let offset = price.saturating_sub(origin as u128);
+    (offset / (width as u128).max(1)).min(buckets as u128 - 1) as usize
+}
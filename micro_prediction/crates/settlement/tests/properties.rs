@@ -0,0 +1,283 @@
+use micro_prediction_settlement::{
+    chunk::{scan_chunk, settle_chunk, ScanState},
+    determine_winners,
+    draw::draw_index,
+    entry_weight_bps, merkle, payout_pool, score_interval_predictions, Prediction, RangePrediction,
+    TieBreak, WinnerParams, BASE_WEIGHT_BPS, MAX_PAYOUT_TIERS,
+};
+use proptest::prelude::*;
+
+// Bounds keep `net_pool * weight` in the interval rule inside u128, as it has to
+// be in the circuit too.
+const MAX_PRICE: u128 = 1 << 40;
+const MAX_STAKE: u128 = 1 << 36;
+
+fn account(index: usize) -> [u8; 32] {
+    let mut account = [0u8; 32];
+    account[..8].copy_from_slice(&(index as u64).to_le_bytes());
+    account
+}
+
+fn predictions() -> impl Strategy<Value = Vec<Prediction>> {
+    prop::collection::vec(
+        (
+            0..MAX_PRICE,
+            1..MAX_STAKE,
+            BASE_WEIGHT_BPS..=2 * BASE_WEIGHT_BPS,
+        ),
+        0..24,
+    )
+    .prop_map(|entries| {
+        entries
+            .into_iter()
+            .enumerate()
+            .map(|(index, (predicted_price, stake, weight_bps))| Prediction {
+                account: account(index),
+                predicted_price,
+                stake,
+                weight_bps,
+            })
+            .collect()
+    })
+}
+
+/// Tiers summing to at most 100%.
+fn payout_tiers() -> impl Strategy<Value = [u16; MAX_PAYOUT_TIERS]> {
+    prop::array::uniform5(0u16..=2_000)
+}
+
+fn total_stake(predictions: &[Prediction]) -> u64 {
+    predictions.iter().map(|p| p.stake as u64).sum()
+}
+
+proptest! {
+    #[test]
+    fn winner_take_all_never_pays_more_than_staked(
+        predictions in predictions(),
+        final_price in 0..MAX_PRICE,
+        fee_bps in 0u16..=10_000,
+        random_ties in any::<bool>(),
+        seed in any::<[u8; 32]>(),
+    ) {
+        let params = WinnerParams {
+            fee_bps,
+            payout_pool: total_stake(&predictions),
+            tie_break: if random_ties { TieBreak::Random } else { TieBreak::Split },
+            ..Default::default()
+        };
+        let settlement = determine_winners(&predictions, final_price, &params, &seed);
+
+        prop_assert_eq!(settlement.payouts.len(), predictions.len());
+        prop_assert!(settlement.total_payout as u128 + settlement.fee_total as u128
+            <= total_stake(&predictions) as u128);
+        if !predictions.is_empty() {
+            prop_assert!(settlement.payouts.iter().any(|entry| entry.payout > 0)
+                || settlement.fee_total > 0
+                || fee_bps == 10_000);
+        }
+    }
+
+    #[test]
+    fn random_tie_break_conserves_the_split_total(
+        predictions in predictions(),
+        final_price in 0..MAX_PRICE,
+        seed in any::<[u8; 32]>(),
+    ) {
+        let split = WinnerParams { payout_pool: total_stake(&predictions), ..Default::default() };
+        let random = WinnerParams { tie_break: TieBreak::Random, ..split };
+        let split = determine_winners(&predictions, final_price, &split, &seed);
+        let random = determine_winners(&predictions, final_price, &random, &seed);
+
+        // Fees round per entry in one case and once in the other.
+        prop_assert!(random.total_payout >= split.total_payout);
+        prop_assert!(random.payouts.iter().filter(|entry| entry.payout > 0).count() <= 1);
+    }
+
+    #[test]
+    fn winner_take_all_pays_the_weighted_stake(
+        stake in 1..MAX_STAKE,
+        weight_bps in BASE_WEIGHT_BPS..=2 * BASE_WEIGHT_BPS,
+        loser_stake in 1..MAX_STAKE,
+    ) {
+        let winner = Prediction {
+            account: account(0),
+            predicted_price: 100,
+            stake,
+            weight_bps,
+        };
+        let loser = Prediction {
+            account: account(1),
+            predicted_price: MAX_PRICE,
+            stake: loser_stake,
+            weight_bps: BASE_WEIGHT_BPS,
+        };
+        let params = WinnerParams {
+            payout_pool: (stake + loser_stake) as u64,
+            ..Default::default()
+        };
+        let settlement = determine_winners(&[winner, loser], 100, &params, &[0; 32]);
+
+        let weighted = (stake * weight_bps as u128 / BASE_WEIGHT_BPS as u128) as u64;
+        prop_assert_eq!(settlement.payouts[0].payout, weighted.min(params.payout_pool));
+        prop_assert_eq!(settlement.payouts[1].payout, 0);
+    }
+
+    #[test]
+    fn chunked_settlement_matches_single_shot(
+        predictions in predictions(),
+        final_price in 0..MAX_PRICE,
+        fee_bps in 0u16..=2_000,
+        house_edge_bps in 0u16..=2_000,
+        chunk_size in 1usize..8,
+    ) {
+        let pool = payout_pool(total_stake(&predictions), 0, house_edge_bps).unwrap();
+        let scan = predictions
+            .chunks(chunk_size)
+            .fold(ScanState::default(), |scan, chunk| scan_chunk(scan, chunk, final_price));
+        let chunked: Vec<_> = predictions
+            .chunks(chunk_size)
+            .map(|chunk| settle_chunk(&scan, chunk, final_price, fee_bps, pool))
+            .collect();
+
+        let params = WinnerParams { fee_bps, payout_pool: pool, ..Default::default() };
+        let single = determine_winners(&predictions, final_price, &params, &[0; 32]);
+        let payouts: Vec<_> = chunked.iter().flat_map(|s| s.payouts.iter().copied()).collect();
+        prop_assert_eq!(&payouts, &single.payouts);
+        let paid: u128 = chunked
+            .iter()
+            .map(|s| s.total_payout as u128 + s.fee_total as u128)
+            .sum();
+        prop_assert!(paid <= pool as u128);
+    }
+
+    #[test]
+    fn tiered_payouts_stay_within_the_pool(
+        predictions in predictions(),
+        final_price in 0..MAX_PRICE,
+        fee_bps in 0u16..=2_000,
+        payout_tiers in payout_tiers(),
+        lucky_draw_bps in 0u16..=2_000,
+        carried_in in 0u64..1 << 40,
+        seed in any::<[u8; 32]>(),
+    ) {
+        let params = WinnerParams {
+            fee_bps,
+            payout_tiers,
+            payout_pool: total_stake(&predictions) + carried_in,
+            lucky_draw_bps,
+            ..Default::default()
+        };
+        let settlement = determine_winners(&predictions, final_price, &params, &seed);
+
+        prop_assert!(settlement.total_payout as u128 + settlement.fee_total as u128
+            <= params.payout_pool as u128);
+    }
+
+    #[test]
+    fn winner_take_all_lucky_draw_leaves_room_for_fees(
+        predictions in predictions(),
+        final_price in 0..MAX_PRICE,
+        fee_bps in 1u16..=2_000,
+        lucky_draw_bps in 1u16..=2_000,
+        random_ties in any::<bool>(),
+        carried_in in 0u64..1 << 40,
+        seed in any::<[u8; 32]>(),
+    ) {
+        let params = WinnerParams {
+            fee_bps,
+            payout_tiers: [0; MAX_PAYOUT_TIERS],
+            payout_pool: total_stake(&predictions) + carried_in,
+            tie_break: if random_ties { TieBreak::Random } else { TieBreak::Split },
+            lucky_draw_bps,
+        };
+        let settlement = determine_winners(&predictions, final_price, &params, &seed);
+
+        prop_assert!(settlement.total_payout as u128 + settlement.fee_total as u128
+            <= params.payout_pool as u128);
+    }
+
+    #[test]
+    fn interval_payouts_stay_within_the_net_pool(
+        ranges in prop::collection::vec((0..MAX_PRICE, 0..MAX_PRICE, 1..MAX_STAKE), 0..24),
+        final_price in 0..MAX_PRICE,
+        fee_bps in 0u16..=2_000,
+        alpha_bps in 0u16..=10_000,
+        max_penalty in 0u64..1 << 40,
+        payout_pool in 0u64..1 << 44,
+    ) {
+        let predictions: Vec<RangePrediction> = ranges
+            .into_iter()
+            .enumerate()
+            .map(|(index, (lower, upper, stake))| RangePrediction {
+                account: account(index),
+                lower,
+                upper,
+                stake,
+                weight_bps: BASE_WEIGHT_BPS,
+            })
+            .collect();
+        let settlement = score_interval_predictions(
+            &predictions, final_price, fee_bps, alpha_bps, max_penalty, payout_pool,
+        );
+
+        prop_assert!(settlement.total_payout + settlement.fee_total <= payout_pool);
+    }
+
+    #[test]
+    fn entry_weight_decays_from_the_full_bonus(
+        start_ts in -1_000_000i64..1_000_000,
+        duration in 1i64..1_000_000,
+        elapsed in -10i64..1_000_010,
+        early_bonus_bps in 0u16..=10_000,
+    ) {
+        let end_ts = start_ts + duration;
+        let weight = entry_weight_bps(start_ts, end_ts, start_ts + elapsed, early_bonus_bps);
+
+        prop_assert!(weight >= BASE_WEIGHT_BPS);
+        prop_assert!(weight <= BASE_WEIGHT_BPS + early_bonus_bps);
+        let later = entry_weight_bps(start_ts, end_ts, start_ts + elapsed + 1, early_bonus_bps);
+        prop_assert!(later <= weight);
+    }
+
+    #[test]
+    fn payout_pool_never_exceeds_funding(
+        total_stake in any::<u64>(),
+        carried_in in any::<u64>(),
+        house_edge_bps in 0u16..=10_000,
+    ) {
+        if let Some(pool) = payout_pool(total_stake, carried_in, house_edge_bps) {
+            prop_assert!(pool as u128 <= total_stake as u128 + carried_in as u128);
+        }
+    }
+
+    #[test]
+    fn draws_stay_in_range(seed in any::<[u8; 32]>(), n in 1u64..10_000) {
+        prop_assert!(draw_index(&seed, b"tag", n) < n);
+    }
+
+    #[test]
+    fn every_leaf_proves_against_the_root(
+        leaves in prop::collection::vec(any::<[u8; 32]>(), 1..40),
+        index in any::<prop::sample::Index>(),
+    ) {
+        let index = index.index(leaves.len());
+        let root = merkle::root(&leaves);
+        prop_assert!(merkle::verify(&merkle::proof(&leaves, index), &root, leaves[index]));
+    }
+
+    #[test]
+    fn chunk_roots_compose(
+        leaves in prop::collection::vec(any::<[u8; 32]>(), 2..64),
+        chunk_size in 1usize..16,
+        index in any::<prop::sample::Index>(),
+    ) {
+        let index = index.index(leaves.len());
+        let chunks: Vec<&[[u8; 32]]> = leaves.chunks(chunk_size).collect();
+        let chunk_roots: Vec<[u8; 32]> = chunks.iter().map(|chunk| merkle::root(chunk)).collect();
+        let top = merkle::root(&chunk_roots);
+
+        let mut proof = merkle::proof(chunks[index / chunk_size], index % chunk_size);
+        proof.extend(merkle::proof(&chunk_roots, index / chunk_size));
+        prop_assert!(merkle::verify(&proof, &top, leaves[index]));
+    }
+}
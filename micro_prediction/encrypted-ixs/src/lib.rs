@@ -4,90 +4,899 @@ use arcis_imports::*;
 mod circuits {
     use arcis_imports::*;
 
+    pub const MAX_PAYOUT_TIERS: usize = 5;
+
+    pub const BASE_WEIGHT_BPS: u128 = 10_000;
+    pub const SENTIMENT_BUCKETS: usize = 8;
+    /// Below this many entries only the count and total stake are revealed, so
+    /// a lone participant's price cannot be read back from the mean.
+    pub const MIN_SENTIMENT_PARTICIPANTS: u32 = 3;
+    /// Narrowest sentiment bucket, in basis points of the weighted mean price.
+    /// Narrower layouts reveal no histogram, so a caller cannot place a tight
+    /// bucket around a guessed price to read back a single entry.
+    pub const MIN_SENTIMENT_BUCKET_BPS: u128 = 100;
+
+    /// Predictions per chunk in chunked resolution. Must match
+    /// `RESOLUTION_CHUNK_SIZE` in the program, which rejects any chunk whose
+    /// leaf count exceeds it.
+    pub const RESOLUTION_CHUNK_SIZE: usize = 32;
+
+    /// Price buckets tracked by the incremental round accumulator.
+    pub const ACCUMULATOR_BUCKETS: usize = 16;
+
+    /// `tie_break` modes; must match `TieBreak` in the program.
+    pub const TIE_BREAK_SPLIT: u8 = 0;
+    pub const TIE_BREAK_RANDOM: u8 = 1;
+
+    /// Domain prefix of every draw; must match `draw::DRAW_DOMAIN` in the
+    /// program so draws can be replayed from the published seed.
+    pub const DRAW_DOMAIN: &[u8] = b"micro_prediction:draw:v1";
+    pub const TIE_BREAK_TAG: &[u8] = b"tie_break";
+    pub const LUCKY_DRAW_TAG: &[u8] = b"lucky_draw";
+    /// Domain prefix of the leaf that commits a settlement to its seed; must
+    /// match `draw::SEED_DOMAIN` in the program.
+    pub const SEED_DOMAIN: &[u8] = b"micro_prediction:seed:v1";
+
+    /// Domain prefix of a payout leaf; must match `merkle::LEAF_DOMAIN` in the
+    /// program.
+    pub const LEAF_DOMAIN: &[u8] = b"micro_prediction:payout:v1";
+    /// Levels `merkle_root` hashes; enough for any settlement's leaf count.
+    pub const MAX_MERKLE_DEPTH: usize = 32;
+
+    /// `account` is the `Prediction` PDA and `owner` the x25519 key context the
+    /// user encrypted with at submission; each result is re-encrypted to it.
+    /// `weight_bps` is the public early-entry weight recorded on the
+    /// `Prediction` account (10_000 = no bonus).
     #[derive(Clone, Copy, MxeSerializable)]
     pub struct EncryptedPrediction {
+        pub account: [u8; 32],
         pub commitment: [u8; 32],
+        pub owner: Shared,
         pub predicted_price: EncScalar<Shared>,
         pub stake: EncScalar<Shared>,
+        pub weight_bps: u16,
     }
 
-    #[derive(Clone, MxeSerializable)]
-    pub struct SettlementEntry {
+    /// Prediction for interval-score markets: a central interval the owner
+    /// expects the final price to land in.
+    #[derive(Clone, Copy, MxeSerializable)]
+    pub struct EncryptedRangePrediction {
+        pub account: [u8; 32],
         pub commitment: [u8; 32],
+        pub owner: Shared,
+        pub lower: EncScalar<Shared>,
+        pub upper: EncScalar<Shared>,
+        pub stake: EncScalar<Shared>,
+        pub weight_bps: u16,
+    }
+
+    /// One entry per prediction, losers included with a zero payout so the
+    /// public output does not reveal who won.
+    #[derive(Clone, Copy, MxeSerializable)]
+    pub struct SettlementEntry {
+        pub account: [u8; 32],
+        pub owner: Shared,
+        pub payout: u64,
+    }
+
+    /// What a participant decrypts client-side. `blinding` opens their payout
+    /// leaf so they can claim with a Merkle proof.
+    #[derive(Clone, Copy, MxeSerializable)]
+    pub struct UserPayout {
+        pub account: [u8; 32],
         pub payout: u64,
+        pub blinding: [u8; 32],
     }
 
+    /// Public part of a settlement: totals plus the blinded payout leaves and
+    /// their Merkle root, which the program stores as `result_commitment`.
     #[derive(Clone, MxeSerializable)]
-    pub struct SettlementResult {
+    pub struct SettlementSummary {
         pub round_id: u64,
         pub final_price: i64,
         pub fee_total: u64,
-        pub settlements: Vec<SettlementEntry>,
+        pub total_payout: u64,
+        pub leaves: Vec<[u8; 32]>,
+        pub payout_root: [u8; 32],
+        /// Seed every draw of the settlement was derived from; all zero when
+        /// the round has no draws. Otherwise its `seed_leaf` is the last of
+        /// `leaves`, so `payout_root` vouches for it.
+        pub randomness_seed: [u8; 32],
+    }
+
+    #[derive(Clone, MxeSerializable)]
+    pub struct SettlementOutput {
+        pub summary: SettlementSummary,
+        pub user_results: Vec<Enc<Shared, UserPayout>>,
+    }
+
+    /// MXE-owned state carried between the phase-one chunk scans.
+    /// `winning_weight` is the weighted stake of the entries at
+    /// `min_distance` so far.
+    #[derive(Clone, Copy, MxeSerializable)]
+    pub struct ScanState {
+        pub entry_count: u32,
+        pub total_stake: u64,
+        pub min_distance: u128,
+        pub winning_weight: u128,
+    }
+
+    /// Public result of settling one chunk in phase two. `leaves` and
+    /// `chunk_root` follow the same scheme as `SettlementSummary`, so a claim
+    /// proof is the proof within the chunk followed by the chunk root's proof.
+    #[derive(Clone, MxeSerializable)]
+    pub struct ChunkSummary {
+        pub round_id: u64,
+        pub chunk_index: u16,
+        pub scanned_entries: u32,
+        pub scanned_stake: u64,
+        pub fee_total: u64,
+        pub total_payout: u64,
+        pub leaves: Vec<[u8; 32]>,
+        pub chunk_root: [u8; 32],
+    }
+
+    #[derive(Clone, MxeSerializable)]
+    pub struct ChunkSettlement {
+        pub summary: ChunkSummary,
+        pub user_results: Vec<Enc<Shared, UserPayout>>,
+    }
+
+    /// MXE-owned running state of a round, folded one prediction at a time so
+    /// resolution never needs the whole prediction set at once. Prices are
+    /// grouped into `ACCUMULATOR_BUCKETS` buckets of the round's public bucket
+    /// layout; `bucket_weight` is the weighted stake per bucket.
+    #[derive(Clone, Copy, MxeSerializable)]
+    pub struct RoundAccumulator {
+        pub entry_count: u32,
+        pub total_stake: u64,
+        pub bucket_weight: [u64; ACCUMULATOR_BUCKETS],
+        pub bucket_count: [u32; ACCUMULATOR_BUCKETS],
+    }
+
+    /// Public result of resolving an accumulator: the bucket closest to the
+    /// final price among non-empty ones and the weight sharing `net_pool`.
+    #[derive(Clone, Copy, MxeSerializable)]
+    pub struct AccumulatorResolution {
+        pub round_id: u64,
+        pub entry_count: u32,
+        pub winning_bucket: u8,
+        pub winning_weight: u64,
+        pub net_pool: u64,
+        pub fee_total: u64,
+    }
+
+    #[derive(Clone, Copy, MxeSerializable)]
+    pub struct RoundSentiment {
+        pub round_id: u64,
+        pub participant_count: u32,
+        pub total_stake: u64,
+        pub weighted_mean_price: u64,
+        /// Centre of the histogram bucket holding the stake-weighted median.
+        pub median_price: u64,
+        pub histogram: [u32; SENTIMENT_BUCKETS],
     }
 
     #[instruction]
-    pub fn determine_winners(
-        predictions: Enc<Vec<EncryptedPrediction>, Shared>,
+    pub fn init_round_accumulator(mxe: Mxe) -> Enc<Mxe, RoundAccumulator> {
+        mxe.from_arcis(RoundAccumulator {
+            entry_count: 0,
+            total_stake: 0,
+            bucket_weight: [0u64; ACCUMULATOR_BUCKETS],
+            bucket_count: [0u32; ACCUMULATOR_BUCKETS],
+        })
+    }
+
+    /// Folds one submitted prediction into the accumulator, or takes a
+    /// cancelled one back out when `remove` is set.
+    #[instruction]
+    pub fn accumulate_prediction(
+        accumulator: Enc<Mxe, RoundAccumulator>,
+        prediction: EncryptedPrediction,
+        bucket_origin: u64,
+        bucket_width: u64,
+        remove: bool,
+    ) -> Enc<Mxe, RoundAccumulator> {
+        let mut state = accumulator.to_arcis();
+        let stake = prediction.stake.to_arcis() as u64;
+        let weight = weighted_stake(stake, prediction.weight_bps);
+        let bucket = bucket_index(
+            prediction.predicted_price.to_arcis(),
+            bucket_origin,
+            bucket_width,
+            ACCUMULATOR_BUCKETS,
+        );
+
+        if remove {
+            state.entry_count -= 1;
+            state.total_stake -= stake;
+        } else {
+            state.entry_count += 1;
+            state.total_stake += stake;
+        }
+        // `bucket` is secret, so every bucket is touched and only the matching
+        // one changes.
+        for index in 0..ACCUMULATOR_BUCKETS {
+            if index == bucket {
+                if remove {
+                    state.bucket_weight[index] -= weight;
+                    state.bucket_count[index] -= 1;
+                } else {
+                    state.bucket_weight[index] += weight;
+                    state.bucket_count[index] += 1;
+                }
+            }
+        }
+
+        accumulator.owner.from_arcis(state)
+    }
+
+    /// Final computation for accumulator rounds: only looks at the fixed-size
+    /// accumulator, never at individual predictions.
+    #[instruction]
+    pub fn resolve_accumulator(
+        accumulator: Enc<Mxe, RoundAccumulator>,
         final_price: EncScalar<Shared>,
-        fee_bps: u16,
         round_id: u64,
-    ) -> Enc<SettlementResult, Shared> {
-        let predictions = predictions.to_arcis();
+        bucket_origin: u64,
+        bucket_width: u64,
+        fee_bps: u16,
+        payout_pool: u64,
+    ) -> AccumulatorResolution {
+        let state = accumulator.to_arcis();
         let final_price_val = final_price.to_arcis();
 
-        let mut min_diff: Option<u128> = None;
-        for prediction in predictions.iter() {
-            let price = prediction.predicted_price.to_arcis();
-            let diff = distance(price, final_price_val);
-            min_diff = Some(match min_diff {
-                Some(current_min) if diff < current_min => diff,
-                Some(current_min) => current_min,
-                None => diff,
-            });
+        // Bucket counts are secret, so every bucket is visited and empty ones
+        // simply never win.
+        let mut winning_bucket: u8 = 0;
+        let mut winning_weight: u64 = 0;
+        let mut best_diff: u128 = u128::MAX;
+        for bucket in 0..ACCUMULATOR_BUCKETS {
+            let center = bucket_origin as u128
+                + bucket as u128 * bucket_width as u128
+                + bucket_width as u128 / 2;
+            let diff = distance(center, final_price_val);
+            let closer = state.bucket_count[bucket] > 0 && diff < best_diff;
+            best_diff = if closer { diff } else { best_diff };
+            winning_bucket = if closer { bucket as u8 } else { winning_bucket };
+            winning_weight = if closer {
+                state.bucket_weight[bucket]
+            } else {
+                winning_weight
+            };
+        }
+
+        let pool = if state.entry_count > 0 { payout_pool as u128 } else { 0 };
+        let fee_total = pool * fee_bps as u128 / 10_000u128;
+        AccumulatorResolution {
+            round_id,
+            entry_count: state.entry_count,
+            winning_bucket,
+            winning_weight,
+            net_pool: (pool - fee_total) as u64,
+            fee_total: fee_total as u64,
         }
+        .reveal()
+    }
+
+    /// Per-entry settlement for accumulator rounds. Reveals only the blinded
+    /// payout leaf; the payout itself is encrypted to the entry's owner.
+    #[instruction]
+    pub fn settle_accumulated_entry(
+        prediction: EncryptedPrediction,
+        resolution: AccumulatorResolution,
+        bucket_origin: u64,
+        bucket_width: u64,
+    ) -> (Enc<Shared, UserPayout>, [u8; 32]) {
+        let bucket = bucket_index(
+            prediction.predicted_price.to_arcis(),
+            bucket_origin,
+            bucket_width,
+            ACCUMULATOR_BUCKETS,
+        );
+        let weight = weighted_stake(prediction.stake.to_arcis() as u64, prediction.weight_bps);
+        let payout = if bucket == resolution.winning_bucket as usize && resolution.winning_weight > 0
+        {
+            (resolution.net_pool as u128 * weight as u128 / resolution.winning_weight as u128)
+                as u64
+        } else {
+            0
+        };
+
+        let blinding = ArcisRNG::gen_bytes::<32>();
+        let leaf = payout_leaf(&prediction.account, payout, &blinding).reveal();
+        let result = prediction.owner.from_arcis(UserPayout {
+            account: prediction.account,
+            payout,
+            blinding,
+        });
+        (result, leaf)
+    }
+
+    fn weighted_stake(stake: u64, weight_bps: u16) -> u64 {
+        (stake as u128 * weight_bps as u128 / BASE_WEIGHT_BPS) as u64
+    }
+
+    fn bucket_index(price: u128, origin: u64, width: u64, buckets: usize) -> usize {
+        let offset = price.saturating_sub(origin as u128);
+        (offset / (width as u128).max(1)).min(buckets as u128 - 1) as usize
+    }
 
-        let min_diff = min_diff.unwrap_or_default();
+    fn bucket_centre(price: u128, origin: u64, width: u64, buckets: usize) -> u128 {
+        let offset = price.saturating_sub(origin as u128);
+        let bucket = (offset / (width as u128).max(1)).min(buckets as u128 - 1);
+        origin as u128 + bucket * width as u128 + width as u128 / 2
+    }
 
+    /// Reveals aggregate statistics over a locked round without exposing any
+    /// single prediction. Bucket `i` counts prices in
+    /// `[bucket_origin + i * bucket_width, bucket_origin + (i + 1) * bucket_width)`;
+    /// the first and last buckets also absorb everything below and above. The
+    /// histogram stays empty unless `bucket_width` is at least
+    /// `MIN_SENTIMENT_BUCKET_BPS` of the weighted mean price. The median is only
+    /// revealed as the centre of its bucket, and only alongside the histogram,
+    /// since the exact median is always some participant's price.
+    #[instruction]
+    pub fn reveal_round_sentiment(
+        predictions: Vec<EncryptedPrediction>,
+        round_id: u64,
+        bucket_origin: u64,
+        bucket_width: u64,
+    ) -> RoundSentiment {
+        let mut participant_count: u32 = 0;
         let mut total_stake: u128 = 0;
-        let mut winners: Vec<&EncryptedPrediction> = Vec::new();
+        let mut weighted_sum: u128 = 0;
+        let mut histogram = [0u32; SENTIMENT_BUCKETS];
         for prediction in predictions.iter() {
             let price = prediction.predicted_price.to_arcis();
-            let diff = distance(price, final_price_val);
             let stake = prediction.stake.to_arcis();
+            participant_count += 1;
             total_stake = total_stake.saturating_add(stake);
-            if diff == min_diff {
-                winners.push(prediction);
+            weighted_sum = weighted_sum.saturating_add(price.saturating_mul(stake));
+
+            let bucket = bucket_index(price, bucket_origin, bucket_width, SENTIMENT_BUCKETS);
+            for (index, count) in histogram.iter_mut().enumerate() {
+                if index == bucket {
+                    *count += 1;
+                }
+            }
+        }
+
+        // Stake-weighted lower median: the smallest price with at least half the
+        // total stake at or below it.
+        let mut median_price: u128 = 0;
+        let mut median_found = false;
+        for candidate in predictions.iter() {
+            let candidate_price = candidate.predicted_price.to_arcis();
+            let mut stake_at_or_below: u128 = 0;
+            for prediction in predictions.iter() {
+                if prediction.predicted_price.to_arcis() <= candidate_price {
+                    stake_at_or_below = stake_at_or_below.saturating_add(prediction.stake.to_arcis());
+                }
+            }
+            if stake_at_or_below * 2 >= total_stake
+                && (!median_found || candidate_price < median_price)
+            {
+                median_price = candidate_price;
+                median_found = true;
+            }
+        }
+
+        let enough_participants = participant_count >= MIN_SENTIMENT_PARTICIPANTS;
+        let weighted_mean_price = if enough_participants && total_stake > 0 {
+            weighted_sum / total_stake
+        } else {
+            0
+        };
+        let wide_enough = bucket_width as u128 * 10_000u128
+            >= weighted_mean_price * MIN_SENTIMENT_BUCKET_BPS;
+
+        RoundSentiment {
+            round_id,
+            participant_count,
+            total_stake: total_stake as u64,
+            weighted_mean_price: weighted_mean_price as u64,
+            median_price: if enough_participants && wide_enough {
+                bucket_centre(median_price, bucket_origin, bucket_width, SENTIMENT_BUCKETS) as u64
+            } else {
+                0
+            },
+            histogram: if enough_participants && wide_enough {
+                histogram
+            } else {
+                [0u32; SENTIMENT_BUCKETS]
+            },
+        }
+        .reveal()
+    }
+
+    /// `payout_tiers` holds the share in basis points of `payout_pool` paid to
+    /// the 1st, 2nd, ... closest distinct distance. All zero keeps the original
+    /// winner-take-all rule where the closest entries get their stake back,
+    /// scaled by their early-entry weight.
+    ///
+    /// Under the winner-take-all rule `TIE_BREAK_RANDOM` hands the combined
+    /// stake of all closest entries to one of them, drawn in input order.
+    /// `lucky_draw_bps` of `payout_pool` goes to one entry drawn uniformly in
+    /// input order; the relayer passes predictions sorted by account. Draws use
+    /// a seed generated inside the cluster and published with the result.
+    #[instruction]
+    pub fn determine_winners(
+        predictions: Vec<EncryptedPrediction>,
+        final_price: EncScalar<Shared>,
+        fee_bps: u16,
+        round_id: u64,
+        payout_tiers: [u16; MAX_PAYOUT_TIERS],
+        payout_pool: u64,
+        tie_break: u8,
+        lucky_draw_bps: u16,
+    ) -> SettlementOutput {
+        let final_price_val = final_price.to_arcis();
+        let seed = ArcisRNG::gen_bytes::<32>();
+        let lucky_pool = payout_pool as u128 * lucky_draw_bps as u128 / 10_000u128;
+
+        let (mut settlements, fee_total) = if payout_tiers.iter().any(|tier| *tier > 0) {
+            tiered_payouts(
+                &predictions,
+                final_price_val,
+                fee_bps,
+                &payout_tiers,
+                payout_pool - lucky_pool as u64,
+            )
+        } else {
+            closest_payouts(
+                &predictions,
+                final_price_val,
+                fee_bps,
+                tie_break,
+                payout_pool,
+                &seed,
+            )
+        };
+
+        if lucky_pool > 0 && !settlements.is_empty() {
+            // Fees are paid out of the pool too.
+            let paid: u128 = settlements.iter().map(|entry| entry.payout as u128).sum();
+            let available = (payout_pool as u128)
+                .saturating_sub(paid)
+                .saturating_sub(fee_total as u128);
+            let lucky = draw_index(&seed, LUCKY_DRAW_TAG, settlements.len() as u64);
+            let prize = lucky_pool.min(available) as u64;
+            // The drawn index is secret, so every entry is visited.
+            for (index, entry) in settlements.iter_mut().enumerate() {
+                if index as u64 == lucky {
+                    entry.payout += prize;
+                }
             }
         }
 
+        let draws = tie_break == TIE_BREAK_RANDOM || lucky_draw_bps > 0;
+        let mut extra_leaves: Vec<[u8; 32]> = Vec::new();
+        if draws && !settlements.is_empty() {
+            extra_leaves.push(seed_leaf(&seed));
+        }
+        let mut output = seal_settlement(
+            round_id,
+            final_price_val as i64,
+            fee_total,
+            settlements,
+            extra_leaves,
+        );
+        if draws {
+            output.summary.randomness_seed = seed.reveal();
+        }
+        output
+    }
+
+    /// Original winner-take-all rule: the closest entries get their weighted
+    /// stake back minus the fee, or a single drawn one gets all of it. When
+    /// the weighted stakes exceed `payout_pool` they are scaled down to it.
+    fn closest_payouts(
+        predictions: &Vec<EncryptedPrediction>,
+        final_price_val: u128,
+        fee_bps: u16,
+        tie_break: u8,
+        payout_pool: u64,
+        seed: &[u8; 32],
+    ) -> (Vec<SettlementEntry>, u64) {
+        let mut min_diff: u128 = u128::MAX;
+        for prediction in predictions.iter() {
+            let diff = distance(prediction.predicted_price.to_arcis(), final_price_val);
+            min_diff = if diff < min_diff { diff } else { min_diff };
+        }
+
+        let mut tied_weight: u128 = 0;
+        for prediction in predictions.iter() {
+            if distance(prediction.predicted_price.to_arcis(), final_price_val) == min_diff {
+                tied_weight += weighted_stake(
+                    prediction.stake.to_arcis() as u64,
+                    prediction.weight_bps,
+                ) as u128;
+            }
+        }
+        let pool = payout_pool as u128;
+
         let mut payouts: Vec<SettlementEntry> = Vec::new();
         let mut fee_total: u128 = 0;
+        let mut tied_count: u64 = 0;
+        let mut tied_gross: u128 = 0;
+        for prediction in predictions.iter() {
+            let price = prediction.predicted_price.to_arcis();
+            let diff = distance(price, final_price_val);
+            let mut payout: u128 = 0;
+            if diff == min_diff {
+                let weight =
+                    weighted_stake(prediction.stake.to_arcis() as u64, prediction.weight_bps)
+                        as u128;
+                let gross = if tied_weight > pool {
+                    weight * pool / tied_weight
+                } else {
+                    weight
+                };
+                let fee = gross * fee_bps as u128 / 10_000u128;
+                payout = gross.saturating_sub(fee);
+                fee_total = fee_total.saturating_add(fee);
+                tied_count += 1;
+                tied_gross += gross;
+            }
+            payouts.push(SettlementEntry {
+                account: prediction.account,
+                owner: prediction.owner,
+                payout: payout as u64,
+            });
+        }
+
+        if tie_break == TIE_BREAK_RANDOM && tied_count > 1 {
+            let winner = draw_index(seed, TIE_BREAK_TAG, tied_count);
+            let mut tied_seen: u64 = 0;
+            let winner_payout = (tied_gross - tied_gross * fee_bps as u128 / 10_000u128) as u64;
+            for (prediction, entry) in predictions.iter().zip(payouts.iter_mut()) {
+                if distance(prediction.predicted_price.to_arcis(), final_price_val) == min_diff {
+                    entry.payout = if tied_seen == winner { winner_payout } else { 0 };
+                    tied_seen += 1;
+                }
+            }
+            fee_total = tied_gross * fee_bps as u128 / 10_000u128;
+        }
+
+        (payouts, fee_total as u64)
+    }
+
+    /// Uniform index below `n` derived from the settlement seed. Must match
+    /// `draw::draw_index` in the program.
+    fn draw_index(seed: &[u8; 32], tag: &[u8], n: u64) -> u64 {
+        let digest = sha256(&[DRAW_DOMAIN, tag, seed]);
+        let mut word = [0u8; 8];
+        word.copy_from_slice(&digest[..8]);
+        u64::from_le_bytes(word) % n.max(1)
+    }
+
+    /// Leaf committing a settlement to its seed. Must match `draw::seed_leaf`
+    /// in the program.
+    fn seed_leaf(seed: &[u8; 32]) -> [u8; 32] {
+        sha256(&[SEED_DOMAIN, seed])
+    }
+
+    /// Phase one of chunked resolution: folds one chunk of at most
+    /// `RESOLUTION_CHUNK_SIZE` predictions into the running minimum distance,
+    /// the weight tied on it and the total stake. Pass `None` as the state for
+    /// the first chunk.
+    #[instruction]
+    pub fn scan_chunk(
+        mxe: Mxe,
+        state: Option<Enc<Mxe, ScanState>>,
+        chunk: Vec<EncryptedPrediction>,
+        final_price: EncScalar<Shared>,
+    ) -> Enc<Mxe, ScanState> {
+        let final_price_val = final_price.to_arcis();
+        let mut scan = match state {
+            Some(state) => state.to_arcis(),
+            None => ScanState {
+                entry_count: 0,
+                total_stake: 0,
+                min_distance: u128::MAX,
+                winning_weight: 0,
+            },
+        };
+
+        for prediction in chunk.iter() {
+            let diff = distance(prediction.predicted_price.to_arcis(), final_price_val);
+            let weight =
+                weighted_stake(prediction.stake.to_arcis() as u64, prediction.weight_bps) as u128;
+            scan.winning_weight = if diff < scan.min_distance {
+                weight
+            } else if diff == scan.min_distance {
+                scan.winning_weight + weight
+            } else {
+                scan.winning_weight
+            };
+            if diff < scan.min_distance {
+                scan.min_distance = diff;
+            }
+            scan.entry_count += 1;
+            scan.total_stake += prediction.stake.to_arcis() as u64;
+        }
+
+        mxe.from_arcis(scan)
+    }
+
+    /// Phase two of chunked resolution: pays the entries of one chunk against
+    /// the global minimum from phase one, using the same winner-take-all rule
+    /// as `determine_winners`, so the winners' weighted stakes are scaled down
+    /// to `payout_pool` when they exceed it. Chunks may be settled in any
+    /// order. Every entry
+    /// gets a leaf, so an oversized chunk shows up in `leaves` and is refused
+    /// by `record_chunk_settlement`.
+    #[instruction]
+    pub fn settle_chunk(
+        state: Enc<Mxe, ScanState>,
+        chunk: Vec<EncryptedPrediction>,
+        final_price: EncScalar<Shared>,
+        fee_bps: u16,
+        round_id: u64,
+        chunk_index: u16,
+        payout_pool: u64,
+    ) -> ChunkSettlement {
+        let scan = state.to_arcis();
+        let final_price_val = final_price.to_arcis();
+        let pool = payout_pool as u128;
 
-        if !winners.is_empty() {
-            for winner in winners.iter() {
-                let stake = winner.stake.to_arcis();
-                let gross = stake;
+        let mut settlements: Vec<SettlementEntry> = Vec::new();
+        let mut fee_total: u128 = 0;
+        for prediction in chunk.iter() {
+            let diff = distance(prediction.predicted_price.to_arcis(), final_price_val);
+            let mut payout: u128 = 0;
+            if diff == scan.min_distance {
+                let weight =
+                    weighted_stake(prediction.stake.to_arcis() as u64, prediction.weight_bps)
+                        as u128;
+                let gross = if scan.winning_weight > pool {
+                    weight * pool / scan.winning_weight
+                } else {
+                    weight
+                };
                 let fee = gross * fee_bps as u128 / 10_000u128;
-                let payout = gross.saturating_sub(fee);
+                payout = gross.saturating_sub(fee);
                 fee_total = fee_total.saturating_add(fee);
-                payouts.push(SettlementEntry {
-                    commitment: winner.commitment,
-                    payout: payout as u64,
-                });
             }
+            settlements.push(SettlementEntry {
+                account: prediction.account,
+                owner: prediction.owner,
+                payout: payout as u64,
+            });
         }
 
-        Enc::from_arcis(
-            SettlementResult {
+        let sealed = seal_settlement(
+            round_id,
+            final_price_val as i64,
+            fee_total as u64,
+            settlements,
+            Vec::new(),
+        );
+        ChunkSettlement {
+            summary: ChunkSummary {
                 round_id,
-                final_price: final_price_val as i64,
-                fee_total: fee_total as u64,
-                settlements: payouts,
+                chunk_index,
+                // Lets the program check that phase one saw every entry.
+                scanned_entries: scan.entry_count.reveal(),
+                scanned_stake: scan.total_stake.reveal(),
+                fee_total: sealed.summary.fee_total,
+                total_payout: sealed.summary.total_payout,
+                leaves: sealed.summary.leaves,
+                chunk_root: sealed.summary.payout_root,
             },
-            predictions.owner,
+            user_results: sealed.user_results,
+        }
+    }
+
+    /// Draws a blinding per entry, reveals only the blinded payout leaves, their
+    /// root and the totals, and encrypts each payout to its owner.
+    /// `extra_leaves` go after the payout leaves, under the same root.
+    fn seal_settlement(
+        round_id: u64,
+        final_price: i64,
+        fee_total: u64,
+        settlements: Vec<SettlementEntry>,
+        extra_leaves: Vec<[u8; 32]>,
+    ) -> SettlementOutput {
+        let mut total_payout: u64 = 0;
+        let mut leaves: Vec<[u8; 32]> = Vec::new();
+        let mut user_results: Vec<Enc<Shared, UserPayout>> = Vec::new();
+        for entry in settlements.iter() {
+            let blinding = ArcisRNG::gen_bytes::<32>();
+            total_payout = total_payout.saturating_add(entry.payout);
+            leaves.push(payout_leaf(&entry.account, entry.payout, &blinding).reveal());
+            user_results.push(entry.owner.from_arcis(UserPayout {
+                account: entry.account,
+                payout: entry.payout,
+                blinding,
+            }));
+        }
+
+        for leaf in extra_leaves.iter() {
+            leaves.push(*leaf);
+        }
+        let payout_root = merkle_root(&leaves);
+        SettlementOutput {
+            summary: SettlementSummary {
+                round_id,
+                final_price,
+                fee_total: fee_total.reveal(),
+                total_payout: total_payout.reveal(),
+                leaves,
+                payout_root,
+                randomness_seed: [0u8; 32],
+            },
+            user_results,
+        }
+    }
+
+    fn payout_leaf(account: &[u8; 32], payout: u64, blinding: &[u8; 32]) -> [u8; 32] {
+        sha256(&[LEAF_DOMAIN, account, &payout.to_le_bytes(), blinding])
+    }
+
+    /// Sorted-pair Merkle root; an odd node is carried up unchanged. Must match
+    /// `merkle::root` in the program. Always runs `MAX_MERKLE_DEPTH` levels; a
+    /// level of one node carries it up unchanged, so the extra ones are no-ops.
+    fn merkle_root(leaves: &Vec<[u8; 32]>) -> [u8; 32] {
+        let mut level = leaves.clone();
+        if level.is_empty() {
+            level.push([0u8; 32]);
+        }
+        for _ in 0..MAX_MERKLE_DEPTH {
+            let mut next: Vec<[u8; 32]> = Vec::new();
+            for pair in level.chunks(2) {
+                if pair.len() == 2 {
+                    let (a, b) = if pair[0] <= pair[1] {
+                        (pair[0], pair[1])
+                    } else {
+                        (pair[1], pair[0])
+                    };
+                    next.push(sha256(&[&a, &b]));
+                } else {
+                    next.push(pair[0]);
+                }
+            }
+            level = next;
+        }
+        level[0]
+    }
+
+    /// Scores each interval with the interval score for a central
+    /// `(1 - alpha)` interval (width plus `2 / alpha` times the miss distance,
+    /// lower is better), turns it into a reward `max_penalty - penalty` and pays
+    /// the pool proportionally to reward × stake × early-entry weight.
+    #[instruction]
+    pub fn score_interval_predictions(
+        predictions: Vec<EncryptedRangePrediction>,
+        final_price: EncScalar<Shared>,
+        fee_bps: u16,
+        round_id: u64,
+        alpha_bps: u16,
+        max_penalty: u64,
+        payout_pool: u64,
+    ) -> SettlementOutput {
+        let final_price_val = final_price.to_arcis();
+
+        let pool = payout_pool as u128;
+        let fee_total = pool * fee_bps as u128 / 10_000u128;
+        let net_pool = pool - fee_total;
+
+        let mut weights: Vec<u128> = Vec::new();
+        let mut total_weight: u128 = 0;
+        for prediction in predictions.iter() {
+            let penalty = interval_penalty(
+                prediction.lower.to_arcis(),
+                prediction.upper.to_arcis(),
+                final_price_val,
+                alpha_bps,
+            );
+            let reward = (max_penalty as u128).saturating_sub(penalty);
+            let weight = reward * prediction.stake.to_arcis() * prediction.weight_bps as u128
+                / BASE_WEIGHT_BPS;
+            total_weight = total_weight.saturating_add(weight);
+            weights.push(weight);
+        }
+
+        let mut payouts: Vec<SettlementEntry> = Vec::new();
+        for (prediction, weight) in predictions.iter().zip(weights.iter()) {
+            let payout = if total_weight > 0 {
+                net_pool * *weight / total_weight
+            } else {
+                0
+            };
+            payouts.push(SettlementEntry {
+                account: prediction.account,
+                owner: prediction.owner,
+                payout: payout as u64,
+            });
+        }
+
+        seal_settlement(
+            round_id,
+            final_price_val as i64,
+            fee_total as u64,
+            payouts,
+            Vec::new(),
         )
     }
 
+    /// Interval score in price units. An inverted interval scores as the worst
+    /// possible entry.
+    fn interval_penalty(lower: u128, upper: u128, price: u128, alpha_bps: u16) -> u128 {
+        let invalid = lower > upper || alpha_bps == 0;
+        let width = upper.saturating_sub(lower);
+        let miss = lower.saturating_sub(price) + price.saturating_sub(upper);
+        let penalty =
+            width.saturating_add(miss.saturating_mul(2 * 10_000u128) / (alpha_bps as u128).max(1));
+        if invalid {
+            u128::MAX
+        } else {
+            penalty
+        }
+    }
+
+    /// Splits the pool across tiers of distinct distances, closest first. Entries
+    /// tied on a tier's distance share that tier in proportion to their
+    /// early-entry weight, which is an even split when no bonus is configured.
+    /// Tiers with no entries left are not paid and stay in escrow for rollover.
+    fn tiered_payouts(
+        predictions: &Vec<EncryptedPrediction>,
+        final_price: u128,
+        fee_bps: u16,
+        payout_tiers: &[u16; MAX_PAYOUT_TIERS],
+        payout_pool: u64,
+    ) -> (Vec<SettlementEntry>, u64) {
+        let pool = payout_pool as u128;
+        let fee_total = pool * fee_bps as u128 / 10_000u128;
+        let net_pool = pool - fee_total;
+
+        // Every tier visits every entry; once the distances run out `found`
+        // stays false and the remaining tiers pay nothing.
+        let mut payouts: Vec<u128> = predictions.iter().map(|_| 0u128).collect();
+        let mut previous_diff: u128 = 0;
+        let mut has_previous = false;
+        for tier_bps in payout_tiers.iter() {
+            // Next distinct distance strictly further than the previous tier.
+            let mut tier_diff: u128 = u128::MAX;
+            let mut found = false;
+            for prediction in predictions.iter() {
+                let diff = distance(prediction.predicted_price.to_arcis(), final_price);
+                let beyond_previous = !has_previous || diff > previous_diff;
+                tier_diff = if beyond_previous && diff < tier_diff { diff } else { tier_diff };
+                found = found || beyond_previous;
+            }
+            let paid = *tier_bps > 0 && found;
+
+            let mut tied_weight: u128 = 0;
+            for prediction in predictions.iter() {
+                if distance(prediction.predicted_price.to_arcis(), final_price) == tier_diff {
+                    tied_weight += prediction.weight_bps as u128;
+                }
+            }
+            let tier_amount = net_pool * *tier_bps as u128 / 10_000u128;
+            for (prediction, payout) in predictions.iter().zip(payouts.iter_mut()) {
+                let diff = distance(prediction.predicted_price.to_arcis(), final_price);
+                if paid && diff == tier_diff {
+                    *payout += tier_amount * prediction.weight_bps as u128 / tied_weight.max(1);
+                }
+            }
+            previous_diff = if paid { tier_diff } else { previous_diff };
+            has_previous = has_previous || paid;
+        }
+
+        let settlements = predictions
+            .iter()
+            .zip(payouts.iter())
+            .map(|(prediction, payout)| SettlementEntry {
+                account: prediction.account,
+                owner: prediction.owner,
+                payout: *payout as u64,
+            })
+            .collect();
+        (settlements, fee_total as u64)
+    }
+
     fn distance(a: u128, b: u128) -> u128 {
         if a >= b {
             a - b
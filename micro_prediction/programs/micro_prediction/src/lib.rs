@@ -1,11 +1,26 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions as ix_sysvar;
+use anchor_lang::system_program;
 use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use micro_prediction_settlement as settlement;
+
+pub mod oracle;
+
+pub use settlement::{draw, merkle};
+
+pub use oracle::{
+    PriceAttestation, PriceObservation, PriceSource, PriceSourceKind, SampleFilter,
+    DEFAULT_MAX_PRICE_AGE_SECS, DEFAULT_MAX_PRICE_AGE_SLOTS, DEFAULT_MAX_PRICE_DEVIATION_BPS,
+    MAX_PRICE_OBSERVATIONS, MAX_PRICE_SOURCES, MIN_PRICE_OBSERVATIONS,
+};
 
 // Custom getrandom implementation for Solana BPF
 #[cfg(all(target_os = "solana", not(feature = "std")))]
 #[no_mangle]
 pub extern "C" fn getrandom(buf: *mut u8, len: usize) -> i32 {
-    // Fill buffer with zeros (deterministic behavior for Solana on-chain)
+    // Fill buffer with zeros (deterministic behavior for Solana on-chain).
+    // Nothing on-chain may rely on this for draws; those use the seed the
+    // settlement circuit generates, see `Round::randomness_seed`.
     unsafe {
         core::ptr::write_bytes(buf, 0, len);
     }
@@ -16,6 +31,37 @@ pub const CONFIG_SEED: &[u8] = b"config";
 pub const ROUND_SEED: &[u8] = b"round";
 pub const ESCROW_SEED: &[u8] = b"escrow";
 pub const PREDICTION_SEED: &[u8] = b"prediction";
+pub const OBSERVATIONS_SEED: &[u8] = b"observations";
+pub const ROLLOVER_SEED: &[u8] = b"rollover";
+pub const ROUND_STATS_SEED: &[u8] = b"round_stats";
+pub const ACCUMULATOR_SEED: &[u8] = b"accumulator";
+pub const RESOLUTION_CHUNKS_SEED: &[u8] = b"resolution_chunks";
+
+/// Must match `MAX_PAYOUT_TIERS` in the `determine_winners` circuit.
+pub const MAX_PAYOUT_TIERS: usize = 5;
+/// Weight of an entry without any early-entry bonus.
+pub const BASE_WEIGHT_BPS: u16 = settlement::BASE_WEIGHT_BPS;
+pub const MAX_EARLY_BONUS_BPS: u16 = 10_000;
+pub const MAX_LUCKY_DRAW_BPS: u16 = 2_000;
+/// Upper bound on the flat amendment fee, in base units of the round mint.
+pub const MAX_AMENDMENT_FEE: u64 = 10_000_000;
+/// Must match `SENTIMENT_BUCKETS` in the `reveal_round_sentiment` circuit.
+pub const SENTIMENT_BUCKETS: usize = 8;
+/// Narrowest sentiment bucket in basis points of the weighted mean price; must
+/// match `MIN_SENTIMENT_BUCKET_BPS` in the `reveal_round_sentiment` circuit.
+pub const MIN_SENTIMENT_BUCKET_BPS: u64 = 100;
+/// Price buckets in the MXE round accumulator; matches `ACCUMULATOR_BUCKETS`
+/// in the circuits.
+pub const ACCUMULATOR_BUCKETS: usize = 16;
+/// Ciphertexts making up an encrypted `RoundAccumulator`: entry count, total
+/// stake, then per-bucket weight and count.
+pub const ACCUMULATOR_STATE_LEN: usize = 2 + 2 * ACCUMULATOR_BUCKETS;
+/// Upper bound on chunks in chunked resolution, set by the settled bitmap.
+pub const MAX_RESOLUTION_CHUNKS: usize = 256;
+/// Most entries in one chunk; matches `RESOLUTION_CHUNK_SIZE` in the circuits.
+pub const RESOLUTION_CHUNK_SIZE: u32 = 32;
+/// Ciphertexts making up an encrypted `ScanState`.
+pub const SCAN_STATE_LEN: usize = 4;
 
 declare_id!("3btqev6Y8xNxqwFxFKaDPihQyVZ1gs2DpBNsDukmHxNX");
 
@@ -36,7 +82,267 @@ pub mod micro_prediction {
         config.token_mint = ctx.accounts.token_mint.key();
         config.fee_treasury = ctx.accounts.fee_treasury.key();
         config.fee_bps = fee_bps;
+        config.amendment_fee = 0;
+        config.house_edge_bps = 0;
+        config.rollover_vault = Pubkey::default();
         config.bump = ctx.bumps.config;
+        config.rollover_bump = 0;
+        config.accrued_fees = 0;
+
+        Ok(())
+    }
+
+    /// Grows a config created before the amendment fee, house edge, rollover
+    /// and fee tracking fields existed to the current layout. The new fields
+    /// start zeroed.
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        let config = &ctx.accounts.config;
+        let data_len = config.data_len();
+        {
+            let data = config.try_borrow_data()?;
+            require!(
+                data.len() >= Config::LEGACY_SPACE && data[..8] == *Config::DISCRIMINATOR,
+                anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+            );
+            require!(
+                data[8..40] == ctx.accounts.authority.key().to_bytes(),
+                ErrorCode::Unauthorized
+            );
+        }
+        require!(data_len < Config::SPACE, ErrorCode::ConfigUpToDate);
+
+        grow_account(
+            &ctx.accounts.authority,
+            config,
+            &ctx.accounts.system_program,
+            Config::SPACE,
+        )
+    }
+
+    /// Grows a round created before the payout rule, price source,
+    /// accumulator and chunking fields existed to the current layout. The new
+    /// fields take the values `initialize_round` gives an unconfigured round,
+    /// without a house edge since the stakes were taken without one.
+    pub fn migrate_round(ctx: Context<MigrateRound>, _round_id: u64) -> Result<()> {
+        let round_info = &ctx.accounts.round;
+        {
+            let data = round_info.try_borrow_data()?;
+            require!(
+                data.len() >= Round::LEGACY_SPACE && data[..8] == *Round::DISCRIMINATOR,
+                anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+            );
+        }
+        require!(round_info.data_len() < Round::SPACE, ErrorCode::AccountUpToDate);
+
+        grow_account(
+            &ctx.accounts.authority,
+            round_info,
+            &ctx.accounts.system_program,
+            Round::SPACE,
+        )?;
+
+        let mut data = round_info.try_borrow_mut_data()?;
+        let mut round = Round::try_deserialize(&mut &data[..])?;
+        round.price_sources = vec![PriceSource {
+            kind: PriceSourceKind::PythPush as u8,
+            key: round.pyth_price_account,
+        }];
+        round.min_price_sources = 1;
+        round.max_price_age_secs = DEFAULT_MAX_PRICE_AGE_SECS;
+        round.max_price_deviation_bps = DEFAULT_MAX_PRICE_DEVIATION_BPS;
+        round.max_price_age_slots = DEFAULT_MAX_PRICE_AGE_SLOTS;
+        round.try_serialize(&mut &mut data[..])?;
+
+        Ok(())
+    }
+
+    /// Grows a prediction created before encrypted payloads, entry weights
+    /// and accumulator folding existed to the current layout. The entry gets
+    /// the base weight and an empty payload, so it can be refunded but not
+    /// settled by a circuit.
+    pub fn migrate_prediction(ctx: Context<MigratePrediction>) -> Result<()> {
+        let prediction_info = &ctx.accounts.prediction;
+        {
+            let data = prediction_info.try_borrow_data()?;
+            require!(
+                data.len() >= Prediction::LEGACY_SPACE
+                    && data[..8] == *Prediction::DISCRIMINATOR,
+                anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+            );
+        }
+        require!(
+            prediction_info.data_len() < Prediction::SPACE,
+            ErrorCode::AccountUpToDate
+        );
+
+        grow_account(
+            &ctx.accounts.authority,
+            prediction_info,
+            &ctx.accounts.system_program,
+            Prediction::SPACE,
+        )?;
+
+        let mut data = prediction_info.try_borrow_mut_data()?;
+        let mut prediction = Prediction::try_deserialize(&mut &data[..])?;
+        prediction.weight_bps = BASE_WEIGHT_BPS;
+        prediction.try_serialize(&mut &mut data[..])?;
+
+        Ok(())
+    }
+
+    pub fn set_amendment_fee(ctx: Context<UpdateConfig>, amendment_fee: u64) -> Result<()> {
+        require!(amendment_fee <= MAX_AMENDMENT_FEE, ErrorCode::InvalidAmendmentFee);
+        ctx.accounts.config.amendment_fee = amendment_fee;
+        Ok(())
+    }
+
+    pub fn set_house_edge(ctx: Context<UpdateConfig>, house_edge_bps: u16) -> Result<()> {
+        require!(house_edge_bps <= 10_000, ErrorCode::InvalidFeeBps);
+        ctx.accounts.config.house_edge_bps = house_edge_bps;
+        Ok(())
+    }
+
+    pub fn init_rollover_vault(ctx: Context<InitRolloverVault>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.rollover_vault = ctx.accounts.rollover_vault.key();
+        config.rollover_bump = ctx.bumps.rollover_vault;
+        Ok(())
+    }
+
+    /// Lets an operator add funds to the jackpot carried into future rounds.
+    pub fn top_up_rollover(ctx: Context<TopUpRollover>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidStakeAmount);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.funder_token_account.to_account_info(),
+            to: ctx.accounts.rollover_vault.to_account_info(),
+            authority: ctx.accounts.funder.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+        );
+        token::transfer(cpi_ctx, amount)?;
+
+        Ok(())
+    }
+
+    /// Moves `amount` from the rollover vault into an open round's pot. Fees
+    /// accrued for the house cannot be used as a seed.
+    pub fn seed_round_from_rollover(
+        ctx: Context<SeedRoundFromRollover>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidStakeAmount);
+        let round = &mut ctx.accounts.round;
+        require!(round.status == RoundStatus::Open as u8, ErrorCode::RoundNotOpen);
+
+        let config = &ctx.accounts.config;
+        let jackpot = ctx
+            .accounts
+            .rollover_vault
+            .amount
+            .saturating_sub(config.accrued_fees);
+        require!(amount <= jackpot, ErrorCode::InsufficientEscrow);
+        let seeds = [CONFIG_SEED, &[config.bump]];
+        let signer_seeds = [&seeds[..]];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.rollover_vault.to_account_info(),
+            to: ctx.accounts.escrow_vault.to_account_info(),
+            authority: config.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            &signer_seeds,
+        );
+        token::transfer(cpi_ctx, amount)?;
+
+        round.carried_in = round
+            .carried_in
+            .checked_add(amount)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+
+        Ok(())
+    }
+
+    /// Permissionless: moves whatever a finalized round did not commit to
+    /// payouts into the rollover vault. Committed payouts stay in escrow for
+    /// winners who have not claimed yet. Only the round's house edge and
+    /// settlement fees are credited to `Config::accrued_fees`; the part of the
+    /// pool nobody won stays in the vault as jackpot for later rounds. For
+    /// refunded rounds only the carried-in amount goes back; stakes stay for
+    /// refunds.
+    pub fn sweep_round_to_rollover(ctx: Context<SweepRoundToRollover>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let round = &mut ctx.accounts.round;
+        let amount = if round.status == RoundStatus::Finalized as u8 {
+            round.unreserved_balance().ok_or(ErrorCode::NumericalOverflow)?
+        } else if round.status == RoundStatus::Refunded as u8 {
+            round
+                .carried_in
+                .checked_sub(round.rolled_over)
+                .ok_or(ErrorCode::NumericalOverflow)?
+        } else {
+            return err!(ErrorCode::InvalidRoundState);
+        };
+        require!(amount > 0, ErrorCode::NothingToSweep);
+
+        let seeds = [ROUND_SEED, &round.round_id.to_le_bytes(), &[round.bump]];
+        let signer_seeds = [&seeds[..]];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.escrow_vault.to_account_info(),
+            to: ctx.accounts.rollover_vault.to_account_info(),
+            authority: round.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            &signer_seeds,
+        );
+        token::transfer(cpi_ctx, amount)?;
+
+        let rolled_over = round
+            .rolled_over
+            .checked_add(amount)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        if round.status == RoundStatus::Finalized as u8 {
+            // House revenue leaves escrow first, whichever sweep moves it.
+            let revenue = round.house_revenue().ok_or(ErrorCode::NumericalOverflow)?;
+            let credited = rolled_over.min(revenue) - round.rolled_over.min(revenue);
+            config.accrued_fees = config
+                .accrued_fees
+                .checked_add(credited)
+                .ok_or(ErrorCode::NumericalOverflow)?;
+        }
+        round.rolled_over = rolled_over;
+
+        Ok(())
+    }
+
+    /// Pays `amount` of the house revenue swept into the rollover vault (fees and
+    /// house edge) out to the fee treasury. Capped at `Config::accrued_fees`, so
+    /// carried jackpots and top-ups stay in the vault.
+    pub fn collect_fees(ctx: Context<CollectFees>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidStakeAmount);
+
+        let config = &mut ctx.accounts.config;
+        require!(amount <= config.accrued_fees, ErrorCode::InsufficientAccruedFees);
+        config.accrued_fees -= amount;
+
+        let seeds = [CONFIG_SEED, &[config.bump]];
+        let signer_seeds = [&seeds[..]];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.rollover_vault.to_account_info(),
+            to: ctx.accounts.fee_treasury.to_account_info(),
+            authority: config.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            &signer_seeds,
+        );
+        token::transfer(cpi_ctx, amount)?;
 
         Ok(())
     }
@@ -62,9 +368,31 @@ pub mod micro_prediction {
         round.escrow_vault = ctx.accounts.escrow_vault.key();
         round.total_stake = 0;
         round.total_paid = 0;
+        round.carried_in = 0;
+        round.rolled_over = 0;
+        round.house_edge_bps = ctx.accounts.config.house_edge_bps;
+        round.payout_tiers = Vec::new();
+        round.round_type = RoundType::ClosestPrice as u8;
+        round.interval_alpha_bps = 0;
+        round.max_interval_penalty = 0;
+        round.early_bonus_bps = 0;
+        round.committed_payout = 0;
         round.final_price = None;
         round.settlement_timestamp = None;
         round.pyth_price_account = pyth_price_account;
+        round.price_sources = vec![PriceSource {
+            kind: PriceSourceKind::PythPush as u8,
+            key: pyth_price_account,
+        }];
+        round.min_price_sources = 1;
+        round.max_price_age_secs = DEFAULT_MAX_PRICE_AGE_SECS;
+        round.max_price_deviation_bps = DEFAULT_MAX_PRICE_DEVIATION_BPS;
+        round.max_price_age_slots = DEFAULT_MAX_PRICE_AGE_SLOTS;
+        round.min_pull_signatures = 0;
+        round.price_sources_used = 0;
+        round.resolution_mode = ResolutionMode::Spot as u8;
+        round.twap_window_secs = 0;
+        round.observation_count = 0;
         round.arcium_comp_id = None;
         round.result_commitment = None;
         round.bump = ctx.bumps.round;
@@ -73,12 +401,205 @@ pub mod micro_prediction {
         Ok(())
     }
 
+    /// Replaces the round's price sources. Frozen once anyone has staked, like
+    /// the payout rules.
+    #[allow(clippy::too_many_arguments)]
+    pub fn configure_price_sources(
+        ctx: Context<ConfigurePriceSources>,
+        sources: Vec<PriceSource>,
+        min_price_sources: u8,
+        max_price_age_secs: u32,
+        max_price_deviation_bps: u16,
+        max_price_age_slots: u64,
+        min_pull_signatures: u8,
+    ) -> Result<()> {
+        oracle::validate_sources(&sources, min_price_sources)?;
+        require!(max_price_deviation_bps <= 10_000, ErrorCode::InvalidPriceSources);
+
+        let clock = Clock::get()?;
+        let round = &mut ctx.accounts.round;
+        round.require_configurable()?;
+        require!(clock.unix_timestamp < round.end_ts, ErrorCode::RoundClosed);
+
+        round.price_sources = sources;
+        round.min_price_sources = min_price_sources;
+        round.max_price_age_secs = max_price_age_secs;
+        round.max_price_deviation_bps = max_price_deviation_bps;
+        round.max_price_age_slots = max_price_age_slots;
+        round.min_pull_signatures = min_pull_signatures;
+
+        Ok(())
+    }
+
+    /// Switches the round to TWAP resolution over the last `window_secs` before
+    /// `end_ts` and creates the observation ring buffer. Frozen once anyone has
+    /// staked.
+    pub fn configure_twap(ctx: Context<ConfigureTwap>, window_secs: u32) -> Result<()> {
+        let clock = Clock::get()?;
+        let round = &mut ctx.accounts.round;
+        round.require_configurable()?;
+        require!(
+            window_secs as usize >= MIN_PRICE_OBSERVATIONS
+                && (window_secs as i64) <= round.end_ts - round.start_ts,
+            ErrorCode::InvalidTwapWindow
+        );
+        require!(
+            clock.unix_timestamp < round.end_ts - window_secs as i64,
+            ErrorCode::InvalidTwapWindow
+        );
+
+        round.resolution_mode = ResolutionMode::Twap as u8;
+        round.twap_window_secs = window_secs;
+
+        let observations = &mut ctx.accounts.observations;
+        observations.round = round.key();
+        observations.head = 0;
+        observations.len = 0;
+        observations.observations = [PriceObservation::default(); MAX_PRICE_OBSERVATIONS];
+        observations.bump = ctx.bumps.observations;
+
+        Ok(())
+    }
+
+    /// Permissionless crank: records the current aggregated price of the round's
+    /// sources while inside the TWAP window. Observations must be at least
+    /// `window / MAX_PRICE_OBSERVATIONS` apart so a burst cannot dominate it.
+    pub fn record_price_observation(
+        ctx: Context<RecordPriceObservation>,
+        attestations: Vec<PriceAttestation>,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let round = &ctx.accounts.round;
+        require!(
+            round.resolution_mode == ResolutionMode::Twap as u8,
+            ErrorCode::InvalidResolutionMode
+        );
+        require!(round.status == RoundStatus::Open as u8, ErrorCode::RoundNotOpen);
+        require!(
+            clock.unix_timestamp >= round.end_ts - round.twap_window_secs as i64
+                && clock.unix_timestamp <= round.end_ts,
+            ErrorCode::OutsideTwapWindow
+        );
+
+        let observations = &mut ctx.accounts.observations;
+        if let Some(last) = observations.latest() {
+            require!(
+                clock.unix_timestamp > last.timestamp,
+                ErrorCode::DuplicateObservation
+            );
+            require!(
+                clock.unix_timestamp - last.timestamp >= round.min_observation_spacing(),
+                ErrorCode::ObservationTooSoon
+            );
+        }
+
+        let samples = oracle::collect_samples(
+            &round.price_sources,
+            round.round_id,
+            &round.sample_filter(clock.unix_timestamp, clock.slot, false),
+            ctx.remaining_accounts,
+            &attestations,
+            &ctx.accounts.instructions_sysvar,
+        )?;
+        let (price, _) = oracle::aggregate(
+            &samples,
+            round.min_price_sources,
+            round.max_price_deviation_bps,
+        )?;
+
+        observations.push(PriceObservation {
+            price,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Sets the share of the pool paid to each of the closest distinct distances.
+    /// Only allowed before anyone has staked so the rules are fixed up front; an
+    /// empty table keeps winner-take-all.
+    pub fn set_payout_tiers(ctx: Context<ConfigureRound>, payout_tiers: Vec<u16>) -> Result<()> {
+        require!(payout_tiers.len() <= MAX_PAYOUT_TIERS, ErrorCode::InvalidPayoutTiers);
+        let total_bps = payout_tiers.iter().map(|tier| *tier as u32).sum::<u32>();
+        require!(
+            total_bps <= 10_000 && payout_tiers.iter().all(|tier| *tier > 0),
+            ErrorCode::InvalidPayoutTiers
+        );
+
+        let round = &mut ctx.accounts.round;
+        round.require_rules_configurable()?;
+        round.payout_tiers = payout_tiers;
+
+        Ok(())
+    }
+
+    /// Switches the round between closest-price and interval-score settlement.
+    /// Interval rounds are scored by `score_interval_predictions` with
+    /// `interval_alpha_bps` and `max_interval_penalty` as public parameters.
+    pub fn set_round_type(
+        ctx: Context<ConfigureRound>,
+        round_type: u8,
+        interval_alpha_bps: u16,
+        max_interval_penalty: u64,
+    ) -> Result<()> {
+        match RoundType::from_u8(round_type) {
+            Some(RoundType::ClosestPrice) => {}
+            Some(RoundType::IntervalScore) => require!(
+                interval_alpha_bps > 0
+                    && interval_alpha_bps <= 10_000
+                    && max_interval_penalty > 0,
+                ErrorCode::InvalidScoringParams
+            ),
+            None => return err!(ErrorCode::InvalidScoringParams),
+        }
+
+        let round = &mut ctx.accounts.round;
+        round.require_rules_configurable()?;
+        round.round_type = round_type;
+        round.interval_alpha_bps = interval_alpha_bps;
+        round.max_interval_penalty = max_interval_penalty;
+
+        Ok(())
+    }
+
+    /// Early entries get up to `early_bonus_bps` of extra weight, decaying
+    /// linearly from `start_ts` to zero at `end_ts`.
+    pub fn set_early_bonus(ctx: Context<ConfigureRound>, early_bonus_bps: u16) -> Result<()> {
+        require!(early_bonus_bps <= MAX_EARLY_BONUS_BPS, ErrorCode::InvalidEarlyBonus);
+
+        let round = &mut ctx.accounts.round;
+        round.require_rules_configurable()?;
+        round.early_bonus_bps = early_bonus_bps;
+
+        Ok(())
+    }
+
+    /// Sets how ties are broken and the share of the payout pool handed to a
+    /// randomly drawn entry, both decided by randomness from the settlement
+    /// computation.
+    pub fn set_draw_rules(
+        ctx: Context<ConfigureRound>,
+        tie_break: u8,
+        lucky_draw_bps: u16,
+    ) -> Result<()> {
+        require!(TieBreak::from_u8(tie_break).is_some(), ErrorCode::InvalidDrawRules);
+        require!(lucky_draw_bps <= MAX_LUCKY_DRAW_BPS, ErrorCode::InvalidDrawRules);
+
+        let round = &mut ctx.accounts.round;
+        round.require_rules_configurable()?;
+        round.tie_break = tie_break;
+        round.lucky_draw_bps = lucky_draw_bps;
+
+        Ok(())
+    }
+
     pub fn submit_prediction(
         ctx: Context<SubmitPrediction>,
         commitment: [u8; 32],
         window_index: u8,
         stake: u64,
         prediction_index: u16,
+        payload: EncryptedPayload,
     ) -> Result<()> {
         require!(stake > 0, ErrorCode::InvalidStakeAmount);
 
@@ -92,9 +613,6 @@ pub mod micro_prediction {
         require_keys_eq!(config.token_mint, ctx.accounts.user_token_account.mint);
         require_keys_eq!(round.token_mint, config.token_mint);
 
-        let seeds = [ROUND_SEED, &round.round_id.to_le_bytes(), &[round.bump]];
-        let signer_seeds = [&seeds[..]];
-
         let cpi_accounts = Transfer {
             from: ctx.accounts.user_token_account.to_account_info(),
             to: ctx.accounts.escrow_vault.to_account_info(),
@@ -116,12 +634,113 @@ pub mod micro_prediction {
         prediction.owner = ctx.accounts.user.key();
         prediction.token_mint = round.token_mint;
         prediction.commitment = commitment;
+        prediction.payload = payload;
         prediction.stake = stake;
         prediction.window_index = window_index;
         prediction.status = PredictionStatus::Submitted as u8;
         prediction.prediction_index = prediction_index;
+        prediction.weight_bps = round.entry_weight_bps(clock.unix_timestamp);
+        prediction.accumulated = false;
+        prediction.settlement_leaf = None;
         prediction.bump = ctx.bumps.prediction;
 
+        if round.uses_accumulator {
+            round.pending_folds = round
+                .pending_folds
+                .checked_add(1)
+                .ok_or(ErrorCode::NumericalOverflow)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the commitment and encrypted payload and moves the stake to
+    /// `new_stake` while the round is still accepting predictions. The stake
+    /// delta moves to or from escrow and the configured amendment fee goes to the
+    /// treasury.
+    pub fn amend_prediction(
+        ctx: Context<AmendPrediction>,
+        commitment: [u8; 32],
+        payload: EncryptedPayload,
+        new_stake: u64,
+    ) -> Result<()> {
+        require!(new_stake > 0, ErrorCode::InvalidStakeAmount);
+
+        let clock = Clock::get()?;
+        let config = &ctx.accounts.config;
+        let round = &mut ctx.accounts.round;
+        require!(round.status == RoundStatus::Open as u8, ErrorCode::RoundNotOpen);
+        require!(clock.unix_timestamp >= round.start_ts, ErrorCode::RoundNotStarted);
+        require!(clock.unix_timestamp <= round.end_ts, ErrorCode::RoundClosed);
+        require_keys_eq!(config.token_mint, ctx.accounts.user_token_account.mint);
+
+        let prediction = &mut ctx.accounts.prediction;
+        require_keys_eq!(prediction.owner, ctx.accounts.user.key(), ErrorCode::Unauthorized);
+        require!(
+            prediction.status == PredictionStatus::Submitted as u8,
+            ErrorCode::PredictionFinalized
+        );
+        // Folded ciphertexts cannot be swapped out of the accumulator.
+        require!(!prediction.accumulated, ErrorCode::PredictionAlreadyAccumulated);
+
+        if new_stake > prediction.stake {
+            let delta = new_stake - prediction.stake;
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.user_token_account.to_account_info(),
+                to: ctx.accounts.escrow_vault.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+            );
+            token::transfer(cpi_ctx, delta)?;
+
+            round.total_stake = round
+                .total_stake
+                .checked_add(delta)
+                .ok_or(ErrorCode::NumericalOverflow)?;
+        } else if new_stake < prediction.stake {
+            let delta = prediction.stake - new_stake;
+            let seeds = [ROUND_SEED, &round.round_id.to_le_bytes(), &[round.bump]];
+            let signer_seeds = [&seeds[..]];
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.escrow_vault.to_account_info(),
+                to: ctx.accounts.user_token_account.to_account_info(),
+                authority: round.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                &signer_seeds,
+            );
+            token::transfer(cpi_ctx, delta)?;
+
+            round.total_stake = round
+                .total_stake
+                .checked_sub(delta)
+                .ok_or(ErrorCode::NumericalOverflow)?;
+        }
+
+        if config.amendment_fee > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.user_token_account.to_account_info(),
+                to: ctx.accounts.fee_treasury.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+            );
+            token::transfer(cpi_ctx, config.amendment_fee)?;
+        }
+
+        prediction.commitment = commitment;
+        prediction.payload = payload;
+        prediction.stake = new_stake;
+        // An amended entry is weighted as if it were placed now.
+        prediction.weight_bps = round.entry_weight_bps(clock.unix_timestamp);
+
         Ok(())
     }
 
@@ -161,31 +780,372 @@ pub mod micro_prediction {
         prediction.status = PredictionStatus::Cancelled as u8;
         prediction.stake = 0;
 
+        if round.uses_accumulator {
+            // A folded entry has to be folded back out; an unfolded one no
+            // longer needs folding at all.
+            round.pending_folds = if prediction.accumulated {
+                round.pending_folds.checked_add(1)
+            } else {
+                round.pending_folds.checked_sub(1)
+            }
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        }
+
         Ok(())
     }
 
-    pub fn begin_resolution(
-        ctx: Context<BeginResolution>,
-        result_commitment: Option<[u8; 32]>,
-        arcium_comp_id: Option<Pubkey>,
+    /// Switches a round to incremental resolution. `initial_state` is the
+    /// output of the `init_round_accumulator` circuit.
+    ///
+    /// Accumulator rounds are a round type of their own: the winner is the
+    /// bucket whose centre is closest to the final price and its entries split
+    /// the pool by stake. Payout tiers, interval scoring, draws and the early
+    /// bonus cannot be expressed over buckets, so the round must have none of
+    /// them and they stay locked afterwards.
+    pub fn init_round_accumulator(
+        ctx: Context<InitRoundAccumulator>,
+        bucket_origin: u64,
+        bucket_width: u64,
+        initial_state: [[u8; 32]; ACCUMULATOR_STATE_LEN],
+        nonce: u128,
     ) -> Result<()> {
         let round = &mut ctx.accounts.round;
-        require!(round.status == RoundStatus::Open as u8, ErrorCode::RoundNotOpen);
-        round.status = RoundStatus::Resolving as u8;
-        round.result_commitment = result_commitment;
-        round.arcium_comp_id = arcium_comp_id;
+        round.require_configurable()?;
+        require!(
+            bucket_width > 0 && round.uses_default_rules(),
+            ErrorCode::InvalidAccumulator
+        );
+
+        let accumulator = &mut ctx.accounts.accumulator;
+        accumulator.round = round.key();
+        accumulator.state = initial_state;
+        accumulator.nonce = nonce;
+        accumulator.folded_count = 0;
+        accumulator.bucket_origin = bucket_origin;
+        accumulator.bucket_width = bucket_width;
+        accumulator.last_comp_id = None;
+        accumulator.bump = ctx.bumps.accumulator;
+
+        round.uses_accumulator = true;
+
         Ok(())
     }
 
-    pub fn settle_prediction(
-        ctx: Context<SettlePrediction>,
-        payout: u64,
-        commitment: [u8; 32],
+    /// Stores the accumulator re-encrypted by `accumulate_prediction` after
+    /// folding in a submitted prediction, or folding out a cancelled one.
+    pub fn fold_prediction(
+        ctx: Context<FoldPrediction>,
+        new_state: [[u8; 32]; ACCUMULATOR_STATE_LEN],
+        nonce: u128,
+        arcium_comp_id: Option<Pubkey>,
     ) -> Result<()> {
         let round = &mut ctx.accounts.round;
         let prediction = &mut ctx.accounts.prediction;
-        
-        require!(round.status == RoundStatus::Resolving as u8, ErrorCode::RoundNotResolving);
+        let accumulator = &mut ctx.accounts.accumulator;
+        require_keys_eq!(prediction.round, round.key(), ErrorCode::RoundMismatch);
+
+        let adding = prediction.status == PredictionStatus::Submitted as u8;
+        let removing = prediction.status == PredictionStatus::Cancelled as u8;
+        require!(
+            (adding && !prediction.accumulated) || (removing && prediction.accumulated),
+            ErrorCode::NothingToFold
+        );
+        require!(round.status == RoundStatus::Open as u8, ErrorCode::RoundNotOpen);
+
+        accumulator.state = new_state;
+        accumulator.nonce = nonce;
+        accumulator.last_comp_id = arcium_comp_id;
+        accumulator.folded_count = if adding {
+            accumulator.folded_count.checked_add(1)
+        } else {
+            accumulator.folded_count.checked_sub(1)
+        }
+        .ok_or(ErrorCode::NumericalOverflow)?;
+
+        prediction.accumulated = adding;
+        round.pending_folds = round
+            .pending_folds
+            .checked_sub(1)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+
+        Ok(())
+    }
+
+    pub fn begin_resolution(
+        ctx: Context<BeginResolution>,
+        result_commitment: Option<[u8; 32]>,
+        arcium_comp_id: Option<Pubkey>,
+    ) -> Result<()> {
+        let round = &mut ctx.accounts.round;
+        require!(round.status == RoundStatus::Open as u8, ErrorCode::RoundNotOpen);
+        require!(round.pending_folds == 0, ErrorCode::AccumulatorNotCaughtUp);
+        round.status = RoundStatus::Resolving as u8;
+        round.result_commitment = result_commitment;
+        round.arcium_comp_id = arcium_comp_id;
+        Ok(())
+    }
+
+    /// Records the public part of a settlement computation: the Merkle root over
+    /// the blinded payout leaves, the total it pays out and the fees it kept
+    /// back for the house. Individual payouts stay encrypted to their owners
+    /// until claimed. Accumulator rounds commit the `net_pool` and `fee_total`
+    /// revealed by `resolve_accumulator` and prove each claim against the
+    /// entry's recorded leaf instead of the root. A round with draws publishes
+    /// its seed with `seed_proof`, the proof of `draw::seed_leaf` under
+    /// `result_commitment`.
+    pub fn commit_settlement(
+        ctx: Context<CommitSettlement>,
+        result_commitment: [u8; 32],
+        total_payout: u64,
+        fee_total: u64,
+        randomness_seed: Option<[u8; 32]>,
+        seed_proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let round = &mut ctx.accounts.round;
+        require!(round.status == RoundStatus::Resolving as u8, ErrorCode::RoundNotResolving);
+        require!(round.chunk_count == 0, ErrorCode::ChunkedResolutionActive);
+        require!(
+            round.result_commitment.is_none(),
+            ErrorCode::SettlementAlreadyCommitted
+        );
+        let pool = round.payout_pool().ok_or(ErrorCode::NumericalOverflow)?;
+        let committed = total_payout
+            .checked_add(fee_total)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        require!(committed <= pool, ErrorCode::InsufficientEscrow);
+        require!(
+            randomness_seed.is_some() || !round.uses_draws(),
+            ErrorCode::MissingRandomnessSeed
+        );
+        if let Some(seed) = &randomness_seed {
+            require!(
+                merkle::verify(&seed_proof, &result_commitment, draw::seed_leaf(seed)),
+                ErrorCode::InvalidRandomnessSeed
+            );
+        }
+
+        round.result_commitment = Some(result_commitment);
+        round.committed_payout = total_payout;
+        round.committed_fees = fee_total;
+        round.randomness_seed = randomness_seed;
+
+        Ok(())
+    }
+
+    /// Starts chunked resolution for a round too large for one
+    /// `determine_winners` call. The relayer splits the predictions into
+    /// `chunk_count` chunks, scans them in order (`record_chunk_scan`) and then
+    /// settles them in any order (`record_chunk_settlement`).
+    pub fn begin_chunked_resolution(
+        ctx: Context<BeginChunkedResolution>,
+        chunk_count: u16,
+    ) -> Result<()> {
+        let round = &mut ctx.accounts.round;
+        require!(round.status == RoundStatus::Resolving as u8, ErrorCode::RoundNotResolving);
+        require!(
+            round.result_commitment.is_none(),
+            ErrorCode::SettlementAlreadyCommitted
+        );
+        require!(
+            chunk_count > 0 && chunk_count as usize <= MAX_RESOLUTION_CHUNKS,
+            ErrorCode::InvalidChunkLayout
+        );
+        // `settle_chunk` only implements plain winner-take-all.
+        require!(
+            !round.uses_accumulator && round.uses_default_rules(),
+            ErrorCode::InvalidChunkLayout
+        );
+
+        let chunks = &mut ctx.accounts.resolution_chunks;
+        chunks.round = round.key();
+        chunks.scan_state = [[0u8; 32]; SCAN_STATE_LEN];
+        chunks.scan_nonce = 0;
+        chunks.settled = [0u8; MAX_RESOLUTION_CHUNKS / 8];
+        chunks.chunk_roots = [[0u8; 32]; MAX_RESOLUTION_CHUNKS];
+        chunks.settled_entries = 0;
+        chunks.scanned_entries = 0;
+        chunks.scanned_stake = 0;
+        chunks.last_comp_id = None;
+        chunks.bump = ctx.bumps.resolution_chunks;
+
+        round.chunk_count = chunk_count;
+        round.chunks_scanned = 0;
+        round.chunks_settled = 0;
+        round.committed_payout = 0;
+        round.committed_fees = 0;
+
+        Ok(())
+    }
+
+    /// Phase one: stores the running `ScanState` after `scan_chunk` folded in
+    /// chunk `chunk_index`, together with the chunk's entry count and stake.
+    /// Chunks must be scanned in order, and the last one must bring the
+    /// scanned stake up to the round's total.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_chunk_scan(
+        ctx: Context<RecordChunkResolution>,
+        chunk_index: u16,
+        scan_state: [[u8; 32]; SCAN_STATE_LEN],
+        nonce: u128,
+        chunk_entries: u32,
+        chunk_stake: u64,
+        arcium_comp_id: Option<Pubkey>,
+    ) -> Result<()> {
+        let round = &mut ctx.accounts.round;
+        require!(round.status == RoundStatus::Resolving as u8, ErrorCode::RoundNotResolving);
+        require!(
+            chunk_index == round.chunks_scanned && chunk_index < round.chunk_count,
+            ErrorCode::ChunkOutOfOrder
+        );
+        require!(
+            chunk_entries > 0 && chunk_entries <= RESOLUTION_CHUNK_SIZE,
+            ErrorCode::InvalidChunkLayout
+        );
+
+        let chunks = &mut ctx.accounts.resolution_chunks;
+        let scanned_entries = chunks
+            .scanned_entries
+            .checked_add(chunk_entries)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        let scanned_stake = chunks
+            .scanned_stake
+            .checked_add(chunk_stake)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        require!(scanned_stake <= round.total_stake, ErrorCode::InvalidChunkLayout);
+        // Phase one must cover every staked entry.
+        if chunk_index + 1 == round.chunk_count {
+            require!(scanned_stake == round.total_stake, ErrorCode::ScanIncomplete);
+        }
+
+        chunks.scan_state = scan_state;
+        chunks.scan_nonce = nonce;
+        chunks.scanned_entries = scanned_entries;
+        chunks.scanned_stake = scanned_stake;
+        chunks.last_comp_id = arcium_comp_id;
+        round.chunks_scanned += 1;
+
+        Ok(())
+    }
+
+    /// Phase two: records the payout root and fees of one chunk from
+    /// `settle_chunk`. `chunk_entries` is the number of leaves the chunk
+    /// revealed; `scanned_entries` and `scanned_stake` are the scan totals the
+    /// circuit settled against and must match the ones phase one recorded.
+    /// Together they make sure no chunk was oversized and every scanned entry
+    /// got a leaf. Once every chunk has landed, the root over the chunk roots becomes
+    /// the round's `result_commitment` and claims can start.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_chunk_settlement(
+        ctx: Context<RecordChunkResolution>,
+        chunk_index: u16,
+        chunk_root: [u8; 32],
+        chunk_payout: u64,
+        chunk_fees: u64,
+        chunk_entries: u32,
+        scanned_entries: u32,
+        scanned_stake: u64,
+    ) -> Result<()> {
+        let round = &mut ctx.accounts.round;
+        require!(round.status == RoundStatus::Resolving as u8, ErrorCode::RoundNotResolving);
+        require!(round.chunk_count > 0, ErrorCode::InvalidChunkLayout);
+        require!(round.chunks_scanned == round.chunk_count, ErrorCode::ScanIncomplete);
+        require!(
+            chunk_index < round.chunk_count
+                && chunk_entries > 0
+                && chunk_entries <= RESOLUTION_CHUNK_SIZE,
+            ErrorCode::InvalidChunkLayout
+        );
+
+        let chunks = &mut ctx.accounts.resolution_chunks;
+        require!(
+            scanned_entries == chunks.scanned_entries && scanned_stake == chunks.scanned_stake,
+            ErrorCode::ScanMismatch
+        );
+        require!(!chunks.is_settled(chunk_index), ErrorCode::ChunkAlreadyRecorded);
+
+        let committed_payout = round
+            .committed_payout
+            .checked_add(chunk_payout)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        let committed_fees = round
+            .committed_fees
+            .checked_add(chunk_fees)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        let pool = round.payout_pool().ok_or(ErrorCode::NumericalOverflow)?;
+        require!(
+            committed_payout
+                .checked_add(committed_fees)
+                .is_some_and(|committed| committed <= pool),
+            ErrorCode::InsufficientEscrow
+        );
+
+        let settled_entries = chunks
+            .settled_entries
+            .checked_add(chunk_entries)
+            .ok_or(ErrorCode::NumericalOverflow)?;
+        require!(settled_entries <= chunks.scanned_entries, ErrorCode::InvalidChunkLayout);
+
+        chunks.mark_settled(chunk_index);
+        chunks.chunk_roots[chunk_index as usize] = chunk_root;
+        chunks.settled_entries = settled_entries;
+        round.committed_payout = committed_payout;
+        round.committed_fees = committed_fees;
+        round.chunks_settled += 1;
+
+        if round.chunks_settled == round.chunk_count {
+            require!(settled_entries == chunks.scanned_entries, ErrorCode::InvalidChunkLayout);
+            round.result_commitment = Some(merkle::root(
+                &chunks.chunk_roots[..round.chunk_count as usize],
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Records the leaf revealed by `settle_accumulated_entry` for a prediction
+    /// in an accumulator round. Takes the place of the Merkle proof at claim
+    /// time.
+    pub fn record_entry_settlement(
+        ctx: Context<RecordEntrySettlement>,
+        leaf: [u8; 32],
+    ) -> Result<()> {
+        let round = &ctx.accounts.round;
+        let prediction = &mut ctx.accounts.prediction;
+        require!(round.status == RoundStatus::Resolving as u8, ErrorCode::RoundNotResolving);
+        require!(round.uses_accumulator, ErrorCode::InvalidAccumulator);
+        require_keys_eq!(prediction.round, round.key(), ErrorCode::RoundMismatch);
+        require!(prediction.accumulated, ErrorCode::NothingToFold);
+        require!(
+            prediction.settlement_leaf.is_none(),
+            ErrorCode::SettlementAlreadyCommitted
+        );
+
+        prediction.settlement_leaf = Some(leaf);
+
+        Ok(())
+    }
+
+    /// Pays a prediction the amount its owner decrypted from the settlement
+    /// output. The payout is only trusted once its blinded leaf is proven to be
+    /// part of the committed result. Claims stay open after finalization.
+    pub fn settle_prediction(
+        ctx: Context<SettlePrediction>,
+        payout: u64,
+        commitment: [u8; 32],
+        blinding: [u8; 32],
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let round = &mut ctx.accounts.round;
+        let prediction = &mut ctx.accounts.prediction;
+
+        require!(
+            round.status == RoundStatus::Resolving as u8
+                || round.status == RoundStatus::Finalized as u8,
+            ErrorCode::RoundNotResolving
+        );
+        let result_commitment = round
+            .result_commitment
+            .ok_or(ErrorCode::SettlementNotCommitted)?;
         require_keys_eq!(prediction.round, round.key(), ErrorCode::RoundMismatch);
         require!(
             prediction.status == PredictionStatus::Submitted as u8,
@@ -195,10 +1155,16 @@ pub mod micro_prediction {
             prediction.commitment == commitment,
             ErrorCode::CommitmentMismatch
         );
+        let leaf = merkle::payout_leaf(&prediction.key().to_bytes(), payout, &blinding);
+        let proven = match prediction.settlement_leaf {
+            Some(recorded) => recorded == leaf,
+            None => merkle::verify(&proof, &result_commitment, leaf),
+        };
+        require!(proven, ErrorCode::InvalidPayoutProof);
 
         // Verify sufficient funds
         let available = round
-            .total_stake
+            .committed_payout
             .checked_sub(round.total_paid)
             .ok_or(ErrorCode::NumericalOverflow)?;
         require!(payout <= available, ErrorCode::InsufficientEscrow);
@@ -231,14 +1197,14 @@ pub mod micro_prediction {
         Ok(())
     }
 
+    /// Returns the stake of an entry in a refunded round. Open rounds use
+    /// `cancel_prediction` instead, which also folds the entry back out of the
+    /// accumulator.
     pub fn refund_prediction(ctx: Context<RefundPrediction>) -> Result<()> {
-        let round = &ctx.accounts.round;
+        let round = &mut ctx.accounts.round;
         let prediction = &mut ctx.accounts.prediction;
         
-        require!(
-            round.status == RoundStatus::Refunded as u8 || round.status == RoundStatus::Open as u8,
-            ErrorCode::InvalidRoundState
-        );
+        require!(round.status == RoundStatus::Refunded as u8, ErrorCode::InvalidRoundState);
         require!(
             prediction.status == PredictionStatus::Submitted as u8
                 || prediction.status == PredictionStatus::Cancelled as u8,
@@ -261,6 +1227,12 @@ pub mod micro_prediction {
                 &signer_seeds,
             );
             token::transfer(cpi_ctx, amount)?;
+
+            // Keeps the escrow equal to `unswept_balance` like a cancellation.
+            round.total_stake = round
+                .total_stake
+                .checked_sub(amount)
+                .ok_or(ErrorCode::NumericalOverflow)?;
         }
 
         prediction.status = PredictionStatus::Refunded as u8;
@@ -270,10 +1242,107 @@ pub mod micro_prediction {
         Ok(())
     }
 
+    /// Permissionless: reads every configured price source (Pyth accounts are
+    /// passed as remaining accounts) and records the aggregated price. Every
+    /// source must be supplied; only prices published at or before `end_ts`
+    /// count. Pull sources also pass the first update published after `end_ts`
+    /// to prove theirs is the latest one before it.
+    pub fn resolve_round_price(
+        ctx: Context<ResolveRoundPrice>,
+        attestations: Vec<PriceAttestation>,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let round = &mut ctx.accounts.round;
+        require!(
+            round.status == RoundStatus::Open as u8
+                || round.status == RoundStatus::Resolving as u8,
+            ErrorCode::InvalidRoundState
+        );
+        require!(clock.unix_timestamp >= round.end_ts, ErrorCode::RoundNotEnded);
+        require!(round.final_price.is_none(), ErrorCode::PriceAlreadyResolved);
+
+        if round.resolution_mode == ResolutionMode::Twap as u8 {
+            let observations = ctx
+                .accounts
+                .observations
+                .as_ref()
+                .ok_or(ErrorCode::NoPriceObservations)?;
+            let ordered = observations.ordered();
+            require!(
+                ordered.len() >= MIN_PRICE_OBSERVATIONS,
+                ErrorCode::NoPriceObservations
+            );
+            round.final_price = Some(oracle::time_weighted_average(&ordered, round.end_ts)?);
+            round.observation_count = ordered.len() as u16;
+            return Ok(());
+        }
+
+        let samples = oracle::collect_samples(
+            &round.price_sources,
+            round.round_id,
+            &round.sample_filter(round.end_ts, clock.slot, true),
+            ctx.remaining_accounts,
+            &attestations,
+            &ctx.accounts.instructions_sysvar,
+        )?;
+        let (price, used_mask) = oracle::aggregate(
+            &samples,
+            round.min_price_sources,
+            round.max_price_deviation_bps,
+        )?;
+
+        round.final_price = Some(price);
+        round.price_sources_used = used_mask;
+
+        Ok(())
+    }
+
+    /// Stores the aggregate statistics revealed by `reveal_round_sentiment`. Only
+    /// accepted once the round is locked so no new entry can be steered by them.
+    pub fn record_round_stats(
+        ctx: Context<RecordRoundStats>,
+        stats: RoundStatsInput,
+        arcium_comp_id: Option<Pubkey>,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let round = &ctx.accounts.round;
+        require!(clock.unix_timestamp > round.end_ts, ErrorCode::RoundNotEnded);
+        require!(stats.bucket_width > 0, ErrorCode::InvalidRoundStats);
+        require!(
+            stats.bucket_width as u128 * 10_000
+                >= stats.weighted_mean_price as u128 * MIN_SENTIMENT_BUCKET_BPS as u128,
+            ErrorCode::InvalidRoundStats
+        );
+        require!(stats.median_is_bucket_centre(), ErrorCode::InvalidRoundStats);
+
+        let round_stats = &mut ctx.accounts.round_stats;
+        round_stats.round = round.key();
+        round_stats.participant_count = stats.participant_count;
+        round_stats.total_stake = stats.total_stake;
+        round_stats.weighted_mean_price = stats.weighted_mean_price;
+        round_stats.median_price = stats.median_price;
+        round_stats.bucket_origin = stats.bucket_origin;
+        round_stats.bucket_width = stats.bucket_width;
+        round_stats.histogram = stats.histogram;
+        round_stats.arcium_comp_id = arcium_comp_id;
+        round_stats.recorded_ts = clock.unix_timestamp;
+        round_stats.bump = ctx.bumps.round_stats;
+
+        Ok(())
+    }
+
     pub fn finalize_round(ctx: Context<FinalizeRound>, final_price: i64, timestamp: i64) -> Result<()> {
         let round = &mut ctx.accounts.round;
         require!(round.status == RoundStatus::Resolving as u8, ErrorCode::RoundNotResolving);
-        
+        require!(
+            round.chunks_settled == round.chunk_count,
+            ErrorCode::ChunksOutstanding
+        );
+        // A price resolved from the oracles takes precedence over the relayer's.
+        if let Some(resolved_price) = round.final_price {
+            require!(resolved_price == final_price, ErrorCode::FinalPriceMismatch);
+        }
+
         round.status = RoundStatus::Finalized as u8;
         round.final_price = Some(final_price);
         round.settlement_timestamp = Some(timestamp);
@@ -284,12 +1353,39 @@ pub mod micro_prediction {
     pub fn mark_round_refunded(ctx: Context<MarkRoundRefunded>) -> Result<()> {
         let round = &mut ctx.accounts.round;
         require!(round.status != RoundStatus::Finalized as u8, ErrorCode::RoundAlreadySettled);
+        // Claims are paid as soon as a result is committed, partly out of
+        // other entrants' stakes, so after one the escrow no longer covers
+        // every stake.
+        require!(round.total_paid == 0, ErrorCode::ClaimsAlreadyPaid);
         round.status = RoundStatus::Refunded as u8;
 
         Ok(())
     }
 }
 
+/// Tops `account` up to the rent-exempt minimum for `space` out of `payer`
+/// and resizes it, zero-filling the new bytes.
+fn grow_account<'info>(
+    payer: &Signer<'info>,
+    account: &UncheckedAccount<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let top_up = rent.saturating_sub(account.lamports());
+    if top_up > 0 {
+        let cpi_accounts = system_program::Transfer {
+            from: payer.to_account_info(),
+            to: account.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(system_program.to_account_info(), cpi_accounts);
+        system_program::transfer(cpi_ctx, top_up)?;
+    }
+    account.resize(space)?;
+
+    Ok(())
+}
+
 #[derive(Accounts)]
 pub struct Initialize<'info> {
     #[account(mut)]
@@ -308,6 +1404,150 @@ pub struct Initialize<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// CHECK: an old-layout config cannot be deserialized; the handler checks
+    /// the discriminator and authority by hand.
+    #[account(mut, seeds = [CONFIG_SEED], bump, owner = crate::ID)]
+    pub config: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(round_id: u64)]
+pub struct MigrateRound<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: an old-layout round cannot be deserialized; the handler checks
+    /// the discriminator by hand.
+    #[account(mut, seeds = [ROUND_SEED, &round_id.to_le_bytes()], bump, owner = crate::ID)]
+    pub round: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePrediction<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: an old-layout prediction cannot be deserialized; the handler
+    /// checks the discriminator by hand.
+    #[account(mut, owner = crate::ID)]
+    pub prediction: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitRolloverVault<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(constraint = token_mint.key() == config.token_mint)]
+    pub token_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [ROLLOVER_SEED],
+        bump,
+        token::mint = token_mint,
+        token::authority = config,
+    )]
+    pub rollover_vault: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TopUpRollover<'info> {
+    pub funder: Signer<'info>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub funder_token_account: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [ROLLOVER_SEED], bump = config.rollover_bump)]
+    pub rollover_vault: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SeedRoundFromRollover<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+    #[account(mut, seeds = [ROLLOVER_SEED], bump = config.rollover_bump)]
+    pub rollover_vault: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [ESCROW_SEED, &round.round_id.to_le_bytes()], bump = round.escrow_bump)]
+    pub escrow_vault: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SweepRoundToRollover<'info> {
+    #[account(mut, seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+    #[account(mut, seeds = [ESCROW_SEED, &round.round_id.to_le_bytes()], bump = round.escrow_bump)]
+    pub escrow_vault: Account<'info, TokenAccount>,
+    #[account(mut, seeds = [ROLLOVER_SEED], bump = config.rollover_bump)]
+    pub rollover_vault: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [ROLLOVER_SEED], bump = config.rollover_bump)]
+    pub rollover_vault: Account<'info, TokenAccount>,
+    #[account(mut, address = config.fee_treasury)]
+    pub fee_treasury: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
 #[derive(Accounts)]
 #[instruction(round_id: u64, start_ts: i64, end_ts: i64, pyth_price_account: Pubkey)]
 pub struct InitializeRound<'info> {
@@ -343,7 +1583,71 @@ pub struct InitializeRound<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(round_id: u64, prediction_index: u16)]
+pub struct ConfigurePriceSources<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureRound<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureTwap<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [OBSERVATIONS_SEED, &round.round_id.to_le_bytes()],
+        bump,
+        space = PriceObservations::SPACE,
+    )]
+    pub observations: Box<Account<'info, PriceObservations>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RecordPriceObservation<'info> {
+    #[account(seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+    #[account(
+        mut,
+        seeds = [OBSERVATIONS_SEED, &round.round_id.to_le_bytes()],
+        bump = observations.bump,
+    )]
+    pub observations: Box<Account<'info, PriceObservations>>,
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = ix_sysvar::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_commitment: [u8; 32], _window_index: u8, _stake: u64, prediction_index: u16)]
 pub struct SubmitPrediction<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
@@ -351,37 +1655,71 @@ pub struct SubmitPrediction<'info> {
     pub config: Account<'info, Config>,
     #[account(
         mut,
-        seeds = [ROUND_SEED, &round_id.to_le_bytes()],
+        seeds = [ROUND_SEED, &round.round_id.to_le_bytes()],
+        bump = round.bump,
+    )]
+    pub round: Account<'info, Round>,
+    #[account(
+        init,
+        payer = user,
+        seeds = [
+            PREDICTION_SEED,
+            &round.round_id.to_le_bytes(),
+            user.key().as_ref(),
+            &prediction_index.to_le_bytes(),
+        ],
+        bump,
+        space = Prediction::SPACE,
+    )]
+    pub prediction: Account<'info, Prediction>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [ESCROW_SEED, &round.round_id.to_le_bytes()],
+        bump = round.escrow_bump,
+    )]
+    pub escrow_vault: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AmendPrediction<'info> {
+    pub user: Signer<'info>,
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [ROUND_SEED, &round.round_id.to_le_bytes()],
         bump = round.bump,
     )]
     pub round: Account<'info, Round>,
     #[account(
-        init,
-        payer = user,
+        mut,
         seeds = [
             PREDICTION_SEED,
-            &round_id.to_le_bytes(),
+            &round.round_id.to_le_bytes(),
             user.key().as_ref(),
-            &prediction_index.to_le_bytes(),
+            &prediction.prediction_index.to_le_bytes(),
         ],
-        bump,
-        space = Prediction::SPACE,
+        bump = prediction.bump,
     )]
     pub prediction: Account<'info, Prediction>,
     #[account(mut)]
     pub user_token_account: Account<'info, TokenAccount>,
     #[account(
         mut,
-        seeds = [ESCROW_SEED, &round_id.to_le_bytes()],
+        seeds = [ESCROW_SEED, &round.round_id.to_le_bytes()],
         bump = round.escrow_bump,
     )]
     pub escrow_vault: Account<'info, TokenAccount>,
+    #[account(mut, address = config.fee_treasury)]
+    pub fee_treasury: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(round_id: u64, prediction_index: u16)]
 pub struct CancelPrediction<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
@@ -389,7 +1727,7 @@ pub struct CancelPrediction<'info> {
     pub config: Account<'info, Config>,
     #[account(
         mut,
-        seeds = [ROUND_SEED, &round_id.to_le_bytes()],
+        seeds = [ROUND_SEED, &round.round_id.to_le_bytes()],
         bump = round.bump,
     )]
     pub round: Account<'info, Round>,
@@ -397,9 +1735,9 @@ pub struct CancelPrediction<'info> {
         mut,
         seeds = [
             PREDICTION_SEED,
-            &round_id.to_le_bytes(),
+            &round.round_id.to_le_bytes(),
             user.key().as_ref(),
-            &prediction_index.to_le_bytes(),
+            &prediction.prediction_index.to_le_bytes(),
         ],
         bump = prediction.bump,
     )]
@@ -408,13 +1746,114 @@ pub struct CancelPrediction<'info> {
     pub user_token_account: Account<'info, TokenAccount>,
     #[account(
         mut,
-        seeds = [ESCROW_SEED, &round_id.to_le_bytes()],
+        seeds = [ESCROW_SEED, &round.round_id.to_le_bytes()],
         bump = round.escrow_bump,
     )]
     pub escrow_vault: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+pub struct InitRoundAccumulator<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [ACCUMULATOR_SEED, &round.round_id.to_le_bytes()],
+        bump,
+        space = RoundAccumulator::SPACE,
+    )]
+    pub accumulator: Box<Account<'info, RoundAccumulator>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FoldPrediction<'info> {
+    pub settlement_authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        constraint = settlement_authority.key() == config.settlement_authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+    #[account(
+        mut,
+        seeds = [ACCUMULATOR_SEED, &round.round_id.to_le_bytes()],
+        bump = accumulator.bump,
+    )]
+    pub accumulator: Box<Account<'info, RoundAccumulator>>,
+    #[account(mut)]
+    pub prediction: Account<'info, Prediction>,
+}
+
+#[derive(Accounts)]
+pub struct BeginChunkedResolution<'info> {
+    #[account(mut)]
+    pub settlement_authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        constraint = settlement_authority.key() == config.settlement_authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+    #[account(
+        init,
+        payer = settlement_authority,
+        seeds = [RESOLUTION_CHUNKS_SEED, &round.round_id.to_le_bytes()],
+        bump,
+        space = ResolutionChunks::SPACE,
+    )]
+    pub resolution_chunks: Box<Account<'info, ResolutionChunks>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RecordChunkResolution<'info> {
+    pub settlement_authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        constraint = settlement_authority.key() == config.settlement_authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+    #[account(
+        mut,
+        seeds = [RESOLUTION_CHUNKS_SEED, &round.round_id.to_le_bytes()],
+        bump = resolution_chunks.bump,
+    )]
+    pub resolution_chunks: Box<Account<'info, ResolutionChunks>>,
+}
+
+#[derive(Accounts)]
+pub struct RecordEntrySettlement<'info> {
+    pub settlement_authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        constraint = settlement_authority.key() == config.settlement_authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+    #[account(mut)]
+    pub prediction: Account<'info, Prediction>,
+}
+
 #[derive(Accounts)]
 pub struct BeginResolution<'info> {
     #[account(mut)]
@@ -429,6 +1868,19 @@ pub struct BeginResolution<'info> {
     pub round: Account<'info, Round>,
 }
 
+#[derive(Accounts)]
+pub struct CommitSettlement<'info> {
+    pub settlement_authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        constraint = settlement_authority.key() == config.settlement_authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+}
+
 #[derive(Accounts)]
 pub struct SettlePrediction<'info> {
     #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
@@ -437,24 +1889,66 @@ pub struct SettlePrediction<'info> {
     pub prediction: Account<'info, Prediction>,
     #[account(mut, seeds = [ESCROW_SEED, &round.round_id.to_le_bytes()], bump = round.escrow_bump)]
     pub escrow_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = recipient_token_account.owner == prediction.owner @ ErrorCode::Unauthorized,
+        constraint = recipient_token_account.mint == round.token_mint,
+    )]
     pub recipient_token_account: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
 pub struct RefundPrediction<'info> {
-    #[account(seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
     pub round: Account<'info, Round>,
     #[account(mut)]
     pub prediction: Account<'info, Prediction>,
     #[account(mut, seeds = [ESCROW_SEED, &round.round_id.to_le_bytes()], bump = round.escrow_bump)]
     pub escrow_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = user_token_account.owner == prediction.owner @ ErrorCode::Unauthorized,
+        constraint = user_token_account.mint == round.token_mint,
+    )]
     pub user_token_account: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+pub struct ResolveRoundPrice<'info> {
+    #[account(mut, seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+    #[account(seeds = [OBSERVATIONS_SEED, &round.round_id.to_le_bytes()], bump = observations.bump)]
+    pub observations: Option<Box<Account<'info, PriceObservations>>>,
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = ix_sysvar::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RecordRoundStats<'info> {
+    #[account(mut)]
+    pub settlement_authority: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        constraint = settlement_authority.key() == config.settlement_authority @ ErrorCode::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(seeds = [ROUND_SEED, &round.round_id.to_le_bytes()], bump = round.bump)]
+    pub round: Account<'info, Round>,
+    #[account(
+        init,
+        payer = settlement_authority,
+        seeds = [ROUND_STATS_SEED, &round.round_id.to_le_bytes()],
+        bump,
+        space = RoundStats::SPACE,
+    )]
+    pub round_stats: Account<'info, RoundStats>,
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct FinalizeRound<'info> {
     pub settlement_authority: Signer<'info>,
@@ -491,16 +1985,35 @@ pub struct Config {
     pub fee_treasury: Pubkey,
     pub fee_bps: u16,
     pub bump: u8,
+    // Fields below were added after the first deployment; `migrate_config`
+    // grows older accounts to fit them.
+    /// Flat fee charged to the treasury on every `amend_prediction`.
+    pub amendment_fee: u64,
+    /// Share of every round's stakes reserved for the rollover vault.
+    pub house_edge_bps: u16,
+    pub rollover_vault: Pubkey,
+    pub rollover_bump: u8,
+    /// House edge and settlement fees swept into the rollover vault and not
+    /// collected yet; the most `collect_fees` may take out.
+    pub accrued_fees: u64,
 }
 
 impl Config {
-    pub const SPACE: usize = 8  // discriminator
+    /// Size of a config created before `migrate_config` existed.
+    pub const LEGACY_SPACE: usize = 8  // discriminator
         + 32 // authority
         + 32 // settlement_authority
         + 32 // token_mint
         + 32 // fee_treasury
         + 2 // fee_bps
         + 1; // bump
+
+    pub const SPACE: usize = Self::LEGACY_SPACE
+        + 8 // amendment_fee
+        + 2 // house_edge_bps
+        + 32 // rollover_vault
+        + 1 // rollover_bump
+        + 8; // accrued_fees
 }
 
 #[account]
@@ -520,10 +2033,55 @@ pub struct Round {
     pub result_commitment: Option<[u8; 32]>,
     pub bump: u8,
     pub escrow_bump: u8,
+    // Fields below are appended to the original layout, see `migrate_round`.
+    /// Total payout of the committed settlement result.
+    pub committed_payout: u64,
+    /// Fees the committed settlement result kept back for the house.
+    pub committed_fees: u64,
+    /// Amount seeded into this round from the rollover vault.
+    pub carried_in: u64,
+    /// Amount swept from this round into the rollover vault.
+    pub rolled_over: u64,
+    pub house_edge_bps: u16,
+    /// Basis points of the payout pool for each closest distinct distance, passed
+    /// to `determine_winners` as a public parameter. Empty means winner-take-all.
+    pub payout_tiers: Vec<u16>,
+    pub round_type: u8,
+    pub interval_alpha_bps: u16,
+    pub max_interval_penalty: u64,
+    pub early_bonus_bps: u16,
+    pub tie_break: u8,
+    pub lucky_draw_bps: u16,
+    /// Seed generated inside the settlement computation that every draw of
+    /// the round derives from, see `draw::draw_index`.
+    pub randomness_seed: Option<[u8; 32]>,
+    pub price_sources: Vec<PriceSource>,
+    pub min_price_sources: u8,
+    pub max_price_age_secs: u32,
+    pub max_price_deviation_bps: u16,
+    pub max_price_age_slots: u64,
+    pub min_pull_signatures: u8,
+    /// Bitmask over `price_sources` of the sources that fed `final_price`.
+    pub price_sources_used: u8,
+    pub resolution_mode: u8,
+    pub twap_window_secs: u32,
+    /// Number of observations the TWAP `final_price` was computed from.
+    pub observation_count: u16,
+    /// Whether predictions are folded into a `RoundAccumulator` as they arrive.
+    /// Such rounds resolve to the closest bucket centre instead of running
+    /// `determine_winners`, see `init_round_accumulator`.
+    pub uses_accumulator: bool,
+    /// Submissions and cancellations not yet reflected in the accumulator.
+    pub pending_folds: u32,
+    /// Zero unless the round is resolved in chunks.
+    pub chunk_count: u16,
+    pub chunks_scanned: u16,
+    pub chunks_settled: u16,
 }
 
 impl Round {
-    pub const SPACE: usize = 8  // discriminator
+    /// Size of a round created before `migrate_round` existed.
+    pub const LEGACY_SPACE: usize = 8  // discriminator
         + 8  // round_id
         + 8  // start_ts
         + 8  // end_ts
@@ -539,6 +2097,311 @@ impl Round {
         + (1 + 32) // result commitment option
         + 1 // bump
         + 1; // escrow bump
+
+    pub const SPACE: usize = Self::LEGACY_SPACE
+        + 8  // committed_payout
+        + 8  // committed_fees
+        + 8  // carried_in
+        + 8  // rolled_over
+        + 2  // house_edge_bps
+        + (4 + MAX_PAYOUT_TIERS * 2) // payout tiers
+        + 1  // round type
+        + 2  // interval alpha bps
+        + 8  // max interval penalty
+        + 2  // early bonus bps
+        + 1  // tie break
+        + 2  // lucky draw bps
+        + (1 + 32) // randomness seed option
+        + (4 + MAX_PRICE_SOURCES * PriceSource::SIZE) // price sources
+        + 1  // min price sources
+        + 4  // max price age secs
+        + 2  // max price deviation bps
+        + 8  // max price age slots
+        + 1  // min pull signatures
+        + 1  // price sources used
+        + 1  // resolution mode
+        + 4  // twap window secs
+        + 2  // observation count
+        + 1  // uses accumulator
+        + 4  // pending folds
+        + 2  // chunk count
+        + 2  // chunks scanned
+        + 2; // chunks settled
+
+    /// Payout rules and price sources may only change while the round is open
+    /// and unstaked.
+    pub fn require_configurable(&self) -> Result<()> {
+        require!(self.status == RoundStatus::Open as u8, ErrorCode::RoundNotOpen);
+        require!(self.total_stake == 0, ErrorCode::RoundAlreadyStaked);
+        Ok(())
+    }
+
+    /// Like `require_configurable`, and additionally rejects accumulator
+    /// rounds, whose bucket resolution has no notion of custom payout rules.
+    pub fn require_rules_configurable(&self) -> Result<()> {
+        self.require_configurable()?;
+        require!(!self.uses_accumulator, ErrorCode::InvalidAccumulator);
+        Ok(())
+    }
+
+    /// Plain closest-price winner-take-all: no tiers, interval scoring, draws
+    /// or early bonus.
+    pub fn uses_default_rules(&self) -> bool {
+        self.payout_tiers.is_empty()
+            && self.round_type == RoundType::ClosestPrice as u8
+            && !self.uses_draws()
+            && self.early_bonus_bps == 0
+    }
+
+    pub fn uses_draws(&self) -> bool {
+        self.tie_break == TieBreak::Random as u8 || self.lucky_draw_bps > 0
+    }
+
+    /// Settlement weight of an entry placed at `now`: the full bonus at
+    /// `start_ts`, decaying linearly to none at `end_ts`.
+    pub fn entry_weight_bps(&self, now: i64) -> u16 {
+        settlement::entry_weight_bps(self.start_ts, self.end_ts, now, self.early_bonus_bps)
+    }
+
+    pub fn house_edge(&self) -> Option<u64> {
+        settlement::house_edge(self.total_stake, self.house_edge_bps)
+    }
+
+    /// House edge plus the fees of the committed settlement.
+    pub fn house_revenue(&self) -> Option<u64> {
+        self.house_edge()?.checked_add(self.committed_fees)
+    }
+
+    /// Stakes plus carried-in funds minus the house edge reserved for rollover.
+    pub fn payout_pool(&self) -> Option<u64> {
+        settlement::payout_pool(self.total_stake, self.carried_in, self.house_edge_bps)
+    }
+
+    /// Escrow balance not yet paid out or swept.
+    pub fn unswept_balance(&self) -> Option<u64> {
+        self.total_stake
+            .checked_add(self.carried_in)?
+            .checked_sub(self.total_paid)?
+            .checked_sub(self.rolled_over)
+    }
+
+    /// Minimum seconds between two TWAP observations.
+    pub fn min_observation_spacing(&self) -> i64 {
+        (self.twap_window_secs as i64 / MAX_PRICE_OBSERVATIONS as i64).max(1)
+    }
+
+    /// Unswept balance less the committed payouts still waiting to be claimed.
+    pub fn unreserved_balance(&self) -> Option<u64> {
+        let unclaimed = self.committed_payout.checked_sub(self.total_paid)?;
+        self.unswept_balance()?.checked_sub(unclaimed)
+    }
+
+    /// Source requirements for a price at `reference_ts`. `historical` is set
+    /// when resolving a past timestamp rather than sampling the current price.
+    pub fn sample_filter(
+        &self,
+        reference_ts: i64,
+        current_slot: u64,
+        historical: bool,
+    ) -> SampleFilter {
+        SampleFilter {
+            reference_ts,
+            max_age_secs: self.max_price_age_secs,
+            current_slot,
+            max_age_slots: self.max_price_age_slots,
+            min_pull_signatures: self.min_pull_signatures,
+            require_successor: historical,
+        }
+    }
+}
+
+#[account]
+pub struct PriceObservations {
+    pub round: Pubkey,
+    pub head: u16,
+    pub len: u16,
+    pub observations: [PriceObservation; MAX_PRICE_OBSERVATIONS],
+    pub bump: u8,
+}
+
+impl PriceObservations {
+    pub const SPACE: usize = 8  // discriminator
+        + 32 // round
+        + 2  // head
+        + 2  // len
+        + MAX_PRICE_OBSERVATIONS * PriceObservation::SIZE // observations
+        + 1; // bump
+
+    /// Appends an observation, overwriting the oldest once the buffer is full.
+    pub fn push(&mut self, observation: PriceObservation) {
+        let slot = (self.head as usize + self.len as usize) % MAX_PRICE_OBSERVATIONS;
+        self.observations[slot] = observation;
+        if (self.len as usize) < MAX_PRICE_OBSERVATIONS {
+            self.len += 1;
+        } else {
+            self.head = ((self.head as usize + 1) % MAX_PRICE_OBSERVATIONS) as u16;
+        }
+    }
+
+    pub fn latest(&self) -> Option<&PriceObservation> {
+        if self.len == 0 {
+            return None;
+        }
+        let slot = (self.head as usize + self.len as usize - 1) % MAX_PRICE_OBSERVATIONS;
+        Some(&self.observations[slot])
+    }
+
+    /// Observations from oldest to newest.
+    pub fn ordered(&self) -> Vec<PriceObservation> {
+        (0..self.len as usize)
+            .map(|i| self.observations[(self.head as usize + i) % MAX_PRICE_OBSERVATIONS])
+            .collect()
+    }
+}
+
+/// MXE-encrypted running state of an accumulator round. Only the relayer's
+/// `accumulate_prediction` computations can update it; the program just keeps
+/// the latest ciphertexts and how many entries they cover.
+#[account]
+pub struct RoundAccumulator {
+    pub round: Pubkey,
+    pub state: [[u8; 32]; ACCUMULATOR_STATE_LEN],
+    pub nonce: u128,
+    pub folded_count: u32,
+    pub bucket_origin: u64,
+    pub bucket_width: u64,
+    pub last_comp_id: Option<Pubkey>,
+    pub bump: u8,
+}
+
+impl RoundAccumulator {
+    pub const SPACE: usize = 8  // discriminator
+        + 32 // round
+        + ACCUMULATOR_STATE_LEN * 32 // state
+        + 16 // nonce
+        + 4  // folded count
+        + 8  // bucket origin
+        + 8  // bucket width
+        + (1 + 32) // last comp id option
+        + 1; // bump
+}
+
+/// Chunked resolution progress that does not fit on `Round`: the encrypted
+/// phase-one state and the payout root of every settled chunk.
+#[account]
+pub struct ResolutionChunks {
+    pub round: Pubkey,
+    pub scan_state: [[u8; 32]; SCAN_STATE_LEN],
+    pub scan_nonce: u128,
+    /// Bitmap of chunks whose phase-two result has landed.
+    pub settled: [u8; MAX_RESOLUTION_CHUNKS / 8],
+    pub chunk_roots: [[u8; 32]; MAX_RESOLUTION_CHUNKS],
+    /// Leaves across the chunks settled so far.
+    pub settled_entries: u32,
+    /// Entries and stake folded in by phase one.
+    pub scanned_entries: u32,
+    pub scanned_stake: u64,
+    pub last_comp_id: Option<Pubkey>,
+    pub bump: u8,
+}
+
+impl ResolutionChunks {
+    pub const SPACE: usize = 8  // discriminator
+        + 32 // round
+        + SCAN_STATE_LEN * 32 // scan state
+        + 16 // scan nonce
+        + MAX_RESOLUTION_CHUNKS / 8 // settled bitmap
+        + MAX_RESOLUTION_CHUNKS * 32 // chunk roots
+        + 4  // settled entries
+        + 4  // scanned entries
+        + 8  // scanned stake
+        + (1 + 32) // last comp id option
+        + 1; // bump
+
+    pub fn is_settled(&self, chunk_index: u16) -> bool {
+        self.settled[chunk_index as usize / 8] & (1 << (chunk_index % 8)) != 0
+    }
+
+    pub fn mark_settled(&mut self, chunk_index: u16) {
+        self.settled[chunk_index as usize / 8] |= 1 << (chunk_index % 8);
+    }
+}
+
+/// Output of `reveal_round_sentiment` together with the bucket layout it was
+/// computed with.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct RoundStatsInput {
+    pub participant_count: u32,
+    pub total_stake: u64,
+    pub weighted_mean_price: u64,
+    pub median_price: u64,
+    pub bucket_origin: u64,
+    pub bucket_width: u64,
+    pub histogram: [u32; SENTIMENT_BUCKETS],
+}
+
+impl RoundStatsInput {
+    /// Whether `median_price` is zero or the centre of one of the histogram
+    /// buckets, the only values the circuit reveals.
+    pub fn median_is_bucket_centre(&self) -> bool {
+        if self.median_price == 0 {
+            return true;
+        }
+        let width = self.bucket_width as u128;
+        let first_centre = self.bucket_origin as u128 + width / 2;
+        let Some(offset) = (self.median_price as u128).checked_sub(first_centre) else {
+            return false;
+        };
+        offset % width == 0 && offset / width < SENTIMENT_BUCKETS as u128
+    }
+}
+
+#[account]
+pub struct RoundStats {
+    pub round: Pubkey,
+    pub participant_count: u32,
+    pub total_stake: u64,
+    /// Zero when the round had too few participants to reveal it safely.
+    pub weighted_mean_price: u64,
+    /// Centre of the histogram bucket holding the stake-weighted median; zero
+    /// whenever the histogram is withheld.
+    pub median_price: u64,
+    pub bucket_origin: u64,
+    pub bucket_width: u64,
+    pub histogram: [u32; SENTIMENT_BUCKETS],
+    pub arcium_comp_id: Option<Pubkey>,
+    pub recorded_ts: i64,
+    pub bump: u8,
+}
+
+impl RoundStats {
+    pub const SPACE: usize = 8  // discriminator
+        + 32 // round
+        + 4  // participant count
+        + 8  // total stake
+        + 8  // weighted mean price
+        + 8  // median price
+        + 8  // bucket origin
+        + 8  // bucket width
+        + SENTIMENT_BUCKETS * 4 // histogram
+        + (1 + 32) // arcium comp id option
+        + 8  // recorded ts
+        + 1; // bump
+}
+
+/// Ciphertexts of a prediction encrypted for the MXE together with the
+/// user's x25519 key, which settlement results are re-encrypted to.
+/// Closest-price rounds only use the first ciphertext; interval rounds store
+/// the lower and upper bound.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct EncryptedPayload {
+    pub encryption_pubkey: [u8; 32],
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; 2],
+}
+
+impl EncryptedPayload {
+    pub const SIZE: usize = 32 + 16 + 2 * 32;
 }
 
 #[account]
@@ -552,10 +2415,19 @@ pub struct Prediction {
     pub status: u8,
     pub prediction_index: u16,
     pub bump: u8,
+    // Fields below are appended to the original layout, see `migrate_prediction`.
+    pub payload: EncryptedPayload,
+    /// Early-entry weight passed to the settlement circuit.
+    pub weight_bps: u16,
+    /// Whether the entry is currently folded into the round accumulator.
+    pub accumulated: bool,
+    /// Blinded payout leaf revealed by `settle_accumulated_entry`.
+    pub settlement_leaf: Option<[u8; 32]>,
 }
 
 impl Prediction {
-    pub const SPACE: usize = 8  // discriminator
+    /// Size of a prediction created before `migrate_prediction` existed.
+    pub const LEGACY_SPACE: usize = 8  // discriminator
         + 32 // round
         + 32 // owner
         + 32 // token mint
@@ -565,6 +2437,12 @@ impl Prediction {
         + 1  // status
         + 2  // prediction index
         + 1; // bump
+
+    pub const SPACE: usize = Self::LEGACY_SPACE
+        + EncryptedPayload::SIZE // payload
+        + 2  // weight bps
+        + 1  // accumulated
+        + (1 + 32); // settlement leaf option
 }
 
 #[repr(u8)]
@@ -575,6 +2453,48 @@ pub enum RoundStatus {
     Refunded = 3,
 }
 
+#[repr(u8)]
+pub enum RoundType {
+    /// Closest predicted price wins (`determine_winners`).
+    ClosestPrice = 0,
+    /// Interval predictions scored by `score_interval_predictions`.
+    IntervalScore = 1,
+}
+
+impl RoundType {
+    pub fn from_u8(round_type: u8) -> Option<Self> {
+        match round_type {
+            0 => Some(Self::ClosestPrice),
+            1 => Some(Self::IntervalScore),
+            _ => None,
+        }
+    }
+}
+
+#[repr(u8)]
+pub enum TieBreak {
+    /// Every closest entry is paid.
+    Split = 0,
+    /// One closest entry, drawn from the settlement seed, takes their stakes.
+    Random = 1,
+}
+
+impl TieBreak {
+    pub fn from_u8(tie_break: u8) -> Option<Self> {
+        match tie_break {
+            0 => Some(Self::Split),
+            1 => Some(Self::Random),
+            _ => None,
+        }
+    }
+}
+
+#[repr(u8)]
+pub enum ResolutionMode {
+    Spot = 0,
+    Twap = 1,
+}
+
 #[repr(u8)]
 pub enum PredictionStatus {
     Submitted = 0,
@@ -617,4 +2537,84 @@ pub enum ErrorCode {
     RoundAlreadySettled,
     #[msg("Round is in invalid state for this operation")]
     InvalidRoundState,
+    #[msg("Price source configuration is invalid")]
+    InvalidPriceSources,
+    #[msg("Too many price sources")]
+    TooManyPriceSources,
+    #[msg("Round has not ended")]
+    RoundNotEnded,
+    #[msg("Round price already resolved")]
+    PriceAlreadyResolved,
+    #[msg("Not enough fresh, agreeing price sources")]
+    InsufficientPriceSources,
+    #[msg("Final price does not match the resolved oracle price")]
+    FinalPriceMismatch,
+    #[msg("TWAP window is invalid")]
+    InvalidTwapWindow,
+    #[msg("Operation not supported by the round's resolution mode")]
+    InvalidResolutionMode,
+    #[msg("Outside the TWAP observation window")]
+    OutsideTwapWindow,
+    #[msg("Observation already recorded for this timestamp")]
+    DuplicateObservation,
+    #[msg("Not enough price observations recorded")]
+    NoPriceObservations,
+    #[msg("Nothing left to sweep")]
+    NothingToSweep,
+    #[msg("Payout tiers are invalid")]
+    InvalidPayoutTiers,
+    #[msg("Round rules cannot change once stakes are placed")]
+    RoundAlreadyStaked,
+    #[msg("Round type or scoring parameters are invalid")]
+    InvalidScoringParams,
+    #[msg("Early-entry bonus is too large")]
+    InvalidEarlyBonus,
+    #[msg("Round statistics are invalid")]
+    InvalidRoundStats,
+    #[msg("Settlement result already committed")]
+    SettlementAlreadyCommitted,
+    #[msg("Settlement result not committed yet")]
+    SettlementNotCommitted,
+    #[msg("Payout is not part of the committed settlement")]
+    InvalidPayoutProof,
+    #[msg("Round accumulator configuration is invalid")]
+    InvalidAccumulator,
+    #[msg("Prediction has nothing to fold into the accumulator")]
+    NothingToFold,
+    #[msg("Prediction is already folded into the accumulator")]
+    PredictionAlreadyAccumulated,
+    #[msg("Accumulator has unfolded submissions")]
+    AccumulatorNotCaughtUp,
+    #[msg("Chunk layout is invalid")]
+    InvalidChunkLayout,
+    #[msg("Round is being resolved in chunks")]
+    ChunkedResolutionActive,
+    #[msg("Chunks must be scanned in order")]
+    ChunkOutOfOrder,
+    #[msg("Phase-one scan has not covered every entry")]
+    ScanIncomplete,
+    #[msg("Chunk result already recorded")]
+    ChunkAlreadyRecorded,
+    #[msg("Not every chunk has been settled")]
+    ChunksOutstanding,
+    #[msg("Tie-break or lucky draw settings are invalid")]
+    InvalidDrawRules,
+    #[msg("Settlement is missing the randomness seed its draws used")]
+    MissingRandomnessSeed,
+    #[msg("Observation too soon after the previous one")]
+    ObservationTooSoon,
+    #[msg("Amendment fee exceeds the maximum")]
+    InvalidAmendmentFee,
+    #[msg("Config already uses the current layout")]
+    ConfigUpToDate,
+    #[msg("Randomness seed is not committed to by the settlement root")]
+    InvalidRandomnessSeed,
+    #[msg("Amount exceeds the fees accrued in the rollover vault")]
+    InsufficientAccruedFees,
+    #[msg("Claims were already paid out of the round")]
+    ClaimsAlreadyPaid,
+    #[msg("Chunk settlement does not match the recorded scan")]
+    ScanMismatch,
+    #[msg("Account already uses the current layout")]
+    AccountUpToDate,
 }
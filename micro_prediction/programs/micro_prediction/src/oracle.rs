@@ -0,0 +1,439 @@
+use anchor_lang::prelude::borsh;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions as ix_sysvar;
+use pyth_sdk_solana::state::{load_price_account, SolanaPriceAccount};
+
+use crate::ErrorCode;
+
+/// Every resolved price is normalised to this exponent before aggregation.
+pub const PRICE_EXPO: i32 = -8;
+pub const MAX_PRICE_SOURCES: usize = 5;
+pub const DEFAULT_MAX_PRICE_AGE_SECS: u32 = 60;
+pub const DEFAULT_MAX_PRICE_DEVIATION_BPS: u16 = 100;
+/// Roughly one minute of slots.
+pub const DEFAULT_MAX_PRICE_AGE_SLOTS: u64 = 150;
+pub const MAX_PRICE_OBSERVATIONS: usize = 32;
+/// Observations a TWAP round needs before its price can be resolved.
+pub const MIN_PRICE_OBSERVATIONS: usize = 3;
+
+/// Prefix of the message an allowlisted key signs for a price attestation.
+pub const ATTESTATION_DOMAIN: &[u8] = b"micro_prediction:price:v1";
+
+pub const PYTH_RECEIVER_PROGRAM_ID: Pubkey =
+    pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+/// Native program that verifies the attestation signatures.
+pub const ED25519_PROGRAM_ID: Pubkey = pubkey!("Ed25519SigVerify111111111111111111111111111");
+const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PriceSourceKind {
+    PythPush = 0,
+    PythPull = 1,
+    SignedAttestation = 2,
+}
+
+impl PriceSourceKind {
+    pub fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            0 => Some(Self::PythPush),
+            1 => Some(Self::PythPull),
+            2 => Some(Self::SignedAttestation),
+            _ => None,
+        }
+    }
+}
+
+/// A price source configured on a round. `key` is the price account for Pyth
+/// push sources, the 32 byte feed id for Pyth pull sources and the allowlisted
+/// signer for attestations.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct PriceSource {
+    pub kind: u8,
+    pub key: Pubkey,
+}
+
+impl PriceSource {
+    pub const SIZE: usize = 1 + 32;
+}
+
+/// Price signed off-chain by an allowlisted key. The signature is checked by an
+/// Ed25519 program instruction earlier in the same transaction.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct PriceAttestation {
+    pub signer: Pubkey,
+    pub round_id: u64,
+    pub price: i64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+impl PriceAttestation {
+    pub fn message(&self) -> Vec<u8> {
+        let mut message = ATTESTATION_DOMAIN.to_vec();
+        message.extend_from_slice(&self.round_id.to_le_bytes());
+        message.extend_from_slice(&self.price.to_le_bytes());
+        message.extend_from_slice(&self.expo.to_le_bytes());
+        message.extend_from_slice(&self.publish_time.to_le_bytes());
+        message
+    }
+}
+
+#[derive(AnchorDeserialize)]
+pub enum VerificationLevel {
+    Partial { num_signatures: u8 },
+    Full,
+}
+
+impl VerificationLevel {
+    /// Full updates are always accepted. Partial updates need at least
+    /// `min_signatures` guardian signatures; `0` accepts only full updates.
+    pub fn is_sufficient(&self, min_signatures: u8) -> bool {
+        match self {
+            VerificationLevel::Full => true,
+            VerificationLevel::Partial { num_signatures } => {
+                min_signatures > 0 && *num_signatures >= min_signatures
+            }
+        }
+    }
+}
+
+#[derive(AnchorDeserialize)]
+pub struct PriceFeedMessage {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub prev_publish_time: i64,
+    pub ema_price: i64,
+    pub ema_conf: u64,
+}
+
+/// Pyth Solana Receiver `PriceUpdateV2` account body.
+#[derive(AnchorDeserialize)]
+pub struct PriceUpdateV2 {
+    pub write_authority: Pubkey,
+    pub verification_level: VerificationLevel,
+    pub price_message: PriceFeedMessage,
+    pub posted_slot: u64,
+}
+
+/// Staleness and verification requirements applied to every source.
+#[derive(Clone, Copy)]
+pub struct SampleFilter {
+    pub reference_ts: i64,
+    pub max_age_secs: u32,
+    pub current_slot: u64,
+    pub max_age_slots: u64,
+    pub min_pull_signatures: u8,
+    /// Pull sources must prove their update is the latest at or before
+    /// `reference_ts`, see `read_pyth_pull`.
+    pub require_successor: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct PriceSample {
+    pub source_index: u8,
+    pub price: i64,
+    pub publish_time: i64,
+}
+
+pub fn validate_sources(sources: &[PriceSource], min_sources: u8) -> Result<()> {
+    require!(sources.len() <= MAX_PRICE_SOURCES, ErrorCode::TooManyPriceSources);
+    require!(
+        min_sources > 0 && min_sources as usize <= sources.len(),
+        ErrorCode::InvalidPriceSources
+    );
+    for (i, source) in sources.iter().enumerate() {
+        require!(
+            PriceSourceKind::from_u8(source.kind).is_some(),
+            ErrorCode::InvalidPriceSources
+        );
+        require!(
+            !sources[..i].contains(source),
+            ErrorCode::InvalidPriceSources
+        );
+    }
+    Ok(())
+}
+
+/// Reads one sample per configured source that is available. Sources that are
+/// not passed in, insufficiently verified, published after `reference_ts` or
+/// more than `max_age_secs` before it are skipped so a single feed outage does
+/// not block resolution; `aggregate` then requires the round's minimum number
+/// of sources among the rest.
+pub fn collect_samples(
+    sources: &[PriceSource],
+    round_id: u64,
+    filter: &SampleFilter,
+    price_accounts: &[AccountInfo],
+    attestations: &[PriceAttestation],
+    instructions_sysvar: &AccountInfo,
+) -> Result<Vec<PriceSample>> {
+    let mut samples = Vec::with_capacity(sources.len());
+    for (i, source) in sources.iter().enumerate() {
+        let raw = match PriceSourceKind::from_u8(source.kind) {
+            Some(PriceSourceKind::PythPush) => price_accounts
+                .iter()
+                .find(|account| account.key() == source.key)
+                .and_then(|account| read_pyth_push(account, filter.reference_ts)),
+            Some(PriceSourceKind::PythPull) => {
+                read_pyth_pull(price_accounts, &source.key.to_bytes(), filter)
+            }
+            Some(PriceSourceKind::SignedAttestation) => attestations
+                .iter()
+                .find(|attestation| {
+                    attestation.signer == source.key && attestation.round_id == round_id
+                })
+                .filter(|attestation| {
+                    is_attestation_signed(instructions_sysvar, attestation).unwrap_or(false)
+                })
+                .map(|attestation| (attestation.price, attestation.expo, attestation.publish_time)),
+            None => None,
+        };
+
+        let Some((price, expo, publish_time)) = raw else {
+            continue;
+        };
+        if publish_time > filter.reference_ts
+            || filter.reference_ts - publish_time > filter.max_age_secs as i64
+        {
+            continue;
+        }
+        let Some(price) = normalize_price(price, expo) else {
+            continue;
+        };
+        samples.push(PriceSample {
+            source_index: i as u8,
+            price,
+            publish_time,
+        });
+    }
+    Ok(samples)
+}
+
+/// Takes the median of `samples`, drops every sample further than
+/// `max_deviation_bps` from it and returns the median of what is left along
+/// with a bitmask of the source indexes that were used.
+pub fn aggregate(
+    samples: &[PriceSample],
+    min_sources: u8,
+    max_deviation_bps: u16,
+) -> Result<(i64, u8)> {
+    require!(
+        samples.len() >= min_sources as usize && !samples.is_empty(),
+        ErrorCode::InsufficientPriceSources
+    );
+
+    let mut prices: Vec<i64> = samples.iter().map(|s| s.price).collect();
+    let reference = median(&mut prices);
+
+    let mut used_mask = 0u8;
+    let mut kept: Vec<i64> = Vec::with_capacity(samples.len());
+    for sample in samples {
+        let deviation = (sample.price.abs_diff(reference) as u128) * 10_000;
+        if deviation <= reference.unsigned_abs() as u128 * max_deviation_bps as u128 {
+            used_mask |= 1 << sample.source_index;
+            kept.push(sample.price);
+        }
+    }
+    require!(
+        kept.len() >= min_sources as usize,
+        ErrorCode::InsufficientPriceSources
+    );
+
+    Ok((median(&mut kept), used_mask))
+}
+
+fn median(prices: &mut [i64]) -> i64 {
+    prices.sort_unstable();
+    let mid = prices.len() / 2;
+    if prices.len().is_multiple_of(2) {
+        ((prices[mid - 1] as i128 + prices[mid] as i128) / 2) as i64
+    } else {
+        prices[mid]
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceObservation {
+    pub price: i64,
+    pub timestamp: i64,
+}
+
+impl PriceObservation {
+    pub const SIZE: usize = 8 + 8;
+}
+
+/// Time-weighted average of `observations` (oldest first) up to `window_end`.
+/// Each observation holds until the next one; time before the first
+/// observation is not counted.
+pub fn time_weighted_average(observations: &[PriceObservation], window_end: i64) -> Result<i64> {
+    let first = observations.first().ok_or(ErrorCode::NoPriceObservations)?;
+
+    let mut weighted_sum: i128 = 0;
+    let mut total_duration: i128 = 0;
+    for (i, observation) in observations.iter().enumerate() {
+        let until = observations
+            .get(i + 1)
+            .map_or(window_end, |next| next.timestamp)
+            .min(window_end);
+        let duration = until.saturating_sub(observation.timestamp).max(0) as i128;
+        weighted_sum += observation.price as i128 * duration;
+        total_duration += duration;
+    }
+
+    if total_duration == 0 {
+        return Ok(observations.last().unwrap_or(first).price);
+    }
+    i64::try_from(weighted_sum / total_duration).map_err(|_| ErrorCode::NumericalOverflow.into())
+}
+
+pub fn normalize_price(price: i64, expo: i32) -> Option<i64> {
+    let shift = expo.checked_sub(PRICE_EXPO)?;
+    if shift >= 0 {
+        10i64
+            .checked_pow(shift as u32)
+            .and_then(|factor| price.checked_mul(factor))
+    } else {
+        10i64
+            .checked_pow(shift.unsigned_abs())
+            .map(|factor| price / factor)
+    }
+}
+
+/// Reads a push price account, falling back to the previous aggregate when the
+/// current one was published after `reference_ts`.
+fn read_pyth_push(account: &AccountInfo, reference_ts: i64) -> Option<(i64, i32, i64)> {
+    let feed = SolanaPriceAccount::account_info_to_feed(account).ok()?;
+    let price = feed.get_price_unchecked();
+    if price.publish_time <= reference_ts {
+        return Some((price.price, price.expo, price.publish_time));
+    }
+    let data = account.try_borrow_data().ok()?;
+    let raw = load_price_account::<32, ()>(&data).ok()?;
+    Some((raw.prev_price, raw.expo, raw.prev_timestamp))
+}
+
+/// Picks the price a pull source resolves to: the latest update for `feed_id`
+/// among `price_accounts` published at or before `reference_ts`. With
+/// `require_successor` set it only counts if another passed update was
+/// published after `reference_ts` and names it as its predecessor, which proves
+/// no newer price at or before `reference_ts` was left out.
+fn read_pyth_pull(
+    price_accounts: &[AccountInfo],
+    feed_id: &[u8; 32],
+    filter: &SampleFilter,
+) -> Option<(i64, i32, i64)> {
+    let updates: Vec<PriceFeedMessage> = price_accounts
+        .iter()
+        .filter_map(|account| decode_pull_update(account, feed_id, filter))
+        .collect();
+    let latest = updates
+        .iter()
+        .filter(|message| message.publish_time <= filter.reference_ts)
+        .max_by_key(|message| message.publish_time)?;
+    if filter.require_successor
+        && !updates.iter().any(|message| {
+            message.publish_time > filter.reference_ts
+                && message.prev_publish_time == latest.publish_time
+        })
+    {
+        return None;
+    }
+    Some((latest.price, latest.exponent, latest.publish_time))
+}
+
+/// Decodes a receiver-owned `PriceUpdateV2` account for `feed_id` that is
+/// verified well enough and, unless resolving historically, was posted
+/// recently.
+fn decode_pull_update(
+    account: &AccountInfo,
+    feed_id: &[u8; 32],
+    filter: &SampleFilter,
+) -> Option<PriceFeedMessage> {
+    if account.owner != &PYTH_RECEIVER_PROGRAM_ID {
+        return None;
+    }
+    let data = account.try_borrow_data().ok()?;
+    if data.len() < 8 || data[..8] != PRICE_UPDATE_V2_DISCRIMINATOR {
+        return None;
+    }
+    let update = PriceUpdateV2::deserialize(&mut &data[8..]).ok()?;
+    if &update.price_message.feed_id != feed_id
+        || !update
+            .verification_level
+            .is_sufficient(filter.min_pull_signatures)
+        // A historical price is aged by its publish time against the reference
+        // time instead; it may have been posted long before resolution.
+        || (!filter.require_successor
+            && filter.current_slot.saturating_sub(update.posted_slot) > filter.max_age_slots)
+    {
+        return None;
+    }
+    Some(update.price_message)
+}
+
+/// Returns true if an Ed25519 program instruction earlier in this transaction
+/// verified `attestation.signer`'s signature over the attestation message.
+fn is_attestation_signed(
+    instructions_sysvar: &AccountInfo,
+    attestation: &PriceAttestation,
+) -> Result<bool> {
+    let expected_message = attestation.message();
+    let current_index = ix_sysvar::load_current_index_checked(instructions_sysvar)?;
+    for index in 0..current_index {
+        let ix = ix_sysvar::load_instruction_at_checked(index as usize, instructions_sysvar)?;
+        if ix.program_id != ED25519_PROGRAM_ID {
+            continue;
+        }
+        if ed25519_ix_covers(&ix.data, &attestation.signer, &expected_message) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Layout of the Ed25519 program instruction data: a u8 signature count, a u8 of
+// padding and then one 14 byte offsets record per signature. We only accept
+// records that reference data inside the Ed25519 instruction itself.
+fn ed25519_ix_covers(data: &[u8], signer: &Pubkey, message: &[u8]) -> bool {
+    const HEADER_LEN: usize = 2;
+    const OFFSETS_LEN: usize = 14;
+    const SELF_INDEX: u16 = u16::MAX;
+
+    let Some(&count) = data.first() else {
+        return false;
+    };
+    let read_u16 = |at: usize| -> Option<u16> {
+        data.get(at..at + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+
+    (0..count as usize).any(|i| {
+        let base = HEADER_LEN + i * OFFSETS_LEN;
+        let fields: Option<[u16; 7]> = (|| {
+            Some([
+                read_u16(base)?,
+                read_u16(base + 2)?,
+                read_u16(base + 4)?,
+                read_u16(base + 6)?,
+                read_u16(base + 8)?,
+                read_u16(base + 10)?,
+                read_u16(base + 12)?,
+            ])
+        })();
+        let Some([_, sig_ix, pubkey_offset, pubkey_ix, msg_offset, msg_len, msg_ix]) = fields
+        else {
+            return false;
+        };
+        if sig_ix != SELF_INDEX || pubkey_ix != SELF_INDEX || msg_ix != SELF_INDEX {
+            return false;
+        }
+        let pubkey_offset = pubkey_offset as usize;
+        let msg_offset = msg_offset as usize;
+        data.get(pubkey_offset..pubkey_offset + 32) == Some(signer.as_ref())
+            && data.get(msg_offset..msg_offset + msg_len as usize) == Some(message)
+    })
+}
@@ -0,0 +1,1065 @@
+//! In-process harness around LiteSVM for the `micro_prediction` program.
+//!
+//! Loads `target/deploy/micro_prediction.so`, so run `anchor build` (or
+//! `cargo build-sbf`) before `cargo test`. Nothing here touches the network.
+
+#![allow(dead_code, clippy::result_large_err)]
+
+use anchor_lang::prelude::Clock;
+use anchor_lang::system_program;
+use anchor_lang::{
+    AccountDeserialize, AnchorSerialize, Discriminator, InstructionData, ToAccountMetas,
+};
+use anchor_spl::token::spl_token;
+use litesvm::types::{FailedTransactionMetadata, TransactionResult};
+use litesvm::LiteSVM;
+use litesvm_token::{get_spl_account, CreateAccount, CreateMint, MintTo};
+use micro_prediction::oracle::{
+    PriceAttestation, PriceSource, DEFAULT_MAX_PRICE_AGE_SECS, DEFAULT_MAX_PRICE_AGE_SLOTS,
+    DEFAULT_MAX_PRICE_DEVIATION_BPS, PRICE_EXPO, PYTH_RECEIVER_PROGRAM_ID,
+};
+use micro_prediction::{
+    accounts, instruction, EncryptedPayload, ErrorCode, Prediction, ResolutionMode, Round,
+    ACCUMULATOR_SEED, ACCUMULATOR_STATE_LEN, CONFIG_SEED, ESCROW_SEED, OBSERVATIONS_SEED,
+    PREDICTION_SEED, RESOLUTION_CHUNKS_SEED, ROLLOVER_SEED, ROUND_SEED, SCAN_STATE_LEN,
+};
+use solana_ed25519_program::new_ed25519_instruction_with_signature;
+use solana_sdk::account::Account;
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::sysvar;
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+pub const PROGRAM_SO: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../target/deploy/micro_prediction.so"
+);
+pub const START_TS: i64 = 1_700_000_000;
+pub const FEE_BPS: u16 = 250;
+
+pub fn config_pda() -> Pubkey {
+    Pubkey::find_program_address(&[CONFIG_SEED], &micro_prediction::ID).0
+}
+
+pub fn rollover_pda() -> Pubkey {
+    Pubkey::find_program_address(&[ROLLOVER_SEED], &micro_prediction::ID).0
+}
+
+pub fn round_pda(round_id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[ROUND_SEED, &round_id.to_le_bytes()],
+        &micro_prediction::ID,
+    )
+    .0
+}
+
+pub fn observations_pda(round_id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[OBSERVATIONS_SEED, &round_id.to_le_bytes()],
+        &micro_prediction::ID,
+    )
+    .0
+}
+
+pub fn accumulator_pda(round_id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[ACCUMULATOR_SEED, &round_id.to_le_bytes()],
+        &micro_prediction::ID,
+    )
+    .0
+}
+
+pub fn resolution_chunks_pda(round_id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[RESOLUTION_CHUNKS_SEED, &round_id.to_le_bytes()],
+        &micro_prediction::ID,
+    )
+    .0
+}
+
+pub fn escrow_pda(round_id: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[ESCROW_SEED, &round_id.to_le_bytes()],
+        &micro_prediction::ID,
+    )
+    .0
+}
+
+pub fn prediction_pda(round_id: u64, owner: &Pubkey, prediction_index: u16) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            PREDICTION_SEED,
+            &round_id.to_le_bytes(),
+            owner.as_ref(),
+            &prediction_index.to_le_bytes(),
+        ],
+        &micro_prediction::ID,
+    )
+    .0
+}
+
+pub fn ix(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: micro_prediction::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+/// Attestation of `price` for `round_id` by `signer`, in the program's exponent.
+pub fn attestation(
+    signer: &Keypair,
+    round_id: u64,
+    price: i64,
+    publish_time: i64,
+) -> PriceAttestation {
+    PriceAttestation {
+        signer: signer.pubkey(),
+        round_id,
+        price,
+        expo: PRICE_EXPO,
+        publish_time,
+    }
+}
+
+/// Ed25519 program instruction verifying `signer`'s signature over
+/// `attestation`; it has to precede the instruction that reads the price.
+pub fn attestation_ix(signer: &Keypair, attestation: &PriceAttestation) -> Instruction {
+    let message = attestation.message();
+    let signature: [u8; 64] = signer.sign_message(&message).into();
+    new_ed25519_instruction_with_signature(&message, &signature, &signer.pubkey().to_bytes())
+}
+
+/// A funded participant and their token account.
+pub struct User {
+    pub keypair: Keypair,
+    pub token_account: Pubkey,
+}
+
+impl User {
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+}
+
+pub struct TestEnv {
+    pub svm: LiteSVM,
+    pub authority: Keypair,
+    pub settlement_authority: Keypair,
+    pub mint: Pubkey,
+    pub fee_treasury: Pubkey,
+}
+
+impl TestEnv {
+    /// Fresh runtime with the program loaded, a test mint and a fee treasury;
+    /// the config is not initialized yet.
+    pub fn new() -> Self {
+        let mut svm = LiteSVM::new();
+        svm.add_program_from_file(micro_prediction::ID, PROGRAM_SO)
+            .expect("build the program first: target/deploy/micro_prediction.so");
+
+        let authority = Keypair::new();
+        let settlement_authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 100_000_000_000).unwrap();
+        svm.airdrop(&settlement_authority.pubkey(), 10_000_000_000)
+            .unwrap();
+
+        let mint = CreateMint::new(&mut svm, &authority)
+            .decimals(6)
+            .send()
+            .unwrap();
+        let fee_treasury = CreateAccount::new(&mut svm, &authority, &mint)
+            .owner(&authority.pubkey())
+            .send()
+            .unwrap();
+
+        let mut env = Self {
+            svm,
+            authority,
+            settlement_authority,
+            mint,
+            fee_treasury,
+        };
+        env.warp_to(START_TS - 1_000);
+        env
+    }
+
+    /// `new` followed by `initialize` with `FEE_BPS`.
+    pub fn initialized() -> Self {
+        let mut env = Self::new();
+        env.initialize(FEE_BPS).unwrap();
+        env
+    }
+
+    pub fn warp_to(&mut self, unix_timestamp: i64) {
+        let mut clock = self.svm.get_sysvar::<Clock>();
+        clock.unix_timestamp = unix_timestamp;
+        clock.slot += 1;
+        self.svm.set_sysvar(&clock);
+    }
+
+    pub fn send(&mut self, ixs: &[Instruction], signers: &[&Keypair]) -> TransactionResult {
+        // Identical transactions would be rejected as already processed.
+        self.svm.expire_blockhash();
+        let payer = signers[0].pubkey();
+        let tx = Transaction::new_signed_with_payer(
+            ixs,
+            Some(&payer),
+            signers,
+            self.svm.latest_blockhash(),
+        );
+        self.svm.send_transaction(tx)
+    }
+
+    pub fn token_balance(&self, token_account: &Pubkey) -> u64 {
+        get_spl_account::<spl_token::state::Account>(&self.svm, token_account)
+            .unwrap()
+            .amount
+    }
+
+    pub fn round(&self, round_id: u64) -> Round {
+        self.decode(&round_pda(round_id))
+    }
+
+    pub fn prediction(&self, address: &Pubkey) -> Prediction {
+        self.decode(address)
+    }
+
+    pub fn decode<T: AccountDeserialize>(&self, address: &Pubkey) -> T {
+        let account = self.svm.get_account(address).expect("account exists");
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub fn new_user(&mut self, balance: u64) -> User {
+        let keypair = Keypair::new();
+        self.svm.airdrop(&keypair.pubkey(), 10_000_000_000).unwrap();
+        let token_account = CreateAccount::new(&mut self.svm, &keypair, &self.mint)
+            .owner(&keypair.pubkey())
+            .send()
+            .unwrap();
+        MintTo::new(
+            &mut self.svm,
+            &self.authority,
+            &self.mint,
+            &token_account,
+            balance,
+        )
+        .send()
+        .unwrap();
+        User {
+            keypair,
+            token_account,
+        }
+    }
+
+    pub fn initialize(&mut self, fee_bps: u16) -> TransactionResult {
+        let ix = ix(
+            accounts::Initialize {
+                authority: self.authority.pubkey(),
+                token_mint: self.mint,
+                fee_treasury: self.fee_treasury,
+                config: config_pda(),
+                system_program: system_program::ID,
+            },
+            instruction::Initialize {
+                settlement_authority: self.settlement_authority.pubkey(),
+                fee_bps,
+            },
+        );
+        let authority = self.authority.insecure_clone();
+        self.send(&[ix], &[&authority])
+    }
+
+    pub fn init_rollover_vault(&mut self) -> TransactionResult {
+        let authority = self.authority.insecure_clone();
+        let ix = ix(
+            accounts::InitRolloverVault {
+                authority: authority.pubkey(),
+                config: config_pda(),
+                token_mint: self.mint,
+                rollover_vault: rollover_pda(),
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            },
+            instruction::InitRolloverVault {},
+        );
+        self.send(&[ix], &[&authority])
+    }
+
+    pub fn top_up_rollover(&mut self, funder: &User, amount: u64) -> TransactionResult {
+        let ix = ix(
+            accounts::TopUpRollover {
+                funder: funder.pubkey(),
+                config: config_pda(),
+                funder_token_account: funder.token_account,
+                rollover_vault: rollover_pda(),
+                token_program: spl_token::ID,
+            },
+            instruction::TopUpRollover { amount },
+        );
+        self.send(&[ix], &[&funder.keypair])
+    }
+
+    pub fn sweep(&mut self, round_id: u64, payer: &Keypair) -> TransactionResult {
+        let ix = ix(
+            accounts::SweepRoundToRollover {
+                config: config_pda(),
+                round: round_pda(round_id),
+                escrow_vault: escrow_pda(round_id),
+                rollover_vault: rollover_pda(),
+                token_program: spl_token::ID,
+            },
+            instruction::SweepRoundToRollover {},
+        );
+        self.send(&[ix], &[payer])
+    }
+
+    pub fn set_house_edge(&mut self, house_edge_bps: u16) -> TransactionResult {
+        let authority = self.authority.insecure_clone();
+        let ix = ix(
+            accounts::UpdateConfig {
+                authority: authority.pubkey(),
+                config: config_pda(),
+            },
+            instruction::SetHouseEdge { house_edge_bps },
+        );
+        self.send(&[ix], &[&authority])
+    }
+
+    pub fn seed_round(&mut self, round_id: u64, amount: u64) -> TransactionResult {
+        let authority = self.authority.insecure_clone();
+        let ix = ix(
+            accounts::SeedRoundFromRollover {
+                authority: authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+                rollover_vault: rollover_pda(),
+                escrow_vault: escrow_pda(round_id),
+                token_program: spl_token::ID,
+            },
+            instruction::SeedRoundFromRollover { amount },
+        );
+        self.send(&[ix], &[&authority])
+    }
+
+    pub fn collect_fees(&mut self, signer: &Keypair, amount: u64) -> TransactionResult {
+        let ix = ix(
+            accounts::CollectFees {
+                authority: signer.pubkey(),
+                config: config_pda(),
+                rollover_vault: rollover_pda(),
+                fee_treasury: self.fee_treasury,
+                token_program: spl_token::ID,
+            },
+            instruction::CollectFees { amount },
+        );
+        self.send(&[ix], &[signer])
+    }
+
+    pub fn initialize_round(
+        &mut self,
+        round_id: u64,
+        start_ts: i64,
+        end_ts: i64,
+    ) -> TransactionResult {
+        let ix = ix(
+            accounts::InitializeRound {
+                authority: self.authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+                token_mint: self.mint,
+                escrow_vault: escrow_pda(round_id),
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            },
+            instruction::InitializeRound {
+                round_id,
+                start_ts,
+                end_ts,
+                pyth_price_account: Pubkey::new_unique(),
+            },
+        );
+        let authority = self.authority.insecure_clone();
+        self.send(&[ix], &[&authority])
+    }
+
+    /// Opens round `round_id` over `[START_TS, START_TS + 600]` and warps into it.
+    pub fn open_round(&mut self, round_id: u64) {
+        self.initialize_round(round_id, START_TS, START_TS + 600)
+            .unwrap();
+        self.warp_to(START_TS + 10);
+    }
+
+    pub fn submit(
+        &mut self,
+        round_id: u64,
+        user: &User,
+        prediction_index: u16,
+        commitment: [u8; 32],
+        stake: u64,
+    ) -> TransactionResult {
+        let ix = ix(
+            accounts::SubmitPrediction {
+                user: user.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+                prediction: prediction_pda(round_id, &user.pubkey(), prediction_index),
+                user_token_account: user.token_account,
+                escrow_vault: escrow_pda(round_id),
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            },
+            instruction::SubmitPrediction {
+                commitment,
+                window_index: 0,
+                stake,
+                prediction_index,
+                payload: EncryptedPayload::default(),
+            },
+        );
+        self.send(&[ix], &[&user.keypair])
+    }
+
+    pub fn cancel(
+        &mut self,
+        round_id: u64,
+        user: &User,
+        prediction_index: u16,
+    ) -> TransactionResult {
+        let ix = ix(
+            accounts::CancelPrediction {
+                user: user.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+                prediction: prediction_pda(round_id, &user.pubkey(), prediction_index),
+                user_token_account: user.token_account,
+                escrow_vault: escrow_pda(round_id),
+                token_program: spl_token::ID,
+            },
+            instruction::CancelPrediction {},
+        );
+        self.send(&[ix], &[&user.keypair])
+    }
+
+    pub fn amend(
+        &mut self,
+        round_id: u64,
+        user: &User,
+        prediction_index: u16,
+        commitment: [u8; 32],
+        new_stake: u64,
+    ) -> TransactionResult {
+        let ix = ix(
+            accounts::AmendPrediction {
+                user: user.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+                prediction: prediction_pda(round_id, &user.pubkey(), prediction_index),
+                user_token_account: user.token_account,
+                escrow_vault: escrow_pda(round_id),
+                fee_treasury: self.fee_treasury,
+                token_program: spl_token::ID,
+            },
+            instruction::AmendPrediction {
+                commitment,
+                payload: EncryptedPayload::default(),
+                new_stake,
+            },
+        );
+        self.send(&[ix], &[&user.keypair])
+    }
+
+    pub fn begin_resolution(&mut self, round_id: u64, signer: &Keypair) -> TransactionResult {
+        let ix = ix(
+            accounts::BeginResolution {
+                authority: signer.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+            },
+            instruction::BeginResolution {
+                result_commitment: None,
+                arcium_comp_id: None,
+            },
+        );
+        self.send(&[ix], &[signer])
+    }
+
+    pub fn commit_settlement(
+        &mut self,
+        round_id: u64,
+        result_commitment: [u8; 32],
+        total_payout: u64,
+    ) -> TransactionResult {
+        self.commit_settlement_with_fees(round_id, result_commitment, total_payout, 0)
+    }
+
+    /// `commit_settlement` for a result that kept `fee_total` for the house.
+    pub fn commit_settlement_with_fees(
+        &mut self,
+        round_id: u64,
+        result_commitment: [u8; 32],
+        total_payout: u64,
+        fee_total: u64,
+    ) -> TransactionResult {
+        let settlement_authority = self.settlement_authority.insecure_clone();
+        let ix = ix(
+            accounts::CommitSettlement {
+                settlement_authority: settlement_authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+            },
+            instruction::CommitSettlement {
+                result_commitment,
+                total_payout,
+                fee_total,
+                randomness_seed: None,
+                seed_proof: vec![],
+            },
+        );
+        self.send(&[ix], &[&settlement_authority])
+    }
+
+    /// `commit_settlement` for a round with draws; `seed_proof` proves the
+    /// seed's leaf under `result_commitment`.
+    pub fn commit_seeded_settlement(
+        &mut self,
+        round_id: u64,
+        result_commitment: [u8; 32],
+        total_payout: u64,
+        randomness_seed: Option<[u8; 32]>,
+        seed_proof: Vec<[u8; 32]>,
+    ) -> TransactionResult {
+        let settlement_authority = self.settlement_authority.insecure_clone();
+        let ix = ix(
+            accounts::CommitSettlement {
+                settlement_authority: settlement_authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+            },
+            instruction::CommitSettlement {
+                result_commitment,
+                total_payout,
+                fee_total: 0,
+                randomness_seed,
+                seed_proof,
+            },
+        );
+        self.send(&[ix], &[&settlement_authority])
+    }
+
+    pub fn begin_chunked_resolution(
+        &mut self,
+        round_id: u64,
+        chunk_count: u16,
+    ) -> TransactionResult {
+        let settlement_authority = self.settlement_authority.insecure_clone();
+        let ix = ix(
+            accounts::BeginChunkedResolution {
+                settlement_authority: settlement_authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+                resolution_chunks: resolution_chunks_pda(round_id),
+                system_program: system_program::ID,
+            },
+            instruction::BeginChunkedResolution { chunk_count },
+        );
+        self.send(&[ix], &[&settlement_authority])
+    }
+
+    /// Records phase one of `chunk_index`, covering `chunk_entries` entries
+    /// worth `chunk_stake`, with a dummy scan state.
+    pub fn record_chunk_scan(
+        &mut self,
+        round_id: u64,
+        chunk_index: u16,
+        chunk_entries: u32,
+        chunk_stake: u64,
+    ) -> TransactionResult {
+        let settlement_authority = self.settlement_authority.insecure_clone();
+        let ix = ix(
+            self.record_chunk_accounts(round_id),
+            instruction::RecordChunkScan {
+                chunk_index,
+                scan_state: [[0; 32]; SCAN_STATE_LEN],
+                nonce: 0,
+                chunk_entries,
+                chunk_stake,
+                arcium_comp_id: None,
+            },
+        );
+        self.send(&[ix], &[&settlement_authority])
+    }
+
+    /// Records phase two of `chunk_index`; `entries` is
+    /// `(chunk_entries, scanned_entries)`.
+    pub fn record_chunk_settlement(
+        &mut self,
+        round_id: u64,
+        chunk_index: u16,
+        chunk_root: [u8; 32],
+        chunk_payout: u64,
+        entries: (u32, u32),
+        scanned_stake: u64,
+    ) -> TransactionResult {
+        let settlement_authority = self.settlement_authority.insecure_clone();
+        let ix = ix(
+            self.record_chunk_accounts(round_id),
+            instruction::RecordChunkSettlement {
+                chunk_index,
+                chunk_root,
+                chunk_payout,
+                chunk_fees: 0,
+                chunk_entries: entries.0,
+                scanned_entries: entries.1,
+                scanned_stake,
+            },
+        );
+        self.send(&[ix], &[&settlement_authority])
+    }
+
+    fn record_chunk_accounts(&self, round_id: u64) -> accounts::RecordChunkResolution {
+        accounts::RecordChunkResolution {
+            settlement_authority: self.settlement_authority.pubkey(),
+            config: config_pda(),
+            round: round_pda(round_id),
+            resolution_chunks: resolution_chunks_pda(round_id),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn settle(
+        &mut self,
+        round_id: u64,
+        prediction: Pubkey,
+        recipient: &User,
+        payout: u64,
+        commitment: [u8; 32],
+        blinding: [u8; 32],
+        proof: Vec<[u8; 32]>,
+    ) -> TransactionResult {
+        let ix = ix(
+            accounts::SettlePrediction {
+                round: round_pda(round_id),
+                prediction,
+                escrow_vault: escrow_pda(round_id),
+                recipient_token_account: recipient.token_account,
+                token_program: spl_token::ID,
+            },
+            instruction::SettlePrediction {
+                payout,
+                commitment,
+                blinding,
+                proof,
+            },
+        );
+        // Claims are permissionless; the recipient pays for their own.
+        self.send(&[ix], &[&recipient.keypair])
+    }
+
+    pub fn finalize(&mut self, round_id: u64, final_price: i64) -> TransactionResult {
+        let settlement_authority = self.settlement_authority.insecure_clone();
+        let ix = ix(
+            accounts::FinalizeRound {
+                settlement_authority: settlement_authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+            },
+            instruction::FinalizeRound {
+                final_price,
+                timestamp: START_TS + 700,
+            },
+        );
+        self.send(&[ix], &[&settlement_authority])
+    }
+
+    pub fn mark_refunded(&mut self, round_id: u64) -> TransactionResult {
+        let authority = self.authority.insecure_clone();
+        let ix = ix(
+            accounts::MarkRoundRefunded {
+                authority: authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+            },
+            instruction::MarkRoundRefunded {},
+        );
+        self.send(&[ix], &[&authority])
+    }
+
+    /// Rewrites a round and one of its predictions the way the first
+    /// deployment stored them: only the original fields, zero-padded to the
+    /// original sizes.
+    pub fn downgrade_to_legacy(&mut self, round_id: u64, prediction: &Pubkey) {
+        let round = self.round(round_id);
+        let mut data = Round::DISCRIMINATOR.to_vec();
+        (
+            round.round_id,
+            round.start_ts,
+            round.end_ts,
+            round.status,
+            round.token_mint,
+            round.escrow_vault,
+            round.total_stake,
+            round.total_paid,
+        )
+            .serialize(&mut data)
+            .unwrap();
+        (
+            round.final_price,
+            round.settlement_timestamp,
+            round.pyth_price_account,
+            round.arcium_comp_id,
+            round.result_commitment,
+            round.bump,
+            round.escrow_bump,
+        )
+            .serialize(&mut data)
+            .unwrap();
+        self.set_legacy_data(&round_pda(round_id), data, Round::LEGACY_SPACE);
+
+        let entry = self.prediction(prediction);
+        let mut data = Prediction::DISCRIMINATOR.to_vec();
+        (
+            entry.round,
+            entry.owner,
+            entry.token_mint,
+            entry.commitment,
+            entry.stake,
+            entry.window_index,
+            entry.status,
+            entry.prediction_index,
+            entry.bump,
+        )
+            .serialize(&mut data)
+            .unwrap();
+        self.set_legacy_data(prediction, data, Prediction::LEGACY_SPACE);
+    }
+
+    fn set_legacy_data(&mut self, address: &Pubkey, mut data: Vec<u8>, space: usize) {
+        data.resize(space, 0);
+        let mut account = self.svm.get_account(address).expect("account exists");
+        account.lamports = self.svm.minimum_balance_for_rent_exemption(space);
+        account.data = data;
+        self.svm.set_account(*address, account).unwrap();
+    }
+
+    pub fn migrate_round(&mut self, round_id: u64) -> TransactionResult {
+        let authority = self.authority.insecure_clone();
+        let ix = ix(
+            accounts::MigrateRound {
+                authority: authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+                system_program: system_program::ID,
+            },
+            instruction::MigrateRound {
+                _round_id: round_id,
+            },
+        );
+        self.send(&[ix], &[&authority])
+    }
+
+    pub fn migrate_prediction(&mut self, prediction: Pubkey) -> TransactionResult {
+        let authority = self.authority.insecure_clone();
+        let ix = ix(
+            accounts::MigratePrediction {
+                authority: authority.pubkey(),
+                config: config_pda(),
+                prediction,
+                system_program: system_program::ID,
+            },
+            instruction::MigratePrediction {},
+        );
+        self.send(&[ix], &[&authority])
+    }
+
+    pub fn refund(
+        &mut self,
+        round_id: u64,
+        prediction: Pubkey,
+        recipient: &User,
+    ) -> TransactionResult {
+        let ix = ix(
+            accounts::RefundPrediction {
+                round: round_pda(round_id),
+                prediction,
+                escrow_vault: escrow_pda(round_id),
+                user_token_account: recipient.token_account,
+                token_program: spl_token::ID,
+            },
+            instruction::RefundPrediction {},
+        );
+        self.send(&[ix], &[&recipient.keypair])
+    }
+
+    pub fn configure_price_sources(
+        &mut self,
+        round_id: u64,
+        sources: Vec<PriceSource>,
+        min_price_sources: u8,
+    ) -> TransactionResult {
+        let authority = self.authority.insecure_clone();
+        let ix = ix(
+            accounts::ConfigurePriceSources {
+                authority: authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+            },
+            instruction::ConfigurePriceSources {
+                sources,
+                min_price_sources,
+                max_price_age_secs: DEFAULT_MAX_PRICE_AGE_SECS,
+                max_price_deviation_bps: DEFAULT_MAX_PRICE_DEVIATION_BPS,
+                max_price_age_slots: DEFAULT_MAX_PRICE_AGE_SLOTS,
+                min_pull_signatures: 0,
+            },
+        );
+        self.send(&[ix], &[&authority])
+    }
+
+    pub fn configure_twap(&mut self, round_id: u64, window_secs: u32) -> TransactionResult {
+        let authority = self.authority.insecure_clone();
+        let ix = ix(
+            accounts::ConfigureTwap {
+                authority: authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+                observations: observations_pda(round_id),
+                system_program: system_program::ID,
+            },
+            instruction::ConfigureTwap { window_secs },
+        );
+        self.send(&[ix], &[&authority])
+    }
+
+    /// Records a TWAP observation from `price_accounts` and `attestations`,
+    /// paid for by the authority. Attestations issued by one of `oracles` are
+    /// signed in the same transaction; the rest go in unsigned.
+    pub fn record_price_observation(
+        &mut self,
+        round_id: u64,
+        price_accounts: &[Pubkey],
+        attestations: Vec<PriceAttestation>,
+        oracles: &[&Keypair],
+    ) -> TransactionResult {
+        let ix = ix(
+            accounts::RecordPriceObservation {
+                round: round_pda(round_id),
+                observations: observations_pda(round_id),
+                instructions_sysvar: sysvar::instructions::ID,
+            },
+            instruction::RecordPriceObservation {
+                attestations: attestations.clone(),
+            },
+        );
+        self.send_priced(ix, price_accounts, &attestations, oracles)
+    }
+
+    /// Warps to `ts` and records an observation of `price` attested by
+    /// `oracle` at `ts`.
+    pub fn observe(
+        &mut self,
+        round_id: u64,
+        oracle: &Keypair,
+        ts: i64,
+        price: i64,
+    ) -> TransactionResult {
+        self.warp_to(ts);
+        self.record_price_observation(
+            round_id,
+            &[],
+            vec![attestation(oracle, round_id, price, ts)],
+            &[oracle],
+        )
+    }
+
+    /// Resolves the round price like `record_price_observation`. TWAP rounds
+    /// get their observation buffer passed in.
+    pub fn resolve_round_price(
+        &mut self,
+        round_id: u64,
+        price_accounts: &[Pubkey],
+        attestations: Vec<PriceAttestation>,
+        oracles: &[&Keypair],
+    ) -> TransactionResult {
+        let twap = self.round(round_id).resolution_mode == ResolutionMode::Twap as u8;
+        let ix = ix(
+            accounts::ResolveRoundPrice {
+                round: round_pda(round_id),
+                observations: twap.then(|| observations_pda(round_id)),
+                instructions_sysvar: sysvar::instructions::ID,
+            },
+            instruction::ResolveRoundPrice {
+                attestations: attestations.clone(),
+            },
+        );
+        self.send_priced(ix, price_accounts, &attestations, oracles)
+    }
+
+    fn send_priced(
+        &mut self,
+        mut ix: Instruction,
+        price_accounts: &[Pubkey],
+        attestations: &[PriceAttestation],
+        oracles: &[&Keypair],
+    ) -> TransactionResult {
+        ix.accounts.extend(
+            price_accounts
+                .iter()
+                .map(|account| AccountMeta::new_readonly(*account, false)),
+        );
+        let mut ixs: Vec<Instruction> = attestations
+            .iter()
+            .filter_map(|attestation| {
+                oracles
+                    .iter()
+                    .find(|oracle| oracle.pubkey() == attestation.signer)
+                    .map(|oracle| attestation_ix(oracle, attestation))
+            })
+            .collect();
+        ixs.push(ix);
+        let authority = self.authority.insecure_clone();
+        self.send(&ixs, &[&authority])
+    }
+
+    /// Stores a fully verified Pyth `PriceUpdateV2` for `feed_id`, posted at
+    /// the current slot, and returns its address.
+    pub fn post_pull_update(
+        &mut self,
+        feed_id: [u8; 32],
+        price: i64,
+        publish_time: i64,
+        prev_publish_time: i64,
+    ) -> Pubkey {
+        let mut data = vec![34, 241, 35, 99, 157, 126, 244, 205];
+        data.extend_from_slice(&Pubkey::new_unique().to_bytes());
+        // `VerificationLevel::Full`.
+        data.push(1);
+        data.extend_from_slice(&feed_id);
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&PRICE_EXPO.to_le_bytes());
+        data.extend_from_slice(&publish_time.to_le_bytes());
+        data.extend_from_slice(&prev_publish_time.to_le_bytes());
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&self.svm.get_sysvar::<Clock>().slot.to_le_bytes());
+
+        let address = Pubkey::new_unique();
+        let account = Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: PYTH_RECEIVER_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.svm.set_account(address, account).unwrap();
+        address
+    }
+
+    /// Switches the round to an accumulator over 16 buckets of `bucket_width`
+    /// from zero, with a dummy initial ciphertext.
+    pub fn init_round_accumulator(
+        &mut self,
+        round_id: u64,
+        bucket_width: u64,
+    ) -> TransactionResult {
+        let authority = self.authority.insecure_clone();
+        let ix = ix(
+            accounts::InitRoundAccumulator {
+                authority: authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+                accumulator: accumulator_pda(round_id),
+                system_program: system_program::ID,
+            },
+            instruction::InitRoundAccumulator {
+                bucket_origin: 0,
+                bucket_width,
+                initial_state: [[0; 32]; ACCUMULATOR_STATE_LEN],
+                nonce: 0,
+            },
+        );
+        self.send(&[ix], &[&authority])
+    }
+
+    /// Records a fold of `prediction` into the accumulator with a dummy state.
+    pub fn fold(&mut self, round_id: u64, prediction: Pubkey) -> TransactionResult {
+        let settlement_authority = self.settlement_authority.insecure_clone();
+        let ix = ix(
+            accounts::FoldPrediction {
+                settlement_authority: settlement_authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+                accumulator: accumulator_pda(round_id),
+                prediction,
+            },
+            instruction::FoldPrediction {
+                new_state: [[0; 32]; ACCUMULATOR_STATE_LEN],
+                nonce: 0,
+                arcium_comp_id: None,
+            },
+        );
+        self.send(&[ix], &[&settlement_authority])
+    }
+
+    pub fn record_entry_settlement(
+        &mut self,
+        round_id: u64,
+        prediction: Pubkey,
+        leaf: [u8; 32],
+    ) -> TransactionResult {
+        let settlement_authority = self.settlement_authority.insecure_clone();
+        let ix = ix(
+            accounts::RecordEntrySettlement {
+                settlement_authority: settlement_authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+                prediction,
+            },
+            instruction::RecordEntrySettlement { leaf },
+        );
+        self.send(&[ix], &[&settlement_authority])
+    }
+
+    pub fn configure_round(
+        &mut self,
+        round_id: u64,
+        data: impl InstructionData,
+    ) -> TransactionResult {
+        let authority = self.authority.insecure_clone();
+        let ix = ix(
+            accounts::ConfigureRound {
+                authority: authority.pubkey(),
+                config: config_pda(),
+                round: round_pda(round_id),
+            },
+            data,
+        );
+        self.send(&[ix], &[&authority])
+    }
+}
+
+/// Asserts that a transaction failed with the given program error.
+pub fn assert_error(result: TransactionResult, expected: ErrorCode) {
+    let expected_code = u32::from(expected);
+    match result {
+        Err(FailedTransactionMetadata {
+            err: TransactionError::InstructionError(_, InstructionError::Custom(code)),
+            meta,
+        }) => assert_eq!(
+            code, expected_code,
+            "expected error {expected_code}, got {code}; logs: {:#?}",
+            meta.logs
+        ),
+        Err(other) => panic!("expected error {expected_code}, got {:?}", other.err),
+        Ok(meta) => panic!(
+            "expected error {expected_code}, transaction succeeded; logs: {:#?}",
+            meta.logs
+        ),
+    }
+}
+
+pub fn commitment(tag: u8) -> [u8; 32] {
+    [tag; 32]
+}
@@ -0,0 +1,1065 @@
+mod common;
+
+use anchor_lang::system_program;
+use common::*;
+use micro_prediction::oracle::{PriceSource, PriceSourceKind};
+use micro_prediction::{
+    accounts, draw, instruction, merkle, ErrorCode, RoundStatsInput, RoundStatus,
+    MAX_AMENDMENT_FEE, MAX_PAYOUT_TIERS, ROUND_STATS_SEED, SENTIMENT_BUCKETS,
+};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+const ROUND_ID: u64 = 1;
+
+/// Initialized env with round `ROUND_ID` open, one 100-token entry from the
+/// returned user, and the clock past the end of the round.
+fn ended_round_with_entry() -> (TestEnv, User) {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let user = env.new_user(1_000);
+    env.submit(ROUND_ID, &user, 0, commitment(1), 100).unwrap();
+    env.warp_to(START_TS + 601);
+    (env, user)
+}
+
+fn resolving_round_with_entry() -> (TestEnv, User) {
+    let (mut env, user) = ended_round_with_entry();
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    (env, user)
+}
+
+/// Initialized env with round `ROUND_ID` open and priced by a single
+/// attestation key, which is returned.
+fn attested_round() -> (TestEnv, Keypair) {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let oracle = Keypair::new();
+    let source = PriceSource {
+        kind: PriceSourceKind::SignedAttestation as u8,
+        key: oracle.pubkey(),
+    };
+    env.configure_price_sources(ROUND_ID, vec![source], 1)
+        .unwrap();
+    (env, oracle)
+}
+
+/// `attested_round` resolved by a TWAP over the last 320 seconds, so
+/// observations must be at least 10 seconds apart.
+fn twap_round() -> (TestEnv, Keypair) {
+    let (mut env, oracle) = attested_round();
+    env.configure_twap(ROUND_ID, 320).unwrap();
+    (env, oracle)
+}
+
+fn record_round_stats_ix(env: &TestEnv, bucket_width: u64, median_price: u64) -> Instruction {
+    let round_stats = Pubkey::find_program_address(
+        &[ROUND_STATS_SEED, &ROUND_ID.to_le_bytes()],
+        &micro_prediction::ID,
+    )
+    .0;
+    ix(
+        accounts::RecordRoundStats {
+            settlement_authority: env.settlement_authority.pubkey(),
+            config: config_pda(),
+            round: round_pda(ROUND_ID),
+            round_stats,
+            system_program: system_program::ID,
+        },
+        instruction::RecordRoundStats {
+            stats: RoundStatsInput {
+                participant_count: 3,
+                total_stake: 300,
+                weighted_mean_price: 50_000,
+                median_price,
+                bucket_origin: 49_000,
+                bucket_width,
+                histogram: [0; SENTIMENT_BUCKETS],
+            },
+            arcium_comp_id: None,
+        },
+    )
+}
+
+#[test]
+fn initialize_rejects_fee_above_100_percent() {
+    let mut env = TestEnv::new();
+    assert_error(env.initialize(10_001), ErrorCode::InvalidFeeBps);
+}
+
+#[test]
+fn initialize_round_rejects_empty_window() {
+    let mut env = TestEnv::initialized();
+    assert_error(
+        env.initialize_round(ROUND_ID, START_TS, START_TS),
+        ErrorCode::InvalidRoundWindow,
+    );
+}
+
+#[test]
+fn initialize_round_rejects_start_in_the_past() {
+    let mut env = TestEnv::initialized();
+    assert_error(
+        env.initialize_round(ROUND_ID, START_TS - 5_000, START_TS),
+        ErrorCode::RoundAlreadyActive,
+    );
+}
+
+#[test]
+fn submit_before_start_is_rejected() {
+    let mut env = TestEnv::initialized();
+    env.initialize_round(ROUND_ID, START_TS, START_TS + 600)
+        .unwrap();
+    let user = env.new_user(1_000);
+    assert_error(
+        env.submit(ROUND_ID, &user, 0, commitment(1), 100),
+        ErrorCode::RoundNotStarted,
+    );
+}
+
+#[test]
+fn submit_after_end_is_rejected() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    env.warp_to(START_TS + 601);
+    let user = env.new_user(1_000);
+    assert_error(
+        env.submit(ROUND_ID, &user, 0, commitment(1), 100),
+        ErrorCode::RoundClosed,
+    );
+}
+
+#[test]
+fn submit_rejects_zero_stake() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let user = env.new_user(1_000);
+    assert_error(
+        env.submit(ROUND_ID, &user, 0, commitment(1), 0),
+        ErrorCode::InvalidStakeAmount,
+    );
+}
+
+#[test]
+fn submit_into_resolving_round_is_rejected() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    let user = env.new_user(1_000);
+    assert_error(
+        env.submit(ROUND_ID, &user, 0, commitment(1), 100),
+        ErrorCode::RoundNotOpen,
+    );
+}
+
+#[test]
+fn cancel_twice_is_rejected() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let user = env.new_user(1_000);
+    env.submit(ROUND_ID, &user, 0, commitment(1), 100).unwrap();
+    env.cancel(ROUND_ID, &user, 0).unwrap();
+    assert_error(
+        env.cancel(ROUND_ID, &user, 0),
+        ErrorCode::PredictionFinalized,
+    );
+}
+
+#[test]
+fn cancel_after_end_is_rejected() {
+    let (mut env, user) = ended_round_with_entry();
+    assert_error(env.cancel(ROUND_ID, &user, 0), ErrorCode::RoundClosed);
+}
+
+#[test]
+fn begin_resolution_requires_the_authority() {
+    let (mut env, _) = ended_round_with_entry();
+    let intruder = Keypair::new();
+    env.svm.airdrop(&intruder.pubkey(), 1_000_000_000).unwrap();
+    assert_error(
+        env.begin_resolution(ROUND_ID, &intruder),
+        ErrorCode::Unauthorized,
+    );
+}
+
+#[test]
+fn commit_settlement_requires_resolving_round() {
+    let (mut env, _) = ended_round_with_entry();
+    assert_error(
+        env.commit_settlement(ROUND_ID, [1; 32], 0),
+        ErrorCode::RoundNotResolving,
+    );
+}
+
+#[test]
+fn commit_settlement_cannot_exceed_the_pool() {
+    let (mut env, _) = resolving_round_with_entry();
+    assert_error(
+        env.commit_settlement(ROUND_ID, [1; 32], 101),
+        ErrorCode::InsufficientEscrow,
+    );
+}
+
+#[test]
+fn commit_settlement_only_once() {
+    let (mut env, _) = resolving_round_with_entry();
+    env.commit_settlement(ROUND_ID, [1; 32], 50).unwrap();
+    assert_error(
+        env.commit_settlement(ROUND_ID, [2; 32], 50),
+        ErrorCode::SettlementAlreadyCommitted,
+    );
+}
+
+#[test]
+fn settle_requires_a_committed_result() {
+    let (mut env, user) = resolving_round_with_entry();
+    let prediction = prediction_pda(ROUND_ID, &user.pubkey(), 0);
+    assert_error(
+        env.settle(
+            ROUND_ID,
+            prediction,
+            &user,
+            50,
+            commitment(1),
+            [0; 32],
+            vec![],
+        ),
+        ErrorCode::SettlementNotCommitted,
+    );
+}
+
+#[test]
+fn settle_on_open_round_is_rejected() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let user = env.new_user(1_000);
+    env.submit(ROUND_ID, &user, 0, commitment(1), 100).unwrap();
+    let prediction = prediction_pda(ROUND_ID, &user.pubkey(), 0);
+    assert_error(
+        env.settle(
+            ROUND_ID,
+            prediction,
+            &user,
+            50,
+            commitment(1),
+            [0; 32],
+            vec![],
+        ),
+        ErrorCode::RoundNotResolving,
+    );
+}
+
+#[test]
+fn settle_checks_commitment_and_proof() {
+    let (mut env, user) = resolving_round_with_entry();
+    let prediction = prediction_pda(ROUND_ID, &user.pubkey(), 0);
+    let leaf = merkle::payout_leaf(&prediction.to_bytes(), 90, &[9; 32]);
+    env.commit_settlement(ROUND_ID, merkle::root(&[leaf]), 90)
+        .unwrap();
+
+    assert_error(
+        env.settle(
+            ROUND_ID,
+            prediction,
+            &user,
+            90,
+            commitment(2),
+            [9; 32],
+            vec![],
+        ),
+        ErrorCode::CommitmentMismatch,
+    );
+    assert_error(
+        env.settle(
+            ROUND_ID,
+            prediction,
+            &user,
+            95,
+            commitment(1),
+            [9; 32],
+            vec![],
+        ),
+        ErrorCode::InvalidPayoutProof,
+    );
+    env.settle(
+        ROUND_ID,
+        prediction,
+        &user,
+        90,
+        commitment(1),
+        [9; 32],
+        vec![],
+    )
+    .unwrap();
+    assert_error(
+        env.settle(
+            ROUND_ID,
+            prediction,
+            &user,
+            90,
+            commitment(1),
+            [9; 32],
+            vec![],
+        ),
+        ErrorCode::PredictionFinalized,
+    );
+}
+
+#[test]
+fn settle_rejects_prediction_from_another_round() {
+    let mut env = TestEnv::initialized();
+    env.initialize_round(ROUND_ID, START_TS, START_TS + 600)
+        .unwrap();
+    env.initialize_round(ROUND_ID + 1, START_TS, START_TS + 600)
+        .unwrap();
+    env.warp_to(START_TS + 10);
+    let user = env.new_user(1_000);
+    env.submit(ROUND_ID, &user, 0, commitment(1), 100).unwrap();
+    env.submit(ROUND_ID + 1, &user, 0, commitment(1), 100)
+        .unwrap();
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    env.commit_settlement(ROUND_ID, [1; 32], 0).unwrap();
+
+    let other_round_prediction = prediction_pda(ROUND_ID + 1, &user.pubkey(), 0);
+    assert_error(
+        env.settle(
+            ROUND_ID,
+            other_round_prediction,
+            &user,
+            0,
+            commitment(1),
+            [0; 32],
+            vec![],
+        ),
+        ErrorCode::RoundMismatch,
+    );
+}
+
+#[test]
+fn finalized_round_cannot_be_refunded() {
+    let (mut env, _) = resolving_round_with_entry();
+    env.finalize(ROUND_ID, 1).unwrap();
+    assert_error(env.mark_refunded(ROUND_ID), ErrorCode::RoundAlreadySettled);
+}
+
+#[test]
+fn paid_round_cannot_be_refunded() {
+    let (mut env, user) = resolving_round_with_entry();
+    let prediction = prediction_pda(ROUND_ID, &user.pubkey(), 0);
+    let leaf = merkle::payout_leaf(&prediction.to_bytes(), 90, &[9; 32]);
+    env.commit_settlement(ROUND_ID, merkle::root(&[leaf]), 90)
+        .unwrap();
+    env.settle(
+        ROUND_ID,
+        prediction,
+        &user,
+        90,
+        commitment(1),
+        [9; 32],
+        vec![],
+    )
+    .unwrap();
+
+    assert_error(env.mark_refunded(ROUND_ID), ErrorCode::ClaimsAlreadyPaid);
+    assert_eq!(env.round(ROUND_ID).status, RoundStatus::Resolving as u8);
+}
+
+#[test]
+fn refund_requires_a_refunded_round() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let user = env.new_user(1_000);
+    env.submit(ROUND_ID, &user, 0, commitment(1), 100).unwrap();
+    let prediction = prediction_pda(ROUND_ID, &user.pubkey(), 0);
+    assert_error(
+        env.refund(ROUND_ID, prediction, &user),
+        ErrorCode::InvalidRoundState,
+    );
+
+    env.warp_to(START_TS + 601);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    assert_error(
+        env.refund(ROUND_ID, prediction, &user),
+        ErrorCode::InvalidRoundState,
+    );
+}
+
+#[test]
+fn refund_only_pays_the_owner() {
+    let (mut env, user) = resolving_round_with_entry();
+    env.mark_refunded(ROUND_ID).unwrap();
+    let thief = env.new_user(0);
+    let prediction = prediction_pda(ROUND_ID, &user.pubkey(), 0);
+    assert_error(
+        env.refund(ROUND_ID, prediction, &thief),
+        ErrorCode::Unauthorized,
+    );
+}
+
+#[test]
+fn round_rules_are_validated() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+
+    assert_error(
+        env.configure_round(
+            ROUND_ID,
+            instruction::SetPayoutTiers {
+                payout_tiers: vec![1_000; MAX_PAYOUT_TIERS + 1],
+            },
+        ),
+        ErrorCode::InvalidPayoutTiers,
+    );
+    assert_error(
+        env.configure_round(
+            ROUND_ID,
+            instruction::SetRoundType {
+                round_type: 1,
+                interval_alpha_bps: 0,
+                max_interval_penalty: 1,
+            },
+        ),
+        ErrorCode::InvalidScoringParams,
+    );
+    assert_error(
+        env.configure_round(
+            ROUND_ID,
+            instruction::SetEarlyBonus {
+                early_bonus_bps: 10_001,
+            },
+        ),
+        ErrorCode::InvalidEarlyBonus,
+    );
+    assert_error(
+        env.configure_round(
+            ROUND_ID,
+            instruction::SetDrawRules {
+                tie_break: 2,
+                lucky_draw_bps: 0,
+            },
+        ),
+        ErrorCode::InvalidDrawRules,
+    );
+}
+
+#[test]
+fn round_rules_freeze_once_staked() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let user = env.new_user(1_000);
+    env.submit(ROUND_ID, &user, 0, commitment(1), 100).unwrap();
+
+    assert_error(
+        env.configure_round(
+            ROUND_ID,
+            instruction::SetPayoutTiers {
+                payout_tiers: vec![6_000, 4_000],
+            },
+        ),
+        ErrorCode::RoundAlreadyStaked,
+    );
+}
+
+#[test]
+fn accumulator_rounds_take_no_custom_rules() {
+    let mut env = TestEnv::initialized();
+    env.initialize_round(ROUND_ID + 1, START_TS, START_TS + 600)
+        .unwrap();
+    env.open_round(ROUND_ID);
+    env.configure_round(
+        ROUND_ID,
+        instruction::SetEarlyBonus {
+            early_bonus_bps: 5_000,
+        },
+    )
+    .unwrap();
+    assert_error(
+        env.init_round_accumulator(ROUND_ID, 100),
+        ErrorCode::InvalidAccumulator,
+    );
+
+    env.init_round_accumulator(ROUND_ID + 1, 100).unwrap();
+    assert_error(
+        env.configure_round(
+            ROUND_ID + 1,
+            instruction::SetPayoutTiers {
+                payout_tiers: vec![6_000, 4_000],
+            },
+        ),
+        ErrorCode::InvalidAccumulator,
+    );
+    assert_error(
+        env.configure_round(
+            ROUND_ID + 1,
+            instruction::SetDrawRules {
+                tie_break: 0,
+                lucky_draw_bps: 500,
+            },
+        ),
+        ErrorCode::InvalidAccumulator,
+    );
+}
+
+#[test]
+fn chunked_resolution_requires_plain_rules() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    env.configure_round(
+        ROUND_ID,
+        instruction::SetPayoutTiers {
+            payout_tiers: vec![6_000, 4_000],
+        },
+    )
+    .unwrap();
+    let user = env.new_user(1_000);
+    env.submit(ROUND_ID, &user, 0, commitment(1), 100).unwrap();
+    env.warp_to(START_TS + 601);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+
+    assert_error(
+        env.begin_chunked_resolution(ROUND_ID, 1),
+        ErrorCode::InvalidChunkLayout,
+    );
+}
+
+#[test]
+fn chunks_must_cover_every_scanned_entry() {
+    let (mut env, _) = resolving_round_with_entry();
+    env.begin_chunked_resolution(ROUND_ID, 1).unwrap();
+    env.record_chunk_scan(ROUND_ID, 0, 2, 100).unwrap();
+
+    // More leaves than a chunk may hold means the relayer packed it wrong.
+    assert_error(
+        env.record_chunk_settlement(ROUND_ID, 0, [1; 32], 50, (33, 33), 100),
+        ErrorCode::InvalidChunkLayout,
+    );
+    // The last chunk must bring the leaf count up to the scanned entries.
+    assert_error(
+        env.record_chunk_settlement(ROUND_ID, 0, [1; 32], 50, (1, 2), 100),
+        ErrorCode::InvalidChunkLayout,
+    );
+    env.record_chunk_settlement(ROUND_ID, 0, [1; 32], 50, (2, 2), 100)
+        .unwrap();
+}
+
+#[test]
+fn draws_require_a_published_seed() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    env.configure_round(
+        ROUND_ID,
+        instruction::SetDrawRules {
+            tie_break: 1,
+            lucky_draw_bps: 0,
+        },
+    )
+    .unwrap();
+    let user = env.new_user(1_000);
+    env.submit(ROUND_ID, &user, 0, commitment(1), 100).unwrap();
+    env.warp_to(START_TS + 601);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+
+    assert_error(
+        env.commit_settlement(ROUND_ID, [1; 32], 50),
+        ErrorCode::MissingRandomnessSeed,
+    );
+
+    // The seed must be the one the payout root commits to.
+    let seed = [7; 32];
+    let leaf = merkle::payout_leaf(&Pubkey::new_unique().to_bytes(), 50, &[3; 32]);
+    let root = merkle::root(&[leaf, draw::seed_leaf(&seed)]);
+    assert_error(
+        env.commit_seeded_settlement(ROUND_ID, root, 50, Some([8; 32]), vec![leaf]),
+        ErrorCode::InvalidRandomnessSeed,
+    );
+    env.commit_seeded_settlement(ROUND_ID, root, 50, Some(seed), vec![leaf])
+        .unwrap();
+    assert_eq!(env.round(ROUND_ID).randomness_seed, Some(seed));
+}
+
+#[test]
+fn collect_fees_requires_the_authority() {
+    let mut env = TestEnv::initialized();
+    env.init_rollover_vault().unwrap();
+    let funder = env.new_user(1_000);
+    env.top_up_rollover(&funder, 600).unwrap();
+
+    assert_error(
+        env.collect_fees(&funder.keypair, 100),
+        ErrorCode::Unauthorized,
+    );
+    let authority = env.authority.insecure_clone();
+    assert_error(
+        env.collect_fees(&authority, 0),
+        ErrorCode::InvalidStakeAmount,
+    );
+    // A top-up is jackpot, not house revenue.
+    assert_error(
+        env.collect_fees(&authority, 100),
+        ErrorCode::InsufficientAccruedFees,
+    );
+}
+
+#[test]
+fn price_sources_freeze_once_staked() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let user = env.new_user(1_000);
+    env.submit(ROUND_ID, &user, 0, commitment(1), 100).unwrap();
+
+    let source = PriceSource {
+        kind: PriceSourceKind::SignedAttestation as u8,
+        key: Pubkey::new_unique(),
+    };
+    assert_error(
+        env.configure_price_sources(ROUND_ID, vec![source], 1),
+        ErrorCode::RoundAlreadyStaked,
+    );
+}
+
+#[test]
+fn resolution_requires_a_quorum_of_sources() {
+    let (mut env, _) = ended_round_with_entry();
+    assert_error(
+        env.resolve_round_price(ROUND_ID, &[], vec![], &[]),
+        ErrorCode::InsufficientPriceSources,
+    );
+}
+
+#[test]
+fn twap_window_freezes_once_staked() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let user = env.new_user(1_000);
+    env.submit(ROUND_ID, &user, 0, commitment(1), 100).unwrap();
+
+    assert_error(
+        env.configure_twap(ROUND_ID, 300),
+        ErrorCode::RoundAlreadyStaked,
+    );
+}
+
+#[test]
+fn amendment_fee_is_capped() {
+    let mut env = TestEnv::initialized();
+    let authority = env.authority.insecure_clone();
+    let set_fee = ix(
+        accounts::UpdateConfig {
+            authority: authority.pubkey(),
+            config: config_pda(),
+        },
+        instruction::SetAmendmentFee {
+            amendment_fee: MAX_AMENDMENT_FEE + 1,
+        },
+    );
+    assert_error(
+        env.send(&[set_fee], &[&authority]),
+        ErrorCode::InvalidAmendmentFee,
+    );
+}
+
+#[test]
+fn current_config_needs_no_migration() {
+    let mut env = TestEnv::initialized();
+    let authority = env.authority.insecure_clone();
+    let migrate = ix(
+        accounts::MigrateConfig {
+            authority: authority.pubkey(),
+            config: config_pda(),
+            system_program: system_program::ID,
+        },
+        instruction::MigrateConfig {},
+    );
+    assert_error(
+        env.send(&[migrate], &[&authority]),
+        ErrorCode::ConfigUpToDate,
+    );
+}
+
+#[test]
+fn current_round_needs_no_migration() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    assert_error(env.migrate_round(ROUND_ID), ErrorCode::AccountUpToDate);
+}
+
+#[test]
+fn sentiment_buckets_must_scale_with_the_price() {
+    let (mut env, _) = ended_round_with_entry();
+    let settlement_authority = env.settlement_authority.insecure_clone();
+
+    // 1% of the weighted mean is the narrowest accepted bucket.
+    assert_error(
+        env.send(
+            &[record_round_stats_ix(&env, 499, 0)],
+            &[&settlement_authority],
+        ),
+        ErrorCode::InvalidRoundStats,
+    );
+    env.send(
+        &[record_round_stats_ix(&env, 500, 50_250)],
+        &[&settlement_authority],
+    )
+    .unwrap();
+}
+
+#[test]
+fn sentiment_median_is_a_bucket_centre() {
+    let (mut env, _) = ended_round_with_entry();
+    let settlement_authority = env.settlement_authority.insecure_clone();
+
+    // An exact price inside bucket 2 of [49_000, 49_500, 50_000, ...).
+    assert_error(
+        env.send(
+            &[record_round_stats_ix(&env, 500, 50_001)],
+            &[&settlement_authority],
+        ),
+        ErrorCode::InvalidRoundStats,
+    );
+    // Past the last bucket.
+    assert_error(
+        env.send(
+            &[record_round_stats_ix(&env, 500, 49_000 + 8 * 500 + 250)],
+            &[&settlement_authority],
+        ),
+        ErrorCode::InvalidRoundStats,
+    );
+    env.send(
+        &[record_round_stats_ix(&env, 500, 50_250)],
+        &[&settlement_authority],
+    )
+    .unwrap();
+}
+
+#[test]
+fn round_stats_wait_for_the_end() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let settlement_authority = env.settlement_authority.insecure_clone();
+    assert_error(
+        env.send(
+            &[record_round_stats_ix(&env, 500, 50_250)],
+            &[&settlement_authority],
+        ),
+        ErrorCode::RoundNotEnded,
+    );
+}
+
+#[test]
+fn price_sources_are_validated() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let source = || PriceSource {
+        kind: PriceSourceKind::SignedAttestation as u8,
+        key: Pubkey::new_unique(),
+    };
+
+    assert_error(
+        env.configure_price_sources(ROUND_ID, (0..6).map(|_| source()).collect(), 1),
+        ErrorCode::TooManyPriceSources,
+    );
+    assert_error(
+        env.configure_price_sources(ROUND_ID, vec![source()], 0),
+        ErrorCode::InvalidPriceSources,
+    );
+    let duplicate = source();
+    assert_error(
+        env.configure_price_sources(ROUND_ID, vec![duplicate, duplicate], 1),
+        ErrorCode::InvalidPriceSources,
+    );
+}
+
+#[test]
+fn price_resolution_waits_for_the_end() {
+    let (mut env, oracle) = attested_round();
+    env.warp_to(START_TS + 500);
+    assert_error(
+        env.resolve_round_price(
+            ROUND_ID,
+            &[],
+            vec![attestation(&oracle, ROUND_ID, 42_000, START_TS + 500)],
+            &[&oracle],
+        ),
+        ErrorCode::RoundNotEnded,
+    );
+}
+
+#[test]
+fn only_signed_recent_attestations_count() {
+    let (mut env, oracle) = attested_round();
+    env.warp_to(START_TS + 601);
+
+    assert_error(
+        env.resolve_round_price(
+            ROUND_ID,
+            &[],
+            vec![attestation(&oracle, ROUND_ID, 42_000, START_TS + 590)],
+            &[],
+        ),
+        ErrorCode::InsufficientPriceSources,
+    );
+    // Older than `DEFAULT_MAX_PRICE_AGE_SECS` before `end_ts`.
+    assert_error(
+        env.resolve_round_price(
+            ROUND_ID,
+            &[],
+            vec![attestation(&oracle, ROUND_ID, 42_000, START_TS + 530)],
+            &[&oracle],
+        ),
+        ErrorCode::InsufficientPriceSources,
+    );
+}
+
+#[test]
+fn price_resolves_once() {
+    let (mut env, oracle) = attested_round();
+    env.warp_to(START_TS + 601);
+    let attested = attestation(&oracle, ROUND_ID, 42_000, START_TS + 590);
+    env.resolve_round_price(ROUND_ID, &[], vec![attested], &[&oracle])
+        .unwrap();
+    assert_error(
+        env.resolve_round_price(ROUND_ID, &[], vec![attested], &[&oracle]),
+        ErrorCode::PriceAlreadyResolved,
+    );
+}
+
+#[test]
+fn finalize_keeps_the_resolved_price() {
+    let (mut env, oracle) = attested_round();
+    env.warp_to(START_TS + 601);
+    env.resolve_round_price(
+        ROUND_ID,
+        &[],
+        vec![attestation(&oracle, ROUND_ID, 42_000, START_TS + 590)],
+        &[&oracle],
+    )
+    .unwrap();
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    env.commit_settlement(ROUND_ID, [1; 32], 0).unwrap();
+
+    assert_error(
+        env.finalize(ROUND_ID, 41_000),
+        ErrorCode::FinalPriceMismatch,
+    );
+    env.finalize(ROUND_ID, 42_000).unwrap();
+}
+
+#[test]
+fn twap_window_must_fit_the_round() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+
+    assert_error(
+        env.configure_twap(ROUND_ID, 2),
+        ErrorCode::InvalidTwapWindow,
+    );
+    assert_error(
+        env.configure_twap(ROUND_ID, 601),
+        ErrorCode::InvalidTwapWindow,
+    );
+    // The window would already have opened.
+    assert_error(
+        env.configure_twap(ROUND_ID, 595),
+        ErrorCode::InvalidTwapWindow,
+    );
+}
+
+#[test]
+fn observations_need_a_twap_round() {
+    let (mut env, oracle) = attested_round();
+    assert_error(
+        env.observe(ROUND_ID, &oracle, START_TS + 300, 42_000),
+        ErrorCode::InvalidResolutionMode,
+    );
+}
+
+#[test]
+fn observations_stay_inside_the_window() {
+    let (mut env, oracle) = twap_round();
+    assert_error(
+        env.observe(ROUND_ID, &oracle, START_TS + 279, 42_000),
+        ErrorCode::OutsideTwapWindow,
+    );
+    assert_error(
+        env.observe(ROUND_ID, &oracle, START_TS + 601, 42_000),
+        ErrorCode::OutsideTwapWindow,
+    );
+}
+
+#[test]
+fn observations_are_spaced_apart() {
+    let (mut env, oracle) = twap_round();
+    env.observe(ROUND_ID, &oracle, START_TS + 300, 42_000)
+        .unwrap();
+    assert_error(
+        env.observe(ROUND_ID, &oracle, START_TS + 300, 42_100),
+        ErrorCode::DuplicateObservation,
+    );
+    assert_error(
+        env.observe(ROUND_ID, &oracle, START_TS + 309, 42_100),
+        ErrorCode::ObservationTooSoon,
+    );
+    env.observe(ROUND_ID, &oracle, START_TS + 310, 42_100)
+        .unwrap();
+}
+
+#[test]
+fn twap_needs_enough_observations() {
+    let (mut env, oracle) = twap_round();
+    env.observe(ROUND_ID, &oracle, START_TS + 300, 42_000)
+        .unwrap();
+    env.observe(ROUND_ID, &oracle, START_TS + 400, 42_100)
+        .unwrap();
+    env.warp_to(START_TS + 601);
+    assert_error(
+        env.resolve_round_price(ROUND_ID, &[], vec![], &[]),
+        ErrorCode::NoPriceObservations,
+    );
+}
+
+#[test]
+fn sweeping_twice_finds_nothing() {
+    let (mut env, _) = resolving_round_with_entry();
+    env.init_rollover_vault().unwrap();
+    env.commit_settlement(ROUND_ID, [1; 32], 50).unwrap();
+    env.finalize(ROUND_ID, 42_000).unwrap();
+
+    let authority = env.authority.insecure_clone();
+    env.sweep(ROUND_ID, &authority).unwrap();
+    assert_error(env.sweep(ROUND_ID, &authority), ErrorCode::NothingToSweep);
+}
+
+#[test]
+fn accumulator_must_catch_up_before_resolution() {
+    let mut env = TestEnv::initialized();
+    env.initialize_round(ROUND_ID, START_TS, START_TS + 600)
+        .unwrap();
+    env.init_round_accumulator(ROUND_ID, 100).unwrap();
+    env.warp_to(START_TS + 10);
+    let user = env.new_user(1_000);
+    env.submit(ROUND_ID, &user, 0, commitment(1), 100).unwrap();
+    env.warp_to(START_TS + 601);
+
+    let authority = env.authority.insecure_clone();
+    assert_error(
+        env.begin_resolution(ROUND_ID, &authority),
+        ErrorCode::AccumulatorNotCaughtUp,
+    );
+    env.fold(ROUND_ID, prediction_pda(ROUND_ID, &user.pubkey(), 0))
+        .unwrap();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+}
+
+#[test]
+fn folded_entries_stay_folded() {
+    let mut env = TestEnv::initialized();
+    env.initialize_round(ROUND_ID, START_TS, START_TS + 600)
+        .unwrap();
+    env.init_round_accumulator(ROUND_ID, 100).unwrap();
+    env.warp_to(START_TS + 10);
+    let user = env.new_user(1_000);
+    env.submit(ROUND_ID, &user, 0, commitment(1), 100).unwrap();
+    let prediction = prediction_pda(ROUND_ID, &user.pubkey(), 0);
+    env.fold(ROUND_ID, prediction).unwrap();
+
+    assert_error(env.fold(ROUND_ID, prediction), ErrorCode::NothingToFold);
+    assert_error(
+        env.amend(ROUND_ID, &user, 0, commitment(2), 200),
+        ErrorCode::PredictionAlreadyAccumulated,
+    );
+}
+
+#[test]
+fn chunks_are_scanned_in_order() {
+    let (mut env, _) = resolving_round_with_entry();
+    env.begin_chunked_resolution(ROUND_ID, 2).unwrap();
+
+    assert_error(
+        env.record_chunk_scan(ROUND_ID, 1, 1, 50),
+        ErrorCode::ChunkOutOfOrder,
+    );
+    env.record_chunk_scan(ROUND_ID, 0, 1, 50).unwrap();
+    assert_error(
+        env.record_chunk_scan(ROUND_ID, 0, 1, 50),
+        ErrorCode::ChunkOutOfOrder,
+    );
+    env.record_chunk_scan(ROUND_ID, 1, 1, 50).unwrap();
+    assert_error(
+        env.record_chunk_scan(ROUND_ID, 2, 1, 50),
+        ErrorCode::ChunkOutOfOrder,
+    );
+}
+
+#[test]
+fn chunk_scans_must_cover_the_whole_stake() {
+    let (mut env, _) = resolving_round_with_entry();
+    env.begin_chunked_resolution(ROUND_ID, 2).unwrap();
+
+    assert_error(
+        env.record_chunk_scan(ROUND_ID, 0, 0, 50),
+        ErrorCode::InvalidChunkLayout,
+    );
+    assert_error(
+        env.record_chunk_scan(ROUND_ID, 0, 1, 101),
+        ErrorCode::InvalidChunkLayout,
+    );
+    env.record_chunk_scan(ROUND_ID, 0, 1, 50).unwrap();
+    // The last chunk has to bring the scan up to the round's stake.
+    assert_error(
+        env.record_chunk_scan(ROUND_ID, 1, 1, 49),
+        ErrorCode::ScanIncomplete,
+    );
+    env.record_chunk_scan(ROUND_ID, 1, 1, 50).unwrap();
+}
+
+#[test]
+fn chunks_settle_once_after_a_full_scan() {
+    let (mut env, _) = resolving_round_with_entry();
+    env.begin_chunked_resolution(ROUND_ID, 2).unwrap();
+    env.record_chunk_scan(ROUND_ID, 0, 1, 50).unwrap();
+
+    assert_error(
+        env.record_chunk_settlement(ROUND_ID, 0, [1; 32], 50, (1, 2), 100),
+        ErrorCode::ScanIncomplete,
+    );
+    env.record_chunk_scan(ROUND_ID, 1, 1, 50).unwrap();
+    // The settlement has to be computed against the recorded scan.
+    assert_error(
+        env.record_chunk_settlement(ROUND_ID, 0, [1; 32], 50, (1, 2), 99),
+        ErrorCode::ScanMismatch,
+    );
+    assert_error(
+        env.record_chunk_settlement(ROUND_ID, 0, [1; 32], 50, (1, 3), 100),
+        ErrorCode::ScanMismatch,
+    );
+    env.record_chunk_settlement(ROUND_ID, 0, [1; 32], 50, (1, 2), 100)
+        .unwrap();
+    assert_error(
+        env.record_chunk_settlement(ROUND_ID, 0, [1; 32], 50, (1, 2), 100),
+        ErrorCode::ChunkAlreadyRecorded,
+    );
+}
+
+#[test]
+fn chunked_rounds_wait_for_every_chunk() {
+    let (mut env, _) = resolving_round_with_entry();
+    env.begin_chunked_resolution(ROUND_ID, 2).unwrap();
+    env.record_chunk_scan(ROUND_ID, 0, 1, 50).unwrap();
+    env.record_chunk_scan(ROUND_ID, 1, 1, 50).unwrap();
+
+    assert_error(
+        env.commit_settlement(ROUND_ID, [1; 32], 50),
+        ErrorCode::ChunkedResolutionActive,
+    );
+    env.record_chunk_settlement(ROUND_ID, 0, [1; 32], 50, (1, 2), 100)
+        .unwrap();
+    assert_error(env.finalize(ROUND_ID, 42_000), ErrorCode::ChunksOutstanding);
+}
@@ -0,0 +1,439 @@
+//! Random interleavings of submit, cancel, settle, refund and sweep against
+//! the real program, checking escrow accounting after every step.
+
+mod common;
+
+use std::collections::HashMap;
+
+use common::*;
+use micro_prediction::{
+    instruction, merkle, Config, ErrorCode, PredictionStatus, RoundStatus, MAX_PAYOUT_TIERS,
+};
+use micro_prediction_settlement::{self as settlement, merkle::proof, WinnerParams};
+use proptest::prelude::*;
+use solana_sdk::pubkey::Pubkey;
+
+const ROUND_ID: u64 = 1;
+const USERS: usize = 3;
+const INITIAL_BALANCE: u64 = 1_000_000;
+const FINAL_PRICE: u128 = 50_000;
+const PAYOUT_TIERS: [u16; 2] = [6_000, 3_000];
+const HOUSE_EDGE_BPS: u16 = 500;
+const CARRIED_IN: u64 = 20_000;
+
+/// Payout rules of the round under test.
+#[derive(Clone, Copy, Debug)]
+enum RoundKind {
+    WinnerTakeAll,
+    Tiered,
+    /// Winner-take-all with a house edge and a pot seeded from the rollover
+    /// vault.
+    Rollover,
+}
+
+fn round_kind() -> impl Strategy<Value = RoundKind> {
+    prop_oneof![
+        Just(RoundKind::WinnerTakeAll),
+        Just(RoundKind::Tiered),
+        Just(RoundKind::Rollover),
+    ]
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Submit {
+        user: usize,
+        stake: u64,
+        price: u128,
+    },
+    Cancel {
+        entry: prop::sample::Index,
+    },
+    Resolve,
+    Settle {
+        entry: prop::sample::Index,
+    },
+    MarkRefunded,
+    Refund {
+        entry: prop::sample::Index,
+    },
+    Finalize,
+    Sweep,
+    CollectFees,
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        4 => (0..USERS, 1u64..50_000, 40_000u128..60_000)
+            .prop_map(|(user, stake, price)| Op::Submit { user, stake, price }),
+        2 => any::<prop::sample::Index>().prop_map(|entry| Op::Cancel { entry }),
+        1 => Just(Op::Resolve),
+        3 => any::<prop::sample::Index>().prop_map(|entry| Op::Settle { entry }),
+        1 => Just(Op::MarkRefunded),
+        2 => any::<prop::sample::Index>().prop_map(|entry| Op::Refund { entry }),
+        1 => Just(Op::Finalize),
+        1 => Just(Op::Sweep),
+        1 => Just(Op::CollectFees),
+    ]
+}
+
+struct Entry {
+    user: usize,
+    address: Pubkey,
+    commitment: [u8; 32],
+    price: u128,
+    stake: u64,
+}
+
+/// Leaf data of the committed settlement, keyed by prediction.
+struct Claim {
+    payout: u64,
+    blinding: [u8; 32],
+    proof: Vec<[u8; 32]>,
+}
+
+struct Harness {
+    env: TestEnv,
+    kind: RoundKind,
+    users: Vec<User>,
+    /// Tops up the rollover vault that seeds `RoundKind::Rollover` rounds.
+    funder: User,
+    entries: Vec<Entry>,
+    next_index: Vec<u16>,
+    claims: HashMap<Pubkey, Claim>,
+    /// Tokens each prediction has sent back to its owner so far.
+    paid_out: HashMap<Pubkey, u64>,
+}
+
+impl Harness {
+    fn new(kind: RoundKind) -> Self {
+        let mut env = TestEnv::initialized();
+        env.init_rollover_vault().unwrap();
+        let funder = env.new_user(INITIAL_BALANCE);
+        if let RoundKind::Rollover = kind {
+            env.set_house_edge(HOUSE_EDGE_BPS).unwrap();
+            env.top_up_rollover(&funder, CARRIED_IN).unwrap();
+        }
+        env.open_round(ROUND_ID);
+        match kind {
+            RoundKind::WinnerTakeAll => {}
+            RoundKind::Tiered => {
+                let payout_tiers = PAYOUT_TIERS.to_vec();
+                env.configure_round(ROUND_ID, instruction::SetPayoutTiers { payout_tiers })
+                    .unwrap();
+            }
+            RoundKind::Rollover => {
+                env.seed_round(ROUND_ID, CARRIED_IN).unwrap();
+            }
+        }
+        let users = (0..USERS).map(|_| env.new_user(INITIAL_BALANCE)).collect();
+        Self {
+            env,
+            kind,
+            users,
+            funder,
+            entries: Vec::new(),
+            next_index: vec![0; USERS],
+            claims: HashMap::new(),
+            paid_out: HashMap::new(),
+        }
+    }
+
+    fn pick(&self, index: &prop::sample::Index) -> Option<usize> {
+        (!self.entries.is_empty()).then(|| index.index(self.entries.len()))
+    }
+
+    fn apply(&mut self, op: &Op) {
+        match op {
+            Op::Submit { user, stake, price } => {
+                let prediction_index = self.next_index[*user];
+                let commitment = commitment(self.entries.len() as u8);
+                let owner = self.users[*user].pubkey();
+                let result = self.env.submit(
+                    ROUND_ID,
+                    &self.users[*user],
+                    prediction_index,
+                    commitment,
+                    *stake,
+                );
+                if result.is_ok() {
+                    self.next_index[*user] += 1;
+                    self.entries.push(Entry {
+                        user: *user,
+                        address: prediction_pda(ROUND_ID, &owner, prediction_index),
+                        commitment,
+                        price: *price,
+                        stake: *stake,
+                    });
+                }
+            }
+            Op::Cancel { entry } => {
+                let Some(entry) = self.pick(entry) else {
+                    return;
+                };
+                let user = self.entries[entry].user;
+                let prediction = self.env.prediction(&self.entries[entry].address);
+                self.paid(entry, false, |harness| {
+                    harness
+                        .env
+                        .cancel(ROUND_ID, &harness.users[user], prediction.prediction_index)
+                });
+            }
+            Op::Resolve => {
+                self.env.warp_to(START_TS + 601);
+                let authority = self.env.authority.insecure_clone();
+                if self.env.begin_resolution(ROUND_ID, &authority).is_ok() {
+                    self.commit_settlement();
+                }
+            }
+            Op::Settle { entry } => {
+                let Some(entry) = self.pick(entry) else {
+                    return;
+                };
+                let address = self.entries[entry].address;
+                let Some(claim) = self.claims.get(&address) else {
+                    return;
+                };
+                let (payout, blinding, proof) = (claim.payout, claim.blinding, claim.proof.clone());
+                let user = self.entries[entry].user;
+                let commitment = self.entries[entry].commitment;
+                // A committed claim of an unsettled entry must always go through.
+                let status = self.env.round(ROUND_ID).status;
+                let claimable = (status == RoundStatus::Resolving as u8
+                    || status == RoundStatus::Finalized as u8)
+                    && self.env.prediction(&address).status == PredictionStatus::Submitted as u8;
+                self.paid(entry, claimable, |harness| {
+                    harness.env.settle(
+                        ROUND_ID,
+                        address,
+                        &harness.users[user],
+                        payout,
+                        commitment,
+                        blinding,
+                        proof,
+                    )
+                });
+            }
+            Op::MarkRefunded => {
+                let _ = self.env.mark_refunded(ROUND_ID);
+            }
+            Op::Refund { entry } => {
+                let Some(entry) = self.pick(entry) else {
+                    return;
+                };
+                let address = self.entries[entry].address;
+                let user = self.entries[entry].user;
+                // Every stake still in a refunded round must come back.
+                let prediction_status = self.env.prediction(&address).status;
+                let refundable = self.env.round(ROUND_ID).status == RoundStatus::Refunded as u8
+                    && (prediction_status == PredictionStatus::Submitted as u8
+                        || prediction_status == PredictionStatus::Cancelled as u8);
+                self.paid(entry, refundable, |harness| {
+                    harness.env.refund(ROUND_ID, address, &harness.users[user])
+                });
+            }
+            Op::Finalize => {
+                let _ = self.env.finalize(ROUND_ID, FINAL_PRICE as i64);
+            }
+            Op::Sweep => {
+                let authority = self.env.authority.insecure_clone();
+                let _ = self.env.sweep(ROUND_ID, &authority);
+            }
+            Op::CollectFees => {
+                let accrued = self.env.decode::<Config>(&config_pda()).accrued_fees;
+                if accrued > 0 {
+                    let authority = self.env.authority.insecure_clone();
+                    self.env.collect_fees(&authority, accrued).unwrap();
+                }
+            }
+        }
+    }
+
+    /// Runs an instruction that may pay `entry`'s owner and records how much
+    /// it actually moved. With `must_succeed` a failure is a bug rather than a
+    /// rejected no-op.
+    fn paid(
+        &mut self,
+        entry: usize,
+        must_succeed: bool,
+        send: impl FnOnce(&mut Self) -> litesvm::types::TransactionResult,
+    ) {
+        let owner_account = self.users[self.entries[entry].user].token_account;
+        let before = self.env.token_balance(&owner_account);
+        let result = send(self);
+        let after = self.env.token_balance(&owner_account);
+        if must_succeed {
+            assert!(result.is_ok(), "expected success: {result:?}");
+        }
+        if result.is_err() {
+            assert_eq!(before, after, "failed instruction moved tokens");
+            return;
+        }
+        let received = after - before;
+        let total = self
+            .paid_out
+            .entry(self.entries[entry].address)
+            .or_default();
+        if received > 0 {
+            assert_eq!(*total, 0, "prediction paid twice");
+        }
+        *total += received;
+    }
+
+    /// Computes the winners with the plaintext model over the live entries,
+    /// the way the relayer would after a real MPC run, and commits the root.
+    fn commit_settlement(&mut self) {
+        let live: Vec<&Entry> = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                self.env.prediction(&entry.address).status == PredictionStatus::Submitted as u8
+            })
+            .collect();
+        let inputs: Vec<settlement::Prediction> = live
+            .iter()
+            .map(|entry| settlement::Prediction {
+                account: entry.address.to_bytes(),
+                predicted_price: entry.price,
+                stake: entry.stake as u128,
+                weight_bps: settlement::BASE_WEIGHT_BPS,
+            })
+            .collect();
+        let mut payout_tiers = [0u16; MAX_PAYOUT_TIERS];
+        if let RoundKind::Tiered = self.kind {
+            payout_tiers[..PAYOUT_TIERS.len()].copy_from_slice(&PAYOUT_TIERS);
+        }
+        let params = WinnerParams {
+            fee_bps: FEE_BPS,
+            payout_tiers,
+            payout_pool: self.env.round(ROUND_ID).payout_pool().unwrap(),
+            ..Default::default()
+        };
+        let result = settlement::determine_winners(&inputs, FINAL_PRICE, &params, &[0; 32]);
+
+        let leaves: Vec<[u8; 32]> = live
+            .iter()
+            .zip(&result.payouts)
+            .enumerate()
+            .map(|(i, (entry, payout))| {
+                merkle::payout_leaf(&entry.address.to_bytes(), payout.payout, &[i as u8; 32])
+            })
+            .collect();
+        self.env
+            .commit_settlement_with_fees(
+                ROUND_ID,
+                merkle::root(&leaves),
+                result.total_payout,
+                result.fee_total,
+            )
+            .unwrap();
+        for (i, (entry, payout)) in live.iter().zip(&result.payouts).enumerate() {
+            self.claims.insert(
+                entry.address,
+                Claim {
+                    payout: payout.payout,
+                    blinding: [i as u8; 32],
+                    proof: proof(&leaves, i),
+                },
+            );
+        }
+    }
+
+    fn check_invariants(&self) {
+        let round = self.env.round(ROUND_ID);
+        let escrow = self.env.token_balance(&escrow_pda(ROUND_ID));
+        let vault = self.env.token_balance(&rollover_pda());
+        let collected = self.env.token_balance(&self.env.fee_treasury);
+        let config: Config = self.env.decode(&config_pda());
+
+        // No fee leaves the escrow before the round is swept.
+        assert_eq!(Some(escrow), round.unswept_balance(), "escrow drifted");
+        assert!(round.total_paid <= round.committed_payout);
+        assert!(
+            config.accrued_fees <= vault,
+            "accrued fees are not in the vault"
+        );
+
+        // Tokens only move between users, the escrow, the rollover vault and
+        // the fee treasury. Winners can end up ahead, hence the signed sums.
+        let balance = |account: &Pubkey| self.env.token_balance(account) as i128;
+        let staked: i128 = self
+            .users
+            .iter()
+            .map(|user| INITIAL_BALANCE as i128 - balance(&user.token_account))
+            .sum();
+        let funded = INITIAL_BALANCE as i128 - balance(&self.funder.token_account);
+        assert_eq!(
+            staked + funded - collected as i128,
+            (escrow + vault) as i128,
+            "tokens were created or lost"
+        );
+
+        for entry in &self.entries {
+            let paid = self.paid_out.get(&entry.address).copied().unwrap_or(0);
+            let ceiling = match self.claims.get(&entry.address) {
+                Some(claim) => claim.payout.max(entry.stake),
+                None => entry.stake,
+            };
+            assert!(paid <= ceiling, "prediction paid more than it is owed");
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn escrow_matches_round_accounting(
+        kind in round_kind(),
+        ops in prop::collection::vec(op(), 1..40),
+    ) {
+        let mut harness = Harness::new(kind);
+        for op in &ops {
+            harness.apply(op);
+            harness.check_invariants();
+        }
+    }
+}
+
+#[test]
+fn double_settlement_is_rejected() {
+    let mut harness = Harness::new(RoundKind::WinnerTakeAll);
+    harness.apply(&Op::Submit {
+        user: 0,
+        stake: 1_000,
+        price: FINAL_PRICE,
+    });
+    harness.apply(&Op::Resolve);
+    let address = harness.entries[0].address;
+    let claim = &harness.claims[&address];
+    let (payout, blinding, proof) = (claim.payout, claim.blinding, claim.proof.clone());
+
+    let user = &harness.users[0];
+    harness
+        .env
+        .settle(
+            ROUND_ID,
+            address,
+            user,
+            payout,
+            commitment(0),
+            blinding,
+            proof.clone(),
+        )
+        .unwrap();
+    let user = &harness.users[0];
+    assert_error(
+        harness.env.settle(
+            ROUND_ID,
+            address,
+            user,
+            payout,
+            commitment(0),
+            blinding,
+            proof,
+        ),
+        ErrorCode::PredictionFinalized,
+    );
+    harness.check_invariants();
+}
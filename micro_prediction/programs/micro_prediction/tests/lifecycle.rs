@@ -0,0 +1,608 @@
+mod common;
+
+use anchor_lang::prelude::Clock;
+use common::*;
+use micro_prediction::oracle::{PriceSource, PriceSourceKind};
+use micro_prediction::{
+    accounts, instruction, merkle, Config, ErrorCode, PredictionStatus, RoundAccumulator,
+    RoundStatus, BASE_WEIGHT_BPS,
+};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+const ROUND_ID: u64 = 1;
+
+#[test]
+fn full_round_lifecycle() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+
+    let alice = env.new_user(1_000);
+    let bob = env.new_user(1_000);
+    let carol = env.new_user(1_000);
+    env.submit(ROUND_ID, &alice, 0, commitment(1), 100).unwrap();
+    env.submit(ROUND_ID, &bob, 0, commitment(2), 200).unwrap();
+    env.submit(ROUND_ID, &carol, 0, commitment(3), 300).unwrap();
+
+    let escrow = escrow_pda(ROUND_ID);
+    assert_eq!(env.token_balance(&escrow), 600);
+    assert_eq!(env.round(ROUND_ID).total_stake, 600);
+
+    env.cancel(ROUND_ID, &carol, 0).unwrap();
+    assert_eq!(env.token_balance(&carol.token_account), 1_000);
+    assert_eq!(env.token_balance(&escrow), 300);
+    let carol_prediction = prediction_pda(ROUND_ID, &carol.pubkey(), 0);
+    assert_eq!(
+        env.prediction(&carol_prediction).status,
+        PredictionStatus::Cancelled as u8
+    );
+
+    env.warp_to(START_TS + 601);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    assert_eq!(env.round(ROUND_ID).status, RoundStatus::Resolving as u8);
+
+    // Alice is closest and gets her stake back minus the fee.
+    let alice_prediction = prediction_pda(ROUND_ID, &alice.pubkey(), 0);
+    let bob_prediction = prediction_pda(ROUND_ID, &bob.pubkey(), 0);
+    let alice_payout = 100 - 100 * FEE_BPS as u64 / 10_000;
+    let alice_leaf = merkle::payout_leaf(&alice_prediction.to_bytes(), alice_payout, &[7; 32]);
+    let bob_leaf = merkle::payout_leaf(&bob_prediction.to_bytes(), 0, &[8; 32]);
+    let root = merkle::root(&[alice_leaf, bob_leaf]);
+    env.commit_settlement(ROUND_ID, root, alice_payout).unwrap();
+
+    env.settle(
+        ROUND_ID,
+        alice_prediction,
+        &alice,
+        alice_payout,
+        commitment(1),
+        [7; 32],
+        vec![bob_leaf],
+    )
+    .unwrap();
+    env.settle(
+        ROUND_ID,
+        bob_prediction,
+        &bob,
+        0,
+        commitment(2),
+        [8; 32],
+        vec![alice_leaf],
+    )
+    .unwrap();
+
+    assert_eq!(env.token_balance(&alice.token_account), 900 + alice_payout);
+    assert_eq!(env.token_balance(&bob.token_account), 800);
+    assert_eq!(env.token_balance(&escrow), 300 - alice_payout);
+    let round = env.round(ROUND_ID);
+    assert_eq!(round.total_paid, alice_payout);
+    assert!(round.total_paid <= round.total_stake);
+    assert_eq!(
+        env.prediction(&alice_prediction).status,
+        PredictionStatus::Settled as u8
+    );
+
+    env.finalize(ROUND_ID, 42_000).unwrap();
+    let round = env.round(ROUND_ID);
+    assert_eq!(round.status, RoundStatus::Finalized as u8);
+    assert_eq!(round.final_price, Some(42_000));
+}
+
+#[test]
+fn refunded_round_returns_every_stake() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+
+    let alice = env.new_user(1_000);
+    let bob = env.new_user(1_000);
+    env.submit(ROUND_ID, &alice, 0, commitment(1), 250).unwrap();
+    env.submit(ROUND_ID, &alice, 1, commitment(2), 50).unwrap();
+    env.submit(ROUND_ID, &bob, 0, commitment(3), 400).unwrap();
+    assert_eq!(env.token_balance(&alice.token_account), 700);
+
+    env.warp_to(START_TS + 601);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    env.mark_refunded(ROUND_ID).unwrap();
+    assert_eq!(env.round(ROUND_ID).status, RoundStatus::Refunded as u8);
+
+    for index in 0..2 {
+        let prediction = prediction_pda(ROUND_ID, &alice.pubkey(), index);
+        env.refund(ROUND_ID, prediction, &alice).unwrap();
+        assert_eq!(
+            env.prediction(&prediction).status,
+            PredictionStatus::Refunded as u8
+        );
+    }
+    env.refund(ROUND_ID, prediction_pda(ROUND_ID, &bob.pubkey(), 0), &bob)
+        .unwrap();
+
+    assert_eq!(env.token_balance(&alice.token_account), 1_000);
+    assert_eq!(env.token_balance(&bob.token_account), 1_000);
+    assert_eq!(env.token_balance(&escrow_pda(ROUND_ID)), 0);
+}
+
+#[test]
+fn legacy_accounts_migrate_and_refund() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let alice = env.new_user(1_000);
+    env.submit(ROUND_ID, &alice, 0, commitment(1), 250).unwrap();
+    let prediction = prediction_pda(ROUND_ID, &alice.pubkey(), 0);
+    env.downgrade_to_legacy(ROUND_ID, &prediction);
+
+    env.migrate_round(ROUND_ID).unwrap();
+    env.migrate_prediction(prediction).unwrap();
+    let round = env.round(ROUND_ID);
+    assert_eq!(round.total_stake, 250);
+    assert_eq!(round.house_edge_bps, 0);
+    assert_eq!(round.min_price_sources, 1);
+    assert!(
+        round.price_sources
+            == [PriceSource {
+                kind: PriceSourceKind::PythPush as u8,
+                key: round.pyth_price_account,
+            }]
+    );
+    let entry = env.prediction(&prediction);
+    assert_eq!(entry.stake, 250);
+    assert_eq!(entry.weight_bps, BASE_WEIGHT_BPS);
+    assert!(!entry.accumulated);
+
+    env.warp_to(START_TS + 601);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    env.mark_refunded(ROUND_ID).unwrap();
+    env.refund(ROUND_ID, prediction, &alice).unwrap();
+    assert_eq!(env.token_balance(&alice.token_account), 1_000);
+}
+
+#[test]
+fn cancelled_stake_is_not_refunded_twice() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+
+    let alice = env.new_user(1_000);
+    env.submit(ROUND_ID, &alice, 0, commitment(1), 300).unwrap();
+    env.cancel(ROUND_ID, &alice, 0).unwrap();
+    env.mark_refunded(ROUND_ID).unwrap();
+
+    let prediction = prediction_pda(ROUND_ID, &alice.pubkey(), 0);
+    env.refund(ROUND_ID, prediction, &alice).unwrap();
+    assert_eq!(env.token_balance(&alice.token_account), 1_000);
+    assert_eq!(env.token_balance(&escrow_pda(ROUND_ID)), 0);
+}
+
+#[test]
+fn only_accrued_fees_are_collected() {
+    let mut env = TestEnv::initialized();
+    env.init_rollover_vault().unwrap();
+    env.set_house_edge(1_000).unwrap();
+    let funder = env.new_user(1_000);
+    env.top_up_rollover(&funder, 600).unwrap();
+    env.open_round(ROUND_ID);
+
+    let alice = env.new_user(1_000);
+    let bob = env.new_user(1_000);
+    env.submit(ROUND_ID, &alice, 0, commitment(1), 100).unwrap();
+    env.submit(ROUND_ID, &bob, 0, commitment(2), 200).unwrap();
+    env.warp_to(START_TS + 601);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    let alice_prediction = prediction_pda(ROUND_ID, &alice.pubkey(), 0);
+    let leaf = merkle::payout_leaf(&alice_prediction.to_bytes(), 200, &[7; 32]);
+    env.commit_settlement_with_fees(ROUND_ID, merkle::root(&[leaf]), 200, 20)
+        .unwrap();
+    env.settle(
+        ROUND_ID,
+        alice_prediction,
+        &alice,
+        200,
+        commitment(1),
+        [7; 32],
+        vec![],
+    )
+    .unwrap();
+    env.finalize(ROUND_ID, 42_000).unwrap();
+
+    // 30 of house edge and 20 of fees; the other 50 nobody won is jackpot.
+    env.sweep(ROUND_ID, &authority).unwrap();
+    assert_eq!(env.token_balance(&rollover_pda()), 700);
+    let config: Config = env.decode(&config_pda());
+    assert_eq!(config.accrued_fees, 50);
+
+    // Neither the top-up nor the unwon pool can be collected...
+    assert_error(
+        env.collect_fees(&authority, 51),
+        ErrorCode::InsufficientAccruedFees,
+    );
+    // ...nor used to seed a round.
+    env.initialize_round(ROUND_ID + 1, START_TS + 700, START_TS + 1_300)
+        .unwrap();
+    assert_error(
+        env.seed_round(ROUND_ID + 1, 651),
+        ErrorCode::InsufficientEscrow,
+    );
+
+    env.collect_fees(&authority, 50).unwrap();
+    assert_eq!(env.token_balance(&env.fee_treasury), 50);
+    assert_eq!(env.token_balance(&rollover_pda()), 650);
+    let config: Config = env.decode(&config_pda());
+    assert_eq!(config.accrued_fees, 0);
+}
+
+#[test]
+fn sweep_leaves_unclaimed_payouts_claimable() {
+    let mut env = TestEnv::initialized();
+    env.init_rollover_vault().unwrap();
+    env.open_round(ROUND_ID);
+
+    let alice = env.new_user(1_000);
+    let bob = env.new_user(1_000);
+    env.submit(ROUND_ID, &alice, 0, commitment(1), 100).unwrap();
+    env.submit(ROUND_ID, &bob, 0, commitment(2), 200).unwrap();
+
+    env.warp_to(START_TS + 601);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    let alice_prediction = prediction_pda(ROUND_ID, &alice.pubkey(), 0);
+    let alice_leaf = merkle::payout_leaf(&alice_prediction.to_bytes(), 250, &[7; 32]);
+    let bob_leaf = merkle::payout_leaf(
+        &prediction_pda(ROUND_ID, &bob.pubkey(), 0).to_bytes(),
+        0,
+        &[8; 32],
+    );
+    env.commit_settlement(ROUND_ID, merkle::root(&[alice_leaf, bob_leaf]), 250)
+        .unwrap();
+    env.finalize(ROUND_ID, 42_000).unwrap();
+
+    // Only what nobody won leaves escrow; Alice's payout stays reserved.
+    env.sweep(ROUND_ID, &authority).unwrap();
+    assert_eq!(env.token_balance(&rollover_pda()), 50);
+    assert_eq!(env.token_balance(&escrow_pda(ROUND_ID)), 250);
+
+    env.settle(
+        ROUND_ID,
+        alice_prediction,
+        &alice,
+        250,
+        commitment(1),
+        [7; 32],
+        vec![bob_leaf],
+    )
+    .unwrap();
+    assert_eq!(env.token_balance(&alice.token_account), 1_150);
+    assert_eq!(env.token_balance(&escrow_pda(ROUND_ID)), 0);
+}
+
+#[test]
+fn amendments_move_the_stake_difference() {
+    let mut env = TestEnv::initialized();
+    let authority = env.authority.insecure_clone();
+    let set_fee = ix(
+        accounts::UpdateConfig {
+            authority: authority.pubkey(),
+            config: config_pda(),
+        },
+        instruction::SetAmendmentFee { amendment_fee: 5 },
+    );
+    env.send(&[set_fee], &[&authority]).unwrap();
+    env.open_round(ROUND_ID);
+
+    let alice = env.new_user(1_000);
+    env.submit(ROUND_ID, &alice, 0, commitment(1), 100).unwrap();
+    env.amend(ROUND_ID, &alice, 0, commitment(2), 150).unwrap();
+    assert_eq!(env.token_balance(&alice.token_account), 845);
+    assert_eq!(env.token_balance(&escrow_pda(ROUND_ID)), 150);
+
+    env.amend(ROUND_ID, &alice, 0, commitment(3), 60).unwrap();
+    assert_eq!(env.token_balance(&alice.token_account), 930);
+    assert_eq!(env.token_balance(&escrow_pda(ROUND_ID)), 60);
+    assert_eq!(env.token_balance(&env.fee_treasury), 10);
+
+    let prediction = env.prediction(&prediction_pda(ROUND_ID, &alice.pubkey(), 0));
+    assert_eq!(prediction.commitment, commitment(3));
+    assert_eq!(prediction.stake, 60);
+    assert_eq!(env.round(ROUND_ID).total_stake, 60);
+}
+
+#[test]
+fn rollover_seeds_a_round_and_comes_back_on_refund() {
+    let mut env = TestEnv::initialized();
+    env.init_rollover_vault().unwrap();
+    let funder = env.new_user(1_000);
+    env.top_up_rollover(&funder, 500).unwrap();
+    env.open_round(ROUND_ID);
+
+    env.seed_round(ROUND_ID, 300).unwrap();
+    assert_eq!(env.round(ROUND_ID).carried_in, 300);
+    assert_eq!(env.token_balance(&rollover_pda()), 200);
+
+    let alice = env.new_user(1_000);
+    env.submit(ROUND_ID, &alice, 0, commitment(1), 100).unwrap();
+    env.warp_to(START_TS + 601);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    env.mark_refunded(ROUND_ID).unwrap();
+
+    // The stake goes back to Alice and the seeded pot back to the vault.
+    env.refund(
+        ROUND_ID,
+        prediction_pda(ROUND_ID, &alice.pubkey(), 0),
+        &alice,
+    )
+    .unwrap();
+    env.sweep(ROUND_ID, &authority).unwrap();
+    assert_eq!(env.token_balance(&alice.token_account), 1_000);
+    assert_eq!(env.token_balance(&rollover_pda()), 500);
+    assert_eq!(env.token_balance(&escrow_pda(ROUND_ID)), 0);
+}
+
+#[test]
+fn seeded_pot_is_paid_to_the_winner() {
+    let mut env = TestEnv::initialized();
+    env.init_rollover_vault().unwrap();
+    let funder = env.new_user(1_000);
+    env.top_up_rollover(&funder, 500).unwrap();
+    env.open_round(ROUND_ID);
+    env.seed_round(ROUND_ID, 300).unwrap();
+
+    let alice = env.new_user(1_000);
+    env.submit(ROUND_ID, &alice, 0, commitment(1), 100).unwrap();
+    env.warp_to(START_TS + 601);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+
+    let prediction = prediction_pda(ROUND_ID, &alice.pubkey(), 0);
+    let leaf = merkle::payout_leaf(&prediction.to_bytes(), 390, &[7; 32]);
+    env.commit_settlement(ROUND_ID, merkle::root(&[leaf]), 390)
+        .unwrap();
+    env.settle(
+        ROUND_ID,
+        prediction,
+        &alice,
+        390,
+        commitment(1),
+        [7; 32],
+        vec![],
+    )
+    .unwrap();
+    assert_eq!(env.token_balance(&alice.token_account), 1_290);
+    assert_eq!(env.token_balance(&escrow_pda(ROUND_ID)), 10);
+}
+
+#[test]
+fn spot_price_is_the_median_of_agreeing_sources() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let oracles = [Keypair::new(), Keypair::new(), Keypair::new()];
+    let sources = oracles
+        .iter()
+        .map(|oracle| PriceSource {
+            kind: PriceSourceKind::SignedAttestation as u8,
+            key: oracle.pubkey(),
+        })
+        .collect();
+    env.configure_price_sources(ROUND_ID, sources, 2).unwrap();
+
+    env.warp_to(START_TS + 601);
+    // The third source is more than `DEFAULT_MAX_PRICE_DEVIATION_BPS` off.
+    let attestations = [42_000, 42_100, 45_000]
+        .iter()
+        .zip(&oracles)
+        .map(|(price, oracle)| attestation(oracle, ROUND_ID, *price, START_TS + 590))
+        .collect();
+    let signers: Vec<&Keypair> = oracles.iter().collect();
+    env.resolve_round_price(ROUND_ID, &[], attestations, &signers)
+        .unwrap();
+
+    let round = env.round(ROUND_ID);
+    assert_eq!(round.final_price, Some(42_050));
+    assert_eq!(round.price_sources_used, 0b011);
+
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    env.commit_settlement(ROUND_ID, [1; 32], 0).unwrap();
+    env.finalize(ROUND_ID, 42_050).unwrap();
+    assert_eq!(env.round(ROUND_ID).status, RoundStatus::Finalized as u8);
+}
+
+#[test]
+fn unavailable_sources_are_skipped() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let oracles = [Keypair::new(), Keypair::new(), Keypair::new()];
+    let mut sources: Vec<PriceSource> = oracles
+        .iter()
+        .map(|oracle| PriceSource {
+            kind: PriceSourceKind::SignedAttestation as u8,
+            key: oracle.pubkey(),
+        })
+        .collect();
+    // A pull feed with no update at all.
+    sources.push(PriceSource {
+        kind: PriceSourceKind::PythPull as u8,
+        key: Pubkey::new_unique(),
+    });
+    env.configure_price_sources(ROUND_ID, sources, 2).unwrap();
+
+    // The third oracle is offline and the second one's signature is missing.
+    env.warp_to(START_TS + 601);
+    let attestations = vec![
+        attestation(&oracles[0], ROUND_ID, 42_000, START_TS + 590),
+        attestation(&oracles[1], ROUND_ID, 42_100, START_TS + 590),
+    ];
+    assert_error(
+        env.resolve_round_price(ROUND_ID, &[], attestations.clone(), &[&oracles[0]]),
+        ErrorCode::InsufficientPriceSources,
+    );
+    env.resolve_round_price(ROUND_ID, &[], attestations, &[&oracles[0], &oracles[1]])
+        .unwrap();
+    let round = env.round(ROUND_ID);
+    assert_eq!(round.final_price, Some(42_050));
+    assert_eq!(round.price_sources_used, 0b0011);
+}
+
+#[test]
+fn pull_source_resolves_long_after_the_round() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let feed_id = [9; 32];
+    let source = PriceSource {
+        kind: PriceSourceKind::PythPull as u8,
+        key: Pubkey::new_from_array(feed_id),
+    };
+    env.configure_price_sources(ROUND_ID, vec![source], 1)
+        .unwrap();
+
+    let end_ts = env.round(ROUND_ID).end_ts;
+    env.warp_to(end_ts - 10);
+    let latest = env.post_pull_update(feed_id, 42_000, end_ts - 10, end_ts - 20);
+    env.warp_to(end_ts + 5);
+    let successor = env.post_pull_update(feed_id, 43_000, end_ts + 5, end_ts - 10);
+
+    // An hour and many more than `DEFAULT_MAX_PRICE_AGE_SLOTS` slots later.
+    env.warp_to(end_ts + 3_600);
+    let mut clock = env.svm.get_sysvar::<Clock>();
+    clock.slot += 10_000;
+    env.svm.set_sysvar(&clock);
+    env.resolve_round_price(ROUND_ID, &[latest, successor], vec![], &[])
+        .unwrap();
+    assert_eq!(env.round(ROUND_ID).final_price, Some(42_000));
+}
+
+#[test]
+fn twap_round_resolves_to_the_time_weighted_price() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let oracle = Keypair::new();
+    let source = PriceSource {
+        kind: PriceSourceKind::SignedAttestation as u8,
+        key: oracle.pubkey(),
+    };
+    env.configure_price_sources(ROUND_ID, vec![source], 1)
+        .unwrap();
+    env.configure_twap(ROUND_ID, 320).unwrap();
+
+    env.observe(ROUND_ID, &oracle, START_TS + 300, 100_000)
+        .unwrap();
+    env.observe(ROUND_ID, &oracle, START_TS + 400, 200_000)
+        .unwrap();
+    env.observe(ROUND_ID, &oracle, START_TS + 500, 400_000)
+        .unwrap();
+    env.warp_to(START_TS + 601);
+    env.resolve_round_price(ROUND_ID, &[], vec![], &[]).unwrap();
+
+    // Each price held for 100 of the 300 observed seconds.
+    let round = env.round(ROUND_ID);
+    assert_eq!(round.final_price, Some(233_333));
+    assert_eq!(round.observation_count, 3);
+}
+
+#[test]
+fn accumulator_round_settles_from_recorded_leaves() {
+    let mut env = TestEnv::initialized();
+    env.initialize_round(ROUND_ID, START_TS, START_TS + 600)
+        .unwrap();
+    env.init_round_accumulator(ROUND_ID, 100).unwrap();
+    env.warp_to(START_TS + 10);
+
+    let alice = env.new_user(1_000);
+    let bob = env.new_user(1_000);
+    env.submit(ROUND_ID, &alice, 0, commitment(1), 100).unwrap();
+    env.submit(ROUND_ID, &bob, 0, commitment(2), 200).unwrap();
+    let alice_prediction = prediction_pda(ROUND_ID, &alice.pubkey(), 0);
+    let bob_prediction = prediction_pda(ROUND_ID, &bob.pubkey(), 0);
+    env.fold(ROUND_ID, alice_prediction).unwrap();
+    env.fold(ROUND_ID, bob_prediction).unwrap();
+
+    // Bob's cancelled entry has to be folded back out before resolution.
+    env.cancel(ROUND_ID, &bob, 0).unwrap();
+    assert_eq!(env.round(ROUND_ID).pending_folds, 1);
+    env.fold(ROUND_ID, bob_prediction).unwrap();
+    let accumulator: RoundAccumulator = env.decode(&accumulator_pda(ROUND_ID));
+    assert_eq!(accumulator.folded_count, 1);
+
+    env.warp_to(START_TS + 601);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+    env.commit_settlement(ROUND_ID, [0; 32], 97).unwrap();
+    let leaf = merkle::payout_leaf(&alice_prediction.to_bytes(), 97, &[7; 32]);
+    env.record_entry_settlement(ROUND_ID, alice_prediction, leaf)
+        .unwrap();
+
+    env.settle(
+        ROUND_ID,
+        alice_prediction,
+        &alice,
+        97,
+        commitment(1),
+        [7; 32],
+        vec![],
+    )
+    .unwrap();
+    assert_eq!(env.token_balance(&alice.token_account), 997);
+    assert_eq!(env.token_balance(&bob.token_account), 1_000);
+}
+
+#[test]
+fn chunked_resolution_commits_the_root_of_chunk_roots() {
+    let mut env = TestEnv::initialized();
+    env.open_round(ROUND_ID);
+    let alice = env.new_user(1_000);
+    let bob = env.new_user(1_000);
+    let carol = env.new_user(1_000);
+    env.submit(ROUND_ID, &alice, 0, commitment(1), 100).unwrap();
+    env.submit(ROUND_ID, &bob, 0, commitment(2), 100).unwrap();
+    env.submit(ROUND_ID, &carol, 0, commitment(3), 100).unwrap();
+    env.warp_to(START_TS + 601);
+    let authority = env.authority.insecure_clone();
+    env.begin_resolution(ROUND_ID, &authority).unwrap();
+
+    // Alice and Bob land in chunk 0, Carol in chunk 1.
+    let alice_prediction = prediction_pda(ROUND_ID, &alice.pubkey(), 0);
+    let alice_leaf = merkle::payout_leaf(&alice_prediction.to_bytes(), 290, &[7; 32]);
+    let bob_leaf = merkle::payout_leaf(
+        &prediction_pda(ROUND_ID, &bob.pubkey(), 0).to_bytes(),
+        0,
+        &[8; 32],
+    );
+    let carol_leaf = merkle::payout_leaf(
+        &prediction_pda(ROUND_ID, &carol.pubkey(), 0).to_bytes(),
+        0,
+        &[9; 32],
+    );
+    let first_root = merkle::root(&[alice_leaf, bob_leaf]);
+    let second_root = merkle::root(&[carol_leaf]);
+
+    env.begin_chunked_resolution(ROUND_ID, 2).unwrap();
+    env.record_chunk_scan(ROUND_ID, 0, 2, 200).unwrap();
+    env.record_chunk_scan(ROUND_ID, 1, 1, 100).unwrap();
+    // Settled chunks may land in any order.
+    env.record_chunk_settlement(ROUND_ID, 1, second_root, 0, (1, 3), 300)
+        .unwrap();
+    assert_eq!(env.round(ROUND_ID).result_commitment, None);
+    env.record_chunk_settlement(ROUND_ID, 0, first_root, 290, (2, 3), 300)
+        .unwrap();
+
+    let round = env.round(ROUND_ID);
+    assert_eq!(
+        round.result_commitment,
+        Some(merkle::root(&[first_root, second_root]))
+    );
+    assert_eq!(round.committed_payout, 290);
+
+    env.settle(
+        ROUND_ID,
+        alice_prediction,
+        &alice,
+        290,
+        commitment(1),
+        [7; 32],
+        vec![bob_leaf, second_root],
+    )
+    .unwrap();
+    assert_eq!(env.token_balance(&alice.token_account), 1_190);
+    env.finalize(ROUND_ID, 42_000).unwrap();
+}